@@ -0,0 +1,87 @@
+-- The schema of the first release. Existing installations
+-- already have these tables, so nothing is changed there.
+
+CREATE TABLE IF NOT EXISTS users (
+    id       BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    username VARCHAR(255)    NOT NULL,
+    password VARCHAR(255)    NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE (username)
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    session_id VARCHAR(8)      NOT NULL,
+    user_id    BIGINT UNSIGNED NOT NULL,
+    created    TIMESTAMP       NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used  TIMESTAMP       NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (session_id),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS item_databases (
+    id   BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    name VARCHAR(255)    NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE (name)
+);
+
+CREATE TABLE IF NOT EXISTS locations (
+    id          BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    name        VARCHAR(255)    NOT NULL,
+    database_id BIGINT UNSIGNED NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE (database_id, name),
+    FOREIGN KEY (database_id) REFERENCES item_databases (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS tags (
+    id    BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    name  VARCHAR(255)    NOT NULL,
+    color INT UNSIGNED    NOT NULL,
+    icon  BIGINT UNSIGNED NULL,
+    PRIMARY KEY (id),
+    UNIQUE (name)
+);
+
+CREATE TABLE IF NOT EXISTS items (
+    id          BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    name        VARCHAR(255)    NOT NULL,
+    description TEXT            NOT NULL,
+    image       VARCHAR(2048)   NULL,
+    location_id BIGINT UNSIGNED NOT NULL,
+    amount      BIGINT UNSIGNED NOT NULL,
+    last_edited DATETIME        NOT NULL,
+    created     DATETIME        NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE (location_id, name),
+    FOREIGN KEY (location_id) REFERENCES locations (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS item_tags (
+    item_id BIGINT UNSIGNED NOT NULL,
+    tag_id  BIGINT UNSIGNED NOT NULL,
+    PRIMARY KEY (item_id, tag_id),
+    FOREIGN KEY (item_id) REFERENCES items (id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES tags (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS item_properties (
+    item_id   BIGINT UNSIGNED NOT NULL,
+    is_custom BOOLEAN         NOT NULL,
+    name      VARCHAR(255)    NOT NULL,
+    value     TEXT            NOT NULL,
+    FOREIGN KEY (item_id) REFERENCES items (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS item_attachments (
+    item_id BIGINT UNSIGNED NOT NULL,
+    name    VARCHAR(255)    NOT NULL,
+    url     VARCHAR(2048)   NOT NULL,
+    PRIMARY KEY (item_id, name),
+    FOREIGN KEY (item_id) REFERENCES items (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS item_deleted (
+    id      BIGINT UNSIGNED NOT NULL,
+    deleted TIMESTAMP       NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,13 @@
+-- Files that were uploaded for an item (the image and the attachments).
+-- The content is stored outside of the database, addressed by its SHA-256 hash.
+CREATE TABLE item_files (
+    item_id   BIGINT UNSIGNED NOT NULL,
+    is_image  BOOLEAN         NOT NULL,
+    name      VARCHAR(255)    NOT NULL,
+    hash      CHAR(64)        NOT NULL,
+    mime_type VARCHAR(255)    NOT NULL,
+    size      BIGINT UNSIGNED NOT NULL,
+    PRIMARY KEY (item_id, is_image, name),
+    INDEX (hash),
+    FOREIGN KEY (item_id) REFERENCES items (id) ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+-- Locations can be inside of other locations (e.g. House -> Garage -> Shelf).
+-- Deleting the parent is handled by the server, the foreign key only
+-- makes sure that deleting a whole database still works.
+ALTER TABLE locations
+    ADD COLUMN parent_id BIGINT UNSIGNED NULL,
+    ADD FOREIGN KEY (parent_id) REFERENCES locations (id) ON DELETE SET NULL;
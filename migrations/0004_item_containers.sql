@@ -0,0 +1,5 @@
+-- Items can be inside of other items (e.g. a toolbox or a first-aid kit).
+-- The server moves the contents out before it deletes the container.
+ALTER TABLE items
+    ADD COLUMN parent_item_id BIGINT UNSIGNED NULL,
+    ADD FOREIGN KEY (parent_item_id) REFERENCES items (id) ON DELETE SET NULL;
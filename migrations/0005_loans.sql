@@ -0,0 +1,16 @@
+-- Items (or parts of their amount) that are lent to someone.
+-- Returned loans are kept as the loan history of the item.
+CREATE TABLE loans (
+    id       BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    item_id  BIGINT UNSIGNED NOT NULL,
+    amount   BIGINT UNSIGNED NOT NULL,
+    user_id  BIGINT UNSIGNED NULL,
+    borrower VARCHAR(255)    NOT NULL,
+    lent     DATETIME        NOT NULL,
+    due      DATETIME        NULL,
+    returned DATETIME        NULL,
+    PRIMARY KEY (id),
+    INDEX (item_id, returned),
+    FOREIGN KEY (item_id) REFERENCES items (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL
+);
@@ -0,0 +1,3 @@
+-- The minimum stock level of an item. Items at (or below) it are low on stock.
+ALTER TABLE items
+    ADD COLUMN min_amount BIGINT UNSIGNED NULL;
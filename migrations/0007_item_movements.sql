@@ -0,0 +1,18 @@
+-- The stock ledger: every change of the amount or location of an item.
+CREATE TABLE item_movements (
+    id               BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    item_id          BIGINT UNSIGNED NOT NULL,
+    kind             VARCHAR(16)     NOT NULL,
+    amount           BIGINT          NOT NULL,
+    location_id      BIGINT UNSIGNED NULL,
+    from_location_id BIGINT UNSIGNED NULL,
+    reason           VARCHAR(255)    NOT NULL,
+    user_id          BIGINT UNSIGNED NULL,
+    time             DATETIME        NOT NULL,
+    PRIMARY KEY (id),
+    INDEX (item_id, time),
+    FOREIGN KEY (item_id) REFERENCES items (id) ON DELETE CASCADE,
+    FOREIGN KEY (location_id) REFERENCES locations (id) ON DELETE SET NULL,
+    FOREIGN KEY (from_location_id) REFERENCES locations (id) ON DELETE SET NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL
+);
@@ -0,0 +1,13 @@
+-- A snapshot of an item after every change. The revisions
+-- are kept after an item is deleted, so it can be restored.
+CREATE TABLE item_revisions (
+    item_id  BIGINT UNSIGNED NOT NULL,
+    revision BIGINT UNSIGNED NOT NULL,
+    user_id  BIGINT UNSIGNED NULL,
+    time     DATETIME        NOT NULL,
+    reason   VARCHAR(255)    NOT NULL,
+    deleted  BOOLEAN         NOT NULL,
+    snapshot LONGTEXT        NOT NULL,
+    PRIMARY KEY (item_id, revision),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL
+);
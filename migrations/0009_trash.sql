@@ -0,0 +1,29 @@
+-- Soft deletes: deleted objects stay in the trash until they are restored or purged.
+-- Names only have to be unique among the objects that aren't deleted. "active" is
+-- NULL for deleted objects, and NULL values never collide in an unique index.
+-- The new indexes are added before the old ones are dropped, because the
+-- foreign keys need an index that starts with their column.
+
+ALTER TABLE item_databases
+    ADD COLUMN deleted_at DATETIME NULL,
+    ADD COLUMN active BOOLEAN AS (IF(deleted_at IS NULL, TRUE, NULL)) STORED,
+    ADD UNIQUE active_name (name, active);
+ALTER TABLE item_databases DROP INDEX name;
+
+ALTER TABLE locations
+    ADD COLUMN deleted_at DATETIME NULL,
+    ADD COLUMN active BOOLEAN AS (IF(deleted_at IS NULL, TRUE, NULL)) STORED,
+    ADD UNIQUE active_name (database_id, name, active);
+ALTER TABLE locations DROP INDEX database_id;
+
+ALTER TABLE tags
+    ADD COLUMN deleted_at DATETIME NULL,
+    ADD COLUMN active BOOLEAN AS (IF(deleted_at IS NULL, TRUE, NULL)) STORED,
+    ADD UNIQUE active_name (name, active);
+ALTER TABLE tags DROP INDEX name;
+
+ALTER TABLE items
+    ADD COLUMN deleted_at DATETIME NULL,
+    ADD COLUMN active BOOLEAN AS (IF(deleted_at IS NULL, TRUE, NULL)) STORED,
+    ADD UNIQUE active_name (location_id, name, active);
+ALTER TABLE items DROP INDEX location_id;
@@ -0,0 +1,4 @@
+-- The schema of the custom properties of a database, as a JSON array of fields.
+-- NULL means that the database doesn't have a schema (yet).
+ALTER TABLE item_databases
+    ADD COLUMN properties LONGTEXT NULL;
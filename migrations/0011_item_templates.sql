@@ -0,0 +1,27 @@
+-- Templates with the prefilled fields of new items.
+-- Deleting the default location only removes it from the template.
+CREATE TABLE item_templates (
+    id          BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    name        VARCHAR(255)    NOT NULL,
+    description TEXT            NOT NULL,
+    location_id BIGINT UNSIGNED NULL,
+    PRIMARY KEY (id),
+    UNIQUE (name),
+    FOREIGN KEY (location_id) REFERENCES locations (id) ON DELETE SET NULL
+);
+
+CREATE TABLE template_tags (
+    template_id BIGINT UNSIGNED NOT NULL,
+    tag_id      BIGINT UNSIGNED NOT NULL,
+    PRIMARY KEY (template_id, tag_id),
+    FOREIGN KEY (template_id) REFERENCES item_templates (id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES tags (id) ON DELETE CASCADE
+);
+
+CREATE TABLE template_properties (
+    template_id BIGINT UNSIGNED NOT NULL,
+    is_custom   BOOLEAN         NOT NULL,
+    name        VARCHAR(255)    NOT NULL,
+    value       TEXT            NOT NULL,
+    FOREIGN KEY (template_id) REFERENCES item_templates (id) ON DELETE CASCADE
+);
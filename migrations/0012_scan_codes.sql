@@ -0,0 +1,11 @@
+-- Barcodes, QR codes and NFC tags on the labels of items and locations.
+-- A code is unique, so a scan always leads to a single object.
+CREATE TABLE scan_codes (
+    code        VARCHAR(255)    NOT NULL,
+    kind        VARCHAR(16)     NOT NULL,
+    item_id     BIGINT UNSIGNED NULL,
+    location_id BIGINT UNSIGNED NULL,
+    PRIMARY KEY (code),
+    FOREIGN KEY (item_id) REFERENCES items (id) ON DELETE CASCADE,
+    FOREIGN KEY (location_id) REFERENCES locations (id) ON DELETE CASCADE
+);
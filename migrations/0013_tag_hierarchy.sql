@@ -0,0 +1,8 @@
+-- Tags can be below other tags (e.g. Electronics -> Cables -> USB-C) and
+-- can belong to a single database. Tags without a database are global.
+-- Deleting a database also deletes its tags because of the foreign key.
+ALTER TABLE tags
+    ADD COLUMN parent_id BIGINT UNSIGNED NULL,
+    ADD COLUMN database_id BIGINT UNSIGNED NULL,
+    ADD FOREIGN KEY (parent_id) REFERENCES tags (id) ON DELETE SET NULL,
+    ADD FOREIGN KEY (database_id) REFERENCES item_databases (id) ON DELETE CASCADE;
@@ -0,0 +1,14 @@
+-- The uploaded icons. The built-in icons are part of the server and use the ids
+-- below 1001, so they never collide with the uploaded ones. That's also why tags,
+-- locations and databases have no foreign key for their icon. Icons are small,
+-- so their content is stored right in the table.
+CREATE TABLE icons (
+    id        BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    name      VARCHAR(255)    NOT NULL,
+    mime_type VARCHAR(255)    NOT NULL,
+    content   MEDIUMBLOB      NOT NULL,
+    PRIMARY KEY (id)
+) AUTO_INCREMENT = 1001;
+
+ALTER TABLE locations ADD COLUMN icon BIGINT UNSIGNED NULL;
+ALTER TABLE item_databases ADD COLUMN icon BIGINT UNSIGNED NULL;
@@ -0,0 +1,30 @@
+-- Everything that is moved to the trash at once (e.g. a database with its locations,
+-- items and tags) gets the same batch. Restoring it brings back exactly the rows of
+-- the batch, even if something else was deleted within the same second.
+CREATE TABLE trash_batches (
+    id         BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    deleted_at DATETIME        NOT NULL,
+    PRIMARY KEY (id)
+);
+
+ALTER TABLE item_databases ADD COLUMN trash_batch BIGINT UNSIGNED NULL;
+ALTER TABLE locations ADD COLUMN trash_batch BIGINT UNSIGNED NULL;
+ALTER TABLE tags ADD COLUMN trash_batch BIGINT UNSIGNED NULL;
+ALTER TABLE items ADD COLUMN trash_batch BIGINT UNSIGNED NULL;
+
+-- The objects that are already in the trash were matched by the time they were
+-- deleted, so every point in time becomes a batch of its own.
+INSERT INTO trash_batches (deleted_at)
+    SELECT deleted_at FROM item_databases WHERE deleted_at IS NOT NULL
+    UNION SELECT deleted_at FROM locations WHERE deleted_at IS NOT NULL;
+
+UPDATE item_databases JOIN trash_batches ON trash_batches.deleted_at = item_databases.deleted_at
+    SET item_databases.trash_batch = trash_batches.id;
+UPDATE locations JOIN trash_batches ON trash_batches.deleted_at = locations.deleted_at
+    SET locations.trash_batch = trash_batches.id;
+UPDATE tags JOIN item_databases ON item_databases.id = tags.database_id
+    SET tags.trash_batch = item_databases.trash_batch
+    WHERE tags.deleted_at = item_databases.deleted_at;
+UPDATE items JOIN locations ON locations.id = items.location_id
+    SET items.trash_batch = locations.trash_batch
+    WHERE items.deleted_at = locations.deleted_at;
@@ -0,0 +1,223 @@
+use std::fmt;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{HeaderMap, HeaderValue};
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpMessage, HttpResponse, ResponseError};
+use log::error;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::AuthedUser;
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// All errors the api can respond with. Every error has a stable,
+/// machine-readable code (e.g. "item.not_found"), so clients can
+/// branch on it and show localized messages.
+/// The entity names are the singular names used in the routes, e.g. "item".
+#[derive(Debug)]
+pub enum ApiError {
+    /// The id in the url isn't a number.
+    InvalidId(&'static str),
+
+    /// New objects must be sent with id 0, because the id is generated.
+    IdNotZero(&'static str),
+
+    /// The id in the url and the id in the body are different.
+    IdMismatch(&'static str),
+
+    NotFound(&'static str),
+
+    /// There already is an object with this name.
+    Conflict(&'static str),
+
+    /// The object refers to another object that doesn't exist.
+    /// The value is the name of the referenced entity.
+    UnknownReference(&'static str),
+
+    /// Any other malformed request (code, message).
+    BadRequest(&'static str, String),
+
+    /// Authentication failed (code, message).
+    Forbidden(&'static str, &'static str),
+
+    /// An error that was generated by actix itself, like
+    /// a malformed json body or an unknown route.
+    Http(StatusCode, String),
+
+    /// Never shown to the client, only logged.
+    Internal(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl ApiError {
+    /// The machine-readable error code.
+    pub(crate) fn code(&self) -> String {
+        match self {
+            ApiError::InvalidId(entity) => format!("{entity}.invalid_id"),
+            ApiError::IdNotZero(entity) => format!("{entity}.id_not_zero"),
+            ApiError::IdMismatch(entity) => format!("{entity}.id_mismatch"),
+            ApiError::NotFound(entity) => format!("{entity}.not_found"),
+            ApiError::Conflict(entity) => format!("{entity}.conflict"),
+            ApiError::UnknownReference(entity) => format!("{entity}.unknown_reference"),
+            ApiError::BadRequest(code, _) | ApiError::Forbidden(code, _) => code.to_string(),
+            ApiError::Http(status, _) if status.is_server_error() => "internal".to_owned(),
+            ApiError::Http(_, _) => "request.invalid".to_owned(),
+            ApiError::Internal(_) => "internal".to_owned(),
+        }
+    }
+
+    /// Build an RFC 7807 (problem details) response.
+    /// Internal errors don't get a detail message, because
+    /// it could contain sensitive information.
+    fn problem_response(&self, request_id: Option<&str>) -> HttpResponse {
+        let status = self.status_code();
+        let detail = match self {
+            ApiError::Internal(_) => None,
+            ApiError::Http(status, _) if status.is_server_error() => None,
+            _ => Some(self.to_string()),
+        };
+
+        HttpResponse::build(status).insert_header((header::CONTENT_TYPE, "application/problem+json")).json(Problem {
+            kind: "about:blank",
+            title: status.canonical_reason().unwrap_or_default(),
+            status: status.as_u16(),
+            detail,
+            code: self.code(),
+            request_id,
+        })
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::InvalidId(entity) => write!(f, "{entity} id must be a number!"),
+            ApiError::IdNotZero(entity) => write!(f, "{entity} id must be 0!"),
+            ApiError::IdMismatch(entity) => write!(f, "the {entity} ids don't match!"),
+            ApiError::NotFound(entity) => write!(f, "{entity} not found!"),
+            ApiError::Conflict(entity) => write!(f, "there already is a {entity} with this name!"),
+            ApiError::UnknownReference(entity) => write!(f, "unknown {entity} id!"),
+            ApiError::BadRequest(_, message) => write!(f, "{message}"),
+            ApiError::Forbidden(_, message) => write!(f, "{message}"),
+            ApiError::Http(_, message) => write!(f, "{message}"),
+            ApiError::Internal(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidId(_) | ApiError::IdNotZero(_) | ApiError::IdMismatch(_) | ApiError::BadRequest(_, _) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) | ApiError::UnknownReference(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Forbidden(_, _) => StatusCode::FORBIDDEN,
+            ApiError::Http(status, _) => *status,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        self.problem_response(None)
+    }
+}
+
+/// The body of an `application/problem+json` response.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Problem<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+
+    /// The reason phrase of the status code, e.g. "Not Found"
+    title: &'static str,
+    status: u16,
+
+    /// A message for humans. Internal errors don't have one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+
+    /// The machine-readable error code, e.g. "item.not_found"
+    code: String,
+
+    /// The id of the request in the server logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<&'a str>,
+}
+
+/// A random id that is generated for every request, unless a proxy already sent one.
+/// It is logged with every line and sent back in the "X-Request-Id" header and in
+/// every error response, so that the logs can be matched with the client's error.
+#[derive(Clone, Debug)]
+pub(crate) struct RequestId(pub(crate) String);
+
+impl RequestId {
+    pub(crate) const HEADER: &'static str = "X-Request-Id";
+
+    pub(crate) fn generate() -> Self {
+        RequestId(rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect())
+    }
+
+    /// Keep the id of the request, if it has a sane one. Anything else is replaced,
+    /// because the id ends up in the logs and in the response headers.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let sent = headers
+            .get(Self::HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty() && value.len() <= 64 && value.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"-_.".contains(&byte)));
+
+        match sent {
+            Some(sent) => RequestId(sent.to_owned()),
+            None => RequestId::generate(),
+        }
+    }
+
+    /// Send the id back to the client.
+    pub(crate) fn attach<B>(&self, mut res: ServiceResponse<B>) -> ServiceResponse<B> {
+        if let Ok(value) = HeaderValue::from_str(&self.0) {
+            res.headers_mut().insert(header::HeaderName::from_static("x-request-id"), value);
+        }
+        res
+    }
+}
+
+/// Turn every error response into a problem details response
+/// that contains the request id. Errors that don't come from
+/// our handlers (e.g. json parse errors) are converted as well.
+/// Internal errors are logged here (with the route and the user,
+/// if they are known) and removed from the response.
+pub(crate) fn problem_details<B>(res: ServiceResponse<B>) -> ServiceResponse<EitherBody<B>> {
+    let request_id = res.request().extensions().get::<RequestId>().cloned();
+    let request_id = request_id.as_ref().map(|id| id.0.as_str());
+
+    let new_response = match res.response().error() {
+        Some(err) => {
+            let foreign_error;
+            let api_error = match err.as_error::<ApiError>() {
+                Some(api_error) => api_error,
+                None => {
+                    foreign_error = ApiError::Http(res.status(), err.to_string());
+                    &foreign_error
+                }
+            };
+
+            if api_error.status_code().is_server_error() {
+                let route = res.request().match_pattern().unwrap_or_else(|| res.request().path().to_owned());
+                let user_id = res.request().extensions().get::<AuthedUser>().map(|user| user.user_id);
+                match user_id {
+                    Some(user_id) => error!("Internal Server Error (route: {route}, user_id: {user_id}): {api_error}"),
+                    None => error!("Internal Server Error (route: {route}): {api_error}"),
+                }
+            }
+
+            api_error.problem_response(request_id)
+        }
+        None => return res.map_into_left_body(),
+    };
+
+    let (request, _) = res.into_parts();
+    ServiceResponse::new(request, new_response).map_into_right_body()
+}
@@ -0,0 +1,47 @@
+use log::info;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// How many events a slow subscriber can fall behind before it misses some.
+const EVENT_BUFFER: usize = 256;
+
+/// Something that happened and that clients might want to be notified about.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// The amount of an item dropped to (or below) its minimum stock level.
+    LowStock { item: u64, name: String, amount: u64, min_amount: u64 },
+}
+
+impl Event {
+    /// The name of the event, as used for the "type" field.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Event::LowStock { .. } => "low_stock",
+        }
+    }
+}
+
+/// Distributes events to everyone who is subscribed at the moment.
+/// Events are not stored, so nobody is notified about old events.
+pub(crate) struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Events {
+    pub(crate) fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Events { sender }
+    }
+
+    pub(crate) fn publish(&self, event: Event) {
+        info!("Event: {event:?}");
+
+        // Sending only fails if nobody is subscribed, which is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use async_graphql::dataloader::Loader;
+use async_graphql::{Error, ResultExt};
+
+use crate::models::{Database, Item, ItemRevision, Loan, Location, Movement, Tag};
+use crate::storage::Stores;
+use crate::web_handlers::store_error;
+
+/// Loads the objects that other objects refer to. All keys of the same kind that are
+/// requested while resolving a level of the query are collected and loaded at once,
+/// so the items of a list don't read the tables once per item.
+pub(crate) struct StoreLoader(pub(crate) Stores);
+
+/// Declare the keys of the loader. They all wrap the id of an object,
+/// the name tells what is loaded for it.
+macro_rules! keys {
+    ($($(#[$meta:meta])* $key:ident),* $(,)?) => {
+        $(
+            $(#[$meta])*
+            #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+            pub(crate) struct $key(pub(crate) u64);
+
+            impl Key for $key {
+                fn id(&self) -> u64 {
+                    self.0
+                }
+            }
+        )*
+    };
+}
+
+keys! {
+    ItemId,
+    LocationId,
+    DatabaseId,
+    TagId,
+
+    /// The items inside of a container (not recursively)
+    ItemContents,
+
+    /// The items inside of a location (without the ones of its child locations)
+    LocationItems,
+    ChildLocations,
+    DatabaseLocations,
+    ChildTags,
+    TaggedItems,
+
+    /// All loans of an item, the newest first
+    ItemLoans,
+    ItemMovements,
+    ItemRevisions,
+}
+
+trait Key {
+    fn id(&self) -> u64;
+}
+
+/// The ids inside of the keys, to load them all at once.
+fn ids<K: Key>(keys: &[K]) -> Vec<u64> {
+    keys.iter().map(Key::id).collect()
+}
+
+/// One object for every key that exists.
+fn by_id<K: Hash + Eq, V>(keys: &[K], values: Vec<V>, key_of: impl Fn(&V) -> K) -> HashMap<K, V> {
+    values.into_iter().map(|value| (key_of(&value), value)).filter(|(key, _)| keys.contains(key)).collect()
+}
+
+/// The objects that belong to each key. Every key gets a list, even if it's empty.
+fn grouped<K: Hash + Eq + Copy, V>(keys: &[K], values: Vec<V>, key_of: impl Fn(&V) -> Option<K>) -> HashMap<K, Vec<V>> {
+    let mut groups: HashMap<K, Vec<V>> = keys.iter().map(|key| (*key, vec![])).collect();
+    for value in values {
+        if let Some(group) = key_of(&value).and_then(|key| groups.get_mut(&key)) {
+            group.push(value);
+        }
+    }
+    groups
+}
+
+impl Loader<ItemId> for StoreLoader {
+    type Value = Item;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ItemId]) -> Result<HashMap<ItemId, Item>, Error> {
+        let items = self.0.items.get_items_by_ids(&ids(keys)).await.map_err(store_error("item")).extend()?;
+        Ok(by_id(keys, items, |item| ItemId(item.id)))
+    }
+}
+
+impl Loader<LocationId> for StoreLoader {
+    type Value = Location;
+    type Error = Error;
+
+    async fn load(&self, keys: &[LocationId]) -> Result<HashMap<LocationId, Location>, Error> {
+        let locations = self.0.locations.get_locations().await.map_err(store_error("location")).extend()?;
+        Ok(by_id(keys, locations, |location| LocationId(location.id)))
+    }
+}
+
+impl Loader<DatabaseId> for StoreLoader {
+    type Value = Database;
+    type Error = Error;
+
+    async fn load(&self, keys: &[DatabaseId]) -> Result<HashMap<DatabaseId, Database>, Error> {
+        let databases = self.0.databases.get_databases().await.map_err(store_error("database")).extend()?;
+        Ok(by_id(keys, databases, |database| DatabaseId(database.id)))
+    }
+}
+
+impl Loader<TagId> for StoreLoader {
+    type Value = Tag;
+    type Error = Error;
+
+    async fn load(&self, keys: &[TagId]) -> Result<HashMap<TagId, Tag>, Error> {
+        let tags = self.0.tags.get_tags().await.map_err(store_error("tag")).extend()?;
+        Ok(by_id(keys, tags, |tag| TagId(tag.id)))
+    }
+}
+
+impl Loader<ItemContents> for StoreLoader {
+    type Value = Vec<Item>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ItemContents]) -> Result<HashMap<ItemContents, Vec<Item>>, Error> {
+        let items = self.0.items.get_items_by_containers(&ids(keys)).await.map_err(store_error("item")).extend()?;
+        Ok(grouped(keys, items, |item| item.parent_item.map(ItemContents)))
+    }
+}
+
+impl Loader<LocationItems> for StoreLoader {
+    type Value = Vec<Item>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[LocationItems]) -> Result<HashMap<LocationItems, Vec<Item>>, Error> {
+        let items = self.0.items.get_items_by_locations(&ids(keys)).await.map_err(store_error("item")).extend()?;
+        Ok(grouped(keys, items, |item| Some(LocationItems(item.location))))
+    }
+}
+
+impl Loader<ChildLocations> for StoreLoader {
+    type Value = Vec<Location>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ChildLocations]) -> Result<HashMap<ChildLocations, Vec<Location>>, Error> {
+        let locations = self.0.locations.get_locations().await.map_err(store_error("location")).extend()?;
+        Ok(grouped(keys, locations, |location| location.parent.map(ChildLocations)))
+    }
+}
+
+impl Loader<DatabaseLocations> for StoreLoader {
+    type Value = Vec<Location>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[DatabaseLocations]) -> Result<HashMap<DatabaseLocations, Vec<Location>>, Error> {
+        let locations = self.0.locations.get_locations().await.map_err(store_error("location")).extend()?;
+        Ok(grouped(keys, locations, |location| Some(DatabaseLocations(location.database))))
+    }
+}
+
+impl Loader<ChildTags> for StoreLoader {
+    type Value = Vec<Tag>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ChildTags]) -> Result<HashMap<ChildTags, Vec<Tag>>, Error> {
+        let tags = self.0.tags.get_tags().await.map_err(store_error("tag")).extend()?;
+        Ok(grouped(keys, tags, |tag| tag.parent.map(ChildTags)))
+    }
+}
+
+impl Loader<TaggedItems> for StoreLoader {
+    type Value = Vec<Item>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[TaggedItems]) -> Result<HashMap<TaggedItems, Vec<Item>>, Error> {
+        let items = self.0.items.get_items_by_tags(&ids(keys)).await.map_err(store_error("item")).extend()?;
+
+        // An item can have many tags, so it can end up in many lists
+        let mut groups: HashMap<TaggedItems, Vec<Item>> = keys.iter().map(|key| (*key, vec![])).collect();
+        for item in items {
+            for tag_id in &item.tags {
+                if let Some(group) = groups.get_mut(&TaggedItems(*tag_id)) {
+                    group.push(item.clone());
+                }
+            }
+        }
+        Ok(groups)
+    }
+}
+
+impl Loader<ItemLoans> for StoreLoader {
+    type Value = Vec<Loan>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ItemLoans]) -> Result<HashMap<ItemLoans, Vec<Loan>>, Error> {
+        let mut loans = self.0.loans.get_loans_by_items(&ids(keys)).await.map_err(store_error("loan")).extend()?;
+        loans.sort_by_key(|loan| std::cmp::Reverse((loan.lent, loan.id)));
+        Ok(grouped(keys, loans, |loan| Some(ItemLoans(loan.item))))
+    }
+}
+
+impl Loader<ItemMovements> for StoreLoader {
+    type Value = Vec<Movement>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ItemMovements]) -> Result<HashMap<ItemMovements, Vec<Movement>>, Error> {
+        let movements = self.0.movements.get_movements_by_items(&ids(keys)).await.map_err(store_error("movement")).extend()?;
+        Ok(grouped(keys, movements, |movement| Some(ItemMovements(movement.item))))
+    }
+}
+
+impl Loader<ItemRevisions> for StoreLoader {
+    type Value = Vec<ItemRevision>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ItemRevisions]) -> Result<HashMap<ItemRevisions, Vec<ItemRevision>>, Error> {
+        let revisions = self.0.revisions.get_revisions_by_items(&ids(keys)).await.map_err(store_error("revision")).extend()?;
+        Ok(grouped(keys, revisions, |revision| Some(ItemRevisions(revision.item))))
+    }
+}
@@ -0,0 +1,76 @@
+use actix_web::web;
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, EmptySubscription, ErrorExtensions, Schema};
+use log::error;
+
+use crate::error::ApiError;
+use crate::events::Events;
+use crate::models::AuthedUser;
+use crate::storage::Stores;
+
+use loader::StoreLoader;
+use mutation::MutationRoot;
+use query::QueryRoot;
+
+mod loader;
+mod mutation;
+mod query;
+mod relations;
+
+/// The GraphQL api. It offers the same objects as the REST api, but a client can
+/// fetch an object together with the objects it refers to in a single request.
+pub(crate) type GraphqlSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// How deep the queries can be nested and how many fields they can select,
+/// so a single request can't keep the server busy for a long time.
+const MAX_DEPTH: usize = 12;
+const MAX_COMPLEXITY: usize = 1000;
+
+/// Build the schema. The storage backend and the events are shared by all requests,
+/// the user and the loader are added to every request (see [request_data]).
+pub(crate) fn schema(stores: Stores, events: web::Data<Events>) -> GraphqlSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(stores)
+        .data(events)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Attach the user and a new loader to a request. The loader
+/// caches the objects, so it only lives as long as the request.
+pub(crate) fn request_data(request: async_graphql::Request, stores: &Stores, user: AuthedUser) -> async_graphql::Request {
+    request.data(user).data(DataLoader::new(StoreLoader(stores.clone()), actix_web::rt::spawn))
+}
+
+/// The errors have the same code as in the REST api (e.g. "item.not_found"),
+/// it's in the "code" extension of the error. Internal errors are only logged.
+impl ErrorExtensions for ApiError {
+    fn extend(&self) -> async_graphql::Error {
+        let message = match self {
+            ApiError::Internal(err) => {
+                error!("Internal Server Error (GraphQL): {err}");
+                "internal server error".to_owned()
+            }
+            _ => self.to_string(),
+        };
+
+        async_graphql::Error::new(message).extend_with(|_, extensions| extensions.set("code", self.code()))
+    }
+}
+
+fn stores<'a>(ctx: &Context<'a>) -> &'a Stores {
+    ctx.data_unchecked::<Stores>()
+}
+
+fn loader<'a>(ctx: &Context<'a>) -> &'a DataLoader<StoreLoader> {
+    ctx.data_unchecked::<DataLoader<StoreLoader>>()
+}
+
+fn events<'a>(ctx: &Context<'a>) -> &'a Events {
+    ctx.data_unchecked::<web::Data<Events>>()
+}
+
+fn user<'a>(ctx: &Context<'a>) -> &'a AuthedUser {
+    ctx.data_unchecked::<AuthedUser>()
+}
@@ -0,0 +1,208 @@
+use async_graphql::{Context, ErrorExtensions, Object, Result, ResultExt};
+
+use crate::error::ApiError;
+use crate::graphql::{events, stores, user};
+use crate::models::{Database, Item, ItemTemplate, Loan, Location, Tag, TrashKind};
+use crate::properties::check_schema;
+use crate::storage::ChildPolicy;
+use crate::web_handlers::icon::check_icon;
+use crate::web_handlers::item::{create_item, edit_item};
+use crate::web_handlers::loan::{lend_item, CheckOut};
+use crate::web_handlers::movement::{record_movement, NewMovement};
+use crate::web_handlers::stock::{adjust_items, AdjustedItem, Adjustment};
+use crate::web_handlers::{store_error, unix_now, user_change};
+
+pub(crate) struct MutationRoot;
+
+/// The same changes as the "PUT", "POST" and "DELETE" services of the REST api. New objects
+/// are returned after they were created or updated. Only admins can use the services for
+/// backups and emptying the trash, they are only part of the REST api (like the uploads).
+#[Object]
+impl MutationRoot {
+    async fn create_item(&self, ctx: &Context<'_>, item: Item) -> Result<Item> {
+        let stores = stores(ctx);
+        let item_id = create_item(&*stores.items, &*stores.locations, &*stores.databases, user(ctx), item).await.extend()?;
+        stores.items.get_item(item_id).await.map_err(store_error("item")).extend()
+    }
+
+    async fn update_item(&self, ctx: &Context<'_>, item: Item) -> Result<Item> {
+        let stores = stores(ctx);
+        let item_id = item.id;
+        edit_item(&*stores.items, &*stores.locations, &*stores.databases, events(ctx), user(ctx), item)
+            .await
+            .extend()?;
+        stores.items.get_item(item_id).await.map_err(store_error("item")).extend()
+    }
+
+    /// Move the item to the trash
+    async fn delete_item(&self, ctx: &Context<'_>, id: u64) -> Result<bool> {
+        stores(ctx)
+            .items
+            .delete_item(id, &user_change(user(ctx), "deleted"))
+            .await
+            .map_err(store_error("item"))
+            .extend()?;
+        Ok(true)
+    }
+
+    /// Record received, consumed or adjusted pieces of the item
+    async fn record_movement(&self, ctx: &Context<'_>, item_id: u64, movement: NewMovement) -> Result<AdjustedItem> {
+        let stores = stores(ctx);
+        record_movement(&*stores.movements, &*stores.items, events(ctx), user(ctx), item_id, &movement)
+            .await
+            .extend()
+    }
+
+    /// Adjust the amounts of many items at once
+    async fn adjust_amounts(&self, ctx: &Context<'_>, adjustments: Vec<Adjustment>) -> Result<Vec<AdjustedItem>> {
+        adjust_items(&*stores(ctx).movements, events(ctx), user(ctx), &adjustments).await.extend()
+    }
+
+    async fn check_out_item(&self, ctx: &Context<'_>, item_id: u64, check_out: CheckOut) -> Result<Loan> {
+        let stores = stores(ctx);
+        let loan_id = lend_item(&*stores.loans, item_id, &check_out).await.extend()?;
+        stores.loans.get_loan(loan_id).await.map_err(store_error("loan")).extend()
+    }
+
+    async fn check_in_loan(&self, ctx: &Context<'_>, id: u64) -> Result<Loan> {
+        let stores = stores(ctx);
+        stores.loans.check_in(id, unix_now()).await.map_err(store_error("loan")).extend()?;
+        stores.loans.get_loan(id).await.map_err(store_error("loan")).extend()
+    }
+
+    async fn create_tag(&self, ctx: &Context<'_>, tag: Tag) -> Result<Tag> {
+        if tag.id != 0 {
+            return Err(ApiError::IdNotZero("tag").extend());
+        }
+
+        let stores = stores(ctx);
+        check_icon(&*stores.icons, tag.icon).await.extend()?;
+
+        let tag_id = stores.tags.put_tag(&tag).await.map_err(store_error("tag")).extend()?;
+        stores.tags.get_tag(tag_id).await.map_err(store_error("tag")).extend()
+    }
+
+    async fn update_tag(&self, ctx: &Context<'_>, tag: Tag) -> Result<Tag> {
+        let stores = stores(ctx);
+        check_icon(&*stores.icons, tag.icon).await.extend()?;
+
+        stores.tags.update_tag(&tag).await.map_err(store_error("tag")).extend()?;
+        stores.tags.get_tag(tag.id).await.map_err(store_error("tag")).extend()
+    }
+
+    /// Move the tag to the trash
+    async fn delete_tag(&self, ctx: &Context<'_>, id: u64) -> Result<bool> {
+        stores(ctx).tags.delete_tag(id, unix_now()).await.map_err(store_error("tag")).extend()?;
+        Ok(true)
+    }
+
+    /// Merge the tag into another one and return the other tag
+    async fn merge_tag(&self, ctx: &Context<'_>, id: u64, into: u64) -> Result<Tag> {
+        let stores = stores(ctx);
+        stores
+            .tags
+            .merge_tag(id, into, &user_change(user(ctx), "tag merged"))
+            .await
+            .map_err(store_error("tag"))
+            .extend()?;
+        stores.tags.get_tag(into).await.map_err(store_error("tag")).extend()
+    }
+
+    async fn create_template(&self, ctx: &Context<'_>, template: ItemTemplate) -> Result<ItemTemplate> {
+        if template.id != 0 {
+            return Err(ApiError::IdNotZero("template").extend());
+        }
+
+        let stores = stores(ctx);
+        let template_id = stores.templates.put_template(&template).await.map_err(store_error("template")).extend()?;
+        stores.templates.get_template(template_id).await.map_err(store_error("template")).extend()
+    }
+
+    async fn update_template(&self, ctx: &Context<'_>, template: ItemTemplate) -> Result<ItemTemplate> {
+        let stores = stores(ctx);
+        stores.templates.update_template(&template).await.map_err(store_error("template")).extend()?;
+        stores.templates.get_template(template.id).await.map_err(store_error("template")).extend()
+    }
+
+    async fn delete_template(&self, ctx: &Context<'_>, id: u64) -> Result<bool> {
+        stores(ctx).templates.delete_template(id).await.map_err(store_error("template")).extend()?;
+        Ok(true)
+    }
+
+    async fn create_database(&self, ctx: &Context<'_>, database: Database) -> Result<Database> {
+        if database.id != 0 {
+            return Err(ApiError::IdNotZero("database").extend());
+        }
+        check_schema(&database.properties).extend()?;
+
+        let stores = stores(ctx);
+        check_icon(&*stores.icons, database.icon).await.extend()?;
+
+        let database_id = stores.databases.put_database(&database).await.map_err(store_error("database")).extend()?;
+        stores.databases.get_database(database_id).await.map_err(store_error("database")).extend()
+    }
+
+    async fn update_database(&self, ctx: &Context<'_>, database: Database) -> Result<Database> {
+        check_schema(&database.properties).extend()?;
+
+        let stores = stores(ctx);
+        check_icon(&*stores.icons, database.icon).await.extend()?;
+
+        stores.databases.update_database(&database).await.map_err(store_error("database")).extend()?;
+        stores.databases.get_database(database.id).await.map_err(store_error("database")).extend()
+    }
+
+    /// Move the database with all of its locations and items to the trash
+    async fn delete_database(&self, ctx: &Context<'_>, id: u64) -> Result<bool> {
+        stores(ctx)
+            .databases
+            .delete_database(id, &user_change(user(ctx), "database deleted"))
+            .await
+            .map_err(store_error("database"))
+            .extend()?;
+        Ok(true)
+    }
+
+    async fn create_location(&self, ctx: &Context<'_>, location: Location) -> Result<Location> {
+        if location.id != 0 {
+            return Err(ApiError::IdNotZero("location").extend());
+        }
+
+        let stores = stores(ctx);
+        check_icon(&*stores.icons, location.icon).await.extend()?;
+
+        let location_id = stores.locations.put_location(&location).await.map_err(store_error("location")).extend()?;
+        stores.locations.get_location(location_id).await.map_err(store_error("location")).extend()
+    }
+
+    async fn update_location(&self, ctx: &Context<'_>, location: Location) -> Result<Location> {
+        let stores = stores(ctx);
+        check_icon(&*stores.icons, location.icon).await.extend()?;
+
+        stores.locations.update_location(&location).await.map_err(store_error("location")).extend()?;
+        stores.locations.get_location(location.id).await.map_err(store_error("location")).extend()
+    }
+
+    /// Move the location and its items to the trash. By default, the
+    /// location can only be deleted if there are no locations inside.
+    async fn delete_location(&self, ctx: &Context<'_>, id: u64, #[graphql(default_with = "ChildPolicy::Reject")] children: ChildPolicy) -> Result<bool> {
+        stores(ctx)
+            .locations
+            .delete_location(id, children, &user_change(user(ctx), "location deleted"))
+            .await
+            .map_err(store_error("location"))
+            .extend()?;
+        Ok(true)
+    }
+
+    /// Restore a deleted object together with everything that was deleted with it
+    async fn restore_from_trash(&self, ctx: &Context<'_>, kind: TrashKind, id: u64) -> Result<bool> {
+        stores(ctx)
+            .trash
+            .restore_from_trash(kind, id, &user_change(user(ctx), "restored from trash"))
+            .await
+            .map_err(store_error("trash"))
+            .extend()?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,88 @@
+use async_graphql::{Context, Object, Result, ResultExt};
+
+use crate::graphql::stores;
+use crate::models::{Database, Icon, Item, ItemTemplate, Loan, Location, ScanCode, Tag, TrashEntry};
+use crate::web_handlers::icon::all_icons;
+use crate::web_handlers::item::{find_items, ItemFilter};
+use crate::web_handlers::loan::{loans_with_status, LoanStatus};
+use crate::web_handlers::store_error;
+
+pub(crate) struct QueryRoot;
+
+/// The same objects as the "GET" services of the REST api.
+#[Object]
+impl QueryRoot {
+    async fn items(&self, ctx: &Context<'_>, #[graphql(default)] filter: ItemFilter) -> Result<Vec<Item>> {
+        let stores = stores(ctx);
+        find_items(&*stores.items, &*stores.locations, &*stores.databases, &*stores.tags, &filter).await.extend()
+    }
+
+    async fn item(&self, ctx: &Context<'_>, id: u64) -> Result<Item> {
+        stores(ctx).items.get_item(id).await.map_err(store_error("item")).extend()
+    }
+
+    /// The tags, optionally only the ones that can be used in a database (its own and the global ones)
+    async fn tags(&self, ctx: &Context<'_>, database: Option<u64>) -> Result<Vec<Tag>> {
+        let mut tags = stores(ctx).tags.get_tags().await.map_err(store_error("tag")).extend()?;
+
+        if let Some(database_id) = database {
+            tags.retain(|tag| tag.database.is_none_or(|tag_database| tag_database == database_id));
+        }
+
+        Ok(tags)
+    }
+
+    async fn tag(&self, ctx: &Context<'_>, id: u64) -> Result<Tag> {
+        stores(ctx).tags.get_tag(id).await.map_err(store_error("tag")).extend()
+    }
+
+    async fn locations(&self, ctx: &Context<'_>) -> Result<Vec<Location>> {
+        stores(ctx).locations.get_locations().await.map_err(store_error("location")).extend()
+    }
+
+    async fn location(&self, ctx: &Context<'_>, id: u64) -> Result<Location> {
+        stores(ctx).locations.get_location(id).await.map_err(store_error("location")).extend()
+    }
+
+    async fn databases(&self, ctx: &Context<'_>) -> Result<Vec<Database>> {
+        stores(ctx).databases.get_databases().await.map_err(store_error("database")).extend()
+    }
+
+    async fn database(&self, ctx: &Context<'_>, id: u64) -> Result<Database> {
+        stores(ctx).databases.get_database(id).await.map_err(store_error("database")).extend()
+    }
+
+    async fn templates(&self, ctx: &Context<'_>) -> Result<Vec<ItemTemplate>> {
+        stores(ctx).templates.get_templates().await.map_err(store_error("template")).extend()
+    }
+
+    async fn template(&self, ctx: &Context<'_>, id: u64) -> Result<ItemTemplate> {
+        stores(ctx).templates.get_template(id).await.map_err(store_error("template")).extend()
+    }
+
+    async fn loans(&self, ctx: &Context<'_>, #[graphql(default)] status: LoanStatus) -> Result<Vec<Loan>> {
+        let loans = stores(ctx).loans.get_loans().await.map_err(store_error("loan")).extend()?;
+        Ok(loans_with_status(loans, status))
+    }
+
+    async fn loan(&self, ctx: &Context<'_>, id: u64) -> Result<Loan> {
+        stores(ctx).loans.get_loan(id).await.map_err(store_error("loan")).extend()
+    }
+
+    /// The built-in icons first, then the uploaded ones
+    async fn icons(&self, ctx: &Context<'_>) -> Result<Vec<Icon>> {
+        all_icons(&*stores(ctx).icons).await.extend()
+    }
+
+    /// The codes of all items and locations
+    async fn codes(&self, ctx: &Context<'_>) -> Result<Vec<ScanCode>> {
+        stores(ctx).codes.get_codes().await.map_err(store_error("code")).extend()
+    }
+
+    /// The deleted objects, the most recently deleted first
+    async fn trash(&self, ctx: &Context<'_>) -> Result<Vec<TrashEntry>> {
+        let mut entries = stores(ctx).trash.get_trash().await.map_err(store_error("trash")).extend()?;
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+        Ok(entries)
+    }
+}
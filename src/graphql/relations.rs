@@ -0,0 +1,157 @@
+use async_graphql::{ComplexObject, Context, Result};
+
+use crate::graphql::loader;
+use crate::graphql::loader::{
+    ChildLocations, ChildTags, DatabaseId, DatabaseLocations, ItemContents, ItemId, ItemLoans, ItemMovements, ItemRevisions, LocationId, LocationItems, TagId, TaggedItems,
+};
+use crate::models::{Database, Item, ItemRevision, ItemTemplate, Loan, Location, Movement, Tag};
+use crate::web_handlers::loan::lent_amount;
+
+// The objects that the models refer to by their ids. Everything is read
+// through the loader, so a list of objects only needs one read per relation.
+
+#[ComplexObject]
+impl Item {
+    #[graphql(name = "location")]
+    async fn resolve_location(&self, ctx: &Context<'_>) -> Result<Option<Location>> {
+        loader(ctx).load_one(LocationId(self.location)).await
+    }
+
+    /// The container this item is inside of
+    #[graphql(name = "parentItem")]
+    async fn resolve_parent_item(&self, ctx: &Context<'_>) -> Result<Option<Item>> {
+        match self.parent_item {
+            Some(parent_id) => loader(ctx).load_one(ItemId(parent_id)).await,
+            None => Ok(None),
+        }
+    }
+
+    /// The items directly inside of this one
+    async fn contents(&self, ctx: &Context<'_>) -> Result<Vec<Item>> {
+        Ok(loader(ctx).load_one(ItemContents(self.id)).await?.unwrap_or_default())
+    }
+
+    #[graphql(name = "tags")]
+    async fn resolve_tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        let mut tags = loader(ctx).load_many(self.tags.iter().copied().map(TagId)).await?;
+        Ok(self.tags.iter().filter_map(|tag_id| tags.remove(&TagId(*tag_id))).collect())
+    }
+
+    /// All loans of the item, the newest first
+    async fn loans(&self, ctx: &Context<'_>) -> Result<Vec<Loan>> {
+        Ok(loader(ctx).load_one(ItemLoans(self.id)).await?.unwrap_or_default())
+    }
+
+    /// How much of the item is lent right now
+    async fn lent(&self, ctx: &Context<'_>) -> Result<u64> {
+        let loans = loader(ctx).load_one(ItemLoans(self.id)).await?.unwrap_or_default();
+        Ok(lent_amount(&loans, self.id))
+    }
+
+    /// How much of the item isn't lent right now
+    async fn available(&self, ctx: &Context<'_>) -> Result<u64> {
+        Ok(self.amount.saturating_sub(self.lent(ctx).await?))
+    }
+
+    async fn low_stock(&self) -> bool {
+        self.is_low_stock()
+    }
+
+    /// The stock ledger of the item, oldest first
+    async fn movements(&self, ctx: &Context<'_>) -> Result<Vec<Movement>> {
+        Ok(loader(ctx).load_one(ItemMovements(self.id)).await?.unwrap_or_default())
+    }
+
+    /// The saved snapshots of the item, oldest first
+    async fn revisions(&self, ctx: &Context<'_>) -> Result<Vec<ItemRevision>> {
+        Ok(loader(ctx).load_one(ItemRevisions(self.id)).await?.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl Location {
+    #[graphql(name = "database")]
+    async fn resolve_database(&self, ctx: &Context<'_>) -> Result<Option<Database>> {
+        loader(ctx).load_one(DatabaseId(self.database)).await
+    }
+
+    #[graphql(name = "parent")]
+    async fn resolve_parent(&self, ctx: &Context<'_>) -> Result<Option<Location>> {
+        match self.parent {
+            Some(parent_id) => loader(ctx).load_one(LocationId(parent_id)).await,
+            None => Ok(None),
+        }
+    }
+
+    /// The locations directly inside of this one
+    async fn children(&self, ctx: &Context<'_>) -> Result<Vec<Location>> {
+        Ok(loader(ctx).load_one(ChildLocations(self.id)).await?.unwrap_or_default())
+    }
+
+    /// The items directly inside of this location (without the ones of its child locations)
+    async fn items(&self, ctx: &Context<'_>) -> Result<Vec<Item>> {
+        Ok(loader(ctx).load_one(LocationItems(self.id)).await?.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl Database {
+    /// All locations of the database, including the ones inside of other locations
+    async fn locations(&self, ctx: &Context<'_>) -> Result<Vec<Location>> {
+        Ok(loader(ctx).load_one(DatabaseLocations(self.id)).await?.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl Tag {
+    #[graphql(name = "parent")]
+    async fn resolve_parent(&self, ctx: &Context<'_>) -> Result<Option<Tag>> {
+        match self.parent {
+            Some(parent_id) => loader(ctx).load_one(TagId(parent_id)).await,
+            None => Ok(None),
+        }
+    }
+
+    /// The tags directly below this one
+    async fn children(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        Ok(loader(ctx).load_one(ChildTags(self.id)).await?.unwrap_or_default())
+    }
+
+    #[graphql(name = "database")]
+    async fn resolve_database(&self, ctx: &Context<'_>) -> Result<Option<Database>> {
+        match self.database {
+            Some(database_id) => loader(ctx).load_one(DatabaseId(database_id)).await,
+            None => Ok(None),
+        }
+    }
+
+    /// The items with exactly this tag (without the ones of its child tags)
+    async fn items(&self, ctx: &Context<'_>) -> Result<Vec<Item>> {
+        Ok(loader(ctx).load_one(TaggedItems(self.id)).await?.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl Loan {
+    #[graphql(name = "item")]
+    async fn resolve_item(&self, ctx: &Context<'_>) -> Result<Option<Item>> {
+        loader(ctx).load_one(ItemId(self.item)).await
+    }
+}
+
+#[ComplexObject]
+impl ItemTemplate {
+    #[graphql(name = "location")]
+    async fn resolve_location(&self, ctx: &Context<'_>) -> Result<Option<Location>> {
+        match self.location {
+            Some(location_id) => loader(ctx).load_one(LocationId(location_id)).await,
+            None => Ok(None),
+        }
+    }
+
+    #[graphql(name = "tags")]
+    async fn resolve_tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        let mut tags = loader(ctx).load_many(self.tags.iter().copied().map(TagId)).await?;
+        Ok(self.tags.iter().filter_map(|tag_id| tags.remove(&TagId(*tag_id))).collect())
+    }
+}
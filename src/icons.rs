@@ -0,0 +1,230 @@
+use roxmltree::{Document, Node};
+
+/// The uploaded icons get ids from here on. The ids below are reserved for the built-in icons.
+pub(crate) const FIRST_UPLOADED_ICON: u64 = 1001;
+
+/// The width and height of uploaded raster icons in pixels. Bigger images are scaled down.
+pub(crate) const ICON_SIZE: u32 = 128;
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
+/// An icon that is part of the server. The ids must never change, because
+/// tags, locations and databases refer to them. New icons are added at the end.
+pub(crate) struct BuiltinIcon {
+    pub(crate) id: u64,
+    pub(crate) name: &'static str,
+    pub(crate) svg: &'static str,
+}
+
+macro_rules! builtin_icons {
+    ($($id:literal => $name:literal),* $(,)?) => {
+        [$(BuiltinIcon {
+            id: $id,
+            name: $name,
+            svg: include_str!(concat!("../assets/icons/", $name, ".svg")),
+        }),*]
+    };
+}
+
+pub(crate) const BUILTIN_ICONS: [BuiltinIcon; 22] = builtin_icons![
+    1 => "box",
+    2 => "tag",
+    3 => "folder",
+    4 => "home",
+    5 => "shelf",
+    6 => "drawer",
+    7 => "tool",
+    8 => "hammer",
+    9 => "cable",
+    10 => "chip",
+    11 => "battery",
+    12 => "lightbulb",
+    13 => "book",
+    14 => "kitchen",
+    15 => "shirt",
+    16 => "leaf",
+    17 => "key",
+    18 => "gift",
+    19 => "music",
+    20 => "camera",
+    21 => "car",
+    22 => "star",
+];
+
+pub(crate) fn builtin_icon(icon_id: u64) -> Option<&'static BuiltinIcon> {
+    BUILTIN_ICONS.iter().find(|icon| icon.id == icon_id)
+}
+
+/// The elements that only draw something. Everything that can run scripts, load
+/// other resources or embed HTML (script, foreignObject, use, image, ...) is removed.
+const ALLOWED_ELEMENTS: [&str; 16] = [
+    "svg",
+    "g",
+    "path",
+    "circle",
+    "ellipse",
+    "line",
+    "polyline",
+    "polygon",
+    "rect",
+    "title",
+    "desc",
+    "defs",
+    "linearGradient",
+    "radialGradient",
+    "stop",
+    "clipPath",
+];
+
+/// The attributes that only describe shapes and colors. Event handlers,
+/// links and style sheets are removed.
+const ALLOWED_ATTRIBUTES: [&str; 44] = [
+    "viewBox",
+    "width",
+    "height",
+    "preserveAspectRatio",
+    "id",
+    "d",
+    "x",
+    "y",
+    "x1",
+    "y1",
+    "x2",
+    "y2",
+    "cx",
+    "cy",
+    "r",
+    "rx",
+    "ry",
+    "fx",
+    "fy",
+    "points",
+    "transform",
+    "fill",
+    "fill-opacity",
+    "fill-rule",
+    "stroke",
+    "stroke-width",
+    "stroke-opacity",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "stroke-miterlimit",
+    "stroke-dasharray",
+    "stroke-dashoffset",
+    "opacity",
+    "color",
+    "clip-path",
+    "clip-rule",
+    "clipPathUnits",
+    "offset",
+    "stop-color",
+    "stop-opacity",
+    "gradientUnits",
+    "gradientTransform",
+    "spreadMethod",
+    "vector-effect",
+];
+
+/// Rebuild an uploaded SVG with only the elements and attributes that are safe to show.
+/// Returns None if the data isn't an SVG document. Documents with a DTD are rejected
+/// as well, so entities can't blow up the size of the document.
+pub(crate) fn sanitize_svg(data: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(data).ok()?;
+    let document = Document::parse(text).ok()?;
+
+    let root = document.root_element();
+    if root.tag_name().name() != "svg" || root.tag_name().namespace() != Some(SVG_NAMESPACE) {
+        return None;
+    }
+
+    let mut svg = String::with_capacity(text.len());
+    write_element(root, &mut svg);
+    Some(svg)
+}
+
+fn write_element(node: Node, svg: &mut String) {
+    let name = node.tag_name().name();
+    svg.push('<');
+    svg.push_str(name);
+    if name == "svg" {
+        svg.push_str(" xmlns=\"");
+        svg.push_str(SVG_NAMESPACE);
+        svg.push('"');
+    }
+
+    for attribute in node.attributes() {
+        if attribute.namespace().is_none() && ALLOWED_ATTRIBUTES.contains(&attribute.name()) && is_local_value(attribute.value()) {
+            svg.push(' ');
+            svg.push_str(attribute.name());
+            svg.push_str("=\"");
+            push_escaped(svg, attribute.value());
+            svg.push('"');
+        }
+    }
+    svg.push('>');
+
+    for child in node.children() {
+        if child.is_element() && child.tag_name().namespace() == Some(SVG_NAMESPACE) && ALLOWED_ELEMENTS.contains(&child.tag_name().name()) {
+            write_element(child, svg);
+        } else if child.is_text() && matches!(name, "title" | "desc") {
+            push_escaped(svg, child.text().unwrap_or_default());
+        }
+    }
+
+    svg.push_str("</");
+    svg.push_str(name);
+    svg.push('>');
+}
+
+/// Colors and clip paths can refer to other elements with "url(#id)",
+/// but they must not load anything from somewhere else.
+fn is_local_value(value: &str) -> bool {
+    let value = value.to_ascii_lowercase();
+    value.match_indices("url(").all(|(index, _)| value[index + 4..].trim_start().starts_with('#'))
+}
+
+fn push_escaped(svg: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => svg.push_str("&amp;"),
+            '<' => svg.push_str("&lt;"),
+            '>' => svg.push_str("&gt;"),
+            '"' => svg.push_str("&quot;"),
+            c => svg.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sanitize_svg;
+
+    #[test]
+    fn scripts_and_handlers_are_removed() {
+        let svg =
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" onload="alert(1)"><script>alert(2)</script><path d="M0 0h24" fill="red" onclick="alert(3)"/></svg>"#;
+
+        assert_eq!(
+            sanitize_svg(svg.as_bytes()).unwrap(),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24"><path d="M0 0h24" fill="red"></path></svg>"#
+        );
+    }
+
+    #[test]
+    fn only_local_references_are_kept() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg"><rect fill="url(#gradient)" stroke="url(https://example.com/x)"/></svg>"##;
+
+        assert_eq!(
+            sanitize_svg(svg.as_bytes()).unwrap(),
+            r##"<svg xmlns="http://www.w3.org/2000/svg"><rect fill="url(#gradient)"></rect></svg>"##
+        );
+    }
+
+    #[test]
+    fn other_documents_are_rejected() {
+        assert!(sanitize_svg(b"<html><body></body></html>").is_none());
+        assert!(sanitize_svg(b"<svg></svg>").is_none());
+        assert!(sanitize_svg(b"no xml at all").is_none());
+        assert!(sanitize_svg(br#"<!DOCTYPE svg [<!ENTITY a "aaaa">]><svg xmlns="http://www.w3.org/2000/svg">&a;</svg>"#).is_none());
+    }
+}
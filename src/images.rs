@@ -0,0 +1,194 @@
+use std::io::Cursor;
+
+use exif::Context;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, ImageResult};
+
+/// The longest edge of the generated thumbnails in pixels.
+pub(crate) const THUMBNAIL_SIZES: [u32; 3] = [128, 256, 512];
+
+/// The quality (0-100) of re-encoded JPEG images.
+const JPEG_QUALITY: u8 = 85;
+
+/// The name of the blob variant that contains a thumbnail, e.g. "256" or "256.webp".
+/// Without WebP, the thumbnail is a JPEG (or a PNG if the image is transparent).
+pub(crate) fn thumbnail_variant(size: u32, webp: bool) -> String {
+    if webp {
+        format!("{size}.webp")
+    } else {
+        size.to_string()
+    }
+}
+
+/// Render all thumbnails of an image and return them with their variant names.
+/// This is slow for big images, so it shouldn't block a request.
+pub(crate) fn render_thumbnails(data: &[u8]) -> ImageResult<Vec<(String, Vec<u8>)>> {
+    let (image, _) = decode(data)?;
+    let format = if image.color().has_alpha() { ImageFormat::Png } else { ImageFormat::Jpeg };
+
+    let mut thumbnails = vec![];
+    for size in THUMBNAIL_SIZES {
+        // Small images are never scaled up
+        let thumbnail = if image.width() > size || image.height() > size {
+            image.thumbnail(size, size)
+        } else {
+            image.clone()
+        };
+
+        thumbnails.push((thumbnail_variant(size, false), encode(&thumbnail, format)?));
+        thumbnails.push((thumbnail_variant(size, true), encode(&thumbnail, ImageFormat::WebP)?));
+    }
+
+    Ok(thumbnails)
+}
+
+/// Scale an uploaded icon down to the size (if it's bigger) and turn it into a PNG.
+pub(crate) fn render_icon(data: &[u8], size: u32) -> ImageResult<Vec<u8>> {
+    let (image, _) = decode(data)?;
+    let icon = if image.width() > size || image.height() > size {
+        image.thumbnail(size, size)
+    } else {
+        image
+    };
+
+    encode(&icon, ImageFormat::Png)
+}
+
+/// Images from phones often contain the location where they were taken.
+/// Those images are re-encoded without any metadata, but since that costs
+/// quality, all other images are kept as they are and `None` is returned.
+pub(crate) fn strip_location(data: &[u8]) -> ImageResult<Option<Vec<u8>>> {
+    let has_location = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .map(|exif| exif.fields().any(|field| field.tag.context() == Context::Gps))
+        .unwrap_or(false);
+    if !has_location {
+        return Ok(None);
+    }
+
+    // The orientation is part of the metadata as well,
+    // so the image has to be rotated before it's lost.
+    let (image, format) = decode(data)?;
+    Ok(Some(encode(&image, format.unwrap_or(ImageFormat::Png))?))
+}
+
+/// Decode an image and rotate it as described by its EXIF orientation.
+fn decode(data: &[u8]) -> ImageResult<(DynamicImage, Option<ImageFormat>)> {
+    let reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    let format = reader.format();
+
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    Ok((image, format))
+}
+
+/// Encode the image without any metadata. Formats we can't
+/// encode (e.g. GIF animations) are turned into a PNG.
+fn encode(image: &DynamicImage, format: ImageFormat) -> ImageResult<Vec<u8>> {
+    let mut data = Cursor::new(vec![]);
+    match format {
+        // JPEG doesn't support transparency
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))?,
+        ImageFormat::WebP => DynamicImage::ImageRgba8(image.to_rgba8()).write_to(&mut data, ImageFormat::WebP)?,
+        _ => image.write_to(&mut data, ImageFormat::Png)?,
+    }
+
+    Ok(data.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use exif::experimental::Writer;
+    use exif::{Context, Field, In, Tag, Value};
+    use image::{DynamicImage, GenericImageView, ImageFormat, RgbImage};
+
+    use super::{encode, render_thumbnails, strip_location, thumbnail_variant, THUMBNAIL_SIZES};
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        encode(&DynamicImage::ImageRgb8(RgbImage::new(width, height)), ImageFormat::Jpeg).unwrap()
+    }
+
+    /// A JPEG like from a phone: rotated by 90° and with the location where it was taken.
+    fn jpeg_with_location(width: u32, height: u32) -> Vec<u8> {
+        let fields = [
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            Field {
+                tag: Tag::GPSLatitude,
+                ifd_num: In::PRIMARY,
+                value: Value::Rational(vec![(52, 1).into(), (31, 1).into(), (12, 1).into()]),
+            },
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(vec![]);
+        writer.write(&mut tiff, false).unwrap();
+
+        // The EXIF data is an APP1 segment right after the start of the image
+        let mut segment = b"Exif\0\0".to_vec();
+        segment.extend_from_slice(tiff.get_ref());
+        let data = jpeg(width, height);
+        let mut with_exif = data[..2].to_vec();
+        with_exif.extend_from_slice(&[0xFF, 0xE1]);
+        with_exif.extend_from_slice(&(segment.len() as u16 + 2).to_be_bytes());
+        with_exif.extend_from_slice(&segment);
+        with_exif.extend_from_slice(&data[2..]);
+
+        with_exif
+    }
+
+    fn has_location(data: &[u8]) -> bool {
+        exif::Reader::new()
+            .read_from_container(&mut Cursor::new(data))
+            .is_ok_and(|exif| exif.fields().any(|field| field.tag.context() == Context::Gps))
+    }
+
+    #[test]
+    fn the_location_is_removed_from_images() {
+        let data = jpeg_with_location(40, 20);
+        assert!(has_location(&data));
+
+        let stripped = strip_location(&data).unwrap().unwrap();
+        assert!(exif::Reader::new().read_from_container(&mut Cursor::new(&stripped)).is_err());
+        assert_eq!(image::guess_format(&stripped).unwrap(), ImageFormat::Jpeg);
+
+        // The orientation is applied before the metadata is dropped
+        assert_eq!(image::load_from_memory(&stripped).unwrap().dimensions(), (20, 40));
+    }
+
+    #[test]
+    fn images_without_location_are_kept() {
+        assert!(strip_location(&jpeg(40, 20)).unwrap().is_none());
+    }
+
+    #[test]
+    fn thumbnails_fit_into_their_size() {
+        let thumbnails = render_thumbnails(&jpeg_with_location(1000, 300)).unwrap();
+        assert_eq!(thumbnails.len(), 2 * THUMBNAIL_SIZES.len());
+
+        for (variant, data) in &thumbnails {
+            let size = THUMBNAIL_SIZES
+                .iter()
+                .find(|size| *variant == thumbnail_variant(**size, variant.ends_with(".webp")))
+                .unwrap();
+            let (width, height) = image::load_from_memory(data).unwrap().dimensions();
+            assert_eq!(width.max(height), *size, "{variant}");
+            assert!(!has_location(data), "{variant}");
+        }
+
+        // Small images are never scaled up
+        for (variant, data) in render_thumbnails(&jpeg(100, 50)).unwrap() {
+            assert_eq!(image::load_from_memory(&data).unwrap().dimensions(), (100, 50), "{variant}");
+        }
+    }
+}
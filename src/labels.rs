@@ -0,0 +1,282 @@
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+use qrcode::{Color, EcLevel, QrCode};
+
+/// One label: a QR code with the deep link to the object, its name and where it is.
+pub(crate) struct Label {
+    pub(crate) link: String,
+    pub(crate) name: String,
+    pub(crate) path: String,
+}
+
+/// The size and arrangement of the labels. Sheets (like the ones of Avery)
+/// have many labels on each page, thermal printers print one label at a time.
+pub(crate) struct LabelTemplate {
+    pub(crate) name: &'static str,
+    width: f32,
+    height: f32,
+    sheet: Option<Sheet>,
+}
+
+/// A page with a grid of labels. All sizes are in millimeters.
+struct Sheet {
+    width: f32,
+    height: f32,
+    columns: usize,
+    rows: usize,
+    left: f32,
+    top: f32,
+    gap_x: f32,
+    gap_y: f32,
+}
+
+const A4: (f32, f32) = (210.0, 297.0);
+const LETTER: (f32, f32) = (215.9, 279.4);
+
+pub(crate) const LABEL_TEMPLATES: [LabelTemplate; 6] = [
+    sheet_template("avery-l7160", A4, (63.5, 38.1), (3, 7), (7.2, 15.1), (2.5, 0.0)),
+    sheet_template("avery-l7163", A4, (99.1, 38.1), (2, 7), (4.65, 15.1), (2.5, 0.0)),
+    sheet_template("avery-l7651", A4, (38.1, 21.2), (5, 13), (9.75, 10.7), (2.5, 0.0)),
+    sheet_template("avery-5160", LETTER, (66.7, 25.4), (3, 10), (4.8, 12.7), (3.2, 0.0)),
+    thermal_template("thermal-57x32", (57.0, 32.0)),
+    thermal_template("thermal-102x51", (102.0, 51.0)),
+];
+
+const fn sheet_template(name: &'static str, page: (f32, f32), label: (f32, f32), grid: (usize, usize), margin: (f32, f32), gap: (f32, f32)) -> LabelTemplate {
+    LabelTemplate {
+        name,
+        width: label.0,
+        height: label.1,
+        sheet: Some(Sheet {
+            width: page.0,
+            height: page.1,
+            columns: grid.0,
+            rows: grid.1,
+            left: margin.0,
+            top: margin.1,
+            gap_x: gap.0,
+            gap_y: gap.1,
+        }),
+    }
+}
+
+const fn thermal_template(name: &'static str, label: (f32, f32)) -> LabelTemplate {
+    LabelTemplate {
+        name,
+        width: label.0,
+        height: label.1,
+        sheet: None,
+    }
+}
+
+/// The deep link to an item or location in the web UI, e.g. "https://example.com/#/item/12".
+pub(crate) fn label_link(base_url: &str, kind: &str, id: u64) -> String {
+    format!("{}/#/{kind}/{id}", base_url.trim_end_matches('/'))
+}
+
+/// The kind ("item" or "location") and the id of a deep link from a label.
+pub(crate) fn parse_label_link(link: &str) -> Option<(&str, u64)> {
+    let (_, target) = link.rsplit_once("/#/")?;
+    let (kind, id) = target.split_once('/')?;
+
+    if !matches!(kind, "item" | "location") {
+        return None;
+    }
+    Some((kind, id.parse().ok()?))
+}
+
+impl LabelTemplate {
+    pub(crate) fn find(name: &str) -> Option<&'static LabelTemplate> {
+        LABEL_TEMPLATES.iter().find(|template| template.name == name)
+    }
+}
+
+/// The space between the edge of a label and its content in millimeters.
+const PADDING: f32 = 2.0;
+
+/// The dots per millimeter of Zebra printers with 203 dpi.
+const ZPL_DOTS_PER_MM: f32 = 8.0;
+
+/// Render the labels into a PDF. Sheets are filled row by row,
+/// thermal labels get a page of their own.
+pub(crate) fn render_pdf(template: &LabelTemplate, labels: &[Label]) -> Result<Vec<u8>, qrcode::types::QrError> {
+    let (page_width, page_height, per_page) = match &template.sheet {
+        Some(sheet) => (sheet.width, sheet.height, sheet.columns * sheet.rows),
+        None => (template.width, template.height, 1),
+    };
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let font_id = Ref::new(3);
+    let font_name = Name(b"F1");
+
+    let pages: Vec<&[Label]> = labels.chunks(per_page.max(1)).collect();
+    let page_ids: Vec<Ref> = (0..pages.len()).map(|index| Ref::new(4 + 2 * index as i32)).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids(page_ids.iter().copied()).count(page_ids.len() as i32);
+
+    // Helvetica is built into every PDF reader, so no font has to be embedded
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica")).encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    for (page_labels, page_id) in pages.into_iter().zip(page_ids) {
+        let content_id = Ref::new(page_id.get() + 1);
+
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, points(page_width), points(page_height)));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        page.resources().fonts().pair(font_name, font_id);
+        page.finish();
+
+        let mut content = Content::new();
+        for (index, label) in page_labels.iter().enumerate() {
+            // PDF coordinates start at the bottom left of the page
+            let (x, top) = match &template.sheet {
+                Some(sheet) => {
+                    let (column, row) = (index % sheet.columns, index / sheet.columns);
+                    (
+                        sheet.left + column as f32 * (template.width + sheet.gap_x),
+                        sheet.top + row as f32 * (template.height + sheet.gap_y),
+                    )
+                }
+                None => (0.0, 0.0),
+            };
+            let y = page_height - top - template.height;
+
+            draw_label(&mut content, font_name, label, x, y, template.width, template.height)?;
+        }
+        pdf.stream(content_id, &content.finish());
+    }
+
+    Ok(pdf.finish())
+}
+
+/// Draw one label with its bottom left corner at x, y (in millimeters).
+fn draw_label(content: &mut Content, font_name: Name, label: &Label, x: f32, y: f32, width: f32, height: f32) -> Result<(), qrcode::types::QrError> {
+    let code = QrCode::with_error_correction_level(&label.link, EcLevel::M)?;
+    let modules = code.width();
+    let colors = code.to_colors();
+
+    // The QR code fills the height of the label, the text goes right next to it.
+    // It keeps a quiet zone of two modules, the padding is a part of that.
+    let qr_size = height - 2.0 * PADDING;
+    let module = qr_size / (modules + 4) as f32;
+    let qr_x = x + PADDING + 2.0 * module;
+    let qr_y = y + PADDING + 2.0 * module;
+
+    content.set_fill_gray(0.0);
+    for row in 0..modules {
+        // Dark modules next to each other are drawn as one rectangle
+        let mut column = 0;
+        while column < modules {
+            if colors[row * modules + column] == Color::Light {
+                column += 1;
+                continue;
+            }
+
+            let start = column;
+            while column < modules && colors[row * modules + column] == Color::Dark {
+                column += 1;
+            }
+            content.rect(
+                points(qr_x + start as f32 * module),
+                points(qr_y + (modules - row - 1) as f32 * module),
+                points((column - start) as f32 * module),
+                points(module),
+            );
+        }
+    }
+    content.fill_nonzero();
+
+    let text_x = x + qr_size + PADDING;
+    let text_width = width - qr_size - 2.0 * PADDING;
+    let name_size = (points(height) / 4.0).min(12.0);
+    let path_size = name_size * 0.75;
+
+    content.begin_text();
+    content.set_font(font_name, name_size);
+    content.next_line(points(text_x), points(y + height - PADDING) - name_size);
+    content.show(Str(&win_ansi(&fit_text(&label.name, text_width, name_size))));
+    content.set_font(font_name, path_size);
+    content.next_line(0.0, -path_size * 1.5);
+    content.show(Str(&win_ansi(&fit_text(&label.path, text_width, path_size))));
+    content.end_text();
+
+    Ok(())
+}
+
+/// Render the labels as ZPL for Zebra printers, one label after the other.
+pub(crate) fn render_zpl(template: &LabelTemplate, labels: &[Label]) -> Result<String, qrcode::types::QrError> {
+    let dots = |mm: f32| (mm * ZPL_DOTS_PER_MM).round() as u32;
+    let (width, height, padding) = (dots(template.width), dots(template.height), dots(PADDING));
+
+    let mut zpl = String::new();
+    for label in labels {
+        // The printer encodes the QR code itself, but it needs the magnification.
+        // The size is calculated the same way as for the PDF.
+        let modules = QrCode::with_error_correction_level(&label.link, EcLevel::M)?.width() as u32;
+        let qr_size = height - 2 * padding;
+        let magnification = (qr_size / (modules + 4)).clamp(1, 10);
+
+        let text_x = padding + qr_size + padding;
+        let text_width = width.saturating_sub(text_x + padding);
+        let name_size = (height / 5).min(40);
+        let path_size = name_size * 3 / 4;
+
+        zpl.push_str("^XA^CI28");
+        zpl.push_str(&format!("^PW{width}^LL{height}"));
+        zpl.push_str(&format!("^FO{padding},{padding}^BQN,2,{magnification}^FH^FDMA,{}^FS", zpl_text(&label.link)));
+        zpl.push_str(&format!(
+            "^FO{text_x},{padding}^A0N,{name_size},{name_size}^FB{text_width},2,0,L^FH^FD{}^FS",
+            zpl_text(&label.name)
+        ));
+        zpl.push_str(&format!(
+            "^FO{text_x},{}^A0N,{path_size},{path_size}^FB{text_width},2,0,L^FH^FD{}^FS",
+            padding + 2 * name_size + name_size / 2,
+            zpl_text(&label.path)
+        ));
+        zpl.push_str("^XZ\n");
+    }
+
+    Ok(zpl)
+}
+
+/// Escape the characters that have a meaning in ZPL (with ^FH, "_" starts a hex escape).
+fn zpl_text(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '^' => "_5E".to_owned(),
+            '~' => "_7E".to_owned(),
+            '_' => "_5F".to_owned(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Shorten the text to the width, based on the average width of Helvetica.
+fn fit_text(text: &str, width_mm: f32, font_size: f32) -> String {
+    let max_chars = (points(width_mm) / (font_size * 0.55)) as usize;
+    if text.chars().count() <= max_chars {
+        return text.to_owned();
+    }
+
+    let mut shortened: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    shortened.push('…');
+    shortened
+}
+
+/// The built-in fonts only know Latin-1 characters (and a few more).
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '…' => 0x85,
+            c if (c as u32) < 0x80 || (0xA0..=0xFF).contains(&(c as u32)) => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+fn points(mm: f32) -> f32 {
+    mm * 72.0 / 25.4
+}
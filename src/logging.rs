@@ -0,0 +1,79 @@
+use std::future::Future;
+use std::io::Write;
+use std::time::Duration;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header;
+use env_logger::fmt::Formatter;
+use log::{info, Record};
+
+use crate::error::RequestId;
+
+tokio::task_local! {
+    /// The id of the request that is handled by the current task.
+    static CURRENT_REQUEST: RequestId;
+}
+
+/// Set up the logger. Every line that is logged while a request
+/// is handled gets the id of the request (see [in_request]).
+/// The JSON format writes one object per line, e.g. for log collectors.
+pub(crate) fn init(json: bool) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if json {
+        builder.format(write_json);
+    } else {
+        builder.format(write_text);
+    }
+    builder.init();
+}
+
+/// Handle the request with its id attached to the log lines.
+pub(crate) async fn in_request<F: Future>(request_id: RequestId, future: F) -> F::Output {
+    CURRENT_REQUEST.scope(request_id, future).await
+}
+
+/// One line for every handled request, like the access log of a web server.
+/// It's logged as soon as the response is ready, so the request id is still known.
+pub(crate) fn log_request<B>(res: &ServiceResponse<B>, duration: Duration) {
+    let req = res.request();
+    let header = |name| req.headers().get(name).and_then(|value| value.to_str().ok()).unwrap_or("-");
+
+    info!(
+        "{} \"{} {} {:?}\" {} \"{}\" \"{}\" {:.6}",
+        req.peer_addr().map_or_else(|| "-".to_owned(), |addr| addr.ip().to_string()),
+        req.method(),
+        req.uri(),
+        req.version(),
+        res.status().as_u16(),
+        header(header::REFERER),
+        header(header::USER_AGENT),
+        duration.as_secs_f64(),
+    );
+}
+
+fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST.try_with(|request_id| request_id.0.clone()).ok()
+}
+
+/// Like the default format of env_logger, with the request id after the target.
+fn write_text(buf: &mut Formatter, record: &Record) -> std::io::Result<()> {
+    let level_style = buf.default_level_style(record.level());
+    write!(buf, "[{} {:<5} {}", buf.timestamp(), level_style.value(record.level()), record.target())?;
+    if let Some(request_id) = current_request_id() {
+        write!(buf, " {request_id}")?;
+    }
+    writeln!(buf, "] {}", record.args())
+}
+
+fn write_json(buf: &mut Formatter, record: &Record) -> std::io::Result<()> {
+    let mut line = serde_json::json!({
+        "timestamp": buf.timestamp().to_string(),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    if let Some(request_id) = current_request_id() {
+        line["request_id"] = request_id.into();
+    }
+    writeln!(buf, "{line}")
+}
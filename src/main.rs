@@ -1,26 +1,39 @@
-use std::{fs::File, io::BufReader, time::Duration};
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use actix_web::http::StatusCode;
-use actix_web::middleware::{ErrorHandlers, Logger};
-use actix_web::{web, App, HttpServer};
+use actix_web::dev::Service;
+use actix_web::{web, App, HttpMessage, HttpServer};
+use futures_util::FutureExt;
 use rustls::ServerConfig;
 use sqlx::mysql::MySqlPoolOptions;
+use tracing::Instrument;
 
+use events::Events;
+use storage::blob::{BlobStore, LocalBlobStore};
+use storage::{memory::MemoryStore, sql::SqlStore, FileStore, Stores, TrashStore};
+
+mod error;
+mod events;
+mod graphql;
+mod icons;
+mod images;
+mod labels;
+mod logging;
 mod macros;
+mod metrics;
 mod models;
+mod openapi;
+mod properties;
+mod storage;
+mod telemetry;
 mod web_handlers;
 
 #[rustfmt::skip]
 async fn run() -> Result<(), String> {
-    // Setup logger
-    if std::env::var("RUST_LOG").is_err() {
-        std::env::set_var("RUST_LOG", "info");
-    }
-    if std::env::var("RUST_BACKTRACE").is_err() {
-        std::env::set_var("RUST_BACKTRACE", "1");
-    }
-    env_logger::init();
-
     // Load user preferences from config file and environment.
     // Environment variables override the config file!
     let settings = config::Config::builder()
@@ -29,6 +42,22 @@ async fn run() -> Result<(), String> {
         .add_source(config::Environment::with_prefix("APP"))
         .build().map_err(|err| err.to_string())?;
 
+    // Setup logger. With "log_format" set to "json", every line is a JSON object
+    if std::env::var("RUST_LOG").is_err() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    if std::env::var("RUST_BACKTRACE").is_err() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+    }
+    logging::init(settings.get_string("log_format").is_ok_and(|format| format.eq_ignore_ascii_case("json")));
+
+    // Distributed tracing of the requests and the sql queries. "otlp" sends the spans
+    // to the collector at "otlp_endpoint", "stdout" prints them (e.g. for tests)
+    let tracer_provider = telemetry::init(
+        settings.get_string("tracing_exporter").ok().as_deref(),
+        &settings.get_string("otlp_endpoint").unwrap_or_else(|_| "http://localhost:4318/v1/traces".to_owned()),
+    )?;
+
     // Get port and host from config, or use the default port and host: 0.0.0.0:8081
     let host: String = settings.get_string("host").unwrap_or_else(|_| "0.0.0.0".to_owned());
     let port: u16 = settings.get_int("port").unwrap_or(8081).try_into().map_err(|_| "Port number can't be over 65535!")?;
@@ -45,11 +74,45 @@ async fn run() -> Result<(), String> {
         .filter_map(|element| element.into_string().ok())
         .collect();
 
+    // Users with access to the admin services (e.g. full backups)
+    let admins = web::Data::new(web_handlers::auth::Admins(
+        settings.get_array("admin_users")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|element| element.into_int().ok())
+            .filter_map(|user_id| u64::try_from(user_id).ok())
+            .collect(),
+    ));
+
     // Static serving config
     let static_serving: bool = settings.get_bool("static_serving").unwrap_or(true);
     let static_dir: String = settings.get_string("static_dir").unwrap_or_else(|_| "./static".to_owned());
     let index_file: String = settings.get_string("index_file").unwrap_or_else(|_| "index.html".to_owned());
 
+    // The url of the web UI for the links on the printed labels (e.g. "https://storage.example.com").
+    // Labels can only be printed if it's set.
+    let public_url = web::Data::new(web_handlers::label::PublicUrl(settings.get_string("public_url").ok()));
+
+    // Uploaded files (item images and attachments)
+    let upload_dir: String = settings.get_string("upload_dir").unwrap_or_else(|_| "./uploads".to_owned());
+    let blobs: Arc<dyn BlobStore> = Arc::new(LocalBlobStore::new(&upload_dir)
+        .map_err(|err| format!("Cannot create upload directory {upload_dir}! (error: {err})"))?);
+
+    // The api description for "/api/openapi.json". The url depends on the static serving
+    let api_description = web::Data::new(openapi::api_description(if static_serving {"/api"} else {"/"}));
+
+    // Notifications for the clients (e.g. low stock alerts)
+    let events = web::Data::new(Events::new());
+
+    // The Prometheus metrics. With a token, "/metrics" needs "Authorization: Bearer <token>".
+    // With a port of its own, it's only served there (e.g. on a port that isn't public)
+    let metrics = web::Data::new(metrics::Metrics::new());
+    let metrics_token = web::Data::new(web_handlers::metrics::MetricsToken(settings.get_string("metrics_token").ok()));
+    let metrics_port: Option<u16> = match settings.get_int("metrics_port") {
+        Ok(metrics_port) => Some(metrics_port.try_into().map_err(|_| "Metrics port number can't be over 65535!")?),
+        Err(_) => None,
+    };
+
     // Workers
     let num_workers: usize = settings.get_int("workers").unwrap_or(2).try_into().map_err(|_| "Too many workers!")?;
     let num_connections: u32 = settings.get_int("pool_connections")
@@ -58,35 +121,75 @@ async fn run() -> Result<(), String> {
 
     // Database config
     let db_type = settings.get_string("db_type").map_err(|_| "DB type is not specified!")?;
-    if !db_type.eq_ignore_ascii_case("mysql") {
+    let stores = if db_type.eq_ignore_ascii_case("mysql") {
+        let db_host = settings.get_string("db_host").map_err(|_| "DB host is not specified!")?;
+        let db_port: u16 = settings.get_int("db_port").unwrap_or(3306).try_into().map_err(|_| "DB port number can't be over 65535!")?;
+        let db_user = settings.get_string("db_user").map_err(|_| "DB user is not specified!")?;
+        let db_password = settings.get_string("db_password").map_err(|_| "DB password is not specified!")?;
+        let db_database = settings.get_string("db_database").map_err(|_| "DB database is not specified!")?;
+        let db_url = format!("{db_type}://{db_user}:{db_password}@{db_host}:{db_port}/{db_database}");
+
+        // Establish MySQL server connection (Timeout after 15 seconds)
+        let pool = MySqlPoolOptions::new()
+            .max_connections(num_connections)
+            .acquire_timeout(Duration::from_secs(15))
+            .connect(&db_url)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::Tls(msg) if msg.to_string().eq("InvalidDNSNameError") => "Insecure SQL server connection! Domain and specified host don't match!".to_string(),
+                sqlx::Error::Tls(msg) => format!("TLS Error! {msg}"),
+                _ => err.to_string(),
+            })?;
+
+        // Create missing tables or update the existing ones
+        let store = SqlStore::new(pool);
+        store.migrate().await.map_err(|err| format!("Cannot migrate the database! (error: {err})"))?;
+
+        Stores::new(store)
+    } else if db_type.eq_ignore_ascii_case("memory") {
+        // Nothing gets persisted here! Since there is no users
+        // table, the user from the config is the only one that can log in.
+        let store = MemoryStore::new();
+        if let (Ok(db_user), Ok(db_password)) = (settings.get_string("db_user"), settings.get_string("db_password")) {
+            store.add_user(&db_user, &db_password);
+        }
+
+        Stores::new(store)
+    } else {
         return Err("Unsupported database type!".to_owned());
+    };
+
+    // Deleted objects stay in the trash for this many days (0 keeps them forever)
+    let trash_days: i64 = settings.get_int("trash_days").unwrap_or(30);
+    if trash_days > 0 {
+        spawn_trash_purging(stores.trash.clone(), stores.files.clone(), blobs.clone(), trash_days);
     }
 
-    let db_host = settings.get_string("db_host").map_err(|_| "DB host is not specified!")?;
-    let db_port: u16 = settings.get_int("db_port").unwrap_or(3306).try_into().map_err(|_| "DB port number can't be over 65535!")?;
-    let db_user = settings.get_string("db_user").map_err(|_| "DB user is not specified!")?;
-    let db_password = settings.get_string("db_password").map_err(|_| "DB password is not specified!")?;
-    let db_database = settings.get_string("db_database").map_err(|_| "DB database is not specified!")?;
-    let db_url = format!("{db_type}://{db_user}:{db_password}@{db_host}:{db_port}/{db_database}");
-
-    // Establish MySQL server connection (Timeout after 15 seconds)
-    let pool = MySqlPoolOptions::new()
-        .max_connections(num_connections)
-        .acquire_timeout(Duration::from_secs(15))
-        .connect(&db_url)
-        .await
-        .map_err(|err| match err {
-            sqlx::Error::Tls(msg) if msg.to_string().eq("InvalidDNSNameError") => "Insecure SQL server connection! Domain and specified host don't match!".to_string(),
-            sqlx::Error::Tls(msg) => format!("TLS Error! {msg}"),
-            _ => err.to_string(),
-        })?;
+    // The GraphQL api uses the same storage backend as the REST api
+    let graphql_schema = web::Data::new(graphql::schema(stores.clone(), events.clone()));
+    let shared_stores = web::Data::new(stores.clone());
+
+    // The metrics server only needs the data of the "/metrics" service
+    let metrics_server = match metrics_port {
+        Some(metrics_port) => {
+            let (shared_stores, metrics, metrics_token) = (shared_stores.clone(), metrics.clone(), metrics_token.clone());
+            println!("Serving metrics on http://{host}:{metrics_port}/metrics");
+            let metrics_server = HttpServer::new(move || {
+                App::new()
+                    .wrap_fn(|req, srv| srv.call(req).map(|res| res.map(error::problem_details)))
+                    .app_data(shared_stores.clone())
+                    .app_data(metrics.clone())
+                    .app_data(metrics_token.clone())
+                    .service(web_handlers::metrics::get_metrics)
+            });
+            Some(metrics_server.bind((host.as_str(), metrics_port)).map_err(|err| err.to_string())?.workers(1).run())
+        }
+        None => None,
+    };
 
     // Setup server
     println!("Starting server on {protocol}://{host}:{port}", protocol = if use_ssl {"https"} else {"http"});
     let mut server = HttpServer::new(move || {
-        // Create a simple logger that writes all incoming requests to the console
-        let logger = Logger::default();
-
         // Cross-Origin Requests
         let mut cors = actix_cors::Cors::default().allow_any_header().allow_any_method().max_age(3600);
         if allowed_domains.is_empty() {
@@ -99,15 +202,72 @@ async fn run() -> Result<(), String> {
 
         // Create a new App that handles all client requests
         let mut app = App::new()
-            .wrap(logger)
             .wrap(cors)
 
-            // If an internal error occurs, remove the sensetive content from the response
-            .wrap(ErrorHandlers::new().handler(StatusCode::INTERNAL_SERVER_ERROR, web_handlers::sanitize_internal_error))
+            // Give every request an id (or keep the one of a proxy), log it and turn all errors into
+            // problem details (RFC 7807). If an internal error occurs, the sensitive content is
+            // removed from the response. Everything that's logged meanwhile gets the request id,
+            // everything that's traced (e.g. the sql queries) ends up in the span of the request.
+            .wrap_fn(|req, srv| {
+                let request_id = error::RequestId::from_headers(req.headers());
+                req.extensions_mut().insert(request_id.clone());
+                let span = telemetry::request_span(&req, &request_id);
+                let start = Instant::now();
 
-            // Provide a clone of the reference to the db pool
+                let response = srv.call(req).map({
+                    let (request_id, span) = (request_id.clone(), span.clone());
+                    move |res| {
+                        res.map(|res| {
+                            let res = request_id.attach(error::problem_details(res));
+                            telemetry::record_response(&span, &res);
+                            logging::log_request(&res, start.elapsed());
+                            res
+                        })
+                    }
+                });
+                logging::in_request(request_id, response).instrument(span)
+            })
+
+            // Count the requests and measure how long they took
+            .wrap_fn({
+                let metrics = metrics.clone();
+                move |req, srv| {
+                    let metrics = metrics.clone();
+                    let start = Instant::now();
+                    srv.call(req).map(move |res| {
+                        if let Ok(res) = &res {
+                            metrics.observe_request(res.request(), res.status(), start.elapsed());
+                        }
+                        res
+                    })
+                }
+            })
+
+            // Provide a clone of the references to the storage backend
             // to enable services to access the database
-            .app_data(actix_web::web::Data::new(pool.clone()))
+            .app_data(web::Data::from(stores.items.clone()))
+            .app_data(web::Data::from(stores.tags.clone()))
+            .app_data(web::Data::from(stores.locations.clone()))
+            .app_data(web::Data::from(stores.databases.clone()))
+            .app_data(web::Data::from(stores.sessions.clone()))
+            .app_data(web::Data::from(stores.backups.clone()))
+            .app_data(web::Data::from(stores.files.clone()))
+            .app_data(web::Data::from(stores.loans.clone()))
+            .app_data(web::Data::from(stores.movements.clone()))
+            .app_data(web::Data::from(stores.revisions.clone()))
+            .app_data(web::Data::from(stores.trash.clone()))
+            .app_data(web::Data::from(stores.templates.clone()))
+            .app_data(web::Data::from(stores.codes.clone()))
+            .app_data(web::Data::from(stores.icons.clone()))
+            .app_data(web::Data::from(blobs.clone()))
+            .app_data(events.clone())
+            .app_data(admins.clone())
+            .app_data(public_url.clone())
+            .app_data(api_description.clone())
+            .app_data(graphql_schema.clone())
+            .app_data(shared_stores.clone())
+            .app_data(metrics.clone())
+            .app_data(metrics_token.clone())
 
             // If the user wants to serve static files (in addition to the api),
             // move the api to a sub layer: '/' => '/api'
@@ -115,6 +275,9 @@ async fn run() -> Result<(), String> {
                 //.guard(guard::Header("Content-Type", "application/json"))
                 .default_service(web::route().to(web_handlers::not_implemented))
                 .service(web_handlers::get_system_info)
+                .service(web_handlers::get_openapi)
+                .configure(web_handlers::api_docs)
+                .service(web_handlers::graphql::post_graphql)
                 .service(web_handlers::teapod)
                 .service(web::scope("/v1")
                     .default_service(web::route().to(web_handlers::not_implemented))
@@ -124,16 +287,61 @@ async fn run() -> Result<(), String> {
 
                     // Restricted access
                     .service(web_handlers::auth::delete_auth)
+                    .service(web_handlers::event::get_events)
+                    .service(web_handlers::scan::scan_code)
+                    .service(web_handlers::scan::get_codes)
+                    .service(web_handlers::scan::put_code)
+                    .service(web_handlers::scan::delete_code)
+                    .service(web_handlers::label::get_labels)
+                    .service(web_handlers::stock::get_low_stock_items)
+                    .service(web_handlers::stock::get_shopping_list)
+                    .service(web_handlers::stock::adjust_amounts)
                     .service(web_handlers::item::get_items)
                     .service(web_handlers::item::get_item)
+                    .service(web_handlers::item::get_item_contents)
                     .service(web_handlers::item::put_item)
+                    .service(web_handlers::item::create_item_from_template)
+                    .service(web_handlers::item::duplicate_item)
                     .service(web_handlers::item::update_item)
                     .service(web_handlers::item::delete_item)
+                    .service(web_handlers::file::put_item_image)
+                    .service(web_handlers::file::get_item_image)
+                    .service(web_handlers::file::delete_item_image)
+                    .service(web_handlers::file::post_item_attachment)
+                    .service(web_handlers::file::get_item_attachment)
+                    .service(web_handlers::file::delete_item_attachment)
+                    .service(web_handlers::revision::get_item_history)
+                    .service(web_handlers::revision::get_item_revision)
+                    .service(web_handlers::revision::get_item_diff)
+                    .service(web_handlers::revision::restore_item_revision)
+                    .service(web_handlers::movement::get_item_movements)
+                    .service(web_handlers::movement::post_item_movement)
+                    .service(web_handlers::loan::get_loans)
+                    .service(web_handlers::loan::get_loan)
+                    .service(web_handlers::loan::get_item_loans)
+                    .service(web_handlers::loan::check_out_item)
+                    .service(web_handlers::loan::check_in_loan)
+                    .service(web_handlers::item_csv::export_items_csv)
+                    .service(web_handlers::item_csv::import_items_csv)
                     .service(web_handlers::tag::get_tags)
                     .service(web_handlers::tag::get_tag)
                     .service(web_handlers::tag::put_tag)
                     .service(web_handlers::tag::update_tag)
                     .service(web_handlers::tag::delete_tag)
+                    .service(web_handlers::tag::merge_tag)
+                    .service(web_handlers::icon::get_icons)
+                    .service(web_handlers::icon::get_icon)
+                    .service(web_handlers::icon::put_icon)
+                    .service(web_handlers::icon::delete_icon)
+                    .service(web_handlers::template::get_templates)
+                    .service(web_handlers::template::get_template)
+                    .service(web_handlers::template::put_template)
+                    .service(web_handlers::template::update_template)
+                    .service(web_handlers::template::delete_template)
+                    .service(web_handlers::backup::export_database)
+                    .service(web_handlers::backup::export_all)
+                    .service(web_handlers::backup::restore_database)
+                    .service(web_handlers::backup::restore_all)
                     .service(web_handlers::database::get_databases)
                     .service(web_handlers::database::get_database)
                     .service(web_handlers::database::put_database)
@@ -141,12 +349,24 @@ async fn run() -> Result<(), String> {
                     .service(web_handlers::database::delete_database)
                     .service(web_handlers::location::get_locations)
                     .service(web_handlers::location::get_location)
+                    .service(web_handlers::location::get_location_tree)
                     .service(web_handlers::location::put_location)
                     .service(web_handlers::location::update_location)
                     .service(web_handlers::location::delete_location)
+                    .service(web_handlers::trash::get_trash)
+                    .service(web_handlers::trash::restore_trash_entry)
+                    .service(web_handlers::trash::empty_trash)
                 )
             );
 
+        // The health checks for the orchestrator (e.g. Docker or Kubernetes)
+        app = app.service(web_handlers::health::get_liveness).service(web_handlers::health::get_readiness);
+
+        // Without a port of its own, "/metrics" is served next to the api
+        if metrics_port.is_none() {
+            app = app.service(web_handlers::metrics::get_metrics);
+        }
+
         // After registering the api services, register the static file service.
         // If the user doesn't need static serving, this step will be skipped
         if static_serving {
@@ -189,11 +409,57 @@ async fn run() -> Result<(), String> {
         server.bind((host, port)).map_err(|err| err.to_string())?
     };
 
-    server.workers(num_workers).run().await.map_err(|err| err.to_string())
+    let server = server.workers(num_workers).run();
+    let result = match metrics_server {
+        Some(metrics_server) => futures::future::try_join(server, metrics_server).await.map(|_| ()),
+        None => server.await,
+    };
+
+    // Export the spans that are still waiting
+    if let Some(tracer_provider) = tracer_provider {
+        if let Err(err) = tracer_provider.shutdown() {
+            log::warn!("Couldn't export the last spans: {err}");
+        }
+    }
+
+    result.map_err(|err| err.to_string())
+}
+
+/// Purge the old objects from the trash once an hour.
+fn spawn_trash_purging(trash: Arc<dyn TrashStore>, files: Arc<dyn FileStore>, blobs: Arc<dyn BlobStore>, trash_days: i64) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+
+            let deleted_before = web_handlers::unix_now() - trash_days * 24 * 60 * 60;
+            match trash.purge_trash(deleted_before).await {
+                Ok(0) => {}
+                Ok(purged) => {
+                    log::info!("Purged {purged} objects from the trash");
+                    web_handlers::file::remove_all_unused_blobs(&*files, &*blobs).await;
+                }
+                Err(err) => log::warn!("Couldn't purge the trash: {err}"),
+            }
+        }
+    });
 }
 
 #[actix_web::main]
 async fn main() {
+    // "storagereloaded openapi" prints the api description instead of starting
+    // the server. The CI compares it with the last version to find breaking changes.
+    if std::env::args().nth(1).as_deref() == Some("openapi") {
+        match openapi::api_description("/api").to_pretty_json() {
+            Ok(json) => println!("{json}"),
+            Err(err) => {
+                eprintln!("[Error] {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let result = run().await;
 
     std::process::exit(match result {
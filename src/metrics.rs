@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::HttpRequest;
+
+/// The upper bounds of the buckets of the latency histogram (seconds).
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// The requests with the same method, route and status are counted together.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct RequestLabels {
+    method: String,
+
+    /// The pattern of the route (e.g. "/api/v1/item/{item_id}"), so every item doesn't get its own series
+    route: String,
+    status: u16,
+}
+
+#[derive(Default)]
+struct RequestStats {
+    count: u64,
+    duration_sum: f64,
+
+    /// The number of requests per bucket (not cumulative)
+    buckets: [u64; LATENCY_BUCKETS.len()],
+}
+
+/// Counts the requests and how long they took, for the "/metrics" service.
+/// Everything else in the metrics is read from the stores when it's scraped.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    requests: Mutex<BTreeMap<RequestLabels, RequestStats>>,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub(crate) fn observe_request(&self, req: &HttpRequest, status: StatusCode, duration: Duration) {
+        let labels = RequestLabels {
+            method: req.method().to_string(),
+            route: req.match_pattern().unwrap_or_else(|| "unmatched".to_owned()),
+            status: status.as_u16(),
+        };
+        let seconds = duration.as_secs_f64();
+
+        let mut requests = self.requests.lock().unwrap_or_else(|err| err.into_inner());
+        let stats = requests.entry(labels).or_default();
+        stats.count += 1;
+        stats.duration_sum += seconds;
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            stats.buckets[bucket] += 1;
+        }
+    }
+
+    /// Write the request counts and the latency histograms.
+    pub(crate) fn write_requests(&self, out: &mut MetricsText) {
+        let requests = self.requests.lock().unwrap_or_else(|err| err.into_inner());
+
+        out.family("store_http_requests_total", "counter", "The handled HTTP requests");
+        for (labels, stats) in requests.iter() {
+            let status = labels.status.to_string();
+            out.sample(
+                "store_http_requests_total",
+                &[("method", &labels.method), ("route", &labels.route), ("status", &status)],
+                stats.count,
+            );
+        }
+
+        out.family("store_http_request_duration_seconds", "histogram", "How long it took to handle the HTTP requests");
+        for (labels, stats) in requests.iter() {
+            let status = labels.status.to_string();
+            let label_values = [("method", labels.method.as_str()), ("route", &labels.route), ("status", &status)];
+
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+                cumulative += count;
+                let bound = bound.to_string();
+                out.sample("store_http_request_duration_seconds_bucket", &[&label_values[..], &[("le", &bound)]].concat(), cumulative);
+            }
+            out.sample("store_http_request_duration_seconds_bucket", &[&label_values[..], &[("le", "+Inf")]].concat(), stats.count);
+            out.sample("store_http_request_duration_seconds_sum", &label_values, stats.duration_sum);
+            out.sample("store_http_request_duration_seconds_count", &label_values, stats.count);
+        }
+    }
+}
+
+/// The Prometheus text format (version 0.0.4).
+#[derive(Default)]
+pub(crate) struct MetricsText(String);
+
+impl MetricsText {
+    pub(crate) const CONTENT_TYPE: &'static str = "text/plain; version=0.0.4; charset=utf-8";
+
+    /// Start a metric with its type ("counter", "gauge" or "histogram") and description.
+    pub(crate) fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {name} {help}");
+        let _ = writeln!(self.0, "# TYPE {name} {kind}");
+    }
+
+    pub(crate) fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        self.0.push_str(name);
+        if !labels.is_empty() {
+            self.0.push('{');
+            for (index, (label, label_value)) in labels.iter().enumerate() {
+                if index > 0 {
+                    self.0.push(',');
+                }
+                let _ = write!(self.0, "{label}=\"{}\"", escape_label_value(label_value));
+            }
+            self.0.push('}');
+        }
+        let _ = writeln!(self.0, " {value}");
+    }
+
+    pub(crate) fn into_string(self) -> String {
+        self.0
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
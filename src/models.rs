@@ -1,7 +1,9 @@
+use async_graphql::{Enum, InputObject, SimpleObject};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
 pub struct UserCredentials {
     pub username: String,
     pub password: String,
@@ -9,21 +11,45 @@ pub struct UserCredentials {
 
 /// If this struct is a parameter in an actix service,
 /// it becomes a protected service
-#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, Debug)]
 pub struct AuthedUser {
     pub session_id: String,
     pub user_id: u64,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Like [AuthedUser], but the service
+/// is restricted to admins only
+#[derive(Debug)]
+pub struct AdminUser(pub AuthedUser);
+
+/// The files of the image and the attachments are uploaded
+/// with the REST services, in GraphQL only the urls can be set.
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, InputObject, Debug)]
+#[graphql(complex, input_name = "ItemInput")]
 pub struct Item {
+    #[graphql(default)]
     pub id: u64,
     pub name: String,
     pub description: String,
     pub image: Option<String>,
+
+    /// Items inside of a container always share its location.
+    /// Moving the container moves all of its contents as well.
+    #[graphql(name = "locationId")]
     pub location: u64,
+
+    /// The item (e.g. a toolbox) this item is inside of
+    #[serde(default)]
+    #[graphql(name = "parentItemId")]
+    pub parent_item: Option<u64>,
+    #[graphql(name = "tagIds")]
     pub tags: Vec<u64>,
     pub amount: u64,
+
+    /// The minimum stock level. If the amount drops to (or below)
+    /// this level, the item is low on stock and should be restocked.
+    #[serde(default)]
+    pub min_amount: Option<u64>,
     pub properties_internal: Vec<Property>,
     pub properties_custom: Vec<Property>,
     pub attachments: HashMap<String, String>,
@@ -31,30 +57,380 @@ pub struct Item {
     pub created: i64,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+impl Item {
+    pub fn is_low_stock(&self) -> bool {
+        self.min_amount.is_some_and(|min_amount| self.amount <= min_amount)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, InputObject, Debug)]
+#[graphql(input_name = "PropertyInput")]
 pub struct Property {
     pub name: String,
     pub value: String,
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, Debug)]
+/// The prefilled fields of new items, for items that are nearly the
+/// same (e.g. screws of different sizes). The template is only a
+/// starting point, changing it doesn't change the items created from it.
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, InputObject, Debug)]
+#[graphql(complex, input_name = "ItemTemplateInput")]
+pub struct ItemTemplate {
+    #[graphql(default)]
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    #[graphql(default)]
+    pub description: String,
+
+    /// The location of new items, unless they specify their own
+    #[serde(default)]
+    #[graphql(name = "locationId")]
+    pub location: Option<u64>,
+    #[serde(default)]
+    #[graphql(name = "tagIds", default)]
+    pub tags: Vec<u64>,
+    #[serde(default)]
+    #[graphql(default)]
+    pub properties_internal: Vec<Property>,
+    #[serde(default)]
+    #[graphql(default)]
+    pub properties_custom: Vec<Property>,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, ToSchema, SimpleObject, InputObject, Debug)]
+#[graphql(complex, input_name = "TagInput")]
 pub struct Tag {
+    #[graphql(default)]
     pub id: u64,
     pub name: String,
     pub color: u32,
     pub icon: Option<u64>,
+
+    /// The tag this one is below, e.g. "Cables" below "Electronics".
+    /// Filtering the items by a tag includes its child tags.
+    #[serde(default)]
+    #[sqlx(rename = "parent_id")]
+    #[graphql(name = "parentId")]
+    pub parent: Option<u64>,
+
+    /// The database the tag can be used in. Tags without a database are global.
+    #[serde(default)]
+    #[sqlx(rename = "database_id")]
+    #[graphql(name = "databaseId")]
+    pub database: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, Debug)]
+/// An icon for tags, locations and databases. The built-in icons are
+/// part of the server, all others were uploaded by the users.
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, Debug)]
+pub struct Icon {
+    pub id: u64,
+    pub name: String,
+    pub mime_type: String,
+    pub builtin: bool,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, ToSchema, SimpleObject, InputObject, Debug)]
+#[graphql(complex, input_name = "LocationInput")]
 pub struct Location {
+    #[graphql(default)]
     pub id: u64,
     pub name: String,
     #[sqlx(rename = "database_id")]
+    #[graphql(name = "databaseId")]
     pub database: u64,
+
+    /// The location this one is inside of. It must be in the
+    /// same database. Top level locations don't have a parent.
+    #[serde(default)]
+    #[sqlx(rename = "parent_id")]
+    #[graphql(name = "parentId")]
+    pub parent: Option<u64>,
+    #[serde(default)]
+    pub icon: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, InputObject, Debug)]
+#[graphql(complex, input_name = "DatabaseInput")]
 pub struct Database {
+    #[graphql(default)]
     pub id: u64,
     pub name: String,
+
+    /// The custom properties the items of this database have
+    #[serde(default)]
+    #[graphql(default)]
+    pub properties: Vec<PropertyField>,
+    #[serde(default)]
+    pub icon: Option<u64>,
+}
+
+/// A custom property in the schema of a database. The values of the
+/// property are still stored as strings, but they have to match the type.
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, InputObject, Debug)]
+#[graphql(input_name = "PropertyFieldInput")]
+pub struct PropertyField {
+    pub name: String,
+    #[serde(rename = "type")]
+    #[graphql(name = "type")]
+    pub kind: PropertyType,
+
+    /// Items without a value for the property are rejected
+    #[serde(default)]
+    #[graphql(default)]
+    pub required: bool,
+
+    /// The value of items that don't have one
+    #[serde(default)]
+    pub default: Option<String>,
+
+    /// The allowed values of an enum property
+    #[serde(default)]
+    #[graphql(default)]
+    pub options: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema, Enum, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PropertyType {
+    Text,
+    Number,
+    /// A calendar date like "2024-12-31"
+    Date,
+    /// Either "true" or "false"
+    Boolean,
+    /// One of the options of the field
+    Enum,
+    Url,
+    /// An amount of money with at most two decimal places, like "12.50"
+    Currency,
+}
+
+/// A versioned snapshot of one or more databases with everything inside of them.
+/// The tags of the databases are included, but global tags only if they are in use.
+/// Uploaded images and attachments aren't included, restored items lose them.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+pub struct Backup {
+    pub version: u32,
+    pub created: i64,
+    pub databases: Vec<DatabaseBackup>,
+    pub tags: Vec<Tag>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+pub struct DatabaseBackup {
+    pub database: Database,
+    pub locations: Vec<Location>,
+    pub items: Vec<Item>,
+}
+
+/// A file that was uploaded for an item, either its image or an attachment.
+/// The content is addressed by its hash, so identical files are only stored once.
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, ToSchema, Debug)]
+pub struct ItemFile {
+    pub is_image: bool,
+    pub name: String,
+    pub hash: String,
+    pub mime_type: String,
+    pub size: u64,
+}
+
+/// An item (or a part of its amount) that is lent to someone.
+/// All timestamps are unix timestamps (seconds).
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, Debug)]
+#[graphql(complex)]
+pub struct Loan {
+    pub id: u64,
+    #[graphql(name = "itemId")]
+    pub item: u64,
+    pub amount: u64,
+
+    /// The borrower, if it's a user of this server
+    #[serde(default)]
+    pub user: Option<u64>,
+
+    /// The name of the borrower (can be anyone)
+    #[serde(default)]
+    pub borrower: String,
+    pub lent: i64,
+    #[serde(default)]
+    pub due: Option<i64>,
+
+    /// When the item was checked back in. Open loans don't have this.
+    #[serde(default)]
+    pub returned: Option<i64>,
+}
+
+/// A scannable code on a label, either of an item or of a location.
+/// Every code belongs to exactly one of them.
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, Debug)]
+pub struct ScanCode {
+    pub code: String,
+    pub kind: CodeKind,
+    #[serde(default)]
+    pub item: Option<u64>,
+    #[serde(default)]
+    pub location: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema, Enum, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeKind {
+    /// EAN-8 or EAN-13 barcode
+    Ean,
+
+    /// UPC-A barcode
+    Upc,
+
+    /// QR code with any payload
+    Qr,
+
+    /// The UID of an NFC tag as hex bytes
+    Nfc,
+
+    /// Any other kind of code
+    Other,
+}
+
+impl CodeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CodeKind::Ean => "ean",
+            CodeKind::Upc => "upc",
+            CodeKind::Qr => "qr",
+            CodeKind::Nfc => "nfc",
+            CodeKind::Other => "other",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "ean" => Some(CodeKind::Ean),
+            "upc" => Some(CodeKind::Upc),
+            "qr" => Some(CodeKind::Qr),
+            "nfc" => Some(CodeKind::Nfc),
+            "other" => Some(CodeKind::Other),
+            _ => None,
+        }
+    }
+}
+
+/// What changed the amount or the location of an item.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema, Enum, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MovementKind {
+    /// New pieces were added (e.g. bought)
+    Receive,
+
+    /// Pieces were used up or thrown away
+    Consume,
+
+    /// The amount was corrected (e.g. after counting)
+    Adjust,
+
+    /// The item was moved to another location
+    Transfer,
+}
+
+impl MovementKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MovementKind::Receive => "receive",
+            MovementKind::Consume => "consume",
+            MovementKind::Adjust => "adjust",
+            MovementKind::Transfer => "transfer",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "receive" => Some(MovementKind::Receive),
+            "consume" => Some(MovementKind::Consume),
+            "adjust" => Some(MovementKind::Adjust),
+            "transfer" => Some(MovementKind::Transfer),
+            _ => None,
+        }
+    }
+}
+
+/// An entry in the stock ledger of an item. Every change of
+/// the amount or the location of an item is recorded as one.
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, Debug)]
+pub struct Movement {
+    pub id: u64,
+    pub item: u64,
+    pub kind: MovementKind,
+
+    /// The change of the amount (negative if something was taken away).
+    /// For transfers, this is the amount that was moved.
+    pub amount: i64,
+
+    /// Where the item is after the movement (unknown if the location was deleted)
+    pub location: Option<u64>,
+
+    /// Where the item was moved away from (only for transfers)
+    #[serde(default)]
+    pub from_location: Option<u64>,
+    pub reason: String,
+
+    /// Who did it, if it was a user of this server
+    pub user: Option<u64>,
+
+    /// Unix timestamp (seconds)
+    pub time: i64,
+}
+
+/// A snapshot of an item, saved every time the item is changed.
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, Debug)]
+pub struct ItemRevision {
+    pub item: u64,
+
+    /// Counts up from 1 for every item
+    pub revision: u64,
+
+    /// Who changed the item, if it was a user of this server
+    pub user: Option<u64>,
+
+    /// Unix timestamp (seconds)
+    pub time: i64,
+    pub reason: String,
+
+    /// Whether the item was deleted. The snapshot is the last state before the deletion.
+    pub deleted: bool,
+    pub snapshot: Item,
+}
+
+/// The kinds of objects that end up in the trash when they are deleted.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema, Enum, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashKind {
+    Item,
+    Tag,
+    Location,
+    Database,
+}
+
+impl TrashKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "item" => Some(TrashKind::Item),
+            "tag" => Some(TrashKind::Tag),
+            "location" => Some(TrashKind::Location),
+            "database" => Some(TrashKind::Database),
+            _ => None,
+        }
+    }
+}
+
+/// A deleted object that can still be restored.
+#[derive(Serialize, Deserialize, Clone, ToSchema, SimpleObject, Debug)]
+pub struct TrashEntry {
+    pub kind: TrashKind,
+    pub id: u64,
+    pub name: String,
+
+    /// Unix timestamp (seconds). Objects that were deleted
+    /// together (e.g. a database and its locations) share it.
+    pub deleted_at: i64,
 }
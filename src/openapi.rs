@@ -0,0 +1,158 @@
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::openapi::{Content, Ref, RefOr, ResponseBuilder, Server};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::error::Problem;
+use crate::web_handlers::{self, auth, backup, database, event, file, graphql, icon, item, item_csv, label, loan, location, movement, revision, scan, stock, tag, template, trash};
+
+/// The description of the whole api in the OpenAPI 3 format. The services are documented
+/// at their handlers, this only collects them and adds what all of them have in common.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "StoRe API"),
+    paths(web_handlers::get_system_info, graphql::post_graphql),
+    nest((path = "/v1", api = ApiV1)),
+    components(schemas(Problem)),
+    security(("session" = [])),
+    modifiers(&SessionAuth, &ErrorResponses)
+)]
+struct ApiDoc;
+
+/// The services of version 1, in the same order as they are registered.
+#[derive(OpenApi)]
+#[openapi(paths(
+    auth::get_post_auth,
+    auth::delete_auth,
+    event::get_events,
+    scan::scan_code,
+    scan::get_codes,
+    scan::put_code,
+    scan::delete_code,
+    label::get_labels,
+    stock::get_low_stock_items,
+    stock::get_shopping_list,
+    stock::adjust_amounts,
+    item::get_items,
+    item::get_item,
+    item::get_item_contents,
+    item::put_item,
+    item::create_item_from_template,
+    item::duplicate_item,
+    item::update_item,
+    item::delete_item,
+    file::put_item_image,
+    file::get_item_image,
+    file::delete_item_image,
+    file::post_item_attachment,
+    file::get_item_attachment,
+    file::delete_item_attachment,
+    revision::get_item_history,
+    revision::get_item_revision,
+    revision::get_item_diff,
+    revision::restore_item_revision,
+    movement::get_item_movements,
+    movement::post_item_movement,
+    loan::get_loans,
+    loan::get_loan,
+    loan::get_item_loans,
+    loan::check_out_item,
+    loan::check_in_loan,
+    item_csv::export_items_csv,
+    item_csv::import_items_csv,
+    tag::get_tags,
+    tag::get_tag,
+    tag::put_tag,
+    tag::update_tag,
+    tag::delete_tag,
+    tag::merge_tag,
+    icon::get_icons,
+    icon::get_icon,
+    icon::put_icon,
+    icon::delete_icon,
+    template::get_templates,
+    template::get_template,
+    template::put_template,
+    template::update_template,
+    template::delete_template,
+    backup::export_database,
+    backup::export_all,
+    backup::restore_database,
+    backup::restore_all,
+    database::get_databases,
+    database::get_database,
+    database::put_database,
+    database::update_database,
+    database::delete_database,
+    location::get_locations,
+    location::get_location,
+    location::get_location_tree,
+    location::put_location,
+    location::update_location,
+    location::delete_location,
+    trash::get_trash,
+    trash::restore_trash_entry,
+    trash::empty_trash,
+))]
+struct ApiV1;
+
+// The types below only describe requests and responses that aren't json,
+// the handlers read and write them without these types.
+
+/// The content of a file, e.g. an image or a PDF.
+#[derive(ToSchema)]
+#[schema(value_type = String, format = Binary)]
+#[allow(dead_code)]
+pub(crate) struct Binary(Vec<u8>);
+
+/// A multipart form with a single file. Only the first file of the form is read.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub(crate) struct FileUpload {
+    file: Binary,
+}
+
+/// The protected services need the session id from "POST /v1/auth" in a header.
+struct SessionAuth;
+
+impl Modify for SessionAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(auth::SESSION_HEADER, "The session id from \"POST /v1/auth\""))),
+        );
+    }
+}
+
+/// Every service can fail, and all errors are problem details (RFC 7807).
+/// Instead of listing every status code, they are documented once as the default response.
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.responses.insert(
+            "Error".to_owned(),
+            RefOr::T(
+                ResponseBuilder::new()
+                    .description("The request failed. The code tells why, e.g. \"item.not_found\".")
+                    .content("application/problem+json", Content::new(Some(Ref::from_schema_name("Problem"))))
+                    .build(),
+            ),
+        );
+
+        for path_item in openapi.paths.paths.values_mut() {
+            let operations = [&mut path_item.get, &mut path_item.put, &mut path_item.post, &mut path_item.delete];
+            for operation in operations.into_iter().flatten() {
+                operation.responses.responses.insert("default".to_owned(), RefOr::Ref(Ref::from_response_name("Error")));
+            }
+        }
+    }
+}
+
+/// The api description with the url the api is served at (e.g. "/api").
+pub(crate) fn api_description(api_url: &str) -> utoipa::openapi::OpenApi {
+    let mut openapi = ApiDoc::openapi();
+    openapi.servers = Some(vec![Server::new(api_url)]);
+    openapi
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+
+use sqlx::types::chrono::NaiveDate;
+
+use crate::error::{ApiError, ApiResult};
+use crate::models::{Property, PropertyField, PropertyType};
+
+/// A property value converted to its type. Values of the same
+/// type are compared by their meaning, e.g. 9 is less than 10.
+#[derive(PartialEq, PartialOrd, Debug)]
+pub(crate) enum TypedValue {
+    Text(String),
+    Number(f64),
+    Date(NaiveDate),
+    Boolean(bool),
+}
+
+/// Convert the value to the type of the field.
+/// Returns None if the value doesn't match the type.
+pub(crate) fn parse_value(field: &PropertyField, value: &str) -> Option<TypedValue> {
+    match field.kind {
+        PropertyType::Text => Some(TypedValue::Text(value.to_owned())),
+        PropertyType::Number => value.parse::<f64>().ok().filter(|number| number.is_finite()).map(TypedValue::Number),
+        PropertyType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().map(TypedValue::Date),
+        PropertyType::Boolean => value.parse::<bool>().ok().map(TypedValue::Boolean),
+        PropertyType::Enum => field.options.iter().any(|option| option == value).then(|| TypedValue::Text(value.to_owned())),
+        PropertyType::Url => is_url(value).then(|| TypedValue::Text(value.to_owned())),
+        PropertyType::Currency => value.parse().ok().filter(|_| is_currency(value)).map(TypedValue::Number),
+    }
+}
+
+/// The typed value of a property. Properties that aren't in the schema are text.
+pub(crate) fn typed_value(schema: &[PropertyField], name: &str, value: &str) -> Option<TypedValue> {
+    match schema.iter().find(|field| field.name == name) {
+        Some(field) => parse_value(field, value),
+        None => Some(TypedValue::Text(value.to_owned())),
+    }
+}
+
+/// The typed value of a custom property of an item, if the item has it.
+pub(crate) fn property_value(schema: &[PropertyField], properties: &[Property], name: &str) -> Option<TypedValue> {
+    let property = properties.iter().find(|property| property.name == name)?;
+    typed_value(schema, name, &property.value)
+}
+
+/// Make sure the schema of a database makes sense, before the items get validated against it.
+pub(crate) fn check_schema(schema: &[PropertyField]) -> ApiResult<()> {
+    let mut names = HashSet::new();
+
+    for field in schema {
+        if field.name.trim().is_empty() {
+            return Err(ApiError::BadRequest("property.invalid_name", "a property needs a name!".to_owned()));
+        }
+        if !names.insert(field.name.as_str()) {
+            return Err(ApiError::BadRequest("property.duplicate", format!("the property {} is defined twice!", field.name)));
+        }
+        if field.kind == PropertyType::Enum && field.options.is_empty() {
+            return Err(ApiError::BadRequest(
+                "property.missing_options",
+                format!("the enum property {} has no options!", field.name),
+            ));
+        }
+        if let Some(default) = &field.default {
+            if parse_value(field, default).is_none() {
+                return Err(ApiError::BadRequest(
+                    "property.invalid_default",
+                    format!("the default of the property {} has to be {}!", field.name, describe(field)),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate the custom properties of an item against the schema of its database.
+/// Missing properties get their default value, if the field has one.
+pub(crate) fn apply_schema(schema: &[PropertyField], properties: &mut Vec<Property>) -> ApiResult<()> {
+    // An empty value is the same as no value
+    properties.retain(|property| !property.value.is_empty() || !schema.iter().any(|field| field.name == property.name));
+
+    for field in schema {
+        match properties.iter().find(|property| property.name == field.name) {
+            Some(property) => {
+                if parse_value(field, &property.value).is_none() {
+                    return Err(ApiError::BadRequest(
+                        "property.invalid_value",
+                        format!("the value of the property {} has to be {}!", field.name, describe(field)),
+                    ));
+                }
+            }
+            None => match &field.default {
+                Some(default) => properties.push(Property {
+                    name: field.name.clone(),
+                    value: default.clone(),
+                }),
+                None if field.required => {
+                    return Err(ApiError::BadRequest("property.required", format!("the property {} is required!", field.name)));
+                }
+                None => {}
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// What the value of the field has to look like, for the error messages.
+fn describe(field: &PropertyField) -> String {
+    match field.kind {
+        PropertyType::Text => "a text".to_owned(),
+        PropertyType::Number => "a number".to_owned(),
+        PropertyType::Date => "a date like 2024-12-31".to_owned(),
+        PropertyType::Boolean => "true or false".to_owned(),
+        PropertyType::Enum => format!("one of {}", field.options.join(", ")),
+        PropertyType::Url => "an http or https url".to_owned(),
+        PropertyType::Currency => "an amount with at most two decimal places".to_owned(),
+    }
+}
+
+fn is_url(value: &str) -> bool {
+    let rest = value.strip_prefix("https://").or_else(|| value.strip_prefix("http://"));
+    rest.is_some_and(|rest| !rest.is_empty() && !rest.contains(char::is_whitespace))
+}
+
+fn is_currency(value: &str) -> bool {
+    let value = value.strip_prefix('-').unwrap_or(value);
+    let (whole, cents) = value.split_once('.').unwrap_or((value, "0"));
+
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit());
+    is_digits(whole) && is_digits(cents) && cents.len() <= 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::apply_schema;
+    use crate::models::{Property, PropertyField, PropertyType};
+
+    fn field(name: &str, kind: PropertyType, required: bool, default: Option<&str>) -> PropertyField {
+        PropertyField {
+            name: name.to_owned(),
+            kind,
+            required,
+            default: default.map(str::to_owned),
+            options: vec!["small".to_owned(), "large".to_owned()],
+        }
+    }
+
+    fn property(name: &str, value: &str) -> Property {
+        Property {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+
+    fn error_code(schema: &[PropertyField], mut properties: Vec<Property>) -> String {
+        apply_schema(schema, &mut properties).unwrap_err().code()
+    }
+
+    #[test]
+    fn values_must_match_their_type() {
+        let schema = [
+            field("voltage", PropertyType::Number, false, None),
+            field("bought", PropertyType::Date, false, None),
+            field("size", PropertyType::Enum, false, None),
+            field("price", PropertyType::Currency, false, None),
+            field("manual", PropertyType::Url, false, None),
+        ];
+
+        let mut properties = vec![
+            property("voltage", "1.5"),
+            property("bought", "2024-12-31"),
+            property("size", "large"),
+            property("price", "12.50"),
+            property("manual", "https://example.com/manual.pdf"),
+        ];
+        apply_schema(&schema, &mut properties).unwrap();
+
+        for (name, value) in [
+            ("voltage", "a lot"),
+            ("bought", "31.12.2024"),
+            ("size", "medium"),
+            ("price", "12.505"),
+            ("manual", "ftp://example.com"),
+        ] {
+            assert_eq!(error_code(&schema, vec![property(name, value)]), "property.invalid_value", "{name} = {value}");
+        }
+    }
+
+    #[test]
+    fn missing_values_get_the_default() {
+        let schema = [field("voltage", PropertyType::Number, true, Some("5")), field("notes", PropertyType::Text, false, None)];
+
+        // An empty value counts as missing
+        let mut properties = vec![property("voltage", ""), property("color", "red")];
+        apply_schema(&schema, &mut properties).unwrap();
+
+        assert_eq!(properties.len(), 2);
+        assert!(properties.iter().any(|property| property.name == "voltage" && property.value == "5"));
+        assert!(properties.iter().any(|property| property.name == "color" && property.value == "red"));
+    }
+
+    #[test]
+    fn required_values_without_default_are_rejected() {
+        let schema = [field("voltage", PropertyType::Number, true, None)];
+
+        assert_eq!(error_code(&schema, vec![]), "property.required");
+        assert_eq!(error_code(&schema, vec![property("voltage", "")]), "property.required");
+    }
+}
@@ -0,0 +1,195 @@
+use std::io;
+use std::path::PathBuf;
+
+use actix_web::web;
+use async_trait::async_trait;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use crate::storage::{StoreError, StoreResult};
+
+/// Stores the content of uploaded files, addressed by their SHA-256 hash.
+#[async_trait]
+pub(crate) trait BlobStore: Send + Sync {
+    /// Store the data. If there already is a blob
+    /// with the same hash, nothing is changed.
+    async fn put_blob(&self, hash: &str, data: Vec<u8>) -> StoreResult<()>;
+    async fn get_blob(&self, hash: &str) -> StoreResult<Vec<u8>>;
+
+    /// Delete the blob together with all of its variants.
+    async fn delete_blob(&self, hash: &str) -> StoreResult<()>;
+
+    /// Store a variant of a blob (e.g. a thumbnail with the variant "256.webp").
+    /// Variants are derived from the blob, so an existing variant is kept.
+    /// Returns [StoreError::NotFound] if the blob doesn't exist.
+    async fn put_variant(&self, hash: &str, variant: &str, data: Vec<u8>) -> StoreResult<()>;
+    async fn get_variant(&self, hash: &str, variant: &str) -> StoreResult<Vec<u8>>;
+
+    /// The hashes of all stored blobs.
+    async fn get_blob_hashes(&self) -> StoreResult<Vec<String>>;
+
+    /// Check that new blobs can be stored.
+    async fn check_writable(&self) -> StoreResult<()>;
+}
+
+/// Blob storage in a local directory. To keep the directories small,
+/// the blobs are grouped by the first two characters of their hash.
+pub(crate) struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    /// Use the directory as storage and create it, if it doesn't exist yet.
+    pub(crate) fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+
+        Ok(LocalBlobStore { root })
+    }
+
+    fn blob_path(&self, hash: &str) -> StoreResult<PathBuf> {
+        // The hash is used as file name, so we have to make
+        // sure that it can't be used to escape the directory.
+        if hash.len() != 64 || !hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(StoreError::NotFound);
+        }
+
+        Ok(self.root.join(&hash[..2]).join(hash))
+    }
+
+    /// Variants are stored next to their blob as "{hash}.{variant}".
+    fn variant_path(&self, hash: &str, variant: &str) -> StoreResult<PathBuf> {
+        if variant.is_empty() || !variant.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'.') {
+            return Err(StoreError::NotFound);
+        }
+
+        let mut path = self.blob_path(hash)?;
+        path.set_file_name(format!("{hash}.{variant}"));
+        Ok(path)
+    }
+}
+
+/// Write into a temporary file first and rename it afterwards,
+/// so that nobody can ever read a half written file.
+fn write_atomically(path: PathBuf, data: Vec<u8>) -> io::Result<()> {
+    if path.exists() {
+        return Ok(());
+    }
+
+    let suffix: String = rand::thread_rng().sample_iter(&Alphanumeric).take(8).map(char::from).collect();
+    let temp_path = path.with_extension(format!("tmp-{suffix}"));
+
+    std::fs::create_dir_all(path.parent().unwrap_or(&path))?;
+    std::fs::write(&temp_path, data)?;
+    std::fs::rename(&temp_path, &path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp_path);
+    })
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put_blob(&self, hash: &str, data: Vec<u8>) -> StoreResult<()> {
+        let path = self.blob_path(hash)?;
+        run_blocking(move || write_atomically(path, data)).await
+    }
+
+    async fn get_blob(&self, hash: &str) -> StoreResult<Vec<u8>> {
+        let path = self.blob_path(hash)?;
+        run_blocking(move || std::fs::read(path)).await
+    }
+
+    async fn delete_blob(&self, hash: &str) -> StoreResult<()> {
+        let path = self.blob_path(hash)?;
+        let variant_prefix = format!("{hash}.");
+
+        run_blocking(move || {
+            std::fs::remove_file(&path)?;
+
+            // The variants are useless without their blob
+            if let Some(directory) = path.parent() {
+                for file in std::fs::read_dir(directory)? {
+                    let file = file?;
+                    if file.file_name().to_string_lossy().starts_with(&variant_prefix) {
+                        std::fs::remove_file(file.path())?;
+                    }
+                }
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn put_variant(&self, hash: &str, variant: &str, data: Vec<u8>) -> StoreResult<()> {
+        let blob_path = self.blob_path(hash)?;
+        let path = self.variant_path(hash, variant)?;
+
+        run_blocking(move || {
+            // Variants without their blob would never be deleted
+            if !blob_path.exists() {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+
+            write_atomically(path, data)
+        })
+        .await
+    }
+
+    async fn get_variant(&self, hash: &str, variant: &str) -> StoreResult<Vec<u8>> {
+        let path = self.variant_path(hash, variant)?;
+        run_blocking(move || std::fs::read(path)).await
+    }
+
+    async fn get_blob_hashes(&self) -> StoreResult<Vec<String>> {
+        let root = self.root.clone();
+
+        run_blocking(move || {
+            let mut hashes = vec![];
+            for directory in std::fs::read_dir(root)? {
+                let directory = directory?;
+                if !directory.file_type()?.is_dir() {
+                    continue;
+                }
+
+                for file in std::fs::read_dir(directory.path())? {
+                    let name = file?.file_name().to_string_lossy().into_owned();
+
+                    // Skip variants and temporary files
+                    if name.len() == 64 {
+                        hashes.push(name);
+                    }
+                }
+            }
+
+            Ok(hashes)
+        })
+        .await
+    }
+
+    async fn check_writable(&self) -> StoreResult<()> {
+        // The name is too short for a hash, so the file is never taken for a blob
+        let suffix: String = rand::thread_rng().sample_iter(&Alphanumeric).take(8).map(char::from).collect();
+        let path = self.root.join(format!("health-{suffix}"));
+
+        run_blocking(move || {
+            std::fs::write(&path, b"")?;
+            std::fs::remove_file(&path)
+        })
+        .await
+    }
+}
+
+/// File system operations block the thread, so they
+/// have to run on the thread pool for blocking tasks.
+async fn run_blocking<T, F>(operation: F) -> StoreResult<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match web::block(operation).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(err)) if err.kind() == io::ErrorKind::NotFound => Err(StoreError::NotFound),
+        Ok(Err(err)) => Err(StoreError::Internal(Box::new(err))),
+        Err(err) => Err(StoreError::Internal(Box::new(err))),
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::models::{Backup, Database, Item, Location, Tag};
+use crate::storage::memory::database::insert_database;
+use crate::storage::memory::icon::existing_icon;
+use crate::storage::memory::item::{insert_initial_movement, insert_item};
+use crate::storage::memory::revision::insert_revision;
+use crate::storage::memory::trash::trash_database;
+use crate::storage::memory::{location::insert_location, tag::insert_tag, MemoryData, MemoryStore};
+use crate::storage::{backup_tags, check_item_containers, check_location_parent, restored_tag_name, BackupStore, Change, StoreError, StoreResult};
+
+#[async_trait]
+impl BackupStore for MemoryStore {
+    async fn restore_backup(&self, backup: &Backup, as_new: bool, change: &Change) -> StoreResult<Vec<u64>> {
+        // Work on a copy of the data, so that a failed restore doesn't leave anything behind.
+        let mut data = self.lock();
+        let mut draft = data.clone();
+
+        // The databases are replaced first, so their tags are out of the way
+        let mut database_ids = Vec::with_capacity(backup.databases.len());
+        for database_backup in &backup.databases {
+            // The replaced database can still be restored from the trash
+            if !as_new {
+                let existing = draft
+                    .databases
+                    .rows
+                    .values()
+                    .find(|other| other.name == database_backup.database.name)
+                    .map(|other| other.id);
+                if let Some(database_id) = existing {
+                    trash_database(&mut draft, database_id, change)?;
+                }
+            }
+
+            let icon = existing_icon(&draft, database_backup.database.icon);
+            let database_id = insert_database(
+                &mut draft,
+                &Database {
+                    id: 0,
+                    icon,
+                    ..database_backup.database.clone()
+                },
+            )?;
+            database_ids.push(database_id);
+        }
+
+        let tag_ids = restore_tags(&mut draft, backup, &database_ids)?;
+
+        for (database_backup, &database_id) in backup.databases.iter().zip(&database_ids) {
+            // The parents are set after all locations got their new ids
+            let mut location_ids: HashMap<u64, u64> = HashMap::new();
+            for location in &database_backup.locations {
+                check_location_parent(location, &database_backup.locations)?;
+
+                let icon = existing_icon(&draft, location.icon);
+                let location_id = insert_location(
+                    &mut draft,
+                    &Location {
+                        id: 0,
+                        database: database_id,
+                        parent: None,
+                        icon,
+                        ..location.clone()
+                    },
+                )?;
+                location_ids.insert(location.id, location_id);
+            }
+            for location in &database_backup.locations {
+                if let (Some(parent_id), Some(location)) = (location.parent, draft.locations.rows.get_mut(&location_ids[&location.id])) {
+                    location.parent = Some(location_ids[&parent_id]);
+                }
+            }
+
+            // Like the locations, the containers are set after all items got their new ids
+            check_item_containers(&database_backup.items)?;
+            let mut item_ids: HashMap<u64, u64> = HashMap::new();
+            let mut restored_items = Vec::with_capacity(database_backup.items.len());
+            for item in &database_backup.items {
+                let location = *location_ids.get(&item.location).ok_or(StoreError::UnknownReference("location"))?;
+                let tags = item
+                    .tags
+                    .iter()
+                    .map(|tag_id| tag_ids.get(tag_id).copied().ok_or(StoreError::UnknownReference("tag")))
+                    .collect::<StoreResult<Vec<u64>>>()?;
+
+                let restored = Item {
+                    id: 0,
+                    location,
+                    parent_item: None,
+                    tags,
+                    ..item.clone()
+                };
+                let item_id = insert_item(&mut draft, &restored)?;
+                item_ids.insert(item.id, item_id);
+                restored_items.push((item_id, restored));
+            }
+            for item in &database_backup.items {
+                if let (Some(parent_id), Some(restored)) = (item.parent_item, draft.items.rows.get_mut(&item_ids[&item.id])) {
+                    restored.parent_item = Some(item_ids[&parent_id]);
+                }
+            }
+
+            // The restored items start their history like new items
+            for (item_id, item) in &restored_items {
+                insert_initial_movement(&mut draft, *item_id, item, change);
+                insert_revision(&mut draft, *item_id, change, false);
+            }
+        }
+
+        *data = draft;
+        Ok(database_ids)
+    }
+}
+
+/// Reuse the global tags that already exist and create the others. The tags of a database
+/// are created inside of its restored copy. Returns the new ids of the tags by their old ids.
+fn restore_tags(data: &mut MemoryData, backup: &Backup, database_ids: &[u64]) -> StoreResult<HashMap<u64, u64>> {
+    // Backups don't contain the uploaded icons, so only the existing icons are kept.
+    // The parents are set after all tags got their new ids.
+    let mut tag_ids: HashMap<u64, u64> = HashMap::new();
+    let mut created = vec![];
+    for tag in backup_tags(backup, database_ids)? {
+        let existing = data
+            .tags
+            .rows
+            .values()
+            .find(|other| tag.database.is_none() && other.database.is_none() && other.name == tag.name)
+            .map(|other| other.id);
+        let tag_id = match existing {
+            Some(tag_id) => tag_id,
+            None => {
+                let name = match tag.database {
+                    Some(database_id) if data.tags.rows.values().any(|other| other.name == tag.name) => restored_tag_name(&tag.name, &data.databases.rows[&database_id].name),
+                    _ => tag.name.clone(),
+                };
+                let icon = existing_icon(data, tag.icon);
+                let tag_id = insert_tag(
+                    data,
+                    &Tag {
+                        id: 0,
+                        name,
+                        parent: None,
+                        icon,
+                        ..tag.clone()
+                    },
+                )?;
+                created.push((tag_id, tag.parent));
+                tag_id
+            }
+        };
+
+        tag_ids.insert(tag.id, tag_id);
+    }
+
+    for (tag_id, parent_id) in created {
+        if let (Some(parent_id), Some(tag)) = (parent_id, data.tags.rows.get_mut(&tag_id)) {
+            tag.parent = Some(tag_ids[&parent_id]);
+        }
+    }
+
+    Ok(tag_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::models::{Backup, Database, DatabaseBackup, Item, Location, Tag, TrashKind};
+    use crate::storage::memory::MemoryStore;
+    use crate::storage::{BackupStore, Change, DatabaseStore, ItemStore, LocationStore, RevisionStore, TagStore, TrashStore};
+
+    fn change() -> Change {
+        Change {
+            user: None,
+            time: 1000,
+            reason: "test".to_owned(),
+        }
+    }
+
+    fn tag(name: &str, parent: Option<u64>, database: Option<u64>) -> Tag {
+        Tag {
+            id: 0,
+            name: name.to_owned(),
+            color: 0,
+            icon: None,
+            parent,
+            database,
+        }
+    }
+
+    /// A database with a tag of its own (below a global tag) and an item that uses both.
+    async fn create_database(store: &MemoryStore) -> Backup {
+        let database_id = store
+            .put_database(&Database {
+                id: 0,
+                name: "Workshop".to_owned(),
+                properties: vec![],
+                icon: None,
+            })
+            .await
+            .unwrap();
+        let location_id = store
+            .put_location(&Location {
+                id: 0,
+                name: "Shelf".to_owned(),
+                database: database_id,
+                parent: None,
+                icon: None,
+            })
+            .await
+            .unwrap();
+        let global_id = store.put_tag(&tag("Electronics", None, None)).await.unwrap();
+        let database_tag_id = store.put_tag(&tag("Cables", Some(global_id), Some(database_id))).await.unwrap();
+        store
+            .put_item(
+                &Item {
+                    id: 0,
+                    name: "USB cable".to_owned(),
+                    description: String::new(),
+                    image: None,
+                    location: location_id,
+                    parent_item: None,
+                    tags: vec![global_id, database_tag_id],
+                    amount: 3,
+                    min_amount: None,
+                    properties_internal: vec![],
+                    properties_custom: vec![],
+                    attachments: HashMap::new(),
+                    last_edited: 0,
+                    created: 0,
+                },
+                &change(),
+            )
+            .await
+            .unwrap();
+
+        Backup {
+            version: 1,
+            created: 0,
+            databases: vec![DatabaseBackup {
+                database: store.get_database(database_id).await.unwrap(),
+                locations: store.get_locations().await.unwrap(),
+                items: store.get_items().await.unwrap(),
+            }],
+            tags: store.get_tags().await.unwrap(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn replacing_a_database_restores_its_tags() {
+        let store = MemoryStore::new();
+        let backup = create_database(&store).await;
+        let old_database_id = backup.databases[0].database.id;
+
+        let database_ids = store.restore_backup(&backup, false, &change()).await.unwrap();
+        let database_id = database_ids[0];
+        assert_ne!(database_id, old_database_id);
+
+        // The old database went to the trash together with its tag
+        let trash = store.get_trash().await.unwrap();
+        assert!(trash.iter().any(|entry| entry.kind == TrashKind::Database && entry.id == old_database_id));
+        assert!(trash.iter().any(|entry| entry.kind == TrashKind::Tag && entry.name == "Cables"));
+
+        // The global tag is reused, the tag of the database belongs to the new one
+        let tags = store.get_tags().await.unwrap();
+        let global = tags.iter().find(|tag| tag.name == "Electronics").unwrap();
+        let cables = tags.iter().find(|tag| tag.name == "Cables").unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(cables.database, Some(database_id));
+        assert_eq!(cables.parent, Some(global.id));
+
+        let items = store.get_items().await.unwrap();
+        assert_eq!(items.len(), 1);
+        let mut item_tags = items[0].tags.clone();
+        item_tags.sort();
+        assert_eq!(item_tags, vec![global.id, cables.id]);
+
+        // The restored item starts its history
+        let revisions = store.get_revisions(items[0].id).await.unwrap();
+        assert_eq!(revisions.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn restoring_a_copy_renames_the_tags_of_the_database() {
+        let store = MemoryStore::new();
+        let mut backup = create_database(&store).await;
+        backup.databases[0].database.name = "Copy".to_owned();
+
+        let database_id = store.restore_backup(&backup, true, &change()).await.unwrap()[0];
+
+        let tags = store.get_tags().await.unwrap();
+        let copy = tags.iter().find(|tag| tag.name == "Cables (Copy)").unwrap();
+        assert_eq!(copy.database, Some(database_id));
+        assert_eq!(tags.len(), 3);
+
+        let location = store.get_locations().await.unwrap().into_iter().find(|location| location.database == database_id).unwrap();
+        let item = store.get_items().await.unwrap().into_iter().find(|item| item.location == location.id).unwrap();
+        assert!(item.tags.contains(&copy.id));
+    }
+}
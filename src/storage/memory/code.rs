@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+
+use crate::models::ScanCode;
+use crate::storage::memory::{MemoryData, MemoryStore};
+use crate::storage::{CodeStore, StoreError, StoreResult};
+
+#[async_trait]
+impl CodeStore for MemoryStore {
+    async fn get_codes(&self) -> StoreResult<Vec<ScanCode>> {
+        let data = self.lock();
+        Ok(data.codes.values().filter(|code| is_visible(&data, code)).cloned().collect())
+    }
+
+    async fn find_code(&self, codes: &[String]) -> StoreResult<ScanCode> {
+        let data = self.lock();
+
+        // Like the sql table, the codes are compared case insensitive
+        codes
+            .iter()
+            .find_map(|code| data.codes.get(&code.to_lowercase()))
+            .filter(|code| is_visible(&data, code))
+            .cloned()
+            .ok_or(StoreError::NotFound)
+    }
+
+    async fn put_code(&self, code: &ScanCode) -> StoreResult<()> {
+        let mut data = self.lock();
+
+        if code.item.is_some_and(|item_id| !data.items.rows.contains_key(&item_id)) {
+            return Err(StoreError::UnknownReference("item"));
+        }
+        if code.location.is_some_and(|location_id| !data.locations.rows.contains_key(&location_id)) {
+            return Err(StoreError::UnknownReference("location"));
+        }
+
+        // A code that is already in use is a conflict, even if its object is in the trash
+        let key = code.code.to_lowercase();
+        if data.codes.contains_key(&key) {
+            return Err(StoreError::Conflict);
+        }
+
+        data.codes.insert(key, code.clone());
+        Ok(())
+    }
+
+    async fn delete_code(&self, code: &str) -> StoreResult<()> {
+        self.lock().codes.remove(&code.to_lowercase()).ok_or(StoreError::NotFound)?;
+        Ok(())
+    }
+}
+
+/// The codes of deleted items and locations are hidden until they are restored.
+fn is_visible(data: &MemoryData, code: &ScanCode) -> bool {
+    code.item.is_none_or(|item_id| data.items.rows.contains_key(&item_id)) && code.location.is_none_or(|location_id| data.locations.rows.contains_key(&location_id))
+}
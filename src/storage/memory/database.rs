@@ -0,0 +1,62 @@
+use async_trait::async_trait;
+
+use crate::models::Database;
+use crate::storage::memory::trash::trash_database;
+use crate::storage::memory::{MemoryData, MemoryStore};
+use crate::storage::{Change, DatabaseStore, StoreError, StoreResult};
+
+#[async_trait]
+impl DatabaseStore for MemoryStore {
+    async fn get_databases(&self) -> StoreResult<Vec<Database>> {
+        Ok(self.lock().databases.rows.values().cloned().collect())
+    }
+
+    async fn get_database(&self, database_id: u64) -> StoreResult<Database> {
+        self.lock().databases.rows.get(&database_id).cloned().ok_or(StoreError::NotFound)
+    }
+
+    async fn put_database(&self, database: &Database) -> StoreResult<u64> {
+        insert_database(&mut self.lock(), database)
+    }
+
+    async fn update_database(&self, database: &Database) -> StoreResult<()> {
+        let mut data = self.lock();
+        if !data.databases.rows.contains_key(&database.id) {
+            return Err(StoreError::NotFound);
+        }
+
+        check_database(&data, database)?;
+        data.databases.rows.insert(database.id, database.clone());
+
+        Ok(())
+    }
+
+    async fn delete_database(&self, database_id: u64, change: &Change) -> StoreResult<()> {
+        trash_database(&mut self.lock(), database_id, change)
+    }
+}
+
+/// Like the foreign keys in the sql tables, this cascades
+/// through all locations of the database and their items.
+pub(super) fn insert_database(data: &mut MemoryData, database: &Database) -> StoreResult<u64> {
+    check_database(data, database)?;
+
+    let database_id = data.databases.next_id();
+    data.databases.rows.insert(
+        database_id,
+        Database {
+            id: database_id,
+            ..database.clone()
+        },
+    );
+
+    Ok(database_id)
+}
+
+pub(super) fn check_database(data: &MemoryData, database: &Database) -> StoreResult<()> {
+    if data.databases.rows.values().any(|other| other.id != database.id && other.name == database.name) {
+        return Err(StoreError::Conflict);
+    }
+
+    Ok(())
+}
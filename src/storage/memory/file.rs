@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+
+use crate::models::ItemFile;
+use crate::storage::memory::MemoryStore;
+use crate::storage::{FileStore, StoreError, StoreResult};
+
+#[async_trait]
+impl FileStore for MemoryStore {
+    async fn get_item_files(&self, item_id: u64) -> StoreResult<Vec<ItemFile>> {
+        Ok(self
+            .lock()
+            .files
+            .iter()
+            .filter(|(file_item_id, _)| *file_item_id == item_id)
+            .map(|(_, file)| file.clone())
+            .collect())
+    }
+
+    async fn put_item_file(&self, item_id: u64, file: &ItemFile, url: &str) -> StoreResult<()> {
+        let mut data = self.lock();
+        let item = data.items.rows.get_mut(&item_id).ok_or(StoreError::NotFound)?;
+
+        if file.is_image {
+            item.image = Some(url.to_owned());
+        } else {
+            item.attachments.insert(file.name.clone(), url.to_owned());
+        }
+
+        // An item only has one image, so every image replaces the old one
+        data.files
+            .retain(|(file_item_id, other)| *file_item_id != item_id || other.is_image != file.is_image || (!file.is_image && other.name != file.name));
+        data.files.push((item_id, file.clone()));
+
+        Ok(())
+    }
+
+    async fn delete_item_file(&self, item_id: u64, is_image: bool, name: &str) -> StoreResult<()> {
+        let mut data = self.lock();
+        let position = data
+            .files
+            .iter()
+            .position(|(file_item_id, file)| *file_item_id == item_id && file.is_image == is_image && file.name == name)
+            .ok_or(StoreError::NotFound)?;
+        data.files.remove(position);
+
+        if let Some(item) = data.items.rows.get_mut(&item_id) {
+            if is_image {
+                item.image = None;
+            } else {
+                item.attachments.remove(name);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_file_hashes(&self) -> StoreResult<HashSet<String>> {
+        Ok(self.lock().files.iter().map(|(_, file)| file.hash.clone()).collect())
+    }
+}
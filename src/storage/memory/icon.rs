@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+
+use crate::icons::{builtin_icon, FIRST_UPLOADED_ICON};
+use crate::models::Icon;
+use crate::storage::memory::{MemoryData, MemoryStore};
+use crate::storage::{IconStore, StoreError, StoreResult};
+
+#[async_trait]
+impl IconStore for MemoryStore {
+    async fn get_icons(&self) -> StoreResult<Vec<Icon>> {
+        Ok(self.lock().icons.rows.values().map(|(icon, _)| icon.clone()).collect())
+    }
+
+    async fn get_icon(&self, icon_id: u64) -> StoreResult<(Icon, Vec<u8>)> {
+        self.lock().icons.rows.get(&icon_id).cloned().ok_or(StoreError::NotFound)
+    }
+
+    async fn put_icon(&self, icon: &Icon, data: &[u8]) -> StoreResult<u64> {
+        let mut data_lock = self.lock();
+
+        // Like the AUTO_INCREMENT of the sql table, the ids start after the built-in icons
+        let icon_id = FIRST_UPLOADED_ICON - 1 + data_lock.icons.next_id();
+        let icon = Icon {
+            id: icon_id,
+            builtin: false,
+            ..icon.clone()
+        };
+        data_lock.icons.rows.insert(icon_id, (icon, data.to_vec()));
+
+        Ok(icon_id)
+    }
+
+    async fn delete_icon(&self, icon_id: u64) -> StoreResult<()> {
+        let mut data = self.lock();
+        data.icons.rows.remove(&icon_id).ok_or(StoreError::NotFound)?;
+
+        let MemoryData {
+            tags,
+            locations,
+            databases,
+            trash,
+            ..
+        } = &mut *data;
+        let tag_icons = tags
+            .rows
+            .values_mut()
+            .chain(trash.tags.values_mut().map(|trashed| &mut trashed.row))
+            .map(|tag| &mut tag.icon);
+        let location_icons = locations
+            .rows
+            .values_mut()
+            .chain(trash.locations.values_mut().map(|trashed| &mut trashed.row))
+            .map(|location| &mut location.icon);
+        let database_icons = databases
+            .rows
+            .values_mut()
+            .chain(trash.databases.values_mut().map(|trashed| &mut trashed.row))
+            .map(|database| &mut database.icon);
+
+        for icon in tag_icons.chain(location_icons).chain(database_icons) {
+            if *icon == Some(icon_id) {
+                *icon = None;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The icon, if it exists. Used for restored backups, since they don't contain the uploaded icons.
+pub(super) fn existing_icon(data: &MemoryData, icon: Option<u64>) -> Option<u64> {
+    icon.filter(|icon_id| builtin_icon(*icon_id).is_some() || data.icons.rows.contains_key(icon_id))
+}
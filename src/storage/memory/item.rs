@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::models::{Item, Location, MovementKind, Tag};
+use crate::storage::memory::loan::lent_amount;
+use crate::storage::memory::movement::insert_movement;
+use crate::storage::memory::revision::insert_revision;
+use crate::storage::memory::{count_per_database, MemoryData, MemoryStore, Trashed};
+use crate::storage::{check_lent_amount, creates_cycle, descendants, Change, ImportedItem, ItemStore, LocationPath, StoreError, StoreResult};
+
+#[async_trait]
+impl ItemStore for MemoryStore {
+    async fn get_items(&self) -> StoreResult<Vec<Item>> {
+        Ok(self.lock().items.rows.values().cloned().collect())
+    }
+
+    async fn get_item(&self, item_id: u64) -> StoreResult<Item> {
+        self.lock().items.rows.get(&item_id).cloned().ok_or(StoreError::NotFound)
+    }
+
+    async fn get_items_by_ids(&self, item_ids: &[u64]) -> StoreResult<Vec<Item>> {
+        let data = self.lock();
+        Ok(item_ids.iter().filter_map(|item_id| data.items.rows.get(item_id)).cloned().collect())
+    }
+
+    async fn get_items_by_containers(&self, container_ids: &[u64]) -> StoreResult<Vec<Item>> {
+        let data = self.lock();
+        let items = data.items.rows.values();
+        Ok(items
+            .filter(|item| item.parent_item.is_some_and(|parent_id| container_ids.contains(&parent_id)))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_items_by_locations(&self, location_ids: &[u64]) -> StoreResult<Vec<Item>> {
+        let data = self.lock();
+        Ok(data.items.rows.values().filter(|item| location_ids.contains(&item.location)).cloned().collect())
+    }
+
+    async fn get_items_by_tags(&self, tag_ids: &[u64]) -> StoreResult<Vec<Item>> {
+        let data = self.lock();
+        Ok(data
+            .items
+            .rows
+            .values()
+            .filter(|item| item.tags.iter().any(|tag_id| tag_ids.contains(tag_id)))
+            .cloned()
+            .collect())
+    }
+
+    async fn count_items(&self) -> StoreResult<HashMap<u64, u64>> {
+        let data = self.lock();
+        Ok(count_per_database(
+            data.items
+                .rows
+                .values()
+                .filter_map(|item| data.locations.rows.get(&item.location))
+                .map(|location| location.database),
+        ))
+    }
+
+    async fn put_item(&self, item: &Item, change: &Change) -> StoreResult<u64> {
+        let mut data = self.lock();
+        let item = resolve_container(&data, item)?;
+
+        let item_id = insert_item(&mut data, &item)?;
+        insert_initial_movement(&mut data, item_id, &item, change);
+        insert_revision(&mut data, item_id, change, false);
+
+        Ok(item_id)
+    }
+
+    async fn update_item(&self, item: &Item, change: &Change) -> StoreResult<()> {
+        // Work on a copy, because moving the contents can still fail
+        let mut data = self.lock();
+        let mut draft = data.clone();
+        update_item_row(&mut draft, item, change)?;
+
+        *data = draft;
+        Ok(())
+    }
+
+    async fn delete_item(&self, item_id: u64, change: &Change) -> StoreResult<()> {
+        let mut data = self.lock();
+        if !data.items.rows.contains_key(&item_id) {
+            return Err(StoreError::NotFound);
+        }
+
+        // The last state of the item is kept, so it can be restored
+        insert_revision(&mut data, item_id, change, true);
+        let item = data.items.rows.remove(&item_id).ok_or(StoreError::NotFound)?;
+        data.trash.items.insert(
+            item_id,
+            Trashed {
+                row: item.clone(),
+                deleted_at: change.time,
+                batch: None,
+                item_ids: vec![],
+            },
+        );
+
+        // The contents of a container are taken out and put where the container was
+        let content_ids: Vec<u64> = data
+            .items
+            .rows
+            .values()
+            .filter(|other| other.parent_item == Some(item_id))
+            .map(|content| content.id)
+            .collect();
+        for content_id in content_ids {
+            if let Some(content) = data.items.rows.get_mut(&content_id) {
+                content.parent_item = item.parent_item;
+            }
+            insert_revision(&mut data, content_id, change, false);
+        }
+
+        Ok(())
+    }
+
+    async fn import_items(&self, items: &[ImportedItem], dry_run: bool, change: &Change) -> StoreResult<Vec<StoreResult<u64>>> {
+        // Work on a copy of the data, so that a failed import doesn't leave anything behind.
+        let mut data = self.lock();
+        let mut draft = data.clone();
+
+        let mut results = Vec::with_capacity(items.len());
+        for imported in items {
+            let result = import_item(&mut draft, imported, &results, change);
+            results.push(result);
+        }
+
+        if !dry_run && results.iter().all(Result::is_ok) {
+            *data = draft;
+        }
+
+        Ok(results)
+    }
+}
+
+/// Items with an id other than 0 (deleted items that are restored) keep their id.
+pub(super) fn insert_item(data: &mut MemoryData, item: &Item) -> StoreResult<u64> {
+    check_item(data, item)?;
+
+    let item_id = match item.id {
+        0 => data.items.next_id(),
+        item_id if data.items.rows.contains_key(&item_id) => return Err(StoreError::Conflict),
+        item_id => item_id,
+    };
+    data.items.rows.insert(item_id, Item { id: item_id, ..item.clone() });
+
+    Ok(item_id)
+}
+
+/// Record the initial amount of a new item as received.
+pub(super) fn insert_initial_movement(data: &mut MemoryData, item_id: u64, item: &Item, change: &Change) {
+    if item.amount > 0 {
+        insert_movement(data, &change.movement(item_id, MovementKind::Receive, item.amount as i64, item.location, None));
+    }
+}
+
+/// Items inside of a container get the location of the container.
+/// Returns an error if the container doesn't exist or if the
+/// item would end up inside of itself.
+pub(super) fn resolve_container(data: &MemoryData, item: &Item) -> StoreResult<Item> {
+    let parent_id = match item.parent_item {
+        Some(parent_id) => parent_id,
+        None => return Ok(item.clone()),
+    };
+
+    let container = data.items.rows.get(&parent_id).ok_or(StoreError::UnknownReference("item"))?;
+    if creates_cycle(item.id, parent_id, &item_parents(data)) {
+        return Err(StoreError::Invalid("item.cycle", "an item can't be inside of itself!"));
+    }
+
+    Ok(Item {
+        location: container.location,
+        ..item.clone()
+    })
+}
+
+/// Update an item that isn't deleted and record the changes of its amount and location.
+/// The data is left half-changed if this fails, so the caller has to work on a copy.
+pub(super) fn update_item_row(data: &mut MemoryData, item: &Item, change: &Change) -> StoreResult<()> {
+    let old_item = data.items.rows.get(&item.id).ok_or(StoreError::NotFound)?;
+    let (old_location, old_amount) = (old_item.location, old_item.amount);
+
+    let item = resolve_container(data, item)?;
+    check_item(data, &item)?;
+    check_lent_amount(old_amount, item.amount, lent_amount(data, item.id))?;
+    data.items.rows.insert(item.id, item.clone());
+
+    if item.amount != old_amount {
+        let difference = item.amount as i64 - old_amount as i64;
+        insert_movement(data, &change.movement(item.id, MovementKind::Adjust, difference, item.location, None));
+    }
+
+    // The contents of a container move together with it
+    if item.location != old_location {
+        let movement = change.movement(item.id, MovementKind::Transfer, item.amount as i64, item.location, Some(old_location));
+        insert_movement(data, &movement);
+
+        for content_id in descendants(item.id, &item_parents(data)) {
+            let content = Item {
+                location: item.location,
+                ..data.items.rows[&content_id].clone()
+            };
+
+            check_item(data, &content)?;
+            let movement = change.movement(content_id, MovementKind::Transfer, content.amount as i64, item.location, Some(old_location));
+            data.items.rows.insert(content_id, content);
+            insert_movement(data, &movement);
+            insert_revision(data, content_id, change, false);
+        }
+    }
+    insert_revision(data, item.id, change, false);
+
+    Ok(())
+}
+
+/// The container of every item.
+fn item_parents(data: &MemoryData) -> HashMap<u64, Option<u64>> {
+    data.items.rows.values().map(|item| (item.id, item.parent_item)).collect()
+}
+
+/// Resolve the location, tags and container of an imported item and insert it.
+/// The results of the items that were imported before are needed for the container.
+fn import_item(data: &mut MemoryData, imported: &ImportedItem, results: &[StoreResult<u64>], change: &Change) -> StoreResult<u64> {
+    let location = resolve_location(data, &imported.location)?;
+    let tags = imported.tags.iter().map(|tag_name| resolve_tag(data, tag_name)).collect();
+
+    let item = resolve_container(
+        data,
+        &Item {
+            location,
+            tags,
+            parent_item: imported.container_id(results)?,
+            ..imported.item.clone()
+        },
+    )?;
+    let item_id = insert_item(data, &item)?;
+    insert_initial_movement(data, item_id, &item, change);
+    insert_revision(data, item_id, change, false);
+
+    Ok(item_id)
+}
+
+/// Get the id of a location by its path. If the database is
+/// specified, missing locations are created inside of it.
+fn resolve_location(data: &mut MemoryData, path: &LocationPath) -> StoreResult<u64> {
+    let database_id = match &path.database {
+        Some(database_name) => {
+            let database = data.databases.rows.values().find(|database| database.name == *database_name);
+            database.ok_or(StoreError::UnknownReference("database"))?.id
+        }
+        None => {
+            // The name is only unique inside of a database
+            let location_name = path.path.last().ok_or(StoreError::UnknownReference("location"))?;
+            let mut matching = data.locations.rows.values().filter(|location| location.name == *location_name);
+            return match (matching.next(), matching.next()) {
+                (Some(location), None) => Ok(location.id),
+                (Some(_), Some(_)) => Err(StoreError::Invalid("location.ambiguous", "there are several locations with this name, add the database!")),
+                (None, _) => Err(StoreError::UnknownReference("location")),
+            };
+        }
+    };
+
+    let mut parent = None;
+    for location_name in &path.path {
+        let existing = data
+            .locations
+            .rows
+            .values()
+            .find(|location| location.database == database_id && location.name == *location_name)
+            .map(|location| location.id);
+
+        let location_id = match existing {
+            Some(location_id) => location_id,
+            None => {
+                let location_id = data.locations.next_id();
+                data.locations.rows.insert(
+                    location_id,
+                    Location {
+                        id: location_id,
+                        name: location_name.clone(),
+                        database: database_id,
+                        parent,
+                        icon: None,
+                    },
+                );
+                location_id
+            }
+        };
+        parent = Some(location_id);
+    }
+
+    parent.ok_or(StoreError::UnknownReference("location"))
+}
+
+/// Get the id of a tag by its name or create it.
+fn resolve_tag(data: &mut MemoryData, tag_name: &str) -> u64 {
+    if let Some(tag) = data.tags.rows.values().find(|tag| tag.name == tag_name) {
+        return tag.id;
+    }
+
+    let tag_id = data.tags.next_id();
+    data.tags.rows.insert(
+        tag_id,
+        Tag {
+            id: tag_id,
+            name: tag_name.to_owned(),
+            color: 0,
+            icon: None,
+            parent: None,
+            database: None,
+        },
+    );
+
+    tag_id
+}
+
+/// Enforce the same constraints as the sql tables: unique names per location,
+/// existing location and tags, and only tags of the same database.
+pub(super) fn check_item(data: &MemoryData, item: &Item) -> StoreResult<()> {
+    if data
+        .items
+        .rows
+        .values()
+        .any(|other| other.id != item.id && other.location == item.location && other.name == item.name)
+    {
+        return Err(StoreError::Conflict);
+    }
+
+    let location = data.locations.rows.get(&item.location).ok_or(StoreError::UnknownReference("location"))?;
+
+    for tag_id in &item.tags {
+        let tag = data.tags.rows.get(tag_id).ok_or(StoreError::UnknownReference("tag"))?;
+        if tag.database.is_some_and(|database_id| database_id != location.database) {
+            return Err(StoreError::Invalid("tag.other_database", "the tag belongs to another database!"));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+
+use crate::models::Loan;
+use crate::storage::memory::{MemoryData, MemoryStore};
+use crate::storage::{LoanStore, StoreError, StoreResult};
+
+#[async_trait]
+impl LoanStore for MemoryStore {
+    async fn get_loans(&self) -> StoreResult<Vec<Loan>> {
+        Ok(self.lock().loans.rows.values().cloned().collect())
+    }
+
+    async fn get_loan(&self, loan_id: u64) -> StoreResult<Loan> {
+        self.lock().loans.rows.get(&loan_id).cloned().ok_or(StoreError::NotFound)
+    }
+
+    async fn get_loans_by_items(&self, item_ids: &[u64]) -> StoreResult<Vec<Loan>> {
+        Ok(self.lock().loans.rows.values().filter(|loan| item_ids.contains(&loan.item)).cloned().collect())
+    }
+
+    async fn check_out(&self, loan: &Loan) -> StoreResult<u64> {
+        let mut data = self.lock();
+
+        let item = data.items.rows.get(&loan.item).ok_or(StoreError::UnknownReference("item"))?;
+        if let Some(user_id) = loan.user {
+            if !data.users.values().any(|(_, other_id)| *other_id == user_id) {
+                return Err(StoreError::UnknownReference("user"));
+            }
+        }
+
+        if lent_amount(&data, loan.item) + loan.amount > item.amount {
+            return Err(StoreError::Invalid("loan.not_available", "not enough of the item is available!"));
+        }
+
+        let loan_id = data.loans.next_id();
+        data.loans.rows.insert(
+            loan_id,
+            Loan {
+                id: loan_id,
+                returned: None,
+                ..loan.clone()
+            },
+        );
+
+        Ok(loan_id)
+    }
+
+    async fn check_in(&self, loan_id: u64, returned: i64) -> StoreResult<()> {
+        let mut data = self.lock();
+        let loan = data.loans.rows.get_mut(&loan_id).ok_or(StoreError::NotFound)?;
+        if loan.returned.is_some() {
+            return Err(StoreError::Invalid("loan.already_returned", "the loan was already returned!"));
+        }
+
+        loan.returned = Some(returned);
+        Ok(())
+    }
+}
+
+/// How much of the item is lent right now.
+pub(super) fn lent_amount(data: &MemoryData, item_id: u64) -> u64 {
+    data.loans
+        .rows
+        .values()
+        .filter(|loan| loan.item == item_id && loan.returned.is_none())
+        .map(|loan| loan.amount)
+        .sum()
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::models::{Location, MovementKind};
+use crate::storage::memory::item::check_item;
+use crate::storage::memory::movement::insert_movement;
+use crate::storage::memory::revision::insert_revision;
+use crate::storage::memory::trash::trash_location;
+use crate::storage::memory::{count_per_database, MemoryData, MemoryStore};
+use crate::storage::{check_location_parent, location_descendants, Change, ChildPolicy, LocationStore, StoreError, StoreResult};
+
+#[async_trait]
+impl LocationStore for MemoryStore {
+    async fn get_locations(&self) -> StoreResult<Vec<Location>> {
+        Ok(self.lock().locations.rows.values().cloned().collect())
+    }
+
+    async fn get_location(&self, location_id: u64) -> StoreResult<Location> {
+        self.lock().locations.rows.get(&location_id).cloned().ok_or(StoreError::NotFound)
+    }
+
+    async fn count_locations(&self) -> StoreResult<HashMap<u64, u64>> {
+        Ok(count_per_database(self.lock().locations.rows.values().map(|location| location.database)))
+    }
+
+    async fn put_location(&self, location: &Location) -> StoreResult<u64> {
+        insert_location(&mut self.lock(), location)
+    }
+
+    async fn update_location(&self, location: &Location) -> StoreResult<()> {
+        let mut data = self.lock();
+        let old_location = data.locations.rows.get(&location.id).ok_or(StoreError::NotFound)?;
+
+        // The child locations would end up in another database than their parent
+        if old_location.database != location.database && data.locations.rows.values().any(|other| other.parent == Some(location.id)) {
+            return Err(StoreError::Invalid(
+                "location.has_children",
+                "a location with child locations can't be moved to another database!",
+            ));
+        }
+
+        check_location(&data, location)?;
+        data.locations.rows.insert(location.id, location.clone());
+
+        Ok(())
+    }
+
+    async fn delete_location(&self, location_id: u64, policy: ChildPolicy, change: &Change) -> StoreResult<()> {
+        // Work on a copy, because moving the items can still fail
+        let mut data = self.lock();
+        let mut draft = data.clone();
+        let location = draft.locations.rows.get(&location_id).cloned().ok_or(StoreError::NotFound)?;
+
+        let mut deleted_ids = vec![location_id];
+        match policy {
+            ChildPolicy::Reject => {
+                if draft.locations.rows.values().any(|other| other.parent == Some(location_id)) {
+                    return Err(StoreError::Invalid("location.has_children", "the location still contains other locations!"));
+                }
+            }
+            ChildPolicy::Reparent => {
+                for child in draft.locations.rows.values_mut().filter(|other| other.parent == Some(location_id)) {
+                    child.parent = location.parent;
+                }
+
+                if let Some(parent_id) = location.parent {
+                    let item_ids: Vec<u64> = draft.items.rows.values().filter(|item| item.location == location_id).map(|item| item.id).collect();
+                    for item_id in item_ids {
+                        let mut item = draft.items.rows[&item_id].clone();
+                        item.location = parent_id;
+
+                        check_item(&draft, &item).map_err(|err| match err {
+                            StoreError::Conflict => StoreError::Invalid("location.item_conflict", "the parent location already contains an item with the same name!"),
+                            err => err,
+                        })?;
+                        let movement = change.movement(item_id, MovementKind::Transfer, item.amount as i64, parent_id, Some(location_id));
+                        draft.items.rows.insert(item_id, item);
+
+                        // The items are moved like any other transfer
+                        insert_movement(&mut draft, &movement);
+                        insert_revision(&mut draft, item_id, change, false);
+                    }
+                }
+            }
+            ChildPolicy::Cascade => {
+                let locations: Vec<Location> = draft.locations.rows.values().cloned().collect();
+                deleted_ids.extend(location_descendants(location_id, &locations));
+            }
+        }
+
+        // The items of the locations go to the trash as well
+        let batch = draft.trash.next_batch();
+        for deleted_id in deleted_ids {
+            trash_location(&mut draft, deleted_id, batch, change);
+        }
+
+        *data = draft;
+        Ok(())
+    }
+}
+
+pub(super) fn insert_location(data: &mut MemoryData, location: &Location) -> StoreResult<u64> {
+    check_location(data, location)?;
+
+    let location_id = data.locations.next_id();
+    data.locations.rows.insert(
+        location_id,
+        Location {
+            id: location_id,
+            ..location.clone()
+        },
+    );
+
+    Ok(location_id)
+}
+
+pub(super) fn check_location(data: &MemoryData, location: &Location) -> StoreResult<()> {
+    if data
+        .locations
+        .rows
+        .values()
+        .any(|other| other.id != location.id && other.database == location.database && other.name == location.name)
+    {
+        return Err(StoreError::Conflict);
+    }
+
+    if !data.databases.rows.contains_key(&location.database) {
+        return Err(StoreError::UnknownReference("database"));
+    }
+
+    let locations: Vec<Location> = data.locations.rows.values().cloned().collect();
+    check_location_parent(location, &locations)
+}
@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::models::{Database, Icon, Item, ItemFile, ItemRevision, ItemTemplate, Loan, Location, Movement, ScanCode, Tag};
+
+mod backup;
+mod code;
+mod database;
+mod file;
+mod icon;
+mod item;
+mod loan;
+mod location;
+mod movement;
+mod revision;
+mod session;
+mod status;
+mod tag;
+mod template;
+mod trash;
+
+/// Storage backend that keeps everything in memory.
+/// Nothing is persisted, so this is only useful for
+/// testing and trying out the server without a database.
+#[derive(Default)]
+pub(crate) struct MemoryStore {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Default, Clone)]
+struct MemoryData {
+    items: Table<Item>,
+    tags: Table<Tag>,
+    locations: Table<Location>,
+    databases: Table<Database>,
+
+    /// The uploaded files with the id of their item
+    files: Vec<(u64, ItemFile)>,
+    loans: Table<Loan>,
+    movements: Table<Movement>,
+
+    /// The revisions are kept after the item is deleted
+    revisions: Vec<ItemRevision>,
+    trash: Trash,
+    templates: Table<ItemTemplate>,
+
+    /// The lowercase code => the code
+    codes: BTreeMap<String, ScanCode>,
+
+    /// The uploaded icons with their content
+    icons: Table<(Icon, Vec<u8>)>,
+
+    /// username => (password, user id)
+    users: HashMap<String, (String, u64)>,
+
+    /// session id => user id
+    sessions: HashMap<String, u64>,
+}
+
+/// Deleted objects that can still be restored. They are kept
+/// out of the tables, so nothing else has to skip them.
+#[derive(Default, Clone)]
+struct Trash {
+    items: BTreeMap<u64, Trashed<Item>>,
+    tags: BTreeMap<u64, Trashed<Tag>>,
+    locations: BTreeMap<u64, Trashed<Location>>,
+    databases: BTreeMap<u64, Trashed<Database>>,
+
+    /// Like the trash_batches table
+    last_batch: u64,
+}
+
+impl Trash {
+    /// Start a new batch of objects that are moved to the trash together.
+    fn next_batch(&mut self) -> u64 {
+        self.last_batch += 1;
+        self.last_batch
+    }
+}
+
+#[derive(Clone)]
+struct Trashed<T> {
+    row: T,
+    deleted_at: i64,
+
+    /// The objects that were deleted together (e.g. a database with its
+    /// locations), or none for an item or tag that was deleted on its own
+    batch: Option<u64>,
+
+    /// The items that used a deleted tag (like the item_tags table)
+    item_ids: Vec<u64>,
+}
+
+/// A map of objects with an auto increment id, like an sql table.
+#[derive(Clone)]
+struct Table<T> {
+    rows: BTreeMap<u64, T>,
+    last_id: u64,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Table {
+            rows: BTreeMap::new(),
+            last_id: 0,
+        }
+    }
+}
+
+impl<T> Table<T> {
+    /// Reserve the next free id. Like AUTO_INCREMENT, ids are never reused.
+    fn next_id(&mut self) -> u64 {
+        self.last_id += 1;
+        self.last_id
+    }
+}
+
+/// How often every database id occurs, like `COUNT(*) ... GROUP BY database_id`.
+fn count_per_database(database_ids: impl Iterator<Item = u64>) -> HashMap<u64, u64> {
+    let mut counts = HashMap::new();
+    for database_id in database_ids {
+        *counts.entry(database_id).or_default() += 1;
+    }
+    counts
+}
+
+impl MemoryData {
+    /// Like the foreign keys in the sql tables, the files, loans, codes and
+    /// movements of deleted items (and the codes of deleted locations) get deleted as well. Movements,
+    /// templates and child tags keep no reference to deleted locations (or tags).
+    /// Objects in the trash aren't deleted yet, so they keep everything.
+    fn remove_orphans(&mut self) {
+        let MemoryData {
+            items,
+            tags,
+            locations,
+            files,
+            loans,
+            movements,
+            trash,
+            templates,
+            codes,
+            ..
+        } = self;
+        let item_exists = |item_id: &u64| items.rows.contains_key(item_id) || trash.items.contains_key(item_id);
+        files.retain(|(item_id, _)| item_exists(item_id));
+        loans.rows.retain(|_, loan| item_exists(&loan.item));
+        movements.rows.retain(|_, movement| item_exists(&movement.item));
+        codes.retain(|_, code| {
+            code.item.is_none_or(|item_id| item_exists(&item_id))
+                && code
+                    .location
+                    .is_none_or(|location_id| locations.rows.contains_key(&location_id) || trash.locations.contains_key(&location_id))
+        });
+
+        for movement in movements.rows.values_mut() {
+            for location in [&mut movement.location, &mut movement.from_location] {
+                if location.is_some_and(|location_id| !locations.rows.contains_key(&location_id) && !trash.locations.contains_key(&location_id)) {
+                    *location = None;
+                }
+            }
+        }
+
+        let tag_ids: Vec<u64> = tags.rows.keys().chain(trash.tags.keys()).copied().collect();
+        for tag in tags.rows.values_mut().chain(trash.tags.values_mut().map(|trashed| &mut trashed.row)) {
+            if tag.parent.is_some_and(|parent_id| !tag_ids.contains(&parent_id)) {
+                tag.parent = None;
+            }
+        }
+
+        for template in templates.rows.values_mut() {
+            template.tags.retain(|tag_id| tags.rows.contains_key(tag_id) || trash.tags.contains_key(tag_id));
+            if template
+                .location
+                .is_some_and(|location_id| !locations.rows.contains_key(&location_id) && !trash.locations.contains_key(&location_id))
+            {
+                template.location = None;
+            }
+        }
+    }
+}
+
+impl MemoryStore {
+    pub(crate) fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// Register a user that can log in. There is no other way
+    /// to create users, because the api doesn't support that yet.
+    pub(crate) fn add_user(&self, username: &str, password: &str) -> u64 {
+        let mut data = self.lock();
+        let user_id = data.users.len() as u64 + 1;
+        data.users.insert(username.to_owned(), (password.to_owned(), user_id));
+
+        user_id
+    }
+
+    /// Every operation holds the lock for its whole duration,
+    /// which makes all of them behave like a transaction.
+    fn lock(&self) -> MutexGuard<'_, MemoryData> {
+        // A poisoned lock only means that another thread panicked while holding
+        // it. Since every operation validates before it writes, the data is still fine.
+        self.data.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
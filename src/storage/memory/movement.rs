@@ -0,0 +1,84 @@
+use async_trait::async_trait;
+
+use crate::models::{Movement, MovementKind};
+use crate::storage::memory::loan::lent_amount;
+use crate::storage::memory::revision::insert_revision;
+use crate::storage::memory::{MemoryData, MemoryStore};
+use crate::storage::{apply_delta, check_lent_amount, AdjustedAmount, Change, MovementStore, StoreError, StoreResult};
+
+#[async_trait]
+impl MovementStore for MemoryStore {
+    async fn get_movements(&self, item_id: u64) -> StoreResult<Vec<Movement>> {
+        let mut movements: Vec<Movement> = self.lock().movements.rows.values().filter(|movement| movement.item == item_id).cloned().collect();
+        movements.sort_by_key(|movement| (movement.time, movement.id));
+
+        Ok(movements)
+    }
+
+    async fn get_movements_by_items(&self, item_ids: &[u64]) -> StoreResult<Vec<Movement>> {
+        let data = self.lock();
+        let mut movements: Vec<Movement> = data.movements.rows.values().filter(|movement| item_ids.contains(&movement.item)).cloned().collect();
+        movements.sort_by_key(|movement| (movement.time, movement.id));
+
+        Ok(movements)
+    }
+
+    async fn record_movements(&self, movements: &[Movement]) -> StoreResult<Vec<AdjustedAmount>> {
+        // Work on a copy, so that one invalid movement discards all of them
+        let mut data = self.lock();
+        let mut draft = data.clone();
+
+        let mut adjusted = Vec::with_capacity(movements.len());
+        for movement in movements {
+            if movement.kind == MovementKind::Transfer {
+                return Err(StoreError::Invalid("movement.transfer", "items are transferred by changing their location!"));
+            }
+
+            let lent = lent_amount(&draft, movement.item);
+            let item = draft.items.rows.get_mut(&movement.item).ok_or(StoreError::UnknownReference("item"))?;
+            let old_amount = item.amount;
+            item.amount = apply_delta(old_amount, movement.amount)?;
+            check_lent_amount(old_amount, item.amount, lent)?;
+            item.last_edited = movement.time;
+
+            adjusted.push(AdjustedAmount {
+                item_id: item.id,
+                name: item.name.clone(),
+                old_amount,
+                amount: item.amount,
+                min_amount: item.min_amount,
+            });
+
+            let movement = Movement {
+                location: Some(item.location),
+                from_location: None,
+                ..movement.clone()
+            };
+            check_user(&draft, movement.user)?;
+            insert_movement(&mut draft, &movement);
+            insert_revision(&mut draft, movement.item, &Change::from(&movement), false);
+        }
+
+        *data = draft;
+        Ok(adjusted)
+    }
+}
+
+/// Add an entry to the stock ledger. This doesn't change the item.
+pub(super) fn insert_movement(data: &mut MemoryData, movement: &Movement) {
+    let movement_id = data.movements.next_id();
+    data.movements.rows.insert(
+        movement_id,
+        Movement {
+            id: movement_id,
+            ..movement.clone()
+        },
+    );
+}
+
+fn check_user(data: &MemoryData, user_id: Option<u64>) -> StoreResult<()> {
+    match user_id {
+        Some(user_id) if !data.users.values().any(|(_, other_id)| *other_id == user_id) => Err(StoreError::UnknownReference("user")),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+
+use crate::models::{Item, ItemRevision};
+use crate::storage::memory::item::{insert_initial_movement, insert_item, resolve_container, update_item_row};
+use crate::storage::memory::trash::restore_item;
+use crate::storage::memory::{MemoryData, MemoryStore};
+use crate::storage::{Change, RevisionStore, StoreError, StoreResult};
+
+#[async_trait]
+impl RevisionStore for MemoryStore {
+    async fn get_revisions(&self, item_id: u64) -> StoreResult<Vec<ItemRevision>> {
+        Ok(self.lock().revisions.iter().filter(|revision| revision.item == item_id).cloned().collect())
+    }
+
+    async fn get_revision(&self, item_id: u64, revision: u64) -> StoreResult<ItemRevision> {
+        self.lock()
+            .revisions
+            .iter()
+            .find(|other| other.item == item_id && other.revision == revision)
+            .cloned()
+            .ok_or(StoreError::NotFound)
+    }
+
+    async fn get_revisions_by_items(&self, item_ids: &[u64]) -> StoreResult<Vec<ItemRevision>> {
+        Ok(self.lock().revisions.iter().filter(|revision| item_ids.contains(&revision.item)).cloned().collect())
+    }
+
+    async fn restore_revision(&self, item: &Item, change: &Change) -> StoreResult<()> {
+        // Work on a copy, so that a failed update doesn't leave the item restored from the trash
+        let mut data = self.lock();
+        let mut draft = data.clone();
+
+        if draft.items.rows.contains_key(&item.id) {
+            update_item_row(&mut draft, item, change)?;
+        } else if draft.trash.items.contains_key(&item.id) {
+            restore_item(&mut draft, item.id, change)?;
+            update_item_row(&mut draft, item, change)?;
+        } else {
+            // The item was already purged, so it gets inserted again with the same id
+            let item = resolve_container(&draft, item)?;
+            insert_item(&mut draft, &item)?;
+            insert_initial_movement(&mut draft, item.id, &item, change);
+            insert_revision(&mut draft, item.id, change, false);
+        }
+
+        *data = draft;
+        Ok(())
+    }
+}
+
+/// Save the current state of the item as a new revision.
+pub(super) fn insert_revision(data: &mut MemoryData, item_id: u64, change: &Change, deleted: bool) {
+    let snapshot = match data.items.rows.get(&item_id) {
+        Some(item) => item.clone(),
+        None => return,
+    };
+    let revision = data.revisions.iter().filter(|revision| revision.item == item_id).count() as u64 + 1;
+
+    data.revisions.push(ItemRevision {
+        item: item_id,
+        revision,
+        user: change.user,
+        time: change.time,
+        reason: change.reason.clone(),
+        deleted,
+        snapshot,
+    });
+}
@@ -0,0 +1,43 @@
+use async_trait::async_trait;
+
+use crate::models::{AuthedUser, UserCredentials};
+use crate::storage::memory::MemoryStore;
+use crate::storage::{SessionStore, StoreError, StoreResult};
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn check_credentials(&self, credentials: &UserCredentials) -> StoreResult<u64> {
+        match self.lock().users.get(&credentials.username) {
+            Some((password, user_id)) if *password == credentials.password => Ok(*user_id),
+            _ => Err(StoreError::NotFound),
+        }
+    }
+
+    async fn create_session(&self, session_id: &str, user_id: u64) -> StoreResult<()> {
+        let mut data = self.lock();
+        if data.sessions.contains_key(session_id) {
+            return Err(StoreError::Conflict);
+        }
+
+        data.sessions.insert(session_id.to_owned(), user_id);
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: &str) -> StoreResult<AuthedUser> {
+        match self.lock().sessions.get(session_id) {
+            Some(user_id) => Ok(AuthedUser {
+                session_id: session_id.to_owned(),
+                user_id: *user_id,
+            }),
+            None => Err(StoreError::NotFound),
+        }
+    }
+
+    async fn delete_session(&self, session_id: &str) -> StoreResult<()> {
+        self.lock().sessions.remove(session_id).map(|_| ()).ok_or(StoreError::NotFound)
+    }
+
+    async fn count_sessions(&self) -> StoreResult<u64> {
+        Ok(self.lock().sessions.len() as u64)
+    }
+}
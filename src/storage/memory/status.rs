@@ -0,0 +1,19 @@
+use async_trait::async_trait;
+
+use crate::storage::memory::MemoryStore;
+use crate::storage::{PoolStatus, StatusStore, StoreResult};
+
+#[async_trait]
+impl StatusStore for MemoryStore {
+    async fn pool_status(&self) -> StoreResult<Option<PoolStatus>> {
+        Ok(None)
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        Ok(())
+    }
+
+    async fn pending_migrations(&self) -> StoreResult<Vec<i64>> {
+        Ok(vec![])
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::models::Tag;
+use crate::storage::memory::revision::insert_revision;
+use crate::storage::memory::{count_per_database, MemoryData, MemoryStore, Trashed};
+use crate::storage::{check_tag_merge, check_tag_parent, Change, StoreError, StoreResult, TagStore};
+
+#[async_trait]
+impl TagStore for MemoryStore {
+    async fn get_tags(&self) -> StoreResult<Vec<Tag>> {
+        Ok(visible_tags(&self.lock()))
+    }
+
+    async fn get_tag(&self, tag_id: u64) -> StoreResult<Tag> {
+        let data = self.lock();
+        data.tags.rows.get(&tag_id).map(|tag| visible_tag(&data, tag)).ok_or(StoreError::NotFound)
+    }
+
+    async fn count_tags(&self) -> StoreResult<HashMap<u64, u64>> {
+        Ok(count_per_database(self.lock().tags.rows.values().filter_map(|tag| tag.database)))
+    }
+
+    async fn put_tag(&self, tag: &Tag) -> StoreResult<u64> {
+        insert_tag(&mut self.lock(), tag)
+    }
+
+    async fn update_tag(&self, tag: &Tag) -> StoreResult<()> {
+        let mut data = self.lock();
+        if !data.tags.rows.contains_key(&tag.id) {
+            return Err(StoreError::NotFound);
+        }
+
+        check_tag(&data, tag)?;
+        if let Some(database_id) = tag.database {
+            check_tag_usage(&data, tag.id, database_id)?;
+        }
+        data.tags.rows.insert(tag.id, tag.clone());
+
+        Ok(())
+    }
+
+    async fn delete_tag(&self, tag_id: u64, deleted_at: i64) -> StoreResult<()> {
+        let mut data = self.lock();
+        let tag = data.tags.rows.remove(&tag_id).ok_or(StoreError::NotFound)?;
+
+        // The items don't show the tag until it's restored
+        let mut item_ids = vec![];
+        for item in data.items.rows.values_mut().filter(|item| item.tags.contains(&tag_id)) {
+            item.tags.retain(|id| *id != tag_id);
+            item_ids.push(item.id);
+        }
+
+        data.trash.tags.insert(
+            tag_id,
+            Trashed {
+                row: tag,
+                deleted_at,
+                batch: None,
+                item_ids,
+            },
+        );
+        Ok(())
+    }
+
+    async fn merge_tag(&self, tag_id: u64, into_id: u64, change: &Change) -> StoreResult<()> {
+        let mut data = self.lock();
+        let tags = visible_tags(&data);
+        check_tag_merge(tag_id, into_id, &tags)?;
+        if let Some(database_id) = data.tags.rows[&into_id].database {
+            check_tag_usage(&data, tag_id, database_id)?;
+        }
+
+        data.tags.rows.remove(&tag_id);
+
+        // The deleted items keep their last revision until they are restored
+        let item_ids: Vec<u64> = data.items.rows.values().filter(|item| item.tags.contains(&tag_id)).map(|item| item.id).collect();
+
+        let MemoryData {
+            items, tags, templates, trash, ..
+        } = &mut *data;
+        let item_tags = items
+            .rows
+            .values_mut()
+            .chain(trash.items.values_mut().map(|trashed| &mut trashed.row))
+            .map(|item| &mut item.tags);
+        for tag_ids in item_tags.chain(templates.rows.values_mut().map(|template| &mut template.tags)) {
+            if tag_ids.contains(&tag_id) {
+                tag_ids.retain(|id| *id != tag_id);
+                if !tag_ids.contains(&into_id) {
+                    tag_ids.push(into_id);
+                }
+            }
+        }
+
+        for tag in tags.rows.values_mut().chain(trash.tags.values_mut().map(|trashed| &mut trashed.row)) {
+            if tag.parent == Some(tag_id) {
+                tag.parent = Some(into_id);
+            }
+        }
+
+        for item_id in item_ids {
+            insert_revision(&mut data, item_id, change, false);
+        }
+
+        Ok(())
+    }
+}
+
+pub(super) fn insert_tag(data: &mut MemoryData, tag: &Tag) -> StoreResult<u64> {
+    check_tag(data, tag)?;
+
+    let tag_id = data.tags.next_id();
+    data.tags.rows.insert(tag_id, Tag { id: tag_id, ..tag.clone() });
+
+    Ok(tag_id)
+}
+
+/// Enforce the same constraints as the sql tables (unique names and an existing database)
+/// and make sure the tag fits into the hierarchy of the other tags.
+pub(super) fn check_tag(data: &MemoryData, tag: &Tag) -> StoreResult<()> {
+    if data.tags.rows.values().any(|other| other.id != tag.id && other.name == tag.name) {
+        return Err(StoreError::Conflict);
+    }
+
+    if tag.database.is_some_and(|database_id| !data.databases.rows.contains_key(&database_id)) {
+        return Err(StoreError::UnknownReference("database"));
+    }
+
+    check_tag_parent(tag, &visible_tags(data))
+}
+
+/// A tag can only be limited to a database if no item (not even a deleted one, which
+/// can still be restored) and no template of another database uses it.
+fn check_tag_usage(data: &MemoryData, tag_id: u64, database_id: u64) -> StoreResult<()> {
+    let in_other_database = |location_id: &u64| {
+        let location = data
+            .locations
+            .rows
+            .get(location_id)
+            .or_else(|| data.trash.locations.get(location_id).map(|trashed| &trashed.row));
+        location.is_some_and(|location| location.database != database_id)
+    };
+
+    let mut items = data.items.rows.values().chain(data.trash.items.values().map(|trashed| &trashed.row));
+    if items.any(|item| item.tags.contains(&tag_id) && in_other_database(&item.location)) {
+        return Err(StoreError::Invalid("tag.used_in_other_database", "the tag is used by items of another database!"));
+    }
+
+    let mut templates = data.templates.rows.values();
+    if templates.any(|template| template.tags.contains(&tag_id) && template.location.as_ref().is_some_and(in_other_database)) {
+        return Err(StoreError::Invalid("tag.used_in_other_database", "the tag is used by templates of another database!"));
+    }
+
+    Ok(())
+}
+
+/// All tags that aren't deleted, as they are shown to the user.
+fn visible_tags(data: &MemoryData) -> Vec<Tag> {
+    data.tags.rows.values().map(|tag| visible_tag(data, tag)).collect()
+}
+
+/// Like the deleted tags on the items, a deleted parent is hidden until it's restored.
+fn visible_tag(data: &MemoryData, tag: &Tag) -> Tag {
+    Tag {
+        parent: tag.parent.filter(|parent_id| data.tags.rows.contains_key(parent_id)),
+        ..tag.clone()
+    }
+}
@@ -0,0 +1,80 @@
+use async_trait::async_trait;
+
+use crate::models::ItemTemplate;
+use crate::storage::memory::{MemoryData, MemoryStore};
+use crate::storage::{StoreError, StoreResult, TemplateStore};
+
+#[async_trait]
+impl TemplateStore for MemoryStore {
+    async fn get_templates(&self) -> StoreResult<Vec<ItemTemplate>> {
+        let data = self.lock();
+        Ok(data.templates.rows.values().map(|template| visible_template(&data, template)).collect())
+    }
+
+    async fn get_template(&self, template_id: u64) -> StoreResult<ItemTemplate> {
+        let data = self.lock();
+        data.templates
+            .rows
+            .get(&template_id)
+            .map(|template| visible_template(&data, template))
+            .ok_or(StoreError::NotFound)
+    }
+
+    async fn put_template(&self, template: &ItemTemplate) -> StoreResult<u64> {
+        let mut data = self.lock();
+        check_template(&data, template)?;
+
+        let template_id = data.templates.next_id();
+        data.templates.rows.insert(
+            template_id,
+            ItemTemplate {
+                id: template_id,
+                ..template.clone()
+            },
+        );
+
+        Ok(template_id)
+    }
+
+    async fn update_template(&self, template: &ItemTemplate) -> StoreResult<()> {
+        let mut data = self.lock();
+        if !data.templates.rows.contains_key(&template.id) {
+            return Err(StoreError::NotFound);
+        }
+
+        check_template(&data, template)?;
+        data.templates.rows.insert(template.id, template.clone());
+
+        Ok(())
+    }
+
+    async fn delete_template(&self, template_id: u64) -> StoreResult<()> {
+        self.lock().templates.rows.remove(&template_id).ok_or(StoreError::NotFound)?;
+        Ok(())
+    }
+}
+
+/// Deleted tags and locations are hidden until they are restored.
+fn visible_template(data: &MemoryData, template: &ItemTemplate) -> ItemTemplate {
+    let mut template = template.clone();
+    template.tags.retain(|tag_id| data.tags.rows.contains_key(tag_id));
+    template.location = template.location.filter(|location_id| data.locations.rows.contains_key(location_id));
+
+    template
+}
+
+fn check_template(data: &MemoryData, template: &ItemTemplate) -> StoreResult<()> {
+    if data.templates.rows.values().any(|other| other.id != template.id && other.name == template.name) {
+        return Err(StoreError::Conflict);
+    }
+
+    if template.location.is_some_and(|location_id| !data.locations.rows.contains_key(&location_id)) {
+        return Err(StoreError::UnknownReference("location"));
+    }
+
+    if template.tags.iter().any(|tag_id| !data.tags.rows.contains_key(tag_id)) {
+        return Err(StoreError::UnknownReference("tag"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,304 @@
+use async_trait::async_trait;
+
+use crate::models::{Location, Tag, TrashEntry, TrashKind};
+use crate::storage::memory::database::check_database;
+use crate::storage::memory::item::check_item;
+use crate::storage::memory::location::check_location;
+use crate::storage::memory::revision::insert_revision;
+use crate::storage::memory::tag::check_tag;
+use crate::storage::memory::{MemoryData, MemoryStore, Trashed};
+use crate::storage::{location_descendants, Change, StoreError, StoreResult, TrashStore};
+
+#[async_trait]
+impl TrashStore for MemoryStore {
+    async fn get_trash(&self) -> StoreResult<Vec<TrashEntry>> {
+        let data = self.lock();
+        let trash = &data.trash;
+
+        let entry = |kind, id: &u64, name: &str, deleted_at| TrashEntry {
+            kind,
+            id: *id,
+            name: name.to_owned(),
+            deleted_at,
+        };
+
+        let mut entries = Vec::new();
+        entries.extend(
+            trash
+                .databases
+                .iter()
+                .map(|(id, trashed)| entry(TrashKind::Database, id, &trashed.row.name, trashed.deleted_at)),
+        );
+        entries.extend(
+            trash
+                .locations
+                .iter()
+                .map(|(id, trashed)| entry(TrashKind::Location, id, &trashed.row.name, trashed.deleted_at)),
+        );
+        entries.extend(trash.tags.iter().map(|(id, trashed)| entry(TrashKind::Tag, id, &trashed.row.name, trashed.deleted_at)));
+        entries.extend(trash.items.iter().map(|(id, trashed)| entry(TrashKind::Item, id, &trashed.row.name, trashed.deleted_at)));
+
+        Ok(entries)
+    }
+
+    async fn restore_from_trash(&self, kind: TrashKind, id: u64, change: &Change) -> StoreResult<()> {
+        // Work on a copy, so that a failed restore doesn't leave anything behind
+        let mut data = self.lock();
+        let mut draft = data.clone();
+
+        match kind {
+            TrashKind::Item => restore_item(&mut draft, id, change)?,
+            TrashKind::Tag => restore_tag(&mut draft, id)?,
+            TrashKind::Location => restore_location(&mut draft, id, change)?,
+            TrashKind::Database => restore_database(&mut draft, id, change)?,
+        }
+
+        *data = draft;
+        Ok(())
+    }
+
+    async fn purge_trash(&self, deleted_before: i64) -> StoreResult<u64> {
+        let mut data = self.lock();
+        let MemoryData {
+            items,
+            tags,
+            locations,
+            databases,
+            trash,
+            ..
+        } = &mut *data;
+
+        let count = trash.items.len() + trash.tags.len() + trash.locations.len() + trash.databases.len();
+        trash.items.retain(|_, trashed| trashed.deleted_at >= deleted_before);
+        trash.tags.retain(|_, trashed| trashed.deleted_at >= deleted_before);
+        trash.locations.retain(|_, trashed| trashed.deleted_at >= deleted_before);
+        trash.databases.retain(|_, trashed| trashed.deleted_at >= deleted_before);
+        let purged = count - (trash.items.len() + trash.tags.len() + trash.locations.len() + trash.databases.len());
+
+        // Like the foreign keys in the sql tables, deleting a database or location
+        // deletes everything in it, and the children of a location lose their parent.
+        let database_exists = |database_id: &u64| databases.rows.contains_key(database_id) || trash.databases.contains_key(database_id);
+        trash.locations.retain(|_, trashed| database_exists(&trashed.row.database));
+        let location_exists = |location_id: &u64| locations.rows.contains_key(location_id) || trash.locations.contains_key(location_id);
+        trash.items.retain(|_, trashed| location_exists(&trashed.row.location));
+        let tag_in_database = |tag: &Tag| tag.database.is_none_or(|database_id| database_exists(&database_id));
+        tags.rows.retain(|_, tag| tag_in_database(tag));
+        trash.tags.retain(|_, trashed| tag_in_database(&trashed.row));
+
+        let parent_ids: Vec<u64> = locations.rows.keys().chain(trash.locations.keys()).copied().collect();
+        for location in locations.rows.values_mut().chain(trash.locations.values_mut().map(|trashed| &mut trashed.row)) {
+            if location.parent.is_some_and(|parent_id| !parent_ids.contains(&parent_id)) {
+                location.parent = None;
+            }
+        }
+
+        let tag_exists = |tag_id: &u64| tags.rows.contains_key(tag_id) || trash.tags.contains_key(tag_id);
+        for trashed in trash.items.values_mut() {
+            trashed.row.tags.retain(tag_exists);
+        }
+        let item_ids: Vec<u64> = items.rows.keys().chain(trash.items.keys()).copied().collect();
+        for trashed in trash.tags.values_mut() {
+            trashed.item_ids.retain(|item_id| item_ids.contains(item_id));
+        }
+
+        data.remove_orphans();
+        Ok(purged as u64)
+    }
+}
+
+/// Move the database with all of its locations and items to the trash.
+pub(super) fn trash_database(data: &mut MemoryData, database_id: u64, change: &Change) -> StoreResult<()> {
+    let database = data.databases.rows.remove(&database_id).ok_or(StoreError::NotFound)?;
+    let batch = data.trash.next_batch();
+
+    // Everything inside of the database goes to the trash as well
+    let location_ids: Vec<u64> = data
+        .locations
+        .rows
+        .values()
+        .filter(|location| location.database == database_id)
+        .map(|location| location.id)
+        .collect();
+    for location_id in location_ids {
+        trash_location(data, location_id, batch, change);
+    }
+
+    // The tags of the database can only be used by its items, which are in the trash now
+    let tag_ids: Vec<u64> = data.tags.rows.values().filter(|tag| tag.database == Some(database_id)).map(|tag| tag.id).collect();
+    for tag_id in tag_ids {
+        if let Some(tag) = data.tags.rows.remove(&tag_id) {
+            data.trash.tags.insert(tag_id, trashed(tag, change.time, batch));
+        }
+    }
+
+    data.trash.databases.insert(database_id, trashed(database, change.time, batch));
+    Ok(())
+}
+
+/// Move the location and its items to the trash, as part of the batch.
+pub(super) fn trash_location(data: &mut MemoryData, location_id: u64, batch: u64, change: &Change) {
+    let item_ids: Vec<u64> = data.items.rows.values().filter(|item| item.location == location_id).map(|item| item.id).collect();
+    for item_id in item_ids {
+        // The last state of the item is kept, so it can be restored
+        insert_revision(data, item_id, change, true);
+        if let Some(item) = data.items.rows.remove(&item_id) {
+            data.trash.items.insert(item_id, trashed(item, change.time, batch));
+        }
+    }
+
+    if let Some(location) = data.locations.rows.remove(&location_id) {
+        data.trash.locations.insert(location_id, trashed(location, change.time, batch));
+    }
+}
+
+fn trashed<T>(row: T, deleted_at: i64, batch: u64) -> Trashed<T> {
+    Trashed {
+        row,
+        deleted_at,
+        batch: Some(batch),
+        item_ids: vec![],
+    }
+}
+
+pub(super) fn restore_item(data: &mut MemoryData, item_id: u64, change: &Change) -> StoreResult<()> {
+    let mut item = data.trash.items.remove(&item_id).ok_or(StoreError::NotFound)?.row;
+    if !data.locations.rows.contains_key(&item.location) {
+        return Err(StoreError::Invalid("trash.location_deleted", "the location of the item has to be restored first!"));
+    }
+
+    // A container that is still deleted doesn't hold the item anymore
+    if item.parent_item.is_some_and(|parent_id| !data.items.rows.contains_key(&parent_id)) {
+        item.parent_item = None;
+    }
+
+    // Deleted tags stay hidden until they are restored
+    for tag_id in &item.tags {
+        if let Some(trashed_tag) = data.trash.tags.get_mut(tag_id) {
+            trashed_tag.item_ids.push(item_id);
+        }
+    }
+    let tags = &data.tags;
+    item.tags.retain(|tag_id| tags.rows.contains_key(tag_id));
+
+    // Another item with the same name in the location causes a conflict
+    check_item(data, &item)?;
+    data.items.rows.insert(item_id, item);
+    insert_revision(data, item_id, change, false);
+
+    Ok(())
+}
+
+fn restore_tag(data: &mut MemoryData, tag_id: u64) -> StoreResult<()> {
+    let trashed = data.trash.tags.remove(&tag_id).ok_or(StoreError::NotFound)?;
+
+    // A parent that is still deleted is hidden, so it doesn't have to be checked
+    let visible = Tag {
+        parent: trashed.row.parent.filter(|parent_id| data.tags.rows.contains_key(parent_id)),
+        ..trashed.row.clone()
+    };
+    check_tag(data, &visible)?;
+    data.tags.rows.insert(tag_id, trashed.row);
+
+    for item_id in trashed.item_ids {
+        if let Some(item) = data.items.rows.get_mut(&item_id) {
+            item.tags.push(tag_id);
+        }
+    }
+
+    Ok(())
+}
+
+fn restore_location(data: &mut MemoryData, location_id: u64, change: &Change) -> StoreResult<()> {
+    let trashed = data.trash.locations.get(&location_id).ok_or(StoreError::NotFound)?;
+    let (location, batch) = (trashed.row.clone(), trashed.batch);
+
+    if !data.databases.rows.contains_key(&location.database) {
+        return Err(StoreError::Invalid("trash.database_deleted", "the database of the location has to be restored first!"));
+    }
+    if location.parent.is_some_and(|parent_id| !data.locations.rows.contains_key(&parent_id)) {
+        return Err(StoreError::Invalid("trash.parent_deleted", "the parent location has to be restored first!"));
+    }
+
+    // The child locations that were deleted together with the location come back as well
+    let deleted_together: Vec<Location> = data
+        .trash
+        .locations
+        .values()
+        .filter(|trashed| trashed.batch == batch && trashed.row.database == location.database)
+        .map(|trashed| trashed.row.clone())
+        .collect();
+
+    restore_location_row(data, location_id, change)?;
+    for descendant_id in location_descendants(location_id, &deleted_together) {
+        restore_location_row(data, descendant_id, change)?;
+    }
+
+    Ok(())
+}
+
+fn restore_database(data: &mut MemoryData, database_id: u64, change: &Change) -> StoreResult<()> {
+    let trashed = data.trash.databases.remove(&database_id).ok_or(StoreError::NotFound)?;
+    check_database(data, &trashed.row)?;
+    data.databases.rows.insert(database_id, trashed.row);
+
+    let tag_ids: Vec<u64> = data
+        .trash
+        .tags
+        .values()
+        .filter(|other| other.batch == trashed.batch && other.row.database == Some(database_id))
+        .map(|other| other.row.id)
+        .collect();
+    for tag_id in tag_ids {
+        restore_tag(data, tag_id)?;
+    }
+
+    // The locations come back first, because they can be each others parents
+    let location_ids: Vec<u64> = data
+        .trash
+        .locations
+        .values()
+        .filter(|other| other.batch == trashed.batch && other.row.database == database_id)
+        .map(|other| other.row.id)
+        .collect();
+    for location_id in &location_ids {
+        if let Some(location) = data.trash.locations.remove(location_id) {
+            data.locations.rows.insert(*location_id, location.row);
+        }
+    }
+    for location_id in location_ids {
+        let location = data.locations.rows[&location_id].clone();
+        check_location(data, &location)?;
+        restore_location_items(data, location_id, trashed.batch, change)?;
+    }
+
+    Ok(())
+}
+
+/// Restore the location and the items of its batch.
+fn restore_location_row(data: &mut MemoryData, location_id: u64, change: &Change) -> StoreResult<()> {
+    let trashed = data.trash.locations.remove(&location_id).ok_or(StoreError::NotFound)?;
+    check_location(data, &trashed.row)?;
+    data.locations.rows.insert(location_id, trashed.row);
+
+    restore_location_items(data, location_id, trashed.batch, change)
+}
+
+fn restore_location_items(data: &mut MemoryData, location_id: u64, batch: Option<u64>, change: &Change) -> StoreResult<()> {
+    let item_ids: Vec<u64> = data
+        .trash
+        .items
+        .values()
+        .filter(|trashed| trashed.row.location == location_id && trashed.batch.is_some() && trashed.batch == batch)
+        .map(|trashed| trashed.row.id)
+        .collect();
+
+    for item_id in item_ids {
+        if let Some(trashed) = data.trash.items.remove(&item_id) {
+            check_item(data, &trashed.row)?;
+            data.items.rows.insert(item_id, trashed.row);
+            insert_revision(data, item_id, change, false);
+        }
+    }
+
+    Ok(())
+}
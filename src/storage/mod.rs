@@ -0,0 +1,747 @@
+use std::collections::{HashMap, HashSet};
+use std::{fmt, sync::Arc};
+
+use async_graphql::Enum;
+use async_trait::async_trait;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::models::{
+    AuthedUser, Backup, Database, Icon, Item, ItemFile, ItemRevision, ItemTemplate, Loan, Location, Movement, MovementKind, ScanCode, Tag, TrashEntry, TrashKind, UserCredentials,
+};
+
+pub(crate) mod blob;
+#[cfg(test)]
+mod contract_tests;
+pub(crate) mod memory;
+pub(crate) mod sql;
+
+pub(crate) type StoreResult<T> = Result<T, StoreError>;
+
+/// Everything that can go wrong inside a storage backend.
+/// The web handlers translate these into the matching HTTP errors.
+#[derive(Debug)]
+pub(crate) enum StoreError {
+    /// The requested object doesn't exist.
+    NotFound,
+
+    /// An object with the same (unique) name already exists.
+    Conflict,
+
+    /// The object refers to another object that doesn't exist.
+    /// The value is the name of the referenced entity (e.g. "location").
+    UnknownReference(&'static str),
+
+    /// The change would break a rule of the data model (code, message),
+    /// e.g. a location that would end up inside of itself.
+    Invalid(&'static str, &'static str),
+
+    /// Something went wrong inside the backend itself.
+    Internal(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::NotFound => write!(f, "object not found"),
+            StoreError::Conflict => write!(f, "object already exists"),
+            StoreError::UnknownReference(entity) => write!(f, "unknown {entity} reference"),
+            StoreError::Invalid(_, message) => write!(f, "{message}"),
+            StoreError::Internal(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+#[async_trait]
+pub(crate) trait ItemStore: Send + Sync {
+    async fn get_items(&self) -> StoreResult<Vec<Item>>;
+    async fn get_item(&self, item_id: u64) -> StoreResult<Item>;
+
+    /// The items with these ids. Ids of items that don't exist are skipped.
+    async fn get_items_by_ids(&self, item_ids: &[u64]) -> StoreResult<Vec<Item>>;
+
+    /// The items directly inside of these containers.
+    async fn get_items_by_containers(&self, container_ids: &[u64]) -> StoreResult<Vec<Item>>;
+
+    /// The items directly inside of these locations (without their child locations).
+    async fn get_items_by_locations(&self, location_ids: &[u64]) -> StoreResult<Vec<Item>>;
+
+    /// The items that have at least one of these tags.
+    async fn get_items_by_tags(&self, tag_ids: &[u64]) -> StoreResult<Vec<Item>>;
+
+    /// The number of items in every database (by its id).
+    async fn count_items(&self) -> StoreResult<HashMap<u64, u64>>;
+
+    /// Insert a new item and return its generated id.
+    /// The initial amount is recorded as received.
+    async fn put_item(&self, item: &Item, change: &Change) -> StoreResult<u64>;
+
+    /// Update the item. A different amount is recorded as an adjustment and
+    /// a different location as a transfer (of the item and its contents).
+    /// Returns [StoreError::Invalid] if the amount drops below the lent amount.
+    async fn update_item(&self, item: &Item, change: &Change) -> StoreResult<()>;
+
+    /// Move the item to the trash. The last revision stays, so the
+    /// item can also be restored after it was purged from the trash.
+    async fn delete_item(&self, item_id: u64, change: &Change) -> StoreResult<()>;
+
+    /// Insert many items at once. Tags and locations are resolved by name
+    /// and created if they don't exist yet. The changes are only applied
+    /// if every item could be inserted and `dry_run` isn't set.
+    /// Returns the result for every item (in the same order).
+    async fn import_items(&self, items: &[ImportedItem], dry_run: bool, change: &Change) -> StoreResult<Vec<StoreResult<u64>>>;
+}
+
+/// Who changed something, when and why. Changes of the amount
+/// or location of an item are recorded in the stock ledger with it.
+pub(crate) struct Change {
+    pub(crate) user: Option<u64>,
+    pub(crate) time: i64,
+    pub(crate) reason: String,
+}
+
+impl Change {
+    /// A ledger entry for this change.
+    pub(crate) fn movement(&self, item_id: u64, kind: MovementKind, amount: i64, location: u64, from_location: Option<u64>) -> Movement {
+        Movement {
+            id: 0,
+            item: item_id,
+            kind,
+            amount,
+            location: Some(location),
+            from_location,
+            reason: self.reason.clone(),
+            user: self.user,
+            time: self.time,
+        }
+    }
+}
+
+/// The result of a movement that changed the amount of an item.
+pub(crate) struct AdjustedAmount {
+    pub(crate) item_id: u64,
+    pub(crate) name: String,
+    pub(crate) old_amount: u64,
+    pub(crate) amount: u64,
+    pub(crate) min_amount: Option<u64>,
+}
+
+/// Add a (possibly negative) difference to an amount.
+pub(crate) fn apply_delta(amount: u64, delta: i64) -> StoreResult<u64> {
+    let amount = if delta < 0 {
+        amount.checked_sub(delta.unsigned_abs())
+    } else {
+        amount.checked_add(delta.unsigned_abs())
+    };
+    amount.ok_or(StoreError::Invalid("item.negative_amount", "the amount of an item can't become negative!"))
+}
+
+/// Make sure that a smaller amount still covers the pieces that are lent.
+pub(crate) fn check_lent_amount(old_amount: u64, amount: u64, lent: u64) -> StoreResult<()> {
+    if amount < old_amount && amount < lent {
+        return Err(StoreError::Invalid("item.amount_lent", "the amount of an item can't be less than the lent amount!"));
+    }
+    Ok(())
+}
+
+/// An item from an import (e.g. a CSV file). The location
+/// and tags are referenced by name instead of their id, so
+/// `item.location` and `item.tags` are ignored. Items
+/// inside of a container get the location of the container.
+pub(crate) struct ImportedItem {
+    pub(crate) item: Item,
+    pub(crate) location: LocationPath,
+    pub(crate) tags: Vec<String>,
+
+    /// The index of the imported item that contains this one, instead of
+    /// `item.parent_item`. The container has to come before this item.
+    pub(crate) container: Option<usize>,
+}
+
+impl ImportedItem {
+    /// The id the container of the item got during the import.
+    pub(crate) fn container_id(&self, results: &[StoreResult<u64>]) -> StoreResult<Option<u64>> {
+        match self.container {
+            Some(index) => match results.get(index) {
+                Some(Ok(container_id)) => Ok(Some(*container_id)),
+                _ => Err(StoreError::UnknownReference("item")),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+/// Reference to a location by name. Without a database name, the location
+/// must already exist and the path only contains its name. With a database
+/// name, the path leads from a top-level location of the database down to the
+/// location. Missing locations on the way are created inside of the database.
+pub(crate) struct LocationPath {
+    pub(crate) database: Option<String>,
+    pub(crate) path: Vec<String>,
+}
+
+#[async_trait]
+pub(crate) trait TagStore: Send + Sync {
+    async fn get_tags(&self) -> StoreResult<Vec<Tag>>;
+    async fn get_tag(&self, tag_id: u64) -> StoreResult<Tag>;
+
+    /// The number of tags of every database (by its id). Global tags aren't counted.
+    async fn count_tags(&self) -> StoreResult<HashMap<u64, u64>>;
+
+    /// Insert a new tag and return its generated id.
+    async fn put_tag(&self, tag: &Tag) -> StoreResult<u64>;
+    async fn update_tag(&self, tag: &Tag) -> StoreResult<()>;
+
+    /// Move the tag to the trash. Items don't show it until it's restored.
+    async fn delete_tag(&self, tag_id: u64, deleted_at: i64) -> StoreResult<()>;
+
+    /// Replace the tag with another one on all items and templates, move its child
+    /// tags below the other tag and delete it for good (without the trash).
+    async fn merge_tag(&self, tag_id: u64, into_id: u64, change: &Change) -> StoreResult<()>;
+}
+
+#[async_trait]
+pub(crate) trait TemplateStore: Send + Sync {
+    async fn get_templates(&self) -> StoreResult<Vec<ItemTemplate>>;
+    async fn get_template(&self, template_id: u64) -> StoreResult<ItemTemplate>;
+
+    /// Insert a new template and return its generated id.
+    async fn put_template(&self, template: &ItemTemplate) -> StoreResult<u64>;
+    async fn update_template(&self, template: &ItemTemplate) -> StoreResult<()>;
+    async fn delete_template(&self, template_id: u64) -> StoreResult<()>;
+}
+
+#[async_trait]
+pub(crate) trait LocationStore: Send + Sync {
+    async fn get_locations(&self) -> StoreResult<Vec<Location>>;
+    async fn get_location(&self, location_id: u64) -> StoreResult<Location>;
+
+    /// The number of locations in every database (by its id).
+    async fn count_locations(&self) -> StoreResult<HashMap<u64, u64>>;
+
+    /// Insert a new location and return its generated id.
+    async fn put_location(&self, location: &Location) -> StoreResult<u64>;
+    async fn update_location(&self, location: &Location) -> StoreResult<()>;
+
+    /// Move the location and its items to the trash. What happens
+    /// to the locations inside of it depends on the policy.
+    async fn delete_location(&self, location_id: u64, policy: ChildPolicy, change: &Change) -> StoreResult<()>;
+}
+
+/// What happens to the child locations of a deleted location.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, ToSchema, Enum, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ChildPolicy {
+    /// Don't delete the location if there is anything inside of it.
+    Reject,
+
+    /// Move the child locations and the items into the parent location.
+    /// Top level locations have no parent, so their child locations
+    /// become top level locations and their items are deleted.
+    Reparent,
+
+    /// Delete all child locations (and their items) as well.
+    Cascade,
+}
+
+/// Make sure that the parent of a location exists inside of the same database
+/// and that the location doesn't end up inside of itself. The other locations
+/// must contain at least all locations of the database.
+pub(crate) fn check_location_parent(location: &Location, locations: &[Location]) -> StoreResult<()> {
+    let parent_id = match location.parent {
+        Some(parent_id) => parent_id,
+        None => return Ok(()),
+    };
+
+    let parent = locations.iter().find(|other| other.id == parent_id).ok_or(StoreError::UnknownReference("location"))?;
+    if parent.database != location.database {
+        return Err(StoreError::Invalid(
+            "location.parent_in_other_database",
+            "the parent location must be in the same database!",
+        ));
+    }
+
+    let parents = locations.iter().map(|other| (other.id, other.parent)).collect();
+    if creates_cycle(location.id, parent_id, &parents) {
+        return Err(StoreError::Invalid("location.cycle", "a location can't be inside of itself!"));
+    }
+
+    Ok(())
+}
+
+/// Make sure that the parent of a tag exists and that the tag doesn't end up below
+/// itself. Tags of a database can only be below global tags or tags of the same
+/// database. The other tags must contain at least all tags that aren't deleted.
+pub(crate) fn check_tag_parent(tag: &Tag, tags: &[Tag]) -> StoreResult<()> {
+    if let Some(parent_id) = tag.parent {
+        let parent = tags.iter().find(|other| other.id == parent_id).ok_or(StoreError::UnknownReference("tag"))?;
+        if parent.database.is_some() && parent.database != tag.database {
+            return Err(StoreError::Invalid(
+                "tag.parent_in_other_database",
+                "the parent tag must be global or in the same database!",
+            ));
+        }
+
+        let parents = tags.iter().map(|other| (other.id, other.parent)).collect();
+        if creates_cycle(tag.id, parent_id, &parents) {
+            return Err(StoreError::Invalid("tag.cycle", "a tag can't be below itself!"));
+        }
+    }
+
+    // The child tags have to stay in the same database
+    if tag.database.is_some()
+        && tags
+            .iter()
+            .any(|other| other.id != tag.id && other.parent == Some(tag.id) && other.database != tag.database)
+    {
+        return Err(StoreError::Invalid("tag.children_in_other_database", "the child tags must be in the same database!"));
+    }
+
+    Ok(())
+}
+
+/// Make sure that a tag can be merged into another one. The child tags of
+/// the tag move below the other tag, so it can't be one of them.
+pub(crate) fn check_tag_merge(tag_id: u64, into_id: u64, tags: &[Tag]) -> StoreResult<()> {
+    if !tags.iter().any(|tag| tag.id == tag_id) {
+        return Err(StoreError::NotFound);
+    }
+    let into = tags.iter().find(|tag| tag.id == into_id).ok_or(StoreError::UnknownReference("tag"))?;
+
+    if tag_id == into_id {
+        return Err(StoreError::Invalid("tag.merge_into_itself", "a tag can't be merged into itself!"));
+    }
+    if tag_descendants(tag_id, tags).contains(&into_id) {
+        return Err(StoreError::Invalid("tag.cycle", "a tag can't be merged into one of its child tags!"));
+    }
+    if into.database.is_some() && tags.iter().any(|tag| tag.parent == Some(tag_id) && tag.database != into.database) {
+        return Err(StoreError::Invalid("tag.children_in_other_database", "the child tags must be in the same database!"));
+    }
+
+    Ok(())
+}
+
+/// The ids of all tags below the tag (recursively), without the tag itself.
+pub(crate) fn tag_descendants(tag_id: u64, tags: &[Tag]) -> Vec<u64> {
+    descendants(tag_id, &tags.iter().map(|tag| (tag.id, tag.parent)).collect())
+}
+
+/// The ids of all locations inside of the location (recursively), without the location itself.
+pub(crate) fn location_descendants(location_id: u64, locations: &[Location]) -> Vec<u64> {
+    descendants(location_id, &locations.iter().map(|location| (location.id, location.parent)).collect())
+}
+
+/// Make sure that all containers of the items are part of the list and
+/// that no item is inside of itself. Used to check imported items.
+pub(crate) fn check_item_containers(items: &[Item]) -> StoreResult<()> {
+    let parents: HashMap<u64, Option<u64>> = items.iter().map(|item| (item.id, item.parent_item)).collect();
+
+    for item in items {
+        if let Some(parent_id) = item.parent_item {
+            if !parents.contains_key(&parent_id) {
+                return Err(StoreError::UnknownReference("item"));
+            }
+            if creates_cycle(item.id, parent_id, &parents) {
+                return Err(StoreError::Invalid("item.cycle", "an item can't be inside of itself!"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The tags of a backup, with the new ids of the restored databases (in the same order
+/// as in the backup). Parents that aren't part of the backup are dropped, because older
+/// backups only contain the tags in use.
+pub(crate) fn backup_tags(backup: &Backup, database_ids: &[u64]) -> StoreResult<Vec<Tag>> {
+    let new_database_ids: HashMap<u64, u64> = backup
+        .databases
+        .iter()
+        .map(|database_backup| database_backup.database.id)
+        .zip(database_ids.iter().copied())
+        .collect();
+    let tag_ids: HashSet<u64> = backup.tags.iter().map(|tag| tag.id).collect();
+
+    let tags = backup
+        .tags
+        .iter()
+        .map(|tag| {
+            let database = match tag.database {
+                Some(database_id) => Some(*new_database_ids.get(&database_id).ok_or(StoreError::UnknownReference("database"))?),
+                None => None,
+            };
+            Ok(Tag {
+                parent: tag.parent.filter(|parent_id| tag_ids.contains(parent_id)),
+                database,
+                ..tag.clone()
+            })
+        })
+        .collect::<StoreResult<Vec<Tag>>>()?;
+
+    for tag in &tags {
+        check_tag_parent(tag, &tags)?;
+    }
+
+    Ok(tags)
+}
+
+/// The name of a restored database tag, if another tag already has its name
+/// (e.g. the tag of the original database, if the backup is restored as a copy).
+pub(crate) fn restored_tag_name(tag_name: &str, database_name: &str) -> String {
+    format!("{tag_name} ({database_name})")
+}
+
+/// Whether an object (a location, an item or a tag) would end up inside of itself,
+/// if it was put into the parent. The map contains the parent of every object.
+pub(crate) fn creates_cycle(id: u64, parent_id: u64, parents: &HashMap<u64, Option<u64>>) -> bool {
+    // Walk up to the top and check if we pass the object itself. The counter protects
+    // us from cycles that already exist (they shouldn't, but better safe than an endless loop).
+    let mut ancestor_id = parent_id;
+    for _ in 0..=parents.len() {
+        if ancestor_id == id {
+            return true;
+        }
+
+        match parents.get(&ancestor_id).copied().flatten() {
+            Some(parent_id) => ancestor_id = parent_id,
+            None => return false,
+        }
+    }
+
+    true
+}
+
+/// The ids of all objects inside of the object (recursively), without the object itself.
+/// The map contains the parent of every object.
+pub(crate) fn descendants(id: u64, parents: &HashMap<u64, Option<u64>>) -> Vec<u64> {
+    let mut descendants = vec![];
+    let mut pending = vec![id];
+
+    while let Some(parent_id) = pending.pop() {
+        for (child_id, _) in parents.iter().filter(|(_, parent)| **parent == Some(parent_id)) {
+            // Again, protect against cycles
+            if *child_id != id && !descendants.contains(child_id) {
+                descendants.push(*child_id);
+                pending.push(*child_id);
+            }
+        }
+    }
+
+    descendants
+}
+
+#[async_trait]
+pub(crate) trait DatabaseStore: Send + Sync {
+    async fn get_databases(&self) -> StoreResult<Vec<Database>>;
+    async fn get_database(&self, database_id: u64) -> StoreResult<Database>;
+
+    /// Insert a new database and return its generated id.
+    async fn put_database(&self, database: &Database) -> StoreResult<u64>;
+    async fn update_database(&self, database: &Database) -> StoreResult<()>;
+
+    /// Move the database with all of its locations and items to the trash.
+    async fn delete_database(&self, database_id: u64, change: &Change) -> StoreResult<()>;
+}
+
+#[async_trait]
+pub(crate) trait SessionStore: Send + Sync {
+    /// Look up the user id that belongs to the credentials.
+    /// Returns [StoreError::NotFound] if they are invalid.
+    async fn check_credentials(&self, credentials: &UserCredentials) -> StoreResult<u64>;
+
+    /// Store a new session. Returns [StoreError::Conflict]
+    /// if the session id is already taken.
+    async fn create_session(&self, session_id: &str, user_id: u64) -> StoreResult<()>;
+    async fn get_session(&self, session_id: &str) -> StoreResult<AuthedUser>;
+    async fn delete_session(&self, session_id: &str) -> StoreResult<()>;
+
+    /// How many sessions exist right now.
+    async fn count_sessions(&self) -> StoreResult<u64>;
+}
+
+#[async_trait]
+pub(crate) trait BackupStore: Send + Sync {
+    /// Restore all databases of a backup inside of one transaction and
+    /// return their new ids. All ids are remapped, tags are matched by name.
+    /// If `as_new` is set, the databases are created next to the existing ones,
+    /// otherwise an existing database with the same name is moved to the trash.
+    async fn restore_backup(&self, backup: &Backup, as_new: bool, change: &Change) -> StoreResult<Vec<u64>>;
+}
+
+#[async_trait]
+pub(crate) trait FileStore: Send + Sync {
+    /// The image and attachments of an item.
+    async fn get_item_files(&self, item_id: u64) -> StoreResult<Vec<ItemFile>>;
+
+    /// Link the file to the item. The url is where the file can be downloaded,
+    /// it's used as the image url or attachment url of the item.
+    /// The image or an attachment with the same name gets replaced.
+    async fn put_item_file(&self, item_id: u64, file: &ItemFile, url: &str) -> StoreResult<()>;
+
+    /// Unlink the file from the item and remove the image or attachment url.
+    async fn delete_item_file(&self, item_id: u64, is_image: bool, name: &str) -> StoreResult<()>;
+
+    /// The hashes of all files that are still in use.
+    async fn get_file_hashes(&self) -> StoreResult<HashSet<String>>;
+}
+
+#[async_trait]
+pub(crate) trait CodeStore: Send + Sync {
+    /// The codes of all items and locations that aren't deleted.
+    async fn get_codes(&self) -> StoreResult<Vec<ScanCode>>;
+
+    /// Find the first of the codes that exists. The codes are
+    /// different spellings of the same scan, e.g. UPC and EAN.
+    async fn find_code(&self, codes: &[String]) -> StoreResult<ScanCode>;
+
+    /// Returns [StoreError::Conflict] if the code is already in use.
+    async fn put_code(&self, code: &ScanCode) -> StoreResult<()>;
+    async fn delete_code(&self, code: &str) -> StoreResult<()>;
+}
+
+#[async_trait]
+pub(crate) trait LoanStore: Send + Sync {
+    async fn get_loans(&self) -> StoreResult<Vec<Loan>>;
+    async fn get_loan(&self, loan_id: u64) -> StoreResult<Loan>;
+
+    /// The loans of these items, returned or not.
+    async fn get_loans_by_items(&self, item_ids: &[u64]) -> StoreResult<Vec<Loan>>;
+
+    /// Lend (a part of) an item and return the id of the loan. Returns
+    /// [StoreError::Invalid] if not enough of the item is available.
+    async fn check_out(&self, loan: &Loan) -> StoreResult<u64>;
+
+    /// Mark the loan as returned at the given time.
+    async fn check_in(&self, loan_id: u64, returned: i64) -> StoreResult<()>;
+}
+
+/// The change that caused a movement.
+impl From<&Movement> for Change {
+    fn from(movement: &Movement) -> Self {
+        Change {
+            user: movement.user,
+            time: movement.time,
+            reason: movement.reason.clone(),
+        }
+    }
+}
+
+#[async_trait]
+pub(crate) trait MovementStore: Send + Sync {
+    /// The stock ledger of an item, oldest first.
+    async fn get_movements(&self, item_id: u64) -> StoreResult<Vec<Movement>>;
+
+    /// The stock ledgers of these items, oldest first.
+    async fn get_movements_by_items(&self, item_ids: &[u64]) -> StoreResult<Vec<Movement>>;
+
+    /// Apply the movements to the amounts of their items and record them. Either all
+    /// movements are applied or none. Returns [StoreError::Invalid] if an amount would
+    /// become negative or drop below the lent amount, or for transfers (they happen by
+    /// updating the item).
+    /// The location of the movements is filled in by the store.
+    async fn record_movements(&self, movements: &[Movement]) -> StoreResult<Vec<AdjustedAmount>>;
+}
+
+/// Every change of an item (including its amount and location) saves a revision.
+#[async_trait]
+pub(crate) trait RevisionStore: Send + Sync {
+    /// All revisions of an item, oldest first. They are kept after the item is deleted.
+    async fn get_revisions(&self, item_id: u64) -> StoreResult<Vec<ItemRevision>>;
+    async fn get_revision(&self, item_id: u64, revision: u64) -> StoreResult<ItemRevision>;
+
+    /// The revisions of these items, oldest first.
+    async fn get_revisions_by_items(&self, item_ids: &[u64]) -> StoreResult<Vec<ItemRevision>>;
+
+    /// Bring the item back to the state of a snapshot inside of one transaction. A deleted
+    /// item is taken out of the trash first, or inserted again with its old id if it was purged.
+    async fn restore_revision(&self, item: &Item, change: &Change) -> StoreResult<()>;
+}
+
+/// The uploaded icons. The built-in icons are part of the server, so they aren't stored.
+#[async_trait]
+pub(crate) trait IconStore: Send + Sync {
+    async fn get_icons(&self) -> StoreResult<Vec<Icon>>;
+
+    /// The icon together with its content.
+    async fn get_icon(&self, icon_id: u64) -> StoreResult<(Icon, Vec<u8>)>;
+
+    /// Insert a new icon and return its generated id.
+    async fn put_icon(&self, icon: &Icon, data: &[u8]) -> StoreResult<u64>;
+
+    /// Delete the icon. The tags, locations and databases that use it lose their icon.
+    async fn delete_icon(&self, icon_id: u64) -> StoreResult<()>;
+}
+
+/// Deleted objects stay in the trash until they are restored or purged.
+#[async_trait]
+pub(crate) trait TrashStore: Send + Sync {
+    async fn get_trash(&self) -> StoreResult<Vec<TrashEntry>>;
+
+    /// Restore the object together with everything that was deleted with it
+    /// (e.g. the locations and items of a database). Returns [StoreError::Invalid]
+    /// if the object it belongs to (e.g. the location of an item) is still deleted.
+    async fn restore_from_trash(&self, kind: TrashKind, id: u64, change: &Change) -> StoreResult<()>;
+
+    /// Delete everything for good that was deleted before the given time.
+    /// Returns how many objects were purged.
+    async fn purge_trash(&self, deleted_before: i64) -> StoreResult<u64>;
+}
+
+/// The state of the connection pool of a backend.
+pub(crate) struct PoolStatus {
+    /// The open connections, including the idle ones
+    pub(crate) size: u32,
+    pub(crate) idle: usize,
+
+    /// How long it took to get a connection from the pool (seconds)
+    pub(crate) acquire_seconds: f64,
+}
+
+/// The state of the backend itself, e.g. for the metrics and the health checks.
+#[async_trait]
+pub(crate) trait StatusStore: Send + Sync {
+    /// Backends without a connection pool (like the memory backend) return None.
+    async fn pool_status(&self) -> StoreResult<Option<PoolStatus>>;
+
+    /// Check that the backend can be reached.
+    async fn ping(&self) -> StoreResult<()>;
+
+    /// The versions of the migrations that the backend is still missing.
+    async fn pending_migrations(&self) -> StoreResult<Vec<i64>>;
+}
+
+/// One storage backend, shared as the different store traits.
+/// Each web handler only extracts the store it actually needs.
+#[derive(Clone)]
+pub(crate) struct Stores {
+    pub(crate) items: Arc<dyn ItemStore>,
+    pub(crate) tags: Arc<dyn TagStore>,
+    pub(crate) locations: Arc<dyn LocationStore>,
+    pub(crate) databases: Arc<dyn DatabaseStore>,
+    pub(crate) sessions: Arc<dyn SessionStore>,
+    pub(crate) backups: Arc<dyn BackupStore>,
+    pub(crate) files: Arc<dyn FileStore>,
+    pub(crate) loans: Arc<dyn LoanStore>,
+    pub(crate) movements: Arc<dyn MovementStore>,
+    pub(crate) revisions: Arc<dyn RevisionStore>,
+    pub(crate) trash: Arc<dyn TrashStore>,
+    pub(crate) templates: Arc<dyn TemplateStore>,
+    pub(crate) codes: Arc<dyn CodeStore>,
+    pub(crate) icons: Arc<dyn IconStore>,
+    pub(crate) status: Arc<dyn StatusStore>,
+}
+
+impl Stores {
+    pub(crate) fn new<S>(store: S) -> Self
+    where
+        S: ItemStore
+            + TagStore
+            + LocationStore
+            + DatabaseStore
+            + SessionStore
+            + BackupStore
+            + FileStore
+            + LoanStore
+            + MovementStore
+            + RevisionStore
+            + TrashStore
+            + TemplateStore
+            + CodeStore
+            + IconStore
+            + StatusStore
+            + 'static,
+    {
+        let store = Arc::new(store);
+
+        Stores {
+            items: store.clone(),
+            tags: store.clone(),
+            locations: store.clone(),
+            databases: store.clone(),
+            sessions: store.clone(),
+            backups: store.clone(),
+            files: store.clone(),
+            loans: store.clone(),
+            movements: store.clone(),
+            revisions: store.clone(),
+            trash: store.clone(),
+            templates: store.clone(),
+            codes: store.clone(),
+            icons: store.clone(),
+            status: store,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_delta, check_tag_merge, check_tag_parent, StoreError};
+    use crate::models::Tag;
+
+    fn tag(id: u64, parent: Option<u64>, database: Option<u64>) -> Tag {
+        Tag {
+            id,
+            name: format!("Tag {id}"),
+            color: 0,
+            icon: None,
+            parent,
+            database,
+        }
+    }
+
+    fn invalid_code<T>(result: Result<T, StoreError>) -> &'static str {
+        match result {
+            Err(StoreError::Invalid(code, _)) => code,
+            _ => panic!("the result isn't invalid"),
+        }
+    }
+
+    #[test]
+    fn amounts_cant_become_negative() {
+        assert_eq!(apply_delta(5, -2).unwrap(), 3);
+        assert_eq!(apply_delta(5, 2).unwrap(), 7);
+        assert_eq!(apply_delta(5, -5).unwrap(), 0);
+        assert_eq!(invalid_code(apply_delta(5, -6)), "item.negative_amount");
+        assert_eq!(invalid_code(apply_delta(u64::MAX, 1)), "item.negative_amount");
+    }
+
+    #[test]
+    fn tags_cant_be_below_themselves() {
+        // 1 > 2 > 3
+        let tags = [tag(1, None, None), tag(2, Some(1), None), tag(3, Some(2), None)];
+
+        check_tag_parent(&tag(4, Some(3), None), &tags).unwrap();
+        assert_eq!(invalid_code(check_tag_parent(&tag(1, Some(3), None), &tags)), "tag.cycle");
+        assert_eq!(invalid_code(check_tag_parent(&tag(1, Some(1), None), &tags)), "tag.cycle");
+        assert!(matches!(check_tag_parent(&tag(1, Some(9), None), &tags), Err(StoreError::UnknownReference("tag"))));
+    }
+
+    #[test]
+    fn tags_of_a_database_stay_in_it() {
+        let tags = [tag(1, None, None), tag(2, None, Some(1)), tag(3, Some(2), Some(1))];
+
+        check_tag_parent(&tag(4, Some(1), Some(2)), &tags).unwrap();
+        check_tag_parent(&tag(4, Some(2), Some(1)), &tags).unwrap();
+        assert_eq!(invalid_code(check_tag_parent(&tag(4, Some(2), Some(2)), &tags)), "tag.parent_in_other_database");
+        assert_eq!(invalid_code(check_tag_parent(&tag(4, Some(2), None), &tags)), "tag.parent_in_other_database");
+        assert_eq!(invalid_code(check_tag_parent(&tag(2, None, Some(2)), &tags)), "tag.children_in_other_database");
+    }
+
+    #[test]
+    fn tags_can_only_be_merged_into_other_branches() {
+        // 1 > 2 > 3, 4 (in database 1)
+        let tags = [tag(1, None, None), tag(2, Some(1), None), tag(3, Some(2), None), tag(4, None, Some(1))];
+
+        check_tag_merge(2, 1, &tags).unwrap();
+        check_tag_merge(3, 1, &tags).unwrap();
+        assert_eq!(invalid_code(check_tag_merge(1, 1, &tags)), "tag.merge_into_itself");
+        assert_eq!(invalid_code(check_tag_merge(1, 3, &tags)), "tag.cycle");
+        assert!(matches!(check_tag_merge(9, 1, &tags), Err(StoreError::NotFound)));
+        assert!(matches!(check_tag_merge(1, 9, &tags), Err(StoreError::UnknownReference("tag"))));
+
+        // The global child tags can't end up below a tag of a database
+        assert_eq!(invalid_code(check_tag_merge(1, 4, &tags)), "tag.children_in_other_database");
+    }
+}
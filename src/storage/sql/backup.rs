@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sqlx::{MySql, Row, Transaction};
+
+use crate::models::{Backup, Item};
+use crate::storage::sql::database::properties_to_json;
+use crate::storage::sql::icon::existing_icon;
+use crate::storage::sql::item::{insert_initial_movement, insert_item};
+use crate::storage::sql::revision::insert_revision;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::trash::trash_database;
+use crate::storage::sql::SqlStore;
+use crate::storage::{backup_tags, check_item_containers, check_location_parent, restored_tag_name, BackupStore, Change, StoreError, StoreResult};
+
+#[async_trait]
+impl BackupStore for SqlStore {
+    async fn restore_backup(&self, backup: &Backup, as_new: bool, change: &Change) -> StoreResult<Vec<u64>> {
+        // Either everything gets restored or nothing.
+        let mut tx = self.pool.begin().await?;
+
+        // The databases are replaced first, so their tags are out of the way
+        let mut database_ids = Vec::with_capacity(backup.databases.len());
+        for database_backup in &backup.databases {
+            // The replaced database can still be restored from the trash
+            if !as_new {
+                let existing = sqlx::query("SELECT id FROM item_databases WHERE name = ? AND deleted_at IS NULL FOR UPDATE")
+                    .bind(&database_backup.database.name)
+                    .fetch_optional(traced(&mut tx))
+                    .await?;
+                if let Some(row) = existing {
+                    trash_database(&mut tx, row.get(0), change).await?;
+                }
+            }
+
+            let icon = existing_icon(&mut tx, database_backup.database.icon).await?;
+            sqlx::query("INSERT INTO item_databases (name,properties,icon) VALUES (?,?,?)")
+                .bind(&database_backup.database.name)
+                .bind(properties_to_json(&database_backup.database.properties)?)
+                .bind(icon)
+                .execute(traced(&mut tx))
+                .await?;
+            database_ids.push(sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut tx)).await?.get(0));
+        }
+
+        let tag_ids = restore_tags(&mut tx, backup, &database_ids).await?;
+
+        for (database_backup, &database_id) in backup.databases.iter().zip(&database_ids) {
+            // The parents are set after all locations got their new ids
+            let mut location_ids: HashMap<u64, u64> = HashMap::new();
+            for location in &database_backup.locations {
+                check_location_parent(location, &database_backup.locations)?;
+
+                let icon = existing_icon(&mut tx, location.icon).await?;
+                sqlx::query("INSERT INTO locations (name,database_id,icon) VALUES (?,?,?)")
+                    .bind(&location.name)
+                    .bind(database_id)
+                    .bind(icon)
+                    .execute(traced(&mut tx))
+                    .await?;
+                location_ids.insert(location.id, sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut tx)).await?.get(0));
+            }
+            for location in &database_backup.locations {
+                if let Some(parent_id) = location.parent {
+                    sqlx::query("UPDATE locations SET parent_id = ? WHERE id = ?")
+                        .bind(location_ids[&parent_id])
+                        .bind(location_ids[&location.id])
+                        .execute(traced(&mut tx))
+                        .await?;
+                }
+            }
+
+            // Like the locations, the containers are set after all items got their new ids
+            check_item_containers(&database_backup.items)?;
+            let mut item_ids: HashMap<u64, u64> = HashMap::new();
+            let mut restored_items = Vec::with_capacity(database_backup.items.len());
+            for item in &database_backup.items {
+                let location = *location_ids.get(&item.location).ok_or(StoreError::UnknownReference("location"))?;
+                let tags = item
+                    .tags
+                    .iter()
+                    .map(|tag_id| tag_ids.get(tag_id).copied().ok_or(StoreError::UnknownReference("tag")))
+                    .collect::<StoreResult<Vec<u64>>>()?;
+
+                let restored = Item {
+                    id: 0,
+                    location,
+                    parent_item: None,
+                    tags,
+                    ..item.clone()
+                };
+                let item_id = insert_item(&mut tx, &restored).await?;
+                item_ids.insert(item.id, item_id);
+                restored_items.push((item_id, restored));
+            }
+            for item in &database_backup.items {
+                if let Some(parent_id) = item.parent_item {
+                    sqlx::query("UPDATE items SET parent_item_id = ? WHERE id = ?")
+                        .bind(item_ids[&parent_id])
+                        .bind(item_ids[&item.id])
+                        .execute(traced(&mut tx))
+                        .await?;
+                }
+            }
+
+            // The restored items start their history like new items
+            for (item_id, item) in &restored_items {
+                insert_initial_movement(&mut tx, *item_id, item, change).await?;
+                insert_revision(&mut tx, *item_id, change, false).await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(database_ids)
+    }
+}
+
+/// Reuse the global tags that already exist and create the others. The tags of a database
+/// are created inside of its restored copy. Returns the new ids of the tags by their old ids.
+async fn restore_tags(tx: &mut Transaction<'_, MySql>, backup: &Backup, database_ids: &[u64]) -> StoreResult<HashMap<u64, u64>> {
+    // Backups don't contain the uploaded icons, so only the existing icons are kept.
+    // The parents are set after all tags got their new ids.
+    let mut tag_ids: HashMap<u64, u64> = HashMap::new();
+    let mut created = vec![];
+    for tag in backup_tags(backup, database_ids)? {
+        let existing = sqlx::query("SELECT id, database_id FROM tags WHERE name = ? AND deleted_at IS NULL")
+            .bind(&tag.name)
+            .fetch_optional(traced(&mut *tx))
+            .await?;
+        let existing_global = existing.as_ref().filter(|row| row.get::<Option<u64>, _>(1).is_none());
+
+        let tag_id: u64 = match (existing_global, tag.database) {
+            (Some(row), None) => row.get(0),
+            _ => {
+                let name = match tag.database {
+                    Some(database_id) if existing.is_some() => {
+                        let database_name: String = sqlx::query("SELECT name FROM item_databases WHERE id = ?")
+                            .bind(database_id)
+                            .fetch_one(traced(&mut *tx))
+                            .await?
+                            .get(0);
+                        restored_tag_name(&tag.name, &database_name)
+                    }
+                    _ => tag.name.clone(),
+                };
+
+                let icon = existing_icon(tx, tag.icon).await?;
+                sqlx::query("INSERT INTO tags (name,color,icon,database_id) VALUES (?,?,?,?)")
+                    .bind(name)
+                    .bind(tag.color)
+                    .bind(icon)
+                    .bind(tag.database)
+                    .execute(traced(&mut *tx))
+                    .await?;
+                let tag_id = sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut *tx)).await?.get(0);
+                created.push((tag_id, tag.parent));
+                tag_id
+            }
+        };
+
+        tag_ids.insert(tag.id, tag_id);
+    }
+
+    for (tag_id, parent_id) in created {
+        if let Some(parent_id) = parent_id {
+            sqlx::query("UPDATE tags SET parent_id = ? WHERE id = ?")
+                .bind(tag_ids[&parent_id])
+                .bind(tag_id)
+                .execute(traced(&mut *tx))
+                .await?;
+        }
+    }
+
+    Ok(tag_ids)
+}
@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use sqlx::mysql::MySqlRow;
+use sqlx::Row;
+
+use crate::models::{CodeKind, ScanCode};
+use crate::storage::sql::item::check_location;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::{reference_error, SqlStore};
+use crate::storage::{CodeStore, StoreError, StoreResult};
+
+/// The codes of deleted items and locations are hidden until they are restored.
+const VISIBLE_CODES: &str = "SELECT scan_codes.code, scan_codes.kind, scan_codes.item_id, scan_codes.location_id FROM scan_codes \
+     LEFT JOIN items ON items.id = scan_codes.item_id \
+     LEFT JOIN locations ON locations.id = scan_codes.location_id \
+     WHERE items.deleted_at IS NULL AND locations.deleted_at IS NULL";
+
+#[async_trait]
+impl CodeStore for SqlStore {
+    async fn get_codes(&self) -> StoreResult<Vec<ScanCode>> {
+        let mut connection = self.pool.acquire().await?;
+
+        Ok(sqlx::query(VISIBLE_CODES).fetch_all(traced(&mut connection)).await?.iter().map(sqlrow_to_code).collect())
+    }
+
+    async fn find_code(&self, codes: &[String]) -> StoreResult<ScanCode> {
+        if codes.is_empty() {
+            return Err(StoreError::NotFound);
+        }
+
+        let mut connection = self.pool.acquire().await?;
+
+        let code_sql = format!("{VISIBLE_CODES} AND scan_codes.code IN (?{})", ",?".repeat(codes.len() - 1));
+        let mut code_query = sqlx::query(code_sql.as_str());
+        for code in codes {
+            code_query = code_query.bind(code);
+        }
+        let found: Vec<ScanCode> = code_query.fetch_all(traced(&mut connection)).await?.iter().map(sqlrow_to_code).collect();
+
+        // The first spelling wins, like in the memory store
+        codes
+            .iter()
+            .find_map(|code| found.iter().find(|other| other.code.eq_ignore_ascii_case(code)))
+            .cloned()
+            .ok_or(StoreError::NotFound)
+    }
+
+    async fn put_code(&self, code: &ScanCode) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(item_id) = code.item {
+            sqlx::query("SELECT id FROM items WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+                .bind(item_id)
+                .fetch_optional(traced(&mut tx))
+                .await?
+                .ok_or(StoreError::UnknownReference("item"))?;
+        }
+        if let Some(location_id) = code.location {
+            check_location(&mut tx, location_id).await?;
+        }
+
+        // A code that is already in use is a conflict, even if its object is in the trash
+        sqlx::query("INSERT INTO scan_codes (code,kind,item_id,location_id) VALUES (?,?,?,?)")
+            .bind(&code.code)
+            .bind(code.kind.as_str())
+            .bind(code.item)
+            .bind(code.location)
+            .execute(traced(&mut tx))
+            .await
+            .map_err(reference_error("item"))?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_code(&self, code: &str) -> StoreResult<()> {
+        let mut connection = self.pool.acquire().await?;
+
+        let result = sqlx::query("DELETE FROM scan_codes WHERE code = ?").bind(code).execute(traced(&mut connection)).await?;
+
+        // If nothing was deleted, the code didn't even exist!
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+
+        Ok(())
+    }
+}
+
+fn sqlrow_to_code(row: &MySqlRow) -> ScanCode {
+    let kind: String = row.get(1);
+
+    ScanCode {
+        code: row.get(0),
+        // Only the store writes the kinds, so they are always valid
+        kind: CodeKind::parse(&kind).unwrap_or(CodeKind::Other),
+        item: row.get(2),
+        location: row.get(3),
+    }
+}
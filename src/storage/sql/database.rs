@@ -0,0 +1,100 @@
+use async_trait::async_trait;
+use sqlx::mysql::MySqlRow;
+use sqlx::Row;
+
+use crate::models::{Database, PropertyField};
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::trash::trash_database;
+use crate::storage::sql::SqlStore;
+use crate::storage::{Change, DatabaseStore, StoreError, StoreResult};
+
+#[async_trait]
+impl DatabaseStore for SqlStore {
+    async fn get_databases(&self) -> StoreResult<Vec<Database>> {
+        let mut connection = self.pool.acquire().await?;
+
+        sqlx::query("SELECT id, name, properties, icon FROM item_databases WHERE deleted_at IS NULL")
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .map(sqlrow_to_database)
+            .collect()
+    }
+
+    async fn get_database(&self, database_id: u64) -> StoreResult<Database> {
+        let mut connection = self.pool.acquire().await?;
+
+        let row = sqlx::query("SELECT id, name, properties, icon FROM item_databases WHERE id = ? AND deleted_at IS NULL")
+            .bind(database_id)
+            .fetch_one(traced(&mut connection))
+            .await?;
+
+        sqlrow_to_database(&row)
+    }
+
+    async fn put_database(&self, database: &Database) -> StoreResult<u64> {
+        // We need to make a transaction here because we want to make 2 queries that relate to each other.
+        let mut tx = self.pool.begin().await?;
+
+        // First insert the object into the sql table...
+        sqlx::query("INSERT INTO item_databases (name,properties,icon) VALUES (?,?,?)")
+            .bind(&database.name)
+            .bind(properties_to_json(&database.properties)?)
+            .bind(database.icon)
+            .execute(traced(&mut tx))
+            .await?;
+
+        // ...after that we need to get the autogenerated id from the table.
+        let database_id: u64 = sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut tx)).await?.get(0);
+
+        // Finally, commit the changes to make them permanent
+        tx.commit().await?;
+        Ok(database_id)
+    }
+
+    async fn update_database(&self, database: &Database) -> StoreResult<()> {
+        let mut connection = self.pool.acquire().await?;
+
+        let result = sqlx::query("UPDATE item_databases SET name = ?, properties = ?, icon = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(&database.name)
+            .bind(properties_to_json(&database.properties)?)
+            .bind(database.icon)
+            .bind(database.id)
+            .execute(traced(&mut connection))
+            .await?;
+
+        // If nothing was changed, the database didn't even exist!
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn delete_database(&self, database_id: u64, change: &Change) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        trash_database(&mut tx, database_id, change).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+fn sqlrow_to_database(row: &MySqlRow) -> StoreResult<Database> {
+    let properties: Option<String> = row.get(2);
+    let properties: Vec<PropertyField> = match properties {
+        Some(properties) => serde_json::from_str(&properties).map_err(|err| StoreError::Internal(Box::new(err)))?,
+        None => vec![],
+    };
+
+    Ok(Database {
+        id: row.get(0),
+        name: row.get(1),
+        properties,
+        icon: row.get(3),
+    })
+}
+
+/// The schema is stored as JSON, because it's only ever read as a whole.
+pub(super) fn properties_to_json(properties: &[PropertyField]) -> StoreResult<String> {
+    serde_json::to_string(properties).map_err(|err| StoreError::Internal(Box::new(err)))
+}
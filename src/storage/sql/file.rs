@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::models::ItemFile;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::SqlStore;
+use crate::storage::{FileStore, StoreError, StoreResult};
+
+#[async_trait]
+impl FileStore for SqlStore {
+    async fn get_item_files(&self, item_id: u64) -> StoreResult<Vec<ItemFile>> {
+        let files = sqlx::query_as::<_, ItemFile>("SELECT is_image, name, hash, mime_type, size FROM item_files WHERE item_id = ?")
+            .bind(item_id)
+            .fetch_all(traced(&self.pool))
+            .await?;
+
+        Ok(files)
+    }
+
+    async fn put_item_file(&self, item_id: u64, file: &ItemFile, url: &str) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // If the item doesn't exist, this returns StoreError::NotFound.
+        sqlx::query("SELECT id FROM items WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+            .bind(item_id)
+            .fetch_one(traced(&mut tx))
+            .await?;
+
+        if file.is_image {
+            // An item only has one image, so every image replaces the old one
+            sqlx::query("DELETE FROM item_files WHERE item_id = ? AND is_image = TRUE")
+                .bind(item_id)
+                .execute(traced(&mut tx))
+                .await?;
+            sqlx::query("UPDATE items SET image = ? WHERE id = ?")
+                .bind(url)
+                .bind(item_id)
+                .execute(traced(&mut tx))
+                .await?;
+        } else {
+            sqlx::query("INSERT INTO item_attachments (item_id, name, url) VALUES (?,?,?) ON DUPLICATE KEY UPDATE url = VALUES(url)")
+                .bind(item_id)
+                .bind(&file.name)
+                .bind(url)
+                .execute(traced(&mut tx))
+                .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO item_files (item_id, is_image, name, hash, mime_type, size) VALUES (?,?,?,?,?,?) \
+             ON DUPLICATE KEY UPDATE hash = VALUES(hash), mime_type = VALUES(mime_type), size = VALUES(size)",
+        )
+        .bind(item_id)
+        .bind(file.is_image)
+        .bind(&file.name)
+        .bind(&file.hash)
+        .bind(&file.mime_type)
+        .bind(file.size)
+        .execute(traced(&mut tx))
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_item_file(&self, item_id: u64, is_image: bool, name: &str) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let deletion_query = sqlx::query("DELETE FROM item_files WHERE item_id = ? AND is_image = ? AND name = ?")
+            .bind(item_id)
+            .bind(is_image)
+            .bind(name)
+            .execute(traced(&mut tx))
+            .await?;
+        if deletion_query.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+
+        if is_image {
+            sqlx::query("UPDATE items SET image = NULL WHERE id = ?").bind(item_id).execute(traced(&mut tx)).await?;
+        } else {
+            sqlx::query("DELETE FROM item_attachments WHERE item_id = ? AND name = ?")
+                .bind(item_id)
+                .bind(name)
+                .execute(traced(&mut tx))
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_file_hashes(&self) -> StoreResult<HashSet<String>> {
+        let hashes = sqlx::query("SELECT DISTINCT hash FROM item_files")
+            .fetch_all(traced(&self.pool))
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+
+        Ok(hashes)
+    }
+}
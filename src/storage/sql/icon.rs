@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use sqlx::mysql::MySqlRow;
+use sqlx::{MySql, Row, Transaction};
+
+use crate::icons::builtin_icon;
+use crate::models::Icon;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::SqlStore;
+use crate::storage::{IconStore, StoreError, StoreResult};
+
+#[async_trait]
+impl IconStore for SqlStore {
+    async fn get_icons(&self) -> StoreResult<Vec<Icon>> {
+        let mut connection = self.pool.acquire().await?;
+
+        // The content isn't needed for the list
+        Ok(sqlx::query("SELECT id, name, mime_type FROM icons")
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .map(sqlrow_to_icon)
+            .collect())
+    }
+
+    async fn get_icon(&self, icon_id: u64) -> StoreResult<(Icon, Vec<u8>)> {
+        let mut connection = self.pool.acquire().await?;
+
+        let row = sqlx::query("SELECT id, name, mime_type, content FROM icons WHERE id = ?")
+            .bind(icon_id)
+            .fetch_one(traced(&mut connection))
+            .await?;
+
+        Ok((sqlrow_to_icon(&row), row.get(3)))
+    }
+
+    async fn put_icon(&self, icon: &Icon, data: &[u8]) -> StoreResult<u64> {
+        // We need to make a transaction here because we want to make 2 queries that relate to each other.
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("INSERT INTO icons (name,mime_type,content) VALUES (?,?,?)")
+            .bind(&icon.name)
+            .bind(&icon.mime_type)
+            .bind(data)
+            .execute(traced(&mut tx))
+            .await?;
+        let icon_id: u64 = sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut tx)).await?.get(0);
+
+        tx.commit().await?;
+        Ok(icon_id)
+    }
+
+    async fn delete_icon(&self, icon_id: u64) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query("DELETE FROM icons WHERE id = ?").bind(icon_id).execute(traced(&mut tx)).await?;
+
+        // If nothing was deleted, the icon didn't even exist!
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+
+        // There are no foreign keys (because of the built-in icons), so this is done by hand.
+        // The objects in the trash lose their icon as well.
+        for table in ["tags", "locations", "item_databases"] {
+            sqlx::query(&format!("UPDATE {table} SET icon = NULL WHERE icon = ?"))
+                .bind(icon_id)
+                .execute(traced(&mut tx))
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// The icon, if it exists. Used for restored backups, since they don't contain the uploaded icons.
+pub(super) async fn existing_icon(tx: &mut Transaction<'_, MySql>, icon: Option<u64>) -> StoreResult<Option<u64>> {
+    let icon_id = match icon {
+        Some(icon_id) if builtin_icon(icon_id).is_none() => icon_id,
+        icon => return Ok(icon),
+    };
+
+    let row = sqlx::query("SELECT id FROM icons WHERE id = ?").bind(icon_id).fetch_optional(traced(&mut *tx)).await?;
+    Ok(row.map(|_| icon_id))
+}
+
+fn sqlrow_to_icon(row: &MySqlRow) -> Icon {
+    Icon {
+        id: row.get(0),
+        name: row.get(1),
+        mime_type: row.get(2),
+        builtin: false,
+    }
+}
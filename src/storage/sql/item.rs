@@ -0,0 +1,650 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use sqlx::{types::chrono, MySql, MySqlConnection, Row, Transaction};
+
+use crate::models::{Item, MovementKind, Property};
+use crate::storage::sql::loan::lent_amount;
+use crate::storage::sql::movement::insert_movement;
+use crate::storage::sql::revision::insert_revision;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::{bind_all, placeholders, reference_error, SqlStore};
+use crate::storage::{check_lent_amount, creates_cycle, descendants, Change, ImportedItem, ItemStore, LocationPath, StoreError, StoreResult};
+
+#[async_trait]
+impl ItemStore for SqlStore {
+    async fn get_items(&self) -> StoreResult<Vec<Item>> {
+        let mut connection = self.pool.acquire().await?;
+        fetch_items(&mut connection, None).await
+    }
+
+    async fn get_item(&self, item_id: u64) -> StoreResult<Item> {
+        let mut connection = self.pool.acquire().await?;
+        fetch_item(&mut connection, item_id).await
+    }
+
+    async fn get_items_by_ids(&self, item_ids: &[u64]) -> StoreResult<Vec<Item>> {
+        let mut connection = self.pool.acquire().await?;
+        fetch_items(&mut connection, Some(("items.id IN ({})", item_ids))).await
+    }
+
+    async fn get_items_by_containers(&self, container_ids: &[u64]) -> StoreResult<Vec<Item>> {
+        let mut connection = self.pool.acquire().await?;
+        fetch_items(&mut connection, Some(("items.parent_item_id IN ({})", container_ids))).await
+    }
+
+    async fn get_items_by_locations(&self, location_ids: &[u64]) -> StoreResult<Vec<Item>> {
+        let mut connection = self.pool.acquire().await?;
+        fetch_items(&mut connection, Some(("items.location_id IN ({})", location_ids))).await
+    }
+
+    async fn get_items_by_tags(&self, tag_ids: &[u64]) -> StoreResult<Vec<Item>> {
+        let mut connection = self.pool.acquire().await?;
+        let condition = "items.id IN (SELECT item_tags.item_id FROM item_tags JOIN tags ON tags.id = item_tags.tag_id \
+            WHERE tags.deleted_at IS NULL AND item_tags.tag_id IN ({}))";
+        fetch_items(&mut connection, Some((condition, tag_ids))).await
+    }
+
+    async fn count_items(&self) -> StoreResult<HashMap<u64, u64>> {
+        self.count_per_database(
+            "SELECT locations.database_id, COUNT(*) FROM items JOIN locations ON locations.id = items.location_id \
+            WHERE items.deleted_at IS NULL GROUP BY locations.database_id",
+        )
+        .await
+    }
+
+    async fn put_item(&self, item: &Item, change: &Change) -> StoreResult<u64> {
+        // We need to make a transaction here for two reasons:
+        // 1. we want to make multiple queries that relate to each other
+        // 2. if something goes wrong along the function,
+        //    all changes to the database will be discarded.
+        let mut tx = self.pool.begin().await?;
+        let item = resolve_container(&mut tx, item).await?;
+        let item_id = insert_item(&mut tx, &item).await?;
+        insert_initial_movement(&mut tx, item_id, &item, change).await?;
+        insert_revision(&mut tx, item_id, change, false).await?;
+
+        // Finally, commit the changes to make them permanent
+        tx.commit().await?;
+        Ok(item_id)
+    }
+
+    async fn update_item(&self, item: &Item, change: &Change) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        update_item_row(&mut tx, item, change).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_item(&self, item_id: u64, change: &Change) -> StoreResult<()> {
+        // If something goes wrong (I don't know how),
+        // we roll back to a save state automatically.
+        let mut tx = self.pool.begin().await?;
+
+        // The contents of a container are taken out and put where the container was.
+        // If the item doesn't exist, this returns StoreError::NotFound.
+        let parent_item: Option<u64> = sqlx::query("SELECT parent_item_id FROM items WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+            .bind(item_id)
+            .fetch_one(traced(&mut tx))
+            .await?
+            .get(0);
+        let content_ids: Vec<u64> = sqlx::query("SELECT id FROM items WHERE parent_item_id = ? AND deleted_at IS NULL FOR UPDATE")
+            .bind(item_id)
+            .fetch_all(traced(&mut tx))
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        sqlx::query("UPDATE items SET parent_item_id = ? WHERE parent_item_id = ? AND deleted_at IS NULL")
+            .bind(parent_item)
+            .bind(item_id)
+            .execute(traced(&mut tx))
+            .await?;
+        for content_id in content_ids {
+            insert_revision(&mut tx, content_id, change, false).await?;
+        }
+
+        // The last state of the item is kept, so it can be restored
+        insert_revision(&mut tx, item_id, change, true).await?;
+
+        // The item goes to the trash together with its files, loans and so on.
+        // It's only deleted for good when the trash is purged.
+        sqlx::query("UPDATE items SET deleted_at = ? WHERE id = ?")
+            .bind(chrono::NaiveDateTime::from_timestamp(change.time, 0))
+            .bind(item_id)
+            .execute(traced(&mut tx))
+            .await?;
+
+        // To be able to tell offline clients that something got
+        // deleted, we need to keep track of deleted item ids.
+        sqlx::query("INSERT INTO item_deleted VALUES (?, CURRENT_TIMESTAMP())")
+            .bind(item_id)
+            .execute(traced(&mut tx))
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn import_items(&self, items: &[ImportedItem], dry_run: bool, change: &Change) -> StoreResult<Vec<StoreResult<u64>>> {
+        // Everything happens inside of one transaction,
+        // so that a failed import doesn't leave anything behind.
+        let mut tx = self.pool.begin().await?;
+
+        let mut results = Vec::with_capacity(items.len());
+        for imported in items {
+            let result = import_item(&mut tx, imported, &results, change).await;
+            results.push(result);
+        }
+
+        // Dropping the transaction without committing rolls everything back.
+        if !dry_run && results.iter().all(Result::is_ok) {
+            tx.commit().await?;
+        }
+
+        Ok(results)
+    }
+}
+
+/// Update an item that isn't deleted and record the changes of its amount and location.
+pub(super) async fn update_item_row(tx: &mut Transaction<'_, MySql>, item: &Item, change: &Change) -> StoreResult<()> {
+    // Lock the row and make sure the item exists. The number of affected rows of
+    // the UPDATE can't be used for this, because it's 0 if nothing was changed.
+    let row = sqlx::query("SELECT location_id, amount FROM items WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+        .bind(item.id)
+        .fetch_one(traced(&mut *tx))
+        .await?;
+    let old_location: u64 = row.get(0);
+    let old_amount: u64 = row.get(1);
+    let item = &resolve_container(tx, item).await?;
+    check_location(tx, item.location).await?;
+    check_lent_amount(old_amount, item.amount, lent_amount(tx, item.id).await?)?;
+
+    // The item row is updated in place instead of being deleted and inserted again,
+    // otherwise the foreign keys would also delete the uploaded files of the item.
+    sqlx::query(
+        "UPDATE items SET name = ?, description = ?, image = ?, location_id = ?, parent_item_id = ?, amount = ?, min_amount = ?, last_edited = ?, created = ? WHERE id = ?",
+    )
+    .bind(&item.name)
+    .bind(&item.description)
+    .bind(&item.image)
+    .bind(item.location)
+    .bind(item.parent_item)
+    .bind(item.amount)
+    .bind(item.min_amount)
+    .bind(chrono::NaiveDateTime::from_timestamp(item.last_edited, 0))
+    .bind(chrono::NaiveDateTime::from_timestamp(item.created, 0))
+    .bind(item.id)
+    .execute(traced(&mut *tx))
+    .await
+    .map_err(reference_error("location"))?;
+
+    // The relations are simply replaced. Only the deleted tags are kept,
+    // because the client doesn't know about them and didn't send them.
+    sqlx::query("DELETE item_tags FROM item_tags JOIN tags ON tags.id = item_tags.tag_id WHERE item_tags.item_id = ? AND tags.deleted_at IS NULL")
+        .bind(item.id)
+        .execute(traced(&mut *tx))
+        .await?;
+    for table in ["item_properties", "item_attachments"] {
+        sqlx::query(&format!("DELETE FROM {table} WHERE item_id = ?"))
+            .bind(item.id)
+            .execute(traced(&mut *tx))
+            .await?;
+    }
+    insert_item_relations(tx, item.id, item).await?;
+
+    if item.amount != old_amount {
+        let difference = item.amount as i64 - old_amount as i64;
+        insert_movement(tx, &change.movement(item.id, MovementKind::Adjust, difference, item.location, None)).await?;
+    }
+
+    // The contents of a container move together with it
+    if item.location != old_location {
+        let movement = change.movement(item.id, MovementKind::Transfer, item.amount as i64, item.location, Some(old_location));
+        insert_movement(tx, &movement).await?;
+
+        let parents = item_parents(tx, old_location).await?;
+        for content_id in descendants(item.id, &parents) {
+            sqlx::query("UPDATE items SET location_id = ? WHERE id = ?")
+                .bind(item.location)
+                .bind(content_id)
+                .execute(traced(&mut *tx))
+                .await?;
+
+            let content_amount: u64 = sqlx::query("SELECT amount FROM items WHERE id = ?")
+                .bind(content_id)
+                .fetch_one(traced(&mut *tx))
+                .await?
+                .get(0);
+            let movement = change.movement(content_id, MovementKind::Transfer, content_amount as i64, item.location, Some(old_location));
+            insert_movement(tx, &movement).await?;
+            insert_revision(tx, content_id, change, false).await?;
+        }
+    }
+    insert_revision(tx, item.id, change, false).await?;
+
+    Ok(())
+}
+
+/// The items that aren't deleted, with their tags, properties and attachments. The
+/// condition limits the items, its "{}" is replaced by the placeholders of the ids.
+async fn fetch_items(connection: &mut MySqlConnection, condition: Option<(&str, &[u64])>) -> StoreResult<Vec<Item>> {
+    let (condition, ids) = match condition {
+        Some((_, [])) => return Ok(vec![]),
+        Some((condition, ids)) => (format!(" AND {}", condition.replace("{}", &placeholders(ids.len()))), ids),
+        None => (String::new(), &[][..]),
+    };
+
+    let sql = format!("SELECT * FROM items WHERE deleted_at IS NULL{condition}");
+    let mut items: HashMap<u64, Item> = bind_all(sqlx::query(&sql), ids)
+        .fetch_all(traced(&mut *connection))
+        .await?
+        .iter()
+        .map(sqlrow_to_basic_item)
+        .map(|item| (item.id, item))
+        .collect();
+
+    // Without a condition, the relations of all items are loaded at once.
+    // Otherwise only the ones of the loaded items are needed.
+    if items.is_empty() {
+        return Ok(vec![]);
+    }
+    let item_ids: Vec<u64> = if condition.is_empty() { vec![] } else { items.keys().copied().collect() };
+    let of_items = |column: &str| match item_ids.len() {
+        0 => String::new(),
+        count => format!(" AND {column} IN ({})", placeholders(count)),
+    };
+
+    // (Look at the "attachments" query for an explanation)
+    // Deleted tags are hidden until they are restored.
+    let sql = format!(
+        "SELECT item_tags.item_id, item_tags.tag_id FROM item_tags JOIN tags ON tags.id = item_tags.tag_id WHERE tags.deleted_at IS NULL{}",
+        of_items("item_tags.item_id")
+    );
+    bind_all(sqlx::query(&sql), &item_ids).fetch_all(traced(&mut *connection)).await?.iter().for_each(|row| {
+        let item_id: u64 = row.get(0);
+        let tag_id: u64 = row.get(1);
+
+        if let Some(item) = items.get_mut(&item_id) {
+            item.tags.push(tag_id);
+        }
+    });
+
+    // (Look at the "attachments" query for an explanation)
+    let sql = format!("SELECT item_id, is_custom, name, value FROM item_properties WHERE TRUE{}", of_items("item_id"));
+    bind_all(sqlx::query(&sql), &item_ids).fetch_all(traced(&mut *connection)).await?.iter().for_each(|row| {
+        let item_id: u64 = row.get(0);
+        let is_custom: bool = row.get(1);
+        let name: String = row.get(2);
+        let value: String = row.get(3);
+
+        if let Some(item) = items.get_mut(&item_id) {
+            // Get the internal or custom properties list depending in 'is_custom'
+            let properties: &mut Vec<Property> = if is_custom { &mut item.properties_custom } else { &mut item.properties_internal };
+
+            properties.push(Property { name, value });
+        }
+    });
+
+    // Insert the list of sql rows into the item attachments map.
+    // The sql rows only contain one part of the final map
+    // so we need to go throw the hole list piece by piece.
+    let sql = format!("SELECT item_id, name, url FROM item_attachments WHERE TRUE{}", of_items("item_id"));
+    bind_all(sqlx::query(&sql), &item_ids).fetch_all(traced(&mut *connection)).await?.iter().for_each(|row| {
+        // Get the stuff from the sql row
+        let item_id: u64 = row.get(0);
+        let name: String = row.get(1);
+        let url: String = row.get(2);
+
+        // Get the attachments map and insert the name and url.
+        // Technically getting the item could fail if a invalid
+        // item id is in the attachments sql table, but this
+        // should be prevented by the foreign keys in the sql table.
+        if let Some(item) = items.get_mut(&item_id) {
+            item.attachments.insert(name, url);
+        }
+    });
+
+    // Convert the map back into an array:
+    // Map<u64, Item> -> Vec<Item>
+    Ok(items.into_values().collect())
+}
+
+/// Get an item with all of its relations.
+pub(super) async fn fetch_item(connection: &mut MySqlConnection, item_id: u64) -> StoreResult<Item> {
+    // If the item could not be found, this returns StoreError::NotFound.
+    let row = sqlx::query("SELECT * FROM items WHERE id = ? AND deleted_at IS NULL")
+        .bind(item_id)
+        .fetch_one(traced(&mut *connection))
+        .await?;
+    let mut item = sqlrow_to_basic_item(&row);
+
+    item.tags = sqlx::query("SELECT item_tags.tag_id FROM item_tags JOIN tags ON tags.id = item_tags.tag_id WHERE item_tags.item_id = ? AND tags.deleted_at IS NULL")
+        .bind(item.id)
+        .fetch_all(traced(&mut *connection))
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    sqlx::query("SELECT is_custom, name, value FROM item_properties WHERE item_id = ?")
+        .bind(item.id)
+        .fetch_all(traced(&mut *connection))
+        .await?
+        .iter()
+        .for_each(|row| {
+            let is_custom: bool = row.get(0);
+            let name: String = row.get(1);
+            let value: String = row.get(2);
+
+            // Get the internal or custom properties list depending in 'is_custom'
+            let properties: &mut Vec<Property> = if is_custom { &mut item.properties_custom } else { &mut item.properties_internal };
+
+            properties.push(Property { name, value });
+        });
+
+    item.attachments = sqlx::query("SELECT name, url FROM item_attachments WHERE item_id = ?")
+        .bind(item.id)
+        .fetch_all(traced(&mut *connection))
+        .await?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+
+    Ok(item)
+}
+
+/// Insert a single item with all of its relations and return the generated id.
+/// Items with an id other than 0 (deleted items that are restored) keep their id.
+pub(super) async fn insert_item(tx: &mut Transaction<'_, MySql>, item: &Item) -> StoreResult<u64> {
+    check_location(tx, item.location).await?;
+
+    // First insert the object into the sql table...
+    sqlx::query("INSERT INTO items (id,name,description,image,location_id,parent_item_id,amount,min_amount,last_edited,created) VALUES (?,?,?,?,?,?,?,?,?,?)")
+        .bind(Some(item.id).filter(|item_id| *item_id != 0))
+        .bind(&item.name)
+        .bind(&item.description)
+        .bind(&item.image)
+        .bind(item.location)
+        .bind(item.parent_item)
+        .bind(item.amount)
+        .bind(item.min_amount)
+        .bind(chrono::NaiveDateTime::from_timestamp(item.last_edited, 0))
+        .bind(chrono::NaiveDateTime::from_timestamp(item.created, 0))
+        .execute(traced(&mut *tx))
+        .await
+        .map_err(reference_error("location"))?;
+
+    // After that we need to get the autogenerated item id from the table.
+    let item_id: u64 = match item.id {
+        0 => sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut *tx)).await?.get(0),
+        item_id => item_id,
+    };
+
+    insert_item_relations(tx, item_id, item).await?;
+    Ok(item_id)
+}
+
+/// Items can only be put into locations that aren't deleted.
+pub(super) async fn check_location(tx: &mut Transaction<'_, MySql>, location_id: u64) -> StoreResult<()> {
+    sqlx::query("SELECT id FROM locations WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+        .bind(location_id)
+        .fetch_optional(traced(&mut *tx))
+        .await?
+        .ok_or(StoreError::UnknownReference("location"))?;
+
+    Ok(())
+}
+
+/// Record the initial amount of a new item as received.
+pub(super) async fn insert_initial_movement(tx: &mut Transaction<'_, MySql>, item_id: u64, item: &Item, change: &Change) -> StoreResult<()> {
+    if item.amount > 0 {
+        insert_movement(tx, &change.movement(item_id, MovementKind::Receive, item.amount as i64, item.location, None)).await?;
+    }
+
+    Ok(())
+}
+
+/// Items inside of a container get the location of the container.
+/// Returns an error if the container doesn't exist or if the
+/// item would end up inside of itself.
+pub(super) async fn resolve_container(tx: &mut Transaction<'_, MySql>, item: &Item) -> StoreResult<Item> {
+    let parent_id = match item.parent_item {
+        Some(parent_id) => parent_id,
+        None => return Ok(item.clone()),
+    };
+
+    let location: u64 = sqlx::query("SELECT location_id FROM items WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+        .bind(parent_id)
+        .fetch_optional(traced(&mut *tx))
+        .await?
+        .ok_or(StoreError::UnknownReference("item"))?
+        .get(0);
+
+    // All ancestors of the item share the location of the container
+    let parents = item_parents(tx, location).await?;
+    if creates_cycle(item.id, parent_id, &parents) {
+        return Err(StoreError::Invalid("item.cycle", "an item can't be inside of itself!"));
+    }
+
+    Ok(Item { location, ..item.clone() })
+}
+
+/// The container of every item in the location. The items are locked until
+/// the end of the transaction, so nobody can move them in the meantime.
+async fn item_parents(tx: &mut Transaction<'_, MySql>, location_id: u64) -> StoreResult<HashMap<u64, Option<u64>>> {
+    Ok(sqlx::query("SELECT id, parent_item_id FROM items WHERE location_id = ? AND deleted_at IS NULL FOR UPDATE")
+        .bind(location_id)
+        .fetch_all(traced(&mut *tx))
+        .await?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect())
+}
+
+/// Resolve the location, tags and container of an imported item and insert it.
+/// The results of the items that were imported before are needed for the container.
+async fn import_item(tx: &mut Transaction<'_, MySql>, imported: &ImportedItem, results: &[StoreResult<u64>], change: &Change) -> StoreResult<u64> {
+    let location = resolve_location(tx, &imported.location).await?;
+
+    let mut tags = Vec::with_capacity(imported.tags.len());
+    for tag_name in &imported.tags {
+        tags.push(resolve_tag(tx, tag_name).await?);
+    }
+
+    let item = resolve_container(
+        tx,
+        &Item {
+            location,
+            tags,
+            parent_item: imported.container_id(results)?,
+            ..imported.item.clone()
+        },
+    )
+    .await?;
+    let item_id = insert_item(tx, &item).await?;
+    insert_initial_movement(tx, item_id, &item, change).await?;
+    insert_revision(tx, item_id, change, false).await?;
+
+    Ok(item_id)
+}
+
+/// Get the id of a location by its path. If the database is
+/// specified, missing locations are created inside of it.
+async fn resolve_location(tx: &mut Transaction<'_, MySql>, path: &LocationPath) -> StoreResult<u64> {
+    let database_name = match &path.database {
+        Some(database_name) => database_name,
+        None => {
+            // The name is only unique inside of a database
+            let location_name = path.path.last().ok_or(StoreError::UnknownReference("location"))?;
+            let rows = sqlx::query("SELECT id FROM locations WHERE name = ? AND deleted_at IS NULL LIMIT 2")
+                .bind(location_name)
+                .fetch_all(traced(&mut *tx))
+                .await?;
+            return match rows.as_slice() {
+                [] => Err(StoreError::UnknownReference("location")),
+                [row] => Ok(row.get(0)),
+                _ => Err(StoreError::Invalid("location.ambiguous", "there are several locations with this name, add the database!")),
+            };
+        }
+    };
+
+    let database_id: u64 = sqlx::query("SELECT id FROM item_databases WHERE name = ? AND deleted_at IS NULL")
+        .bind(database_name)
+        .fetch_optional(traced(&mut *tx))
+        .await?
+        .map(|row| row.get(0))
+        .ok_or(StoreError::UnknownReference("database"))?;
+
+    let mut parent: Option<u64> = None;
+    for location_name in &path.path {
+        let row = sqlx::query("SELECT id FROM locations WHERE name = ? AND database_id = ? AND deleted_at IS NULL")
+            .bind(location_name)
+            .bind(database_id)
+            .fetch_optional(traced(&mut *tx))
+            .await?;
+
+        let location_id = match row {
+            Some(row) => row.get(0),
+            None => {
+                sqlx::query("INSERT INTO locations (name,database_id,parent_id) VALUES (?,?,?)")
+                    .bind(location_name)
+                    .bind(database_id)
+                    .bind(parent)
+                    .execute(traced(&mut *tx))
+                    .await?;
+                sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut *tx)).await?.get(0)
+            }
+        };
+        parent = Some(location_id);
+    }
+
+    parent.ok_or(StoreError::UnknownReference("location"))
+}
+
+/// Get the id of a tag by its name or create it.
+async fn resolve_tag(tx: &mut Transaction<'_, MySql>, tag_name: &str) -> StoreResult<u64> {
+    let row = sqlx::query("SELECT id FROM tags WHERE name = ? AND deleted_at IS NULL")
+        .bind(tag_name)
+        .fetch_optional(traced(&mut *tx))
+        .await?;
+    if let Some(row) = row {
+        return Ok(row.get(0));
+    }
+
+    sqlx::query("INSERT INTO tags (name,color,icon) VALUES (?,0,NULL)")
+        .bind(tag_name)
+        .execute(traced(&mut *tx))
+        .await?;
+    Ok(sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut *tx)).await?.get(0))
+}
+
+/// Insert the tags, properties and attachments of an item
+/// into their separate tables. The item itself has to be
+/// inserted beforehand, otherwise the foreign keys fail.
+async fn insert_item_relations(tx: &mut Transaction<'_, MySql>, item_id: u64, item: &Item) -> StoreResult<()> {
+    // (Look at the "attachments" query for an explanation)
+    if !item.tags.is_empty() {
+        // The foreign keys don't know about deleted tags
+        let tag_sql = format!("SELECT COUNT(*) FROM tags WHERE deleted_at IS NULL AND id IN (?{})", ",?".repeat(item.tags.len() - 1));
+        let mut tag_query = sqlx::query(tag_sql.as_str());
+        for tag in &item.tags {
+            tag_query = tag_query.bind(tag);
+        }
+        let existing: i64 = tag_query.fetch_one(traced(&mut *tx)).await?.get(0);
+        if existing as usize != item.tags.iter().collect::<HashSet<_>>().len() {
+            return Err(StoreError::UnknownReference("tag"));
+        }
+
+        // Tags of a database can only be used by its items (global tags have no database_id)
+        let scope_sql = format!(
+            "SELECT COUNT(*) FROM tags JOIN locations ON locations.id = ? WHERE tags.database_id <> locations.database_id AND tags.id IN (?{})",
+            ",?".repeat(item.tags.len() - 1)
+        );
+        let mut scope_query = sqlx::query(scope_sql.as_str()).bind(item.location);
+        for tag in &item.tags {
+            scope_query = scope_query.bind(tag);
+        }
+        let foreign: i64 = scope_query.fetch_one(traced(&mut *tx)).await?.get(0);
+        if foreign > 0 {
+            return Err(StoreError::Invalid("tag.other_database", "the tag belongs to another database!"));
+        }
+
+        let tag_sql: String = format!("INSERT INTO item_tags (item_id,tag_id) VALUES (?,?){}", ", (?,?)".repeat(item.tags.len() - 1));
+
+        let mut tag_insertion = sqlx::query(tag_sql.as_str());
+        for tag in &item.tags {
+            tag_insertion = tag_insertion.bind(item_id).bind(tag);
+        }
+
+        tag_insertion.execute(traced(&mut *tx)).await.map_err(reference_error("tag"))?;
+    }
+
+    // (Look at the "attachments" query for an explanation)
+    if !item.properties_internal.is_empty() || !item.properties_custom.is_empty() {
+        let property_sql: String = format!(
+            "INSERT INTO item_properties (item_id,is_custom,name,value) VALUES (?,?,?,?){}",
+            ", (?,?,?,?)".repeat(item.properties_internal.len() + item.properties_custom.len() - 1),
+        );
+
+        let mut property_insertion = sqlx::query(property_sql.as_str());
+        for property in &item.properties_internal {
+            property_insertion = property_insertion.bind(item_id).bind(false).bind(&property.name).bind(&property.value);
+        }
+
+        for property in &item.properties_custom {
+            property_insertion = property_insertion.bind(item_id).bind(true).bind(&property.name).bind(&property.value);
+        }
+
+        property_insertion.execute(traced(&mut *tx)).await?;
+    }
+
+    // If we have attachments, store them in a separate table.
+    if !item.attachments.is_empty() {
+        // Create an sql query with the right amount of values
+        // to fit all the attachments into a single query.
+        // We only need to the string n -1 times, because we
+        // already have the fist one in the formatting string.
+        let attachment_sql: String = format!(
+            "INSERT INTO item_attachments (item_id,name,url) VALUES (?,?,?){}",
+            ", (?,?,?)".repeat(item.attachments.len() - 1)
+        );
+
+        // Insert all attachments into the sql query.
+        let mut attachment_insertion = sqlx::query(attachment_sql.as_str());
+        for attachment in &item.attachments {
+            attachment_insertion = attachment_insertion.bind(item_id).bind(attachment.0).bind(attachment.1);
+        }
+
+        attachment_insertion.execute(traced(&mut *tx)).await?;
+    }
+
+    Ok(())
+}
+
+/// The item table consists out of multiple tables.
+/// Because of that, we need to make small steps,
+/// to reconstruct the item in code. This function
+/// does the first part of that.
+pub(crate) fn sqlrow_to_basic_item(row: &sqlx::mysql::MySqlRow) -> Item {
+    let last_edited: chrono::NaiveDateTime = row.get(6);
+    let created: chrono::NaiveDateTime = row.get(7);
+
+    Item {
+        id: row.get(0),
+        name: row.get(1),
+        description: row.get(2),
+        image: row.get(3),
+        location: row.get(4),
+        amount: row.get(5),
+        parent_item: row.get(8),
+        min_amount: row.get(9),
+        last_edited: last_edited.timestamp(),
+        created: created.timestamp(),
+        tags: vec![],
+        properties_custom: vec![],
+        properties_internal: vec![],
+        attachments: HashMap::new(),
+    }
+}
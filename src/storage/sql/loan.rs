@@ -0,0 +1,128 @@
+use async_trait::async_trait;
+use sqlx::{types::chrono, MySql, Row, Transaction};
+
+use crate::models::Loan;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::{bind_all, placeholders, reference_error, SqlStore};
+use crate::storage::{LoanStore, StoreError, StoreResult};
+
+#[async_trait]
+impl LoanStore for SqlStore {
+    async fn get_loans(&self) -> StoreResult<Vec<Loan>> {
+        let mut connection = self.pool.acquire().await?;
+
+        Ok(sqlx::query("SELECT * FROM loans")
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .map(sqlrow_to_loan)
+            .collect())
+    }
+
+    async fn get_loan(&self, loan_id: u64) -> StoreResult<Loan> {
+        let mut connection = self.pool.acquire().await?;
+
+        // If the loan could not be found, this returns StoreError::NotFound.
+        let row = sqlx::query("SELECT * FROM loans WHERE id = ?").bind(loan_id).fetch_one(traced(&mut connection)).await?;
+        Ok(sqlrow_to_loan(&row))
+    }
+
+    async fn get_loans_by_items(&self, item_ids: &[u64]) -> StoreResult<Vec<Loan>> {
+        if item_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut connection = self.pool.acquire().await?;
+
+        let sql = format!("SELECT * FROM loans WHERE item_id IN ({})", placeholders(item_ids.len()));
+        Ok(bind_all(sqlx::query(&sql), item_ids)
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .map(sqlrow_to_loan)
+            .collect())
+    }
+
+    async fn check_out(&self, loan: &Loan) -> StoreResult<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        // Lock the item, so that two loans can't take the same pieces at once
+        let amount: u64 = sqlx::query("SELECT amount FROM items WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+            .bind(loan.item)
+            .fetch_optional(traced(&mut tx))
+            .await?
+            .ok_or(StoreError::UnknownReference("item"))?
+            .get(0);
+
+        if lent_amount(&mut tx, loan.item).await? + loan.amount > amount {
+            return Err(StoreError::Invalid("loan.not_available", "not enough of the item is available!"));
+        }
+
+        sqlx::query("INSERT INTO loans (item_id,amount,user_id,borrower,lent,due) VALUES (?,?,?,?,?,?)")
+            .bind(loan.item)
+            .bind(loan.amount)
+            .bind(loan.user)
+            .bind(&loan.borrower)
+            .bind(chrono::NaiveDateTime::from_timestamp(loan.lent, 0))
+            .bind(loan.due.map(|due| chrono::NaiveDateTime::from_timestamp(due, 0)))
+            .execute(traced(&mut tx))
+            .await
+            .map_err(reference_error("user"))?;
+
+        let loan_id: u64 = sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut tx)).await?.get(0);
+
+        tx.commit().await?;
+        Ok(loan_id)
+    }
+
+    async fn check_in(&self, loan_id: u64, returned: i64) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // If the loan could not be found, this returns StoreError::NotFound.
+        let row = sqlx::query("SELECT returned FROM loans WHERE id = ? FOR UPDATE")
+            .bind(loan_id)
+            .fetch_one(traced(&mut tx))
+            .await?;
+        let already_returned: Option<chrono::NaiveDateTime> = row.get(0);
+        if already_returned.is_some() {
+            return Err(StoreError::Invalid("loan.already_returned", "the loan was already returned!"));
+        }
+
+        sqlx::query("UPDATE loans SET returned = ? WHERE id = ?")
+            .bind(chrono::NaiveDateTime::from_timestamp(returned, 0))
+            .bind(loan_id)
+            .execute(traced(&mut tx))
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// How much of the item is lent right now. The item has to be locked by the caller.
+pub(super) async fn lent_amount(tx: &mut Transaction<'_, MySql>, item_id: u64) -> StoreResult<u64> {
+    // (SUM returns a decimal, that's why the cast is needed)
+    Ok(
+        sqlx::query("SELECT CAST(COALESCE(SUM(amount), 0) AS UNSIGNED) FROM loans WHERE item_id = ? AND returned IS NULL")
+            .bind(item_id)
+            .fetch_one(traced(&mut *tx))
+            .await?
+            .get(0),
+    )
+}
+
+fn sqlrow_to_loan(row: &sqlx::mysql::MySqlRow) -> Loan {
+    let lent: chrono::NaiveDateTime = row.get("lent");
+    let due: Option<chrono::NaiveDateTime> = row.get("due");
+    let returned: Option<chrono::NaiveDateTime> = row.get("returned");
+
+    Loan {
+        id: row.get("id"),
+        item: row.get("item_id"),
+        amount: row.get("amount"),
+        user: row.get("user_id"),
+        borrower: row.get("borrower"),
+        lent: lent.timestamp(),
+        due: due.map(|due| due.timestamp()),
+        returned: returned.map(|returned| returned.timestamp()),
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sqlx::{MySql, Row, Transaction};
+
+use crate::models::{Location, MovementKind};
+use crate::storage::sql::movement::insert_movement;
+use crate::storage::sql::revision::insert_revision;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::trash::{trash_batch, trash_location};
+use crate::storage::sql::{reference_error, SqlStore};
+use crate::storage::{check_location_parent, location_descendants, Change, ChildPolicy, LocationStore, StoreError, StoreResult};
+
+#[async_trait]
+impl LocationStore for SqlStore {
+    async fn get_locations(&self) -> StoreResult<Vec<Location>> {
+        let mut connection = self.pool.acquire().await?;
+
+        Ok(sqlx::query_as::<_, Location>("SELECT * FROM locations WHERE deleted_at IS NULL")
+            .fetch_all(traced(&mut connection))
+            .await?)
+    }
+
+    async fn get_location(&self, location_id: u64) -> StoreResult<Location> {
+        let mut connection = self.pool.acquire().await?;
+
+        // Query for the object and auto convert it.
+        Ok(sqlx::query_as::<_, Location>("SELECT * FROM locations WHERE id = ? AND deleted_at IS NULL")
+            .bind(location_id)
+            .fetch_one(traced(&mut connection))
+            .await?)
+    }
+
+    async fn count_locations(&self) -> StoreResult<HashMap<u64, u64>> {
+        self.count_per_database("SELECT database_id, COUNT(*) FROM locations WHERE deleted_at IS NULL GROUP BY database_id")
+            .await
+    }
+
+    async fn put_location(&self, location: &Location) -> StoreResult<u64> {
+        // We need to make a transaction here because we want to make 2 queries that relate to each other.
+        let mut tx = self.pool.begin().await?;
+        check_database(&mut tx, location.database).await?;
+        check_parent(&mut tx, location).await?;
+
+        // First insert the object into the sql table...
+        sqlx::query("INSERT INTO locations (name,database_id,parent_id,icon) VALUES (?,?,?,?)")
+            .bind(&location.name)
+            .bind(location.database)
+            .bind(location.parent)
+            .bind(location.icon)
+            .execute(traced(&mut tx))
+            .await
+            .map_err(reference_error("database"))?;
+
+        // ...after that we need to get the autogenerated id from the table.
+        let location_id: u64 = sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut tx)).await?.get(0);
+
+        // Finally, commit the changes to make them permanent
+        tx.commit().await?;
+        Ok(location_id)
+    }
+
+    async fn update_location(&self, location: &Location) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // If the location doesn't exist, this returns StoreError::NotFound.
+        let old_location = sqlx::query_as::<_, Location>("SELECT * FROM locations WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+            .bind(location.id)
+            .fetch_one(traced(&mut tx))
+            .await?;
+
+        // The child locations would end up in another database than their parent
+        if old_location.database != location.database {
+            let children: i64 = sqlx::query("SELECT COUNT(*) FROM locations WHERE parent_id = ? AND deleted_at IS NULL")
+                .bind(location.id)
+                .fetch_one(traced(&mut tx))
+                .await?
+                .get(0);
+            if children > 0 {
+                return Err(StoreError::Invalid(
+                    "location.has_children",
+                    "a location with child locations can't be moved to another database!",
+                ));
+            }
+        }
+
+        check_database(&mut tx, location.database).await?;
+        check_parent(&mut tx, location).await?;
+
+        sqlx::query("UPDATE locations SET name = ?, database_id = ?, parent_id = ?, icon = ? WHERE id = ?")
+            .bind(&location.name)
+            .bind(location.database)
+            .bind(location.parent)
+            .bind(location.icon)
+            .bind(location.id)
+            .execute(traced(&mut tx))
+            .await
+            .map_err(reference_error("database"))?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_location(&self, location_id: u64, policy: ChildPolicy, change: &Change) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // If the location doesn't exist, this returns StoreError::NotFound.
+        let location = sqlx::query_as::<_, Location>("SELECT * FROM locations WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+            .bind(location_id)
+            .fetch_one(traced(&mut tx))
+            .await?;
+
+        let mut deleted_ids = vec![location_id];
+        match policy {
+            ChildPolicy::Reject => {
+                let children: i64 = sqlx::query("SELECT COUNT(*) FROM locations WHERE parent_id = ? AND deleted_at IS NULL")
+                    .bind(location_id)
+                    .fetch_one(traced(&mut tx))
+                    .await?
+                    .get(0);
+                if children > 0 {
+                    return Err(StoreError::Invalid("location.has_children", "the location still contains other locations!"));
+                }
+            }
+            ChildPolicy::Reparent => {
+                sqlx::query("UPDATE locations SET parent_id = ? WHERE parent_id = ? AND deleted_at IS NULL")
+                    .bind(location.parent)
+                    .bind(location_id)
+                    .execute(traced(&mut tx))
+                    .await?;
+
+                if let Some(parent_id) = location.parent {
+                    let items: Vec<(u64, u64)> = sqlx::query("SELECT id, amount FROM items WHERE location_id = ? AND deleted_at IS NULL FOR UPDATE")
+                        .bind(location_id)
+                        .fetch_all(traced(&mut tx))
+                        .await?
+                        .iter()
+                        .map(|row| (row.get(0), row.get(1)))
+                        .collect();
+
+                    sqlx::query("UPDATE items SET location_id = ? WHERE location_id = ? AND deleted_at IS NULL")
+                        .bind(parent_id)
+                        .bind(location_id)
+                        .execute(traced(&mut tx))
+                        .await
+                        .map_err(|err| match StoreError::from(err) {
+                            StoreError::Conflict => StoreError::Invalid("location.item_conflict", "the parent location already contains an item with the same name!"),
+                            err => err,
+                        })?;
+
+                    // The items are moved like any other transfer
+                    for (item_id, amount) in items {
+                        insert_movement(&mut tx, &change.movement(item_id, MovementKind::Transfer, amount as i64, parent_id, Some(location_id))).await?;
+                        insert_revision(&mut tx, item_id, change, false).await?;
+                    }
+                }
+            }
+            ChildPolicy::Cascade => {
+                let locations = database_locations(&mut tx, location.database).await?;
+                deleted_ids.extend(location_descendants(location_id, &locations));
+            }
+        }
+
+        // The items of the locations go to the trash as well
+        let batch = trash_batch(&mut tx, change).await?;
+        for deleted_id in deleted_ids {
+            trash_location(&mut tx, deleted_id, batch, change).await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// All locations of a database. They are locked until the end
+/// of the transaction, so nobody can move them in the meantime.
+async fn database_locations(tx: &mut Transaction<'_, MySql>, database_id: u64) -> StoreResult<Vec<Location>> {
+    Ok(
+        sqlx::query_as::<_, Location>("SELECT * FROM locations WHERE database_id = ? AND deleted_at IS NULL FOR UPDATE")
+            .bind(database_id)
+            .fetch_all(traced(&mut *tx))
+            .await?,
+    )
+}
+
+/// Locations (and tags) can only be added to databases that aren't deleted.
+pub(super) async fn check_database(tx: &mut Transaction<'_, MySql>, database_id: u64) -> StoreResult<()> {
+    sqlx::query("SELECT id FROM item_databases WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+        .bind(database_id)
+        .fetch_optional(traced(&mut *tx))
+        .await?
+        .ok_or(StoreError::UnknownReference("database"))?;
+
+    Ok(())
+}
+
+async fn check_parent(tx: &mut Transaction<'_, MySql>, location: &Location) -> StoreResult<()> {
+    if location.parent.is_none() {
+        return Ok(());
+    }
+
+    // The parent has to be in the same database, so
+    // we only need the locations of that database.
+    let locations = database_locations(tx, location.database).await?;
+    match check_location_parent(location, &locations) {
+        // The parent is either in another database or doesn't exist at all
+        Err(StoreError::UnknownReference(_)) => {
+            let parent = sqlx::query("SELECT id FROM locations WHERE id = ? AND deleted_at IS NULL")
+                .bind(location.parent)
+                .fetch_optional(traced(&mut *tx))
+                .await?;
+            match parent {
+                Some(_) => Err(StoreError::Invalid(
+                    "location.parent_in_other_database",
+                    "the parent location must be in the same database!",
+                )),
+                None => Err(StoreError::UnknownReference("location")),
+            }
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use sqlx::migrate::Migrator;
+use sqlx::mysql::MySqlArguments;
+use sqlx::query::Query;
+use sqlx::{MySql, MySqlPool, Row};
+
+use crate::storage::sql::traced::traced;
+use crate::storage::{StoreError, StoreResult};
+
+mod backup;
+mod code;
+mod database;
+mod file;
+mod icon;
+mod item;
+mod loan;
+mod location;
+mod movement;
+mod revision;
+mod session;
+mod status;
+mod tag;
+mod template;
+mod traced;
+mod trash;
+
+/// The sql migrations in the "migrations" directory.
+/// They are embedded into the binary at compile time.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Storage backend for MySQL / MariaDB servers.
+pub(crate) struct SqlStore {
+    pool: MySqlPool,
+}
+
+impl SqlStore {
+    pub(crate) fn new(pool: MySqlPool) -> Self {
+        SqlStore { pool }
+    }
+
+    /// Bring the database schema up to date.
+    pub(crate) async fn migrate(&self) -> Result<(), sqlx::migrate::MigrateError> {
+        MIGRATOR.run(&self.pool).await
+    }
+
+    /// Run a `SELECT database_id, COUNT(*) ... GROUP BY database_id` query.
+    async fn count_per_database(&self, sql: &str) -> StoreResult<HashMap<u64, u64>> {
+        let mut connection = self.pool.acquire().await?;
+
+        Ok(sqlx::query(sql)
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .map(|row| (row.get(0), row.get::<i64, _>(1) as u64))
+            .collect())
+    }
+}
+
+impl From<sqlx::Error> for StoreError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => StoreError::NotFound,
+            sqlx::Error::Database(db_error) if db_error.message().starts_with("Duplicate entry") => StoreError::Conflict,
+            _ => StoreError::Internal(Box::new(err)),
+        }
+    }
+}
+
+/// The placeholders of an `IN (...)` list with this many values, e.g. "?,?,?".
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(",")
+}
+
+/// Bind the ids to the placeholders of the query, in order.
+fn bind_all<'q>(mut query: Query<'q, MySql, MySqlArguments>, ids: &'q [u64]) -> Query<'q, MySql, MySqlArguments> {
+    for id in ids {
+        query = query.bind(id);
+    }
+    query
+}
+
+/// Like the [From] conversion, but additionally turns failed
+/// foreign key constraints into [StoreError::UnknownReference].
+/// Sadly MySQL doesn't tell us which key failed in a usable way,
+/// so the caller has to name the referenced entity.
+fn reference_error(reference: &'static str) -> impl Fn(sqlx::Error) -> StoreError {
+    move |err| match err {
+        sqlx::Error::Database(db_error) if db_error.message().starts_with("Cannot add or update a child row: a foreign key constraint fails") => {
+            StoreError::UnknownReference(reference)
+        }
+        _ => StoreError::from(err),
+    }
+}
@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use sqlx::{types::chrono, MySql, Row, Transaction};
+
+use crate::models::{Movement, MovementKind};
+use crate::storage::sql::loan::lent_amount;
+use crate::storage::sql::revision::insert_revision;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::{bind_all, placeholders, reference_error, SqlStore};
+use crate::storage::{apply_delta, check_lent_amount, AdjustedAmount, Change, MovementStore, StoreError, StoreResult};
+
+#[async_trait]
+impl MovementStore for SqlStore {
+    async fn get_movements(&self, item_id: u64) -> StoreResult<Vec<Movement>> {
+        let mut connection = self.pool.acquire().await?;
+
+        Ok(sqlx::query("SELECT * FROM item_movements WHERE item_id = ? ORDER BY time, id")
+            .bind(item_id)
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .map(sqlrow_to_movement)
+            .collect())
+    }
+
+    async fn get_movements_by_items(&self, item_ids: &[u64]) -> StoreResult<Vec<Movement>> {
+        if item_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut connection = self.pool.acquire().await?;
+
+        let sql = format!("SELECT * FROM item_movements WHERE item_id IN ({}) ORDER BY time, id", placeholders(item_ids.len()));
+        Ok(bind_all(sqlx::query(&sql), item_ids)
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .map(sqlrow_to_movement)
+            .collect())
+    }
+
+    async fn record_movements(&self, movements: &[Movement]) -> StoreResult<Vec<AdjustedAmount>> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut adjusted = Vec::with_capacity(movements.len());
+        for movement in movements {
+            if movement.kind == MovementKind::Transfer {
+                return Err(StoreError::Invalid("movement.transfer", "items are transferred by changing their location!"));
+            }
+
+            // Lock the row, so that concurrent movements don't get lost
+            let row = sqlx::query("SELECT name, amount, min_amount, location_id FROM items WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+                .bind(movement.item)
+                .fetch_optional(traced(&mut tx))
+                .await?
+                .ok_or(StoreError::UnknownReference("item"))?;
+            let old_amount: u64 = row.get(1);
+            let amount = apply_delta(old_amount, movement.amount)?;
+            check_lent_amount(old_amount, amount, lent_amount(&mut tx, movement.item).await?)?;
+
+            sqlx::query("UPDATE items SET amount = ?, last_edited = ? WHERE id = ?")
+                .bind(amount)
+                .bind(chrono::NaiveDateTime::from_timestamp(movement.time, 0))
+                .bind(movement.item)
+                .execute(traced(&mut tx))
+                .await?;
+
+            insert_movement(
+                &mut tx,
+                &Movement {
+                    location: Some(row.get(3)),
+                    from_location: None,
+                    ..movement.clone()
+                },
+            )
+            .await?;
+            insert_revision(&mut tx, movement.item, &Change::from(movement), false).await?;
+
+            adjusted.push(AdjustedAmount {
+                item_id: movement.item,
+                name: row.get(0),
+                old_amount,
+                amount,
+                min_amount: row.get(2),
+            });
+        }
+
+        tx.commit().await?;
+        Ok(adjusted)
+    }
+}
+
+/// Add an entry to the stock ledger. This doesn't change the item.
+pub(super) async fn insert_movement(tx: &mut Transaction<'_, MySql>, movement: &Movement) -> StoreResult<()> {
+    sqlx::query("INSERT INTO item_movements (item_id,kind,amount,location_id,from_location_id,reason,user_id,time) VALUES (?,?,?,?,?,?,?,?)")
+        .bind(movement.item)
+        .bind(movement.kind.as_str())
+        .bind(movement.amount)
+        .bind(movement.location)
+        .bind(movement.from_location)
+        .bind(&movement.reason)
+        .bind(movement.user)
+        .bind(chrono::NaiveDateTime::from_timestamp(movement.time, 0))
+        .execute(traced(&mut *tx))
+        .await
+        .map_err(reference_error("user"))?;
+
+    Ok(())
+}
+
+fn sqlrow_to_movement(row: &sqlx::mysql::MySqlRow) -> Movement {
+    let kind: String = row.get("kind");
+    let time: chrono::NaiveDateTime = row.get("time");
+
+    Movement {
+        id: row.get("id"),
+        item: row.get("item_id"),
+        // Only the store writes the kinds, so they are always valid
+        kind: MovementKind::parse(&kind).unwrap_or(MovementKind::Adjust),
+        amount: row.get("amount"),
+        location: row.get("location_id"),
+        from_location: row.get("from_location_id"),
+        reason: row.get("reason"),
+        user: row.get("user_id"),
+        time: time.timestamp(),
+    }
+}
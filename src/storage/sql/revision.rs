@@ -0,0 +1,127 @@
+use async_trait::async_trait;
+use sqlx::{types::chrono, MySql, Row, Transaction};
+
+use crate::models::{Item, ItemRevision};
+use crate::storage::sql::item::{fetch_item, insert_initial_movement, insert_item, resolve_container, update_item_row};
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::trash::restore_item;
+use crate::storage::sql::{bind_all, placeholders, reference_error, SqlStore};
+use crate::storage::{Change, RevisionStore, StoreError, StoreResult};
+
+#[async_trait]
+impl RevisionStore for SqlStore {
+    async fn get_revisions(&self, item_id: u64) -> StoreResult<Vec<ItemRevision>> {
+        let mut connection = self.pool.acquire().await?;
+
+        sqlx::query("SELECT * FROM item_revisions WHERE item_id = ? ORDER BY revision")
+            .bind(item_id)
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .map(sqlrow_to_revision)
+            .collect()
+    }
+
+    async fn get_revision(&self, item_id: u64, revision: u64) -> StoreResult<ItemRevision> {
+        let mut connection = self.pool.acquire().await?;
+
+        // If the revision could not be found, this returns StoreError::NotFound.
+        let row = sqlx::query("SELECT * FROM item_revisions WHERE item_id = ? AND revision = ?")
+            .bind(item_id)
+            .bind(revision)
+            .fetch_one(traced(&mut connection))
+            .await?;
+        sqlrow_to_revision(&row)
+    }
+
+    async fn get_revisions_by_items(&self, item_ids: &[u64]) -> StoreResult<Vec<ItemRevision>> {
+        if item_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut connection = self.pool.acquire().await?;
+
+        let sql = format!(
+            "SELECT * FROM item_revisions WHERE item_id IN ({}) ORDER BY item_id, revision",
+            placeholders(item_ids.len())
+        );
+        bind_all(sqlx::query(&sql), item_ids)
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .map(sqlrow_to_revision)
+            .collect()
+    }
+
+    async fn restore_revision(&self, item: &Item, change: &Change) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted: Option<bool> = sqlx::query("SELECT deleted_at IS NOT NULL FROM items WHERE id = ? FOR UPDATE")
+            .bind(item.id)
+            .fetch_optional(traced(&mut tx))
+            .await?
+            .map(|row| row.get(0));
+        match deleted {
+            Some(false) => update_item_row(&mut tx, item, change).await?,
+            Some(true) => {
+                restore_item(&mut tx, item.id, change).await?;
+                update_item_row(&mut tx, item, change).await?;
+            }
+
+            // The item was already purged, so it gets inserted again with the same id
+            None => {
+                let item = resolve_container(&mut tx, item).await?;
+                insert_item(&mut tx, &item).await?;
+                insert_initial_movement(&mut tx, item.id, &item, change).await?;
+                insert_revision(&mut tx, item.id, change, false).await?;
+
+                // Offline clients should no longer think that the item is deleted
+                sqlx::query("DELETE FROM item_deleted WHERE id = ?").bind(item.id).execute(traced(&mut tx)).await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Save the current state of the item as a new revision.
+pub(super) async fn insert_revision(tx: &mut Transaction<'_, MySql>, item_id: u64, change: &Change, deleted: bool) -> StoreResult<()> {
+    let item = fetch_item(&mut *tx, item_id).await?;
+    let snapshot = serde_json::to_string(&item).map_err(|err| StoreError::Internal(Box::new(err)))?;
+
+    // The item row is locked by the caller, so nobody else can take the same number
+    let revision: u64 = sqlx::query("SELECT CAST(COALESCE(MAX(revision), 0) + 1 AS UNSIGNED) FROM item_revisions WHERE item_id = ?")
+        .bind(item_id)
+        .fetch_one(traced(&mut *tx))
+        .await?
+        .get(0);
+
+    sqlx::query("INSERT INTO item_revisions (item_id,revision,user_id,time,reason,deleted,snapshot) VALUES (?,?,?,?,?,?,?)")
+        .bind(item_id)
+        .bind(revision)
+        .bind(change.user)
+        .bind(chrono::NaiveDateTime::from_timestamp(change.time, 0))
+        .bind(&change.reason)
+        .bind(deleted)
+        .bind(snapshot)
+        .execute(traced(&mut *tx))
+        .await
+        .map_err(reference_error("user"))?;
+
+    Ok(())
+}
+
+fn sqlrow_to_revision(row: &sqlx::mysql::MySqlRow) -> StoreResult<ItemRevision> {
+    let time: chrono::NaiveDateTime = row.get("time");
+    let snapshot: String = row.get("snapshot");
+
+    Ok(ItemRevision {
+        item: row.get("item_id"),
+        revision: row.get("revision"),
+        user: row.get("user_id"),
+        time: time.timestamp(),
+        reason: row.get("reason"),
+        deleted: row.get("deleted"),
+        snapshot: serde_json::from_str(&snapshot).map_err(|err| StoreError::Internal(Box::new(err)))?,
+    })
+}
@@ -0,0 +1,67 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::models::{AuthedUser, UserCredentials};
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::SqlStore;
+use crate::storage::{SessionStore, StoreError, StoreResult};
+
+#[async_trait]
+impl SessionStore for SqlStore {
+    async fn check_credentials(&self, credentials: &UserCredentials) -> StoreResult<u64> {
+        let mut connection = self.pool.acquire().await?;
+
+        // Query for the user_id with the credentials from the request
+        let row = sqlx::query("SELECT id FROM users WHERE username = ? AND password = ?")
+            .bind(&credentials.username)
+            .bind(&credentials.password)
+            .fetch_one(traced(&mut connection))
+            .await?;
+
+        Ok(row.get(0))
+    }
+
+    async fn create_session(&self, session_id: &str, user_id: u64) -> StoreResult<()> {
+        let mut connection = self.pool.acquire().await?;
+
+        sqlx::query("INSERT INTO sessions VALUES (?, ?, CURRENT_TIMESTAMP(), CURRENT_TIMESTAMP())")
+            .bind(session_id)
+            .bind(user_id)
+            .execute(traced(&mut connection))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: &str) -> StoreResult<AuthedUser> {
+        let mut connection = self.pool.acquire().await?;
+
+        Ok(sqlx::query_as::<_, AuthedUser>("SELECT session_id, user_id FROM sessions WHERE session_id = ?")
+            .bind(session_id)
+            .fetch_one(traced(&mut connection))
+            .await?)
+    }
+
+    async fn delete_session(&self, session_id: &str) -> StoreResult<()> {
+        let mut connection = self.pool.acquire().await?;
+
+        let result = sqlx::query("DELETE FROM sessions WHERE session_id = ?")
+            .bind(session_id)
+            .execute(traced(&mut connection))
+            .await?;
+
+        // If nothing was deleted, the session didn't even exist!
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn count_sessions(&self) -> StoreResult<u64> {
+        let mut connection = self.pool.acquire().await?;
+
+        let count: i64 = sqlx::query("SELECT COUNT(*) FROM sessions").fetch_one(traced(&mut connection)).await?.get(0);
+        Ok(count as u64)
+    }
+}
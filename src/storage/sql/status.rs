@@ -0,0 +1,41 @@
+use std::time::Instant;
+
+use async_trait::async_trait;
+use sqlx::migrate::Migrate;
+use sqlx::Connection;
+
+use crate::storage::sql::{SqlStore, MIGRATOR};
+use crate::storage::{PoolStatus, StatusStore, StoreError, StoreResult};
+
+#[async_trait]
+impl StatusStore for SqlStore {
+    async fn pool_status(&self) -> StoreResult<Option<PoolStatus>> {
+        let size = self.pool.size();
+        let idle = self.pool.num_idle();
+
+        // sqlx doesn't keep track of the waiting times, so we measure how long it takes
+        // to get a connection of our own. It's slow if all connections are busy.
+        let start = Instant::now();
+        self.pool.acquire().await?;
+        let acquire_seconds = start.elapsed().as_secs_f64();
+
+        Ok(Some(PoolStatus { size, idle, acquire_seconds }))
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        self.pool.acquire().await?.ping().await?;
+        Ok(())
+    }
+
+    async fn pending_migrations(&self) -> StoreResult<Vec<i64>> {
+        let mut connection = self.pool.acquire().await?;
+        let applied = connection.list_applied_migrations().await.map_err(|err| StoreError::Internal(Box::new(err)))?;
+
+        Ok(MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| migration.version)
+            .filter(|version| !applied.iter().any(|applied| applied.version == *version))
+            .collect())
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sqlx::{types::chrono, MySql, Row, Transaction};
+
+use crate::models::Tag;
+use crate::storage::sql::location::check_database;
+use crate::storage::sql::revision::insert_revision;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::{reference_error, SqlStore};
+use crate::storage::{check_tag_merge, check_tag_parent, Change, StoreError, StoreResult, TagStore};
+
+/// The tags that aren't deleted. Like the deleted tags on the items,
+/// a deleted parent is hidden until it's restored.
+pub(super) const VISIBLE_TAGS: &str = "SELECT tags.id, tags.name, tags.color, tags.icon, tags.database_id, \
+    IF(parents.deleted_at IS NULL, tags.parent_id, NULL) AS parent_id \
+    FROM tags LEFT JOIN tags AS parents ON parents.id = tags.parent_id WHERE tags.deleted_at IS NULL";
+
+#[async_trait]
+impl TagStore for SqlStore {
+    async fn get_tags(&self) -> StoreResult<Vec<Tag>> {
+        let mut connection = self.pool.acquire().await?;
+
+        Ok(sqlx::query_as::<_, Tag>(VISIBLE_TAGS).fetch_all(traced(&mut connection)).await?)
+    }
+
+    async fn get_tag(&self, tag_id: u64) -> StoreResult<Tag> {
+        let mut connection = self.pool.acquire().await?;
+
+        // Query for the object and auto convert it.
+        Ok(sqlx::query_as::<_, Tag>(&format!("{VISIBLE_TAGS} AND tags.id = ?"))
+            .bind(tag_id)
+            .fetch_one(traced(&mut connection))
+            .await?)
+    }
+
+    async fn count_tags(&self) -> StoreResult<HashMap<u64, u64>> {
+        self.count_per_database("SELECT database_id, COUNT(*) FROM tags WHERE deleted_at IS NULL AND database_id IS NOT NULL GROUP BY database_id")
+            .await
+    }
+
+    async fn put_tag(&self, tag: &Tag) -> StoreResult<u64> {
+        // We need to make a transaction here because we want to make 2 queries that relate to each other.
+        let mut tx = self.pool.begin().await?;
+        check_hierarchy(&mut tx, tag).await?;
+
+        // First insert the object into the sql table...
+        sqlx::query("INSERT INTO tags (name,color,icon,parent_id,database_id) VALUES (?,?,?,?,?)")
+            .bind(&tag.name)
+            .bind(tag.color)
+            .bind(tag.icon)
+            .bind(tag.parent)
+            .bind(tag.database)
+            .execute(traced(&mut tx))
+            .await
+            .map_err(reference_error("database"))?;
+
+        // ...after that we need to get the autogenerated id from the table.
+        let tag_id: u64 = sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut tx)).await?.get(0);
+
+        // Finally, commit the changes to make them permanent
+        tx.commit().await?;
+        Ok(tag_id)
+    }
+
+    async fn update_tag(&self, tag: &Tag) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        check_hierarchy(&mut tx, tag).await?;
+        if let Some(database_id) = tag.database {
+            check_usage(&mut tx, tag.id, database_id).await?;
+        }
+
+        let result = sqlx::query("UPDATE tags SET name = ?, color = ?, icon = ?, parent_id = ?, database_id = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(&tag.name)
+            .bind(tag.color)
+            .bind(tag.icon)
+            .bind(tag.parent)
+            .bind(tag.database)
+            .bind(tag.id)
+            .execute(traced(&mut tx))
+            .await
+            .map_err(reference_error("database"))?;
+
+        // If nothing was changed, the tag didn't even exist!
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_tag(&self, tag_id: u64, deleted_at: i64) -> StoreResult<()> {
+        let mut connection = self.pool.acquire().await?;
+
+        // The tag stays linked to its items, but they don't show it until it's restored
+        let result = sqlx::query("UPDATE tags SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(chrono::NaiveDateTime::from_timestamp(deleted_at, 0))
+            .bind(tag_id)
+            .execute(traced(&mut connection))
+            .await?;
+
+        // If nothing was deleted, the tag didn't even exist!
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn merge_tag(&self, tag_id: u64, into_id: u64, change: &Change) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let tags = sqlx::query_as::<_, Tag>(&format!("{VISIBLE_TAGS} FOR UPDATE")).fetch_all(traced(&mut tx)).await?;
+        check_tag_merge(tag_id, into_id, &tags)?;
+        if let Some(database_id) = tags.iter().find(|tag| tag.id == into_id).and_then(|tag| tag.database) {
+            check_usage(&mut tx, tag_id, database_id).await?;
+        }
+
+        // The deleted items keep their last revision until they are restored
+        let item_ids: Vec<u64> =
+            sqlx::query("SELECT items.id FROM item_tags JOIN items ON items.id = item_tags.item_id WHERE item_tags.tag_id = ? AND items.deleted_at IS NULL FOR UPDATE")
+                .bind(tag_id)
+                .fetch_all(traced(&mut tx))
+                .await?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+
+        // The links that already exist for the other tag are skipped. This
+        // also moves the links of the deleted items, so they can be restored.
+        for (table, column) in [("item_tags", "item_id"), ("template_tags", "template_id")] {
+            sqlx::query(&format!("INSERT IGNORE INTO {table} ({column},tag_id) SELECT {column}, ? FROM {table} WHERE tag_id = ?"))
+                .bind(into_id)
+                .bind(tag_id)
+                .execute(traced(&mut tx))
+                .await?;
+        }
+
+        sqlx::query("UPDATE tags SET parent_id = ? WHERE parent_id = ?")
+            .bind(into_id)
+            .bind(tag_id)
+            .execute(traced(&mut tx))
+            .await?;
+
+        // The old links are deleted by the foreign keys
+        sqlx::query("DELETE FROM tags WHERE id = ?").bind(tag_id).execute(traced(&mut tx)).await?;
+        for item_id in item_ids {
+            insert_revision(&mut tx, item_id, change, false).await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Make sure that the database exists and that the tag fits into the hierarchy of the other tags.
+pub(super) async fn check_hierarchy(tx: &mut Transaction<'_, MySql>, tag: &Tag) -> StoreResult<()> {
+    if let Some(database_id) = tag.database {
+        check_database(tx, database_id).await?;
+    }
+
+    let tags = sqlx::query_as::<_, Tag>(&format!("{VISIBLE_TAGS} FOR UPDATE")).fetch_all(traced(&mut *tx)).await?;
+    check_tag_parent(tag, &tags)
+}
+
+/// A tag can only be limited to a database if no item (not even a deleted one, which
+/// can still be restored) and no template of another database uses it.
+async fn check_usage(tx: &mut Transaction<'_, MySql>, tag_id: u64, database_id: u64) -> StoreResult<()> {
+    let foreign_items: i64 = sqlx::query(
+        "SELECT COUNT(*) FROM item_tags JOIN items ON items.id = item_tags.item_id JOIN locations ON locations.id = items.location_id \
+         WHERE item_tags.tag_id = ? AND locations.database_id <> ?",
+    )
+    .bind(tag_id)
+    .bind(database_id)
+    .fetch_one(traced(&mut *tx))
+    .await?
+    .get(0);
+    if foreign_items > 0 {
+        return Err(StoreError::Invalid("tag.used_in_other_database", "the tag is used by items of another database!"));
+    }
+
+    let foreign_templates: i64 = sqlx::query(
+        "SELECT COUNT(*) FROM template_tags JOIN item_templates ON item_templates.id = template_tags.template_id \
+         JOIN locations ON locations.id = item_templates.location_id WHERE template_tags.tag_id = ? AND locations.database_id <> ?",
+    )
+    .bind(tag_id)
+    .bind(database_id)
+    .fetch_one(traced(&mut *tx))
+    .await?
+    .get(0);
+    if foreign_templates > 0 {
+        return Err(StoreError::Invalid("tag.used_in_other_database", "the tag is used by templates of another database!"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use sqlx::{MySql, Row, Transaction};
+
+use crate::models::{ItemTemplate, Property};
+use crate::storage::sql::item::check_location;
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::{reference_error, SqlStore};
+use crate::storage::{StoreError, StoreResult, TemplateStore};
+
+#[async_trait]
+impl TemplateStore for SqlStore {
+    async fn get_templates(&self) -> StoreResult<Vec<ItemTemplate>> {
+        let mut connection = self.pool.acquire().await?;
+
+        // A deleted location is only hidden, like the deleted tags
+        let mut templates: HashMap<u64, ItemTemplate> = sqlx::query(
+            "SELECT item_templates.id, item_templates.name, item_templates.description, locations.id FROM item_templates \
+             LEFT JOIN locations ON locations.id = item_templates.location_id AND locations.deleted_at IS NULL",
+        )
+        .fetch_all(traced(&mut connection))
+        .await?
+        .iter()
+        .map(|row| ItemTemplate {
+            id: row.get(0),
+            name: row.get(1),
+            description: row.get(2),
+            location: row.get(3),
+            tags: vec![],
+            properties_internal: vec![],
+            properties_custom: vec![],
+        })
+        .map(|template| (template.id, template))
+        .collect();
+
+        sqlx::query("SELECT template_tags.template_id, template_tags.tag_id FROM template_tags JOIN tags ON tags.id = template_tags.tag_id WHERE tags.deleted_at IS NULL")
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .for_each(|row| {
+                let template_id: u64 = row.get(0);
+                if let Some(template) = templates.get_mut(&template_id) {
+                    template.tags.push(row.get(1));
+                }
+            });
+
+        sqlx::query("SELECT template_id, is_custom, name, value FROM template_properties")
+            .fetch_all(traced(&mut connection))
+            .await?
+            .iter()
+            .for_each(|row| {
+                let template_id: u64 = row.get(0);
+                let is_custom: bool = row.get(1);
+
+                if let Some(template) = templates.get_mut(&template_id) {
+                    let properties = if is_custom {
+                        &mut template.properties_custom
+                    } else {
+                        &mut template.properties_internal
+                    };
+                    properties.push(Property {
+                        name: row.get(2),
+                        value: row.get(3),
+                    });
+                }
+            });
+
+        Ok(templates.into_values().collect())
+    }
+
+    async fn get_template(&self, template_id: u64) -> StoreResult<ItemTemplate> {
+        // Templates are small and few, so this doesn't need its own queries
+        self.get_templates()
+            .await?
+            .into_iter()
+            .find(|template| template.id == template_id)
+            .ok_or(StoreError::NotFound)
+    }
+
+    async fn put_template(&self, template: &ItemTemplate) -> StoreResult<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(location_id) = template.location {
+            check_location(&mut tx, location_id).await?;
+        }
+
+        sqlx::query("INSERT INTO item_templates (name,description,location_id) VALUES (?,?,?)")
+            .bind(&template.name)
+            .bind(&template.description)
+            .bind(template.location)
+            .execute(traced(&mut tx))
+            .await
+            .map_err(reference_error("location"))?;
+        let template_id: u64 = sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut tx)).await?.get(0);
+
+        insert_template_relations(&mut tx, template_id, template).await?;
+
+        tx.commit().await?;
+        Ok(template_id)
+    }
+
+    async fn update_template(&self, template: &ItemTemplate) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(location_id) = template.location {
+            check_location(&mut tx, location_id).await?;
+        }
+
+        let result = sqlx::query("UPDATE item_templates SET name = ?, description = ?, location_id = ? WHERE id = ?")
+            .bind(&template.name)
+            .bind(&template.description)
+            .bind(template.location)
+            .bind(template.id)
+            .execute(traced(&mut tx))
+            .await
+            .map_err(reference_error("location"))?;
+
+        // MySQL doesn't count rows that didn't change, so the existence is checked separately
+        if result.rows_affected() == 0 {
+            sqlx::query("SELECT id FROM item_templates WHERE id = ? FOR UPDATE")
+                .bind(template.id)
+                .fetch_one(traced(&mut tx))
+                .await?;
+        }
+
+        // The relations are replaced as a whole, like the ones of the items
+        sqlx::query("DELETE FROM template_tags WHERE template_id = ?")
+            .bind(template.id)
+            .execute(traced(&mut tx))
+            .await?;
+        sqlx::query("DELETE FROM template_properties WHERE template_id = ?")
+            .bind(template.id)
+            .execute(traced(&mut tx))
+            .await?;
+        insert_template_relations(&mut tx, template.id, template).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_template(&self, template_id: u64) -> StoreResult<()> {
+        let mut connection = self.pool.acquire().await?;
+
+        // The tags and properties are deleted by the foreign keys
+        let result = sqlx::query("DELETE FROM item_templates WHERE id = ?")
+            .bind(template_id)
+            .execute(traced(&mut connection))
+            .await?;
+
+        // If nothing was deleted, the template didn't even exist!
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+
+        Ok(())
+    }
+}
+
+async fn insert_template_relations(tx: &mut Transaction<'_, MySql>, template_id: u64, template: &ItemTemplate) -> StoreResult<()> {
+    let tag_ids: HashSet<u64> = template.tags.iter().copied().collect();
+    for tag_id in tag_ids {
+        // The foreign keys don't know about deleted tags
+        sqlx::query("SELECT id FROM tags WHERE id = ? AND deleted_at IS NULL")
+            .bind(tag_id)
+            .fetch_optional(traced(&mut *tx))
+            .await?
+            .ok_or(StoreError::UnknownReference("tag"))?;
+
+        sqlx::query("INSERT INTO template_tags (template_id,tag_id) VALUES (?,?)")
+            .bind(template_id)
+            .bind(tag_id)
+            .execute(traced(&mut *tx))
+            .await
+            .map_err(reference_error("tag"))?;
+    }
+
+    let properties = template.properties_internal.iter().map(|property| (false, property));
+    let properties = properties.chain(template.properties_custom.iter().map(|property| (true, property)));
+    for (is_custom, property) in properties {
+        sqlx::query("INSERT INTO template_properties (template_id,is_custom,name,value) VALUES (?,?,?,?)")
+            .bind(template_id)
+            .bind(is_custom)
+            .bind(&property.name)
+            .bind(&property.value)
+            .execute(traced(&mut *tx))
+            .await?;
+    }
+
+    Ok(())
+}
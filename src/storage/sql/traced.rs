@@ -0,0 +1,73 @@
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{FutureExt, Stream};
+use sqlx::mysql::{MySqlQueryResult, MySqlRow, MySqlStatement, MySqlTypeInfo};
+use sqlx::{Describe, Either, Execute, Executor, MySql};
+use tracing::{Instrument, Span};
+
+use crate::telemetry::query_span;
+
+/// Run the queries of an executor (a connection, a transaction or the pool)
+/// in spans of their own, so the traces show which queries a request needed.
+pub(super) fn traced<E>(executor: E) -> Traced<E> {
+    Traced(executor)
+}
+
+#[derive(Debug)]
+pub(super) struct Traced<E>(E);
+
+impl<'c, E> Executor<'c> for Traced<E>
+where
+    E: Executor<'c, Database = MySql>,
+{
+    type Database = MySql;
+
+    fn fetch_many<'e, 'q: 'e, Q>(self, query: Q) -> BoxStream<'e, Result<Either<MySqlQueryResult, MySqlRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        Q: Execute<'q, MySql> + 'q,
+    {
+        let span = query_span(query.sql());
+        let rows = self.0.fetch_many(query);
+        Box::pin(InSpan { span, stream: rows })
+    }
+
+    fn fetch_optional<'e, 'q: 'e, Q>(self, query: Q) -> BoxFuture<'e, Result<Option<MySqlRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        Q: Execute<'q, MySql> + 'q,
+    {
+        let span = query_span(query.sql());
+        self.0.fetch_optional(query).instrument(span).boxed()
+    }
+
+    fn prepare_with<'e, 'q: 'e>(self, sql: &'q str, parameters: &'e [MySqlTypeInfo]) -> BoxFuture<'e, Result<MySqlStatement<'q>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        self.0.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<MySql>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        self.0.describe(sql)
+    }
+}
+
+/// A stream of rows that is polled inside of its span. The span ends when the stream is dropped.
+struct InSpan<'e, T> {
+    span: Span,
+    stream: BoxStream<'e, T>,
+}
+
+impl<T> Stream for InSpan<'_, T> {
+    type Item = T;
+
+    fn poll_next(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<T>> {
+        let this = &mut *self;
+        let _entered = this.span.enter();
+        this.stream.as_mut().poll_next(cx)
+    }
+}
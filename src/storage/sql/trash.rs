@@ -0,0 +1,329 @@
+use async_trait::async_trait;
+use sqlx::{types::chrono, MySql, Row, Transaction};
+
+use crate::models::{Location, Tag, TrashEntry, TrashKind};
+use crate::storage::sql::revision::insert_revision;
+use crate::storage::sql::tag::{check_hierarchy, VISIBLE_TAGS};
+use crate::storage::sql::traced::traced;
+use crate::storage::sql::SqlStore;
+use crate::storage::{location_descendants, Change, StoreError, StoreResult, TrashStore};
+
+#[async_trait]
+impl TrashStore for SqlStore {
+    async fn get_trash(&self) -> StoreResult<Vec<TrashEntry>> {
+        let mut connection = self.pool.acquire().await?;
+
+        let tables = [
+            (TrashKind::Database, "item_databases"),
+            (TrashKind::Location, "locations"),
+            (TrashKind::Tag, "tags"),
+            (TrashKind::Item, "items"),
+        ];
+
+        let mut entries = Vec::new();
+        for (kind, table) in tables {
+            let rows = sqlx::query(&format!("SELECT id, name, deleted_at FROM {table} WHERE deleted_at IS NOT NULL"))
+                .fetch_all(traced(&mut connection))
+                .await?;
+
+            entries.extend(rows.iter().map(|row| {
+                let deleted_at: chrono::NaiveDateTime = row.get(2);
+                TrashEntry {
+                    kind,
+                    id: row.get(0),
+                    name: row.get(1),
+                    deleted_at: deleted_at.timestamp(),
+                }
+            }));
+        }
+
+        Ok(entries)
+    }
+
+    async fn restore_from_trash(&self, kind: TrashKind, id: u64, change: &Change) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        match kind {
+            TrashKind::Item => restore_item(&mut tx, id, change).await?,
+            TrashKind::Tag => {
+                let result = sqlx::query("UPDATE tags SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL")
+                    .bind(id)
+                    .execute(traced(&mut tx))
+                    .await?;
+                if result.rows_affected() == 0 {
+                    return Err(StoreError::NotFound);
+                }
+
+                // The hierarchy of the other tags might have changed in the meantime
+                let tag = sqlx::query_as::<_, Tag>(&format!("{VISIBLE_TAGS} AND tags.id = ?"))
+                    .bind(id)
+                    .fetch_one(traced(&mut tx))
+                    .await?;
+                check_hierarchy(&mut tx, &tag).await?;
+            }
+            TrashKind::Location => restore_location(&mut tx, id, change).await?,
+            TrashKind::Database => restore_database(&mut tx, id, change).await?,
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn purge_trash(&self, deleted_before: i64) -> StoreResult<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        // Deleting the locations and databases also deletes everything
+        // that is still inside of them because of the foreign keys.
+        let mut purged = 0;
+        for table in ["items", "tags", "locations", "item_databases"] {
+            purged += sqlx::query(&format!("DELETE FROM {table} WHERE deleted_at < ?"))
+                .bind(chrono::NaiveDateTime::from_timestamp(deleted_before, 0))
+                .execute(traced(&mut tx))
+                .await?
+                .rows_affected();
+        }
+        sqlx::query("DELETE FROM trash_batches WHERE deleted_at < ?")
+            .bind(chrono::NaiveDateTime::from_timestamp(deleted_before, 0))
+            .execute(traced(&mut tx))
+            .await?;
+
+        tx.commit().await?;
+        Ok(purged)
+    }
+}
+
+/// Start a new batch of objects that are moved to the trash together.
+pub(super) async fn trash_batch(tx: &mut Transaction<'_, MySql>, change: &Change) -> StoreResult<u64> {
+    sqlx::query("INSERT INTO trash_batches (deleted_at) VALUES (?)")
+        .bind(chrono::NaiveDateTime::from_timestamp(change.time, 0))
+        .execute(traced(&mut *tx))
+        .await?;
+    Ok(sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(traced(&mut *tx)).await?.get(0))
+}
+
+/// Move the database with all of its locations and items to the trash.
+pub(super) async fn trash_database(tx: &mut Transaction<'_, MySql>, database_id: u64, change: &Change) -> StoreResult<()> {
+    let batch = trash_batch(tx, change).await?;
+    let result = sqlx::query("UPDATE item_databases SET deleted_at = ?, trash_batch = ? WHERE id = ? AND deleted_at IS NULL")
+        .bind(chrono::NaiveDateTime::from_timestamp(change.time, 0))
+        .bind(batch)
+        .bind(database_id)
+        .execute(traced(&mut *tx))
+        .await?;
+
+    // If nothing was deleted, the database didn't even exist!
+    if result.rows_affected() == 0 {
+        return Err(StoreError::NotFound);
+    }
+
+    // Everything inside of the database goes to the trash as well
+    let location_ids: Vec<u64> = sqlx::query("SELECT id FROM locations WHERE database_id = ? AND deleted_at IS NULL FOR UPDATE")
+        .bind(database_id)
+        .fetch_all(traced(&mut *tx))
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    for location_id in location_ids {
+        trash_location(tx, location_id, batch, change).await?;
+    }
+
+    // The tags of the database can only be used by its items
+    sqlx::query("UPDATE tags SET deleted_at = ?, trash_batch = ? WHERE database_id = ? AND deleted_at IS NULL")
+        .bind(chrono::NaiveDateTime::from_timestamp(change.time, 0))
+        .bind(batch)
+        .bind(database_id)
+        .execute(traced(&mut *tx))
+        .await?;
+
+    Ok(())
+}
+
+/// Move the location and its items to the trash, as part of the batch.
+pub(super) async fn trash_location(tx: &mut Transaction<'_, MySql>, location_id: u64, batch: u64, change: &Change) -> StoreResult<()> {
+    let deleted_at = chrono::NaiveDateTime::from_timestamp(change.time, 0);
+
+    // The last state of the items is kept, so they can be restored
+    let item_ids: Vec<u64> = sqlx::query("SELECT id FROM items WHERE location_id = ? AND deleted_at IS NULL FOR UPDATE")
+        .bind(location_id)
+        .fetch_all(traced(&mut *tx))
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    for item_id in item_ids {
+        insert_revision(tx, item_id, change, true).await?;
+    }
+
+    // To be able to tell offline clients that something got
+    // deleted, we need to keep track of deleted item ids.
+    sqlx::query("INSERT INTO item_deleted SELECT id, CURRENT_TIMESTAMP() FROM items WHERE location_id = ? AND deleted_at IS NULL")
+        .bind(location_id)
+        .execute(traced(&mut *tx))
+        .await?;
+    sqlx::query("UPDATE items SET deleted_at = ?, trash_batch = ? WHERE location_id = ? AND deleted_at IS NULL")
+        .bind(deleted_at)
+        .bind(batch)
+        .bind(location_id)
+        .execute(traced(&mut *tx))
+        .await?;
+    sqlx::query("UPDATE locations SET deleted_at = ?, trash_batch = ? WHERE id = ?")
+        .bind(deleted_at)
+        .bind(batch)
+        .bind(location_id)
+        .execute(traced(&mut *tx))
+        .await?;
+
+    Ok(())
+}
+
+pub(super) async fn restore_item(tx: &mut Transaction<'_, MySql>, item_id: u64, change: &Change) -> StoreResult<()> {
+    let row = sqlx::query("SELECT location_id, parent_item_id FROM items WHERE id = ? AND deleted_at IS NOT NULL FOR UPDATE")
+        .bind(item_id)
+        .fetch_one(traced(&mut *tx))
+        .await?;
+    let location_id: u64 = row.get(0);
+    let parent_item: Option<u64> = row.get(1);
+
+    let location = sqlx::query("SELECT id FROM locations WHERE id = ? AND deleted_at IS NULL")
+        .bind(location_id)
+        .fetch_optional(traced(&mut *tx))
+        .await?;
+    if location.is_none() {
+        return Err(StoreError::Invalid("trash.location_deleted", "the location of the item has to be restored first!"));
+    }
+
+    // A container that is still deleted doesn't hold the item anymore
+    if let Some(parent_id) = parent_item {
+        let parent = sqlx::query("SELECT id FROM items WHERE id = ? AND deleted_at IS NULL")
+            .bind(parent_id)
+            .fetch_optional(traced(&mut *tx))
+            .await?;
+        if parent.is_none() {
+            sqlx::query("UPDATE items SET parent_item_id = NULL WHERE id = ?")
+                .bind(item_id)
+                .execute(traced(&mut *tx))
+                .await?;
+        }
+    }
+
+    // Another item with the same name in the location causes a conflict
+    sqlx::query("UPDATE items SET deleted_at = NULL, trash_batch = NULL WHERE id = ?")
+        .bind(item_id)
+        .execute(traced(&mut *tx))
+        .await?;
+    sqlx::query("DELETE FROM item_deleted WHERE id = ?").bind(item_id).execute(traced(&mut *tx)).await?;
+    insert_revision(tx, item_id, change, false).await
+}
+
+async fn restore_location(tx: &mut Transaction<'_, MySql>, location_id: u64, change: &Change) -> StoreResult<()> {
+    let location = sqlx::query_as::<_, Location>("SELECT * FROM locations WHERE id = ? AND deleted_at IS NOT NULL FOR UPDATE")
+        .bind(location_id)
+        .fetch_one(traced(&mut *tx))
+        .await?;
+    let batch: Option<u64> = sqlx::query("SELECT trash_batch FROM locations WHERE id = ?")
+        .bind(location_id)
+        .fetch_one(traced(&mut *tx))
+        .await?
+        .get(0);
+
+    let database = sqlx::query("SELECT id FROM item_databases WHERE id = ? AND deleted_at IS NULL")
+        .bind(location.database)
+        .fetch_optional(traced(&mut *tx))
+        .await?;
+    if database.is_none() {
+        return Err(StoreError::Invalid("trash.database_deleted", "the database of the location has to be restored first!"));
+    }
+
+    if let Some(parent_id) = location.parent {
+        let parent = sqlx::query("SELECT id FROM locations WHERE id = ? AND deleted_at IS NULL")
+            .bind(parent_id)
+            .fetch_optional(traced(&mut *tx))
+            .await?;
+        if parent.is_none() {
+            return Err(StoreError::Invalid("trash.parent_deleted", "the parent location has to be restored first!"));
+        }
+    }
+
+    // The child locations that were deleted together with the location come back as well
+    let deleted_together = sqlx::query_as::<_, Location>("SELECT * FROM locations WHERE database_id = ? AND trash_batch = ? FOR UPDATE")
+        .bind(location.database)
+        .bind(batch)
+        .fetch_all(traced(&mut *tx))
+        .await?;
+
+    let mut location_ids = location_descendants(location_id, &deleted_together);
+    location_ids.push(location_id);
+    for location_id in location_ids {
+        restore_location_row(tx, location_id, batch, change).await?;
+    }
+
+    Ok(())
+}
+
+async fn restore_database(tx: &mut Transaction<'_, MySql>, database_id: u64, change: &Change) -> StoreResult<()> {
+    let batch: Option<u64> = sqlx::query("SELECT trash_batch FROM item_databases WHERE id = ? AND deleted_at IS NOT NULL FOR UPDATE")
+        .bind(database_id)
+        .fetch_one(traced(&mut *tx))
+        .await?
+        .get(0);
+
+    // Another database (or tag) with the same name causes a conflict
+    sqlx::query("UPDATE item_databases SET deleted_at = NULL, trash_batch = NULL WHERE id = ?")
+        .bind(database_id)
+        .execute(traced(&mut *tx))
+        .await?;
+    sqlx::query("UPDATE tags SET deleted_at = NULL, trash_batch = NULL WHERE database_id = ? AND trash_batch = ?")
+        .bind(database_id)
+        .bind(batch)
+        .execute(traced(&mut *tx))
+        .await?;
+
+    let location_ids: Vec<u64> = sqlx::query("SELECT id FROM locations WHERE database_id = ? AND trash_batch = ? FOR UPDATE")
+        .bind(database_id)
+        .bind(batch)
+        .fetch_all(traced(&mut *tx))
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    for location_id in location_ids {
+        restore_location_row(tx, location_id, batch, change).await?;
+    }
+
+    Ok(())
+}
+
+/// Restore the location and the items of the batch inside of it. Without a batch,
+/// the location was deleted on its own, so the items are restored on their own too.
+async fn restore_location_row(tx: &mut Transaction<'_, MySql>, location_id: u64, batch: Option<u64>, change: &Change) -> StoreResult<()> {
+    sqlx::query("UPDATE locations SET deleted_at = NULL, trash_batch = NULL WHERE id = ?")
+        .bind(location_id)
+        .execute(traced(&mut *tx))
+        .await?;
+
+    let item_ids: Vec<u64> = sqlx::query("SELECT id FROM items WHERE location_id = ? AND trash_batch = ? FOR UPDATE")
+        .bind(location_id)
+        .bind(batch)
+        .fetch_all(traced(&mut *tx))
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    sqlx::query("DELETE FROM item_deleted WHERE id IN (SELECT id FROM items WHERE location_id = ? AND trash_batch = ?)")
+        .bind(location_id)
+        .bind(batch)
+        .execute(traced(&mut *tx))
+        .await?;
+    sqlx::query("UPDATE items SET deleted_at = NULL, trash_batch = NULL WHERE location_id = ? AND trash_batch = ?")
+        .bind(location_id)
+        .bind(batch)
+        .execute(traced(&mut *tx))
+        .await?;
+    for item_id in item_ids {
+        insert_revision(tx, item_id, change, false).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::HeaderMap;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::{TraceContextExt, TracerProvider};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use tracing::{field, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::error::RequestId;
+
+const SERVICE_NAME: &str = "storagereloaded";
+
+/// Set up the distributed tracing. Every request and every sql query gets a span.
+/// The "otlp" exporter sends them to a collector, "stdout" prints them as JSON (e.g. for tests).
+/// Without an exporter, the spans aren't recorded at all.
+///
+/// The provider has to be shut down at the end, so the last spans get exported.
+pub(crate) fn init(exporter: Option<&str>, otlp_endpoint: &str) -> Result<Option<SdkTracerProvider>, String> {
+    let resource = Resource::builder().with_service_name(SERVICE_NAME).build();
+    let provider = match exporter {
+        None => return Ok(None),
+        Some(exporter) if exporter.eq_ignore_ascii_case("otlp") => {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(otlp_endpoint)
+                .build()
+                .map_err(|err| format!("Cannot create the OTLP exporter! (error: {err})"))?;
+            SdkTracerProvider::builder().with_batch_exporter(exporter).with_resource(resource).build()
+        }
+        Some(exporter) if exporter.eq_ignore_ascii_case("stdout") => SdkTracerProvider::builder().with_simple_exporter(StdoutExporter).with_resource(resource).build(),
+        Some(exporter) => return Err(format!("Unknown tracing exporter \"{exporter}\"! (use \"otlp\" or \"stdout\")")),
+    };
+
+    // The trace of a client or a proxy is continued ("traceparent" header)
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+    // The spans are all created in the same places, so their code location doesn't tell anything
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(SERVICE_NAME))
+        .with_location(false)
+        .with_threads(false);
+    let subscriber = tracing_subscriber::registry().with(layer);
+    tracing::subscriber::set_global_default(subscriber).map_err(|err| format!("Cannot set up the tracing! (error: {err})"))?;
+
+    Ok(Some(provider))
+}
+
+/// The span of a request. The route isn't known before the request
+/// is routed, it's added with the status by [record_response].
+pub(crate) fn request_span(req: &ServiceRequest, request_id: &RequestId) -> Span {
+    let span = tracing::info_span!(
+        "HTTP request",
+        otel.name = %req.method(),
+        otel.kind = "server",
+        otel.status_code = field::Empty,
+        http.request.method = %req.method(),
+        http.route = field::Empty,
+        http.response.status_code = field::Empty,
+        url.path = req.path(),
+        request_id = request_id.0.as_str(),
+    );
+
+    let parent = opentelemetry::global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(req.headers())));
+    let _ = span.set_parent(parent);
+    span
+}
+
+pub(crate) fn record_response<B>(span: &Span, res: &ServiceResponse<B>) {
+    let req = res.request();
+    if let Some(route) = req.match_pattern() {
+        // The span was already started, so it can't be renamed through its fields anymore
+        span.context().span().update_name(format!("{} {route}", req.method()));
+        span.record("http.route", route);
+    }
+    span.record("http.response.status_code", res.status().as_u16());
+    if res.status().is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// The span of a sql query. The statement is only recorded without values (see [sanitize_statement]).
+pub(crate) fn query_span(sql: &str) -> Span {
+    // Without tracing, there's no need to look at the statement at all
+    if !tracing::dispatcher::has_been_set() {
+        return Span::none();
+    }
+
+    let statement = sanitize_statement(sql);
+
+    // Name the span like "SELECT items", so the queries can be told apart at a glance
+    let words: Vec<&str> = statement.split(' ').collect();
+    let operation = words.first().map(|word| word.to_uppercase()).unwrap_or_default();
+    let table = words
+        .windows(2)
+        .find(|pair| matches!(pair[0].to_uppercase().as_str(), "FROM" | "INTO" | "UPDATE"))
+        .map_or("", |pair| pair[1]);
+
+    tracing::info_span!(
+        "sql query",
+        otel.name = format!("{operation} {table}").trim_end(),
+        otel.kind = "client",
+        db.system.name = "mysql",
+        db.operation.name = operation.as_str(),
+        db.query.text = statement.as_str(),
+    )
+}
+
+/// Collapse the whitespace of a statement and replace all literals with "?".
+/// The values are bound as parameters anyway, but a statement
+/// with a literal in it must not leak any data into the traces.
+fn sanitize_statement(sql: &str) -> String {
+    let mut statement = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\'' | '"' => {
+                // Quotes are escaped by doubling them
+                while let Some(next) = chars.next() {
+                    if next == char && chars.next_if_eq(&char).is_none() {
+                        break;
+                    }
+                }
+                statement.push('?');
+            }
+            '0'..='9' if !statement.ends_with(|previous: char| previous.is_alphanumeric() || previous == '_') => {
+                while chars.next_if(|next| next.is_ascii_digit() || *next == '.').is_some() {}
+                statement.push('?');
+            }
+            _ if char.is_whitespace() => {
+                if !statement.is_empty() && !statement.ends_with(' ') {
+                    statement.push(' ');
+                }
+            }
+            _ => statement.push(char),
+        }
+    }
+
+    statement.trim_end().to_owned()
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Prints every finished span as a line of JSON.
+#[derive(Debug)]
+struct StdoutExporter;
+
+impl SpanExporter for StdoutExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut stdout = std::io::stdout().lock();
+        for span in batch {
+            let micros = |time: SystemTime| time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_micros());
+            let attributes: BTreeMap<String, String> = span.attributes.iter().map(|attribute| (attribute.key.to_string(), attribute.value.to_string())).collect();
+
+            let line = serde_json::json!({
+                "trace_id": span.span_context.trace_id().to_string(),
+                "span_id": span.span_context.span_id().to_string(),
+                "parent_span_id": span.parent_span_id.to_string(),
+                "name": span.name,
+                "start_us": micros(span.start_time),
+                "duration_us": micros(span.end_time).saturating_sub(micros(span.start_time)),
+                "attributes": attributes,
+            });
+            let _ = writeln!(stdout, "{line}");
+        }
+        Ok(())
+    }
+}
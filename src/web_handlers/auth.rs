@@ -1,33 +1,33 @@
 use std::{collections::HashMap, pin::Pin};
 
-use actix_web::{error, web, FromRequest, HttpRequest, HttpResponse};
-use sqlx::{MySqlPool, Row};
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse};
 
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 
 use crate::collection;
-use crate::models::{AuthedUser, UserCredentials};
-
+use crate::error::{ApiError, ApiResult};
+use crate::models::{AdminUser, AuthedUser, UserCredentials};
+use crate::storage::{SessionStore, StoreError};
+
+/// The header with the session id of the user, for all protected services.
+pub(crate) const SESSION_HEADER: &str = "X-StoRe-Session";
+
+#[utoipa::path(
+    post,
+    path = "/auth",
+    tag = "auth",
+    security(()),
+    responses((status = 201, description = "The session id for the X-StoRe-Session header", body = HashMap<String, String>, example = json!({"session_id": "aB3dE5gH"})))
+)]
 #[actix_web::route("/auth", method = "GET", method = "POST")]
-async fn get_post_auth(pool: web::Data<MySqlPool>, req: web::Json<UserCredentials>) -> actix_web::Result<HttpResponse> {
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
-
-    // Query for the user_id with the credentials from the request
-    let query: Result<sqlx::mysql::MySqlRow, sqlx::Error> = sqlx::query("SELECT id FROM users WHERE username = ? AND password = ?")
-        .bind(&req.username)
-        .bind(&req.password)
-        .fetch_one(&mut connection)
-        .await;
-
+async fn get_post_auth(store: web::Data<dyn SessionStore>, req: web::Json<UserCredentials>) -> ApiResult<HttpResponse> {
     // Check if the user was found and extract the user id,
-    // if there was no row found, return an forbidden error (code 403).
-    let user_id: u64 = query
-        .map_err(|err| match err {
-            sqlx::Error::RowNotFound => error::ErrorForbidden("invalid username or password!"),
-            _ => error::ErrorInternalServerError(err),
-        })?
-        .get(0);
+    // if there was no user found, return an forbidden error (code 403).
+    let user_id: u64 = store.check_credentials(&req).await.map_err(|err| match err {
+        StoreError::NotFound => ApiError::Forbidden("auth.invalid_credentials", "invalid username or password!"),
+        _ => ApiError::Internal(Box::new(err)),
+    })?;
 
     // Generate a unique session_id and save it in the database.
     // We need a infinite loop here because we want to make sure,
@@ -36,22 +36,13 @@ async fn get_post_auth(pool: web::Data<MySqlPool>, req: web::Json<UserCredential
         // Generate 8 random alphanumeric (a-z,A-Z,0-9) characters.
         let session_id: String = rand::thread_rng().sample_iter(&Alphanumeric).take(8).map(char::from).collect();
 
-        // Try to insert that into the sessions sql table...
-        let insertion_query: Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> = sqlx::query("INSERT INTO sessions VALUES (?, ?, CURRENT_TIMESTAMP(), CURRENT_TIMESTAMP())")
-            .bind(&session_id)
-            .bind(&user_id)
-            .execute(&mut connection)
-            .await;
-
-        // If the query failed, try it again (but only if the error occurred because of a duplicate).
-        if let Err(error) = insertion_query {
-            match error {
-                sqlx::Error::Database(db_error) if db_error.message().starts_with("Duplicate entry") => continue,
-                _ => return Err(error::ErrorInternalServerError(error)),
-            };
+        // Try to store the session. If that failed, try it again
+        // (but only if the error occurred because of a duplicate).
+        match store.create_session(&session_id, user_id).await {
+            Ok(()) => break session_id,
+            Err(StoreError::Conflict) => continue,
+            Err(err) => return Err(ApiError::Internal(Box::new(err))),
         }
-
-        break session_id;
     };
 
     let map: HashMap<&str, String> = collection! {
@@ -60,29 +51,28 @@ async fn get_post_auth(pool: web::Data<MySqlPool>, req: web::Json<UserCredential
     Ok(HttpResponse::Created().json(map))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/auth",
+    tag = "auth",
+    responses((status = 200, description = "The session was ended"))
+)]
 #[actix_web::delete("/auth")]
-async fn delete_auth(pool: web::Data<MySqlPool>, session: AuthedUser) -> actix_web::Result<HttpResponse> {
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
-
-    let query: sqlx::mysql::MySqlQueryResult = sqlx::query("DELETE FROM sessions WHERE session_id = ?")
-        .bind(&session.session_id)
-        .execute(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?;
-
+async fn delete_auth(store: web::Data<dyn SessionStore>, session: AuthedUser) -> ApiResult<HttpResponse> {
     // If nothing was deleted, the session didn't even exist!
     // Technically this can't happen, because we made sure
     // the user's session is valid before we even entered
     // this function. (See #AuthedUser for more)
-    if query.rows_affected() == 0 {
-        return Err(error::ErrorForbidden("invalid session id!"));
-    }
+    store.delete_session(&session.session_id).await.map_err(|err| match err {
+        StoreError::NotFound => ApiError::Forbidden("auth.invalid_session", "invalid session id!"),
+        _ => ApiError::Internal(Box::new(err)),
+    })?;
 
     Ok(HttpResponse::Ok().finish())
 }
 
 impl FromRequest for AuthedUser {
-    type Error = actix_web::Error;
+    type Error = ApiError;
     type Future = Pin<Box<dyn futures::Future<Output = Result<Self, Self::Error>>>>;
 
     // Because async trait functions are currently
@@ -90,7 +80,7 @@ impl FromRequest for AuthedUser {
     // And since Futures cannot be moved in memory
     // we need to pin them.
     fn from_request(req_ref: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
-        // We need to clone the request here because the sql operation in this function
+        // We need to clone the request here because the storage operation in this function
         // is async and the compiler can't guarantee us a lifetime that lasts long enough.
         // This only clones the pointer to the request and NOT the request itself.
         let req = req_ref.clone();
@@ -98,26 +88,50 @@ impl FromRequest for AuthedUser {
         Box::pin(async move {
             let session_id = req
                 .headers()
-                .get("X-StoRe-Session")
-                .ok_or_else(|| error::ErrorBadRequest("session id is missing!"))?
+                .get(SESSION_HEADER)
+                .ok_or_else(|| ApiError::BadRequest("auth.missing_session", "session id is missing!".to_owned()))?
                 .to_str()
-                .map_err(|_| error::ErrorBadRequest("invalid characters in session id!"))?;
+                .map_err(|_| ApiError::BadRequest("auth.malformed_session", "invalid characters in session id!".to_owned()))?;
+
+            let store = req
+                .app_data::<web::Data<dyn SessionStore>>()
+                .ok_or_else(|| ApiError::Internal("could not get session store".into()))?;
+
+            let user = store.get_session(session_id).await.map_err(|err| match err {
+                StoreError::NotFound => ApiError::Forbidden("auth.invalid_session", "invalid session id!"),
+                _ => ApiError::Internal(Box::new(err)),
+            })?;
 
-            let pool = req
-                .app_data::<web::Data<MySqlPool>>()
-                .ok_or_else(|| error::ErrorInternalServerError("could not clone sqlx pool"))?;
+            // For the error logs, so they can be traced back to the user
+            req.extensions_mut().insert(user.clone());
+            Ok(user)
+        })
+    }
+}
 
-            let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
+/// The ids of the users that are allowed to use the admin
+/// services (config option "admin_users"). By default, nobody is.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Admins(pub(crate) Vec<u64>);
+
+impl FromRequest for AdminUser {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn futures::Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req_ref: &HttpRequest, payload: &mut actix_web::dev::Payload) -> Self::Future {
+        // First make sure that the user is logged in at all.
+        let user = AuthedUser::from_request(req_ref, payload);
+        let req = req_ref.clone();
+
+        Box::pin(async move {
+            let user = user.await?;
 
-            let query: Result<AuthedUser, sqlx::Error> = sqlx::query_as::<_, AuthedUser>("SELECT session_id, user_id FROM sessions WHERE session_id = ?")
-                .bind(&session_id)
-                .fetch_one(&mut connection)
-                .await;
+            let is_admin = req.app_data::<web::Data<Admins>>().is_some_and(|admins| admins.0.contains(&user.user_id));
+            if !is_admin {
+                return Err(ApiError::Forbidden("auth.not_admin", "only admins are allowed to do this!"));
+            }
 
-            query.map_err(|err| match err {
-                sqlx::Error::RowNotFound => error::ErrorForbidden("invalid session id!"),
-                _ => error::ErrorInternalServerError(err),
-            })
+            Ok(AdminUser(user))
         })
     }
 }
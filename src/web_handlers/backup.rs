@@ -0,0 +1,314 @@
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use log::info;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::collection;
+use crate::error::{ApiError, ApiResult};
+use crate::models::{AdminUser, AuthedUser, Backup, Database, DatabaseBackup};
+use crate::properties::apply_schema;
+use crate::storage::{BackupStore, DatabaseStore, ItemStore, LocationStore, TagStore};
+use crate::web_handlers::auth::Admins;
+use crate::web_handlers::file::remove_missing_files;
+use crate::web_handlers::{get_param, store_error, unix_now, user_change};
+
+/// The version of the backup format. Increase it if the format
+/// changes in a way that older servers can't restore it anymore.
+const BACKUP_VERSION: u32 = 1;
+
+/// Backups bigger than this (256 MiB) are rejected.
+const MAX_BACKUP_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct RestoreOptions {
+    /// Create new databases instead of replacing the existing
+    /// ones with the same name. Only admins can replace databases.
+    #[serde(default)]
+    as_new: bool,
+
+    /// Restore the database under a different name.
+    /// Only possible if the backup contains a single database.
+    name: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/database/{database_id}/export",
+    tag = "backups",
+    params(("database_id" = u64, Path, description = "The id of the database")),
+    responses((status = 200, description = "The backup of the database", body = Backup))
+)]
+#[actix_web::get("/database/{database_id}/export")]
+async fn export_database(
+    items: web::Data<dyn ItemStore>,
+    tags: web::Data<dyn TagStore>,
+    locations: web::Data<dyn LocationStore>,
+    databases: web::Data<dyn DatabaseStore>,
+    _user: AuthedUser,
+    req: HttpRequest,
+) -> ApiResult<HttpResponse> {
+    let database_id: u64 = get_param(&req, "database")?;
+    let database = databases.get_database(database_id).await.map_err(store_error("database"))?;
+
+    let backup = create_backup(vec![database], &**items, &**tags, &**locations).await?;
+    Ok(backup_response(&backup, &format!("database_{database_id}.json")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/export",
+    tag = "backups",
+    responses((status = 200, description = "The backup of all databases (only for admins)", body = Backup))
+)]
+#[actix_web::get("/export")]
+async fn export_all(
+    items: web::Data<dyn ItemStore>,
+    tags: web::Data<dyn TagStore>,
+    locations: web::Data<dyn LocationStore>,
+    databases: web::Data<dyn DatabaseStore>,
+    admin: AdminUser,
+) -> ApiResult<HttpResponse> {
+    let databases = databases.get_databases().await.map_err(store_error("database"))?;
+    info!("User {} created a full backup", admin.0.user_id);
+
+    let backup = create_backup(databases, &**items, &**tags, &**locations).await?;
+    Ok(backup_response(&backup, "backup.json"))
+}
+
+// Needs to be registered before the "/database/{database_id}" service,
+// otherwise "restore" would be interpreted as a database id.
+#[utoipa::path(
+    post,
+    path = "/database/restore",
+    tag = "backups",
+    params(RestoreOptions),
+    request_body(content = Backup),
+    responses(
+        (status = 201, description = "The database was restored", body = HashMap<String, u64>, example = json!({"database_id": 1})),
+        (status = 403, description = "Only admins can replace an existing database (without \"as_new\")")
+    )
+)]
+#[actix_web::post("/database/restore")]
+async fn restore_database(
+    store: web::Data<dyn BackupStore>,
+    admins: web::Data<Admins>,
+    user: AuthedUser,
+    req: HttpRequest,
+    options: web::Query<RestoreOptions>,
+    payload: web::Payload,
+) -> ApiResult<HttpResponse> {
+    // Replacing moves the existing database (with all of its items) to the trash
+    if !options.as_new && !admins.0.contains(&user.user_id) {
+        return Err(ApiError::Forbidden(
+            "auth.not_admin",
+            "only admins are allowed to replace a database, restore it as new instead!",
+        ));
+    }
+
+    let mut backup = read_backup(&req, payload).await?;
+    if backup.databases.len() != 1 {
+        return Err(ApiError::BadRequest(
+            "backup.not_single_database",
+            "the backup must contain exactly one database!".to_owned(),
+        ));
+    }
+
+    if let Some(name) = &options.name {
+        backup.databases[0].database.name = name.clone();
+    }
+
+    let database_ids = store
+        .restore_backup(&backup, options.as_new, &user_change(&user, "restored from backup"))
+        .await
+        .map_err(store_error("database"))?;
+
+    let map: HashMap<&str, u64> = collection! {
+        "database_id" => database_ids[0]
+    };
+    Ok(HttpResponse::Created().json(map))
+}
+
+#[utoipa::path(
+    post,
+    path = "/restore",
+    tag = "backups",
+    params(RestoreOptions),
+    request_body(content = Backup),
+    responses((status = 201, description = "The databases were restored (only for admins)", body = HashMap<String, Vec<u64>>, example = json!({"database_ids": [1, 2]})))
+)]
+#[actix_web::post("/restore")]
+async fn restore_all(store: web::Data<dyn BackupStore>, admin: AdminUser, req: HttpRequest, options: web::Query<RestoreOptions>, payload: web::Payload) -> ApiResult<HttpResponse> {
+    let mut backup = read_backup(&req, payload).await?;
+
+    if let Some(name) = &options.name {
+        match backup.databases.as_mut_slice() {
+            [database_backup] => database_backup.database.name = name.clone(),
+            _ => return Err(ApiError::BadRequest("backup.not_single_database", "only a single database can be renamed!".to_owned())),
+        }
+    }
+
+    let database_ids = store
+        .restore_backup(&backup, options.as_new, &user_change(&admin.0, "restored from backup"))
+        .await
+        .map_err(store_error("database"))?;
+    info!("User {} restored a full backup", admin.0.user_id);
+
+    let map: HashMap<&str, Vec<u64>> = collection! {
+        "database_ids" => database_ids
+    };
+    Ok(HttpResponse::Created().json(map))
+}
+
+/// Collect everything inside of the databases. The tags of the databases
+/// are included, but of the global tags only the ones in use (and their parents).
+async fn create_backup(databases: Vec<Database>, items: &dyn ItemStore, tags: &dyn TagStore, locations: &dyn LocationStore) -> ApiResult<Backup> {
+    let mut all_items = items.get_items().await.map_err(store_error("item"))?;
+    let mut all_locations = locations.get_locations().await.map_err(store_error("location"))?;
+    all_items.sort_by_key(|item| item.id);
+    all_locations.sort_by_key(|location| location.id);
+
+    let database_backups: Vec<DatabaseBackup> = databases
+        .into_iter()
+        .map(|database| {
+            let locations: Vec<_> = all_locations.iter().filter(|location| location.database == database.id).cloned().collect();
+            let items = all_items
+                .iter()
+                .filter(|item| locations.iter().any(|location| location.id == item.location))
+                .cloned()
+                .collect();
+
+            DatabaseBackup { database, locations, items }
+        })
+        .collect();
+
+    let all_tags = tags.get_tags().await.map_err(store_error("tag"))?;
+    let database_ids: HashSet<u64> = database_backups.iter().map(|backup| backup.database.id).collect();
+    let mut tag_ids: HashSet<u64> = database_backups
+        .iter()
+        .flat_map(|backup| backup.items.iter())
+        .flat_map(|item| item.tags.iter().copied())
+        .chain(
+            all_tags
+                .iter()
+                .filter(|tag| tag.database.is_some_and(|database_id| database_ids.contains(&database_id)))
+                .map(|tag| tag.id),
+        )
+        .collect();
+
+    // The hierarchy is restored as well, so the parents are needed
+    let parents: HashMap<u64, Option<u64>> = all_tags.iter().map(|tag| (tag.id, tag.parent)).collect();
+    for tag_id in tag_ids.clone() {
+        let mut parent_id = parents.get(&tag_id).copied().flatten();
+        while let Some(id) = parent_id.filter(|id| tag_ids.insert(*id)) {
+            parent_id = parents.get(&id).copied().flatten();
+        }
+    }
+
+    let tags = all_tags.into_iter().filter(|tag| tag_ids.contains(&tag.id)).collect();
+
+    Ok(Backup {
+        version: BACKUP_VERSION,
+        created: unix_now(),
+        databases: database_backups,
+        tags,
+    })
+}
+
+fn backup_response(backup: &Backup, file_name: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{file_name}\"")))
+        .json(backup)
+}
+
+/// Read and parse the request body. We can't use web::Json here,
+/// because its size limit is way too small for backups.
+async fn read_backup(req: &HttpRequest, mut payload: web::Payload) -> ApiResult<Backup> {
+    let mut data = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|err| ApiError::BadRequest("upload.invalid", err.to_string()))?;
+        if data.len() + chunk.len() > MAX_BACKUP_SIZE {
+            return Err(ApiError::BadRequest(
+                "upload.too_large",
+                format!("the backup can't be bigger than {MAX_BACKUP_SIZE} bytes!"),
+            ));
+        }
+
+        data.extend_from_slice(&chunk);
+    }
+
+    let mut backup: Backup = serde_json::from_slice(&data).map_err(|err| ApiError::BadRequest("backup.invalid", err.to_string()))?;
+    if backup.version > BACKUP_VERSION {
+        return Err(ApiError::BadRequest(
+            "backup.unsupported_version",
+            format!("backups newer than version {BACKUP_VERSION} are not supported!"),
+        ));
+    }
+
+    // The schema of the database might have been changed by hand
+    for database_backup in &mut backup.databases {
+        for item in &mut database_backup.items {
+            apply_schema(&database_backup.database.properties, &mut item.properties_custom).map_err(|err| match err {
+                ApiError::BadRequest(code, message) => ApiError::BadRequest(code, format!("{} (item {})", message, item.name)),
+                err => err,
+            })?;
+            // Backups don't contain the uploaded files, the urls would
+            // point to files that belong to the old item (if they exist)
+            remove_missing_files(req, item, &[])?;
+        }
+    }
+
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web};
+
+    use crate::web_handlers::auth::Admins;
+    use crate::web_handlers::testing::{authed, create_item, create_location, init_stores, send};
+
+    fn services(config: &mut web::ServiceConfig) {
+        // The urls of the uploaded files are checked against the file routes
+        config
+            .service(super::export_database)
+            .service(super::restore_database)
+            .service(crate::web_handlers::file::get_item_image)
+            .service(crate::web_handlers::file::get_item_attachment);
+    }
+
+    #[actix_web::test]
+    async fn only_admins_can_replace_a_database() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![]).await;
+        create_item(&stores, location_id, 1, vec![]).await;
+        let database_id = stores.locations.get_location(location_id).await.unwrap().database;
+
+        let res = send(&stores, services, authed(test::TestRequest::get().uri(&format!("/v1/database/{database_id}/export")))).await;
+        assert_eq!(res.status, 200);
+        let backup = res.body;
+        let restore = |query: &str| authed(test::TestRequest::post().uri(&format!("/v1/database/restore?{query}"))).set_json(&backup);
+
+        let res = send(&stores, services, restore("")).await;
+        assert_eq!(res.status, 403);
+        assert_eq!(res.body["code"], "auth.not_admin");
+        assert_eq!(stores.databases.get_databases().await.unwrap().len(), 1);
+
+        // A copy leaves the existing database alone, so everyone can restore one
+        let res = send(&stores, services, restore("as_new=true&name=Copy")).await;
+        assert_eq!(res.status, 201);
+        assert_eq!(stores.databases.get_databases().await.unwrap().len(), 2);
+
+        fn as_admin(config: &mut web::ServiceConfig) {
+            config.app_data(web::Data::new(Admins(vec![1])));
+            services(config);
+        }
+        let res = send(&stores, as_admin, restore("")).await;
+        assert_eq!(res.status, 201);
+        assert_ne!(res.body["database_id"], database_id);
+        assert!(stores.databases.get_database(database_id).await.is_err());
+    }
+}
@@ -0,0 +1,39 @@
+use actix_web::{web, HttpResponse};
+use futures::stream;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::Events;
+use crate::models::AuthedUser;
+
+/// Stream all events as server-sent events until the client disconnects.
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    responses((status = 200, description = "The events as server-sent events, e.g. \"low_stock\"", content_type = "text/event-stream", body = String))
+)]
+#[actix_web::get("/events")]
+async fn get_events(events: web::Data<Events>, _user: AuthedUser) -> HttpResponse {
+    let receiver = events.subscribe();
+
+    let stream = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let data = serde_json::to_string(&event).unwrap_or_default();
+                    let message = web::Bytes::from(format!("event: {}\ndata: {data}\n\n", event.name()));
+                    return Some((Ok::<_, actix_web::Error>(message), receiver));
+                }
+
+                // The client was too slow, but it should still get the newer events
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream)
+}
@@ -0,0 +1,447 @@
+use actix_multipart::Multipart;
+use actix_web::http::header::{self, CacheControl, CacheDirective, ContentDisposition, DispositionParam, DispositionType, EntityTag, IfNoneMatch};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::{ApiError, ApiResult};
+use crate::images::{self, THUMBNAIL_SIZES};
+use crate::models::{AuthedUser, Item, ItemFile};
+use crate::openapi::{Binary, FileUpload};
+use crate::storage::blob::BlobStore;
+use crate::storage::{FileStore, StoreError};
+use crate::web_handlers::{get_param, read_upload, store_error, Upload};
+
+/// Uploaded files bigger than this (32 MiB) are rejected.
+const MAX_FILE_SIZE: usize = 32 * 1024 * 1024;
+
+/// Linking a file to an item holds this lock (shared) from storing the content until
+/// the file is linked, and unused blobs are only removed while nobody holds it.
+/// Otherwise a blob could be deleted right before an upload with the same content uses it.
+static BLOB_LINKS: RwLock<()> = RwLock::const_new(());
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct AttachmentOptions {
+    /// The name of the attachment. Defaults to the name of the uploaded file.
+    name: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct ImageOptions {
+    /// Get a thumbnail instead of the full image. The thumbnail is at least
+    /// this big (if possible), so clients can simply ask for the size they need.
+    size: Option<u32>,
+
+    /// Set to "webp" to get the thumbnail as WebP image
+    format: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+struct UploadedFile {
+    #[serde(flatten)]
+    file: ItemFile,
+
+    /// Where the file can be downloaded
+    url: String,
+}
+
+#[utoipa::path(
+    put,
+    path = "/item/{item_id}/image",
+    tag = "files",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    request_body(content = FileUpload, content_type = "multipart/form-data"),
+    responses((status = 201, description = "The image was uploaded", body = UploadedFile))
+)]
+#[actix_web::put("/item/{item_id}/image")]
+async fn put_item_image(files: web::Data<dyn FileStore>, blobs: web::Data<dyn BlobStore>, _user: AuthedUser, req: HttpRequest, payload: Multipart) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
+    let mut upload = read_upload(payload, MAX_FILE_SIZE).await?;
+
+    // The location has to be removed before the image is hashed and stored
+    let original = std::mem::take(&mut upload.data);
+    upload.data = web::block(move || match images::strip_location(&original) {
+        Ok(stripped) => Ok(stripped.unwrap_or(original)),
+        Err(err) => Err(err),
+    })
+    .await
+    .map_err(|err| ApiError::Internal(Box::new(err)))?
+    .map_err(|err| ApiError::BadRequest("upload.invalid_image", format!("the image can't be read: {err}")))?;
+
+    let name = upload
+        .file_name
+        .as_deref()
+        .map(sanitize_file_name)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "image".to_owned());
+    let (file, data) = describe_upload(true, name, upload);
+    if !file.mime_type.starts_with("image/") {
+        return Err(ApiError::BadRequest("upload.not_an_image", "the file must be an image!".to_owned()));
+    }
+
+    let url = req.url_for("item_image", [item_id.to_string()]).map_err(|err| ApiError::Internal(Box::new(err)))?;
+    let response = save_file(&**files, &**blobs, item_id, file.clone(), data.clone(), url.path().to_owned()).await?;
+
+    spawn_thumbnail_rendering(blobs, file.hash, data);
+    Ok(response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/item/{item_id}/attachments",
+    tag = "files",
+    params(("item_id" = u64, Path, description = "The id of the item"), AttachmentOptions),
+    request_body(content = FileUpload, content_type = "multipart/form-data"),
+    responses((status = 201, description = "The attachment was uploaded", body = UploadedFile))
+)]
+#[actix_web::post("/item/{item_id}/attachments")]
+async fn post_item_attachment(
+    files: web::Data<dyn FileStore>,
+    blobs: web::Data<dyn BlobStore>,
+    _user: AuthedUser,
+    req: HttpRequest,
+    options: web::Query<AttachmentOptions>,
+    payload: Multipart,
+) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
+    let upload = read_upload(payload, MAX_FILE_SIZE).await?;
+
+    // The name is part of the download url, so only harmless characters are kept
+    let name = options.name.as_deref().or(upload.file_name.as_deref()).map(sanitize_file_name).unwrap_or_default();
+    if name.is_empty() {
+        return Err(ApiError::BadRequest("upload.missing_name", "the attachment needs a name!".to_owned()));
+    }
+
+    let (file, data) = describe_upload(false, name, upload);
+
+    let url = req
+        .url_for("item_attachment", [item_id.to_string(), file.name.clone()])
+        .map_err(|err| ApiError::Internal(Box::new(err)))?;
+    save_file(&**files, &**blobs, item_id, file, data, url.path().to_owned()).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/item/{item_id}/image",
+    tag = "files",
+    params(("item_id" = u64, Path, description = "The id of the item"), ImageOptions),
+    responses(
+        (status = 200, description = "The image or its thumbnail", content_type = "image/*", body = Binary),
+        (status = 304, description = "The content didn't change since the request that returned the ETag")
+    )
+)]
+#[actix_web::get("/item/{item_id}/image", name = "item_image")]
+async fn get_item_image(
+    files: web::Data<dyn FileStore>,
+    blobs: web::Data<dyn BlobStore>,
+    _user: AuthedUser,
+    req: HttpRequest,
+    options: web::Query<ImageOptions>,
+) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
+    let file = find_file(&**files, item_id, true, None).await?;
+
+    let webp = match options.format.as_deref() {
+        None => false,
+        Some("webp") => true,
+        Some(_) => return Err(ApiError::BadRequest("image.invalid_format", "the only supported format is \"webp\"!".to_owned())),
+    };
+
+    // Pick the smallest thumbnail that is big enough
+    let variant = options.size.map(|size| {
+        let size = THUMBNAIL_SIZES
+            .into_iter()
+            .find(|thumbnail_size| *thumbnail_size >= size)
+            .unwrap_or(THUMBNAIL_SIZES[THUMBNAIL_SIZES.len() - 1]);
+        images::thumbnail_variant(size, webp)
+    });
+
+    send_file(&**blobs, &req, file, variant).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/item/{item_id}/attachments/{name}",
+    tag = "files",
+    params(("item_id" = u64, Path, description = "The id of the item"), ("name" = String, Path, description = "The name of the attachment")),
+    responses(
+        (status = 200, description = "The attachment", content_type = "application/octet-stream", body = Binary),
+        (status = 304, description = "The content didn't change since the request that returned the ETag")
+    )
+)]
+#[actix_web::get("/item/{item_id}/attachments/{name}", name = "item_attachment")]
+async fn get_item_attachment(
+    files: web::Data<dyn FileStore>,
+    blobs: web::Data<dyn BlobStore>,
+    _user: AuthedUser,
+    req: HttpRequest,
+    name: web::Path<(String, String)>,
+) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
+    let file = find_file(&**files, item_id, false, Some(&name.1)).await?;
+
+    send_file(&**blobs, &req, file, None).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/item/{item_id}/image",
+    tag = "files",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    responses((status = 200, description = "The image was deleted"))
+)]
+#[actix_web::delete("/item/{item_id}/image")]
+async fn delete_item_image(files: web::Data<dyn FileStore>, blobs: web::Data<dyn BlobStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
+    let file = find_file(&**files, item_id, true, None).await?;
+
+    files.delete_item_file(item_id, true, &file.name).await.map_err(store_error("file"))?;
+    remove_unused_blobs(&**files, &**blobs, vec![file.hash]).await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/item/{item_id}/attachments/{name}",
+    tag = "files",
+    params(("item_id" = u64, Path, description = "The id of the item"), ("name" = String, Path, description = "The name of the attachment")),
+    responses((status = 200, description = "The attachment was deleted"))
+)]
+#[actix_web::delete("/item/{item_id}/attachments/{name}")]
+async fn delete_item_attachment(
+    files: web::Data<dyn FileStore>,
+    blobs: web::Data<dyn BlobStore>,
+    _user: AuthedUser,
+    req: HttpRequest,
+    name: web::Path<(String, String)>,
+) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
+    let file = find_file(&**files, item_id, false, Some(&name.1)).await?;
+
+    files.delete_item_file(item_id, false, &file.name).await.map_err(store_error("file"))?;
+    remove_unused_blobs(&**files, &**blobs, vec![file.hash]).await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Delete the blobs of the given hashes if no file uses them anymore.
+/// Failures are only logged, because the request itself already succeeded.
+pub(crate) async fn remove_unused_blobs(files: &dyn FileStore, blobs: &dyn BlobStore, hashes: Vec<String>) {
+    let _removing = BLOB_LINKS.write().await;
+    let used_hashes = match files.get_file_hashes().await {
+        Ok(used_hashes) => used_hashes,
+        Err(err) => {
+            warn!("Couldn't look up the used files: {err}");
+            return;
+        }
+    };
+
+    for hash in hashes.into_iter().filter(|hash| !used_hashes.contains(hash)) {
+        match blobs.delete_blob(&hash).await {
+            Ok(_) | Err(StoreError::NotFound) => (),
+            Err(err) => warn!("Couldn't delete the unused file {hash}: {err}"),
+        }
+    }
+}
+
+/// Delete every blob that isn't used anymore. This is needed after deleting
+/// locations or databases, because they can take a lot of items with them.
+pub(crate) async fn remove_all_unused_blobs(files: &dyn FileStore, blobs: &dyn BlobStore) {
+    match blobs.get_blob_hashes().await {
+        Ok(hashes) => remove_unused_blobs(files, blobs, hashes).await,
+        Err(err) => warn!("Couldn't list the stored files: {err}"),
+    }
+}
+
+/// Link the files of an item to another item as well, e.g. to a copy of the item.
+/// The content isn't copied, because the blobs are addressed by their hash.
+pub(crate) async fn copy_item_files(files: &dyn FileStore, req: &HttpRequest, from_item_id: u64, to_item_id: u64) -> ApiResult<()> {
+    let _linking = BLOB_LINKS.read().await;
+    for file in files.get_item_files(from_item_id).await.map_err(store_error("file"))? {
+        let url = if file.is_image {
+            req.url_for("item_image", [to_item_id.to_string()])
+        } else {
+            req.url_for("item_attachment", [to_item_id.to_string(), file.name.clone()])
+        }
+        .map_err(|err| ApiError::Internal(Box::new(err)))?;
+
+        files.put_item_file(to_item_id, &file, url.path()).await.map_err(store_error("item"))?;
+    }
+
+    Ok(())
+}
+
+/// Remove the image and attachment urls that point to uploaded files of the item
+/// which aren't among the given files (anymore). Images and attachments from
+/// somewhere else are kept as they are.
+pub(crate) fn remove_missing_files(req: &HttpRequest, item: &mut Item, files: &[ItemFile]) -> ApiResult<()> {
+    let image_url = req.url_for("item_image", [item.id.to_string()]).map_err(|err| ApiError::Internal(Box::new(err)))?;
+    if item.image.as_deref() == Some(image_url.path()) && !files.iter().any(|file| file.is_image) {
+        item.image = None;
+    }
+
+    let mut missing = vec![];
+    for (name, url) in &item.attachments {
+        let attachment_url = req
+            .url_for("item_attachment", [item.id.to_string(), name.clone()])
+            .map_err(|err| ApiError::Internal(Box::new(err)))?;
+        if url == attachment_url.path() && !files.iter().any(|file| !file.is_image && file.name == *name) {
+            missing.push(name.clone());
+        }
+    }
+    for name in missing {
+        item.attachments.remove(&name);
+    }
+
+    Ok(())
+}
+
+/// Render the thumbnails of an image in the background, so that the upload doesn't have to wait.
+fn spawn_thumbnail_rendering(blobs: web::Data<dyn BlobStore>, hash: String, data: Vec<u8>) {
+    actix_web::rt::spawn(async move {
+        let thumbnails = match web::block(move || images::render_thumbnails(&data)).await {
+            Ok(Ok(thumbnails)) => thumbnails,
+
+            // Not every image can be decoded (e.g. SVG). Those are always sent in full size.
+            Ok(Err(err)) => {
+                info!("No thumbnails for the image {hash}: {err}");
+                return;
+            }
+            Err(err) => {
+                warn!("Couldn't render the thumbnails for the image {hash}: {err}");
+                return;
+            }
+        };
+
+        for (variant, data) in thumbnails {
+            match blobs.put_variant(&hash, &variant, data).await {
+                // The image was already deleted again
+                Ok(_) | Err(StoreError::NotFound) => (),
+                Err(err) => warn!("Couldn't store the thumbnail {variant} of the image {hash}: {err}"),
+            }
+        }
+    });
+}
+
+/// Hash the uploaded data and find out its type. The type is sniffed from the
+/// content, the content type sent by the client is only used as a fallback.
+fn describe_upload(is_image: bool, name: String, upload: Upload) -> (ItemFile, Vec<u8>) {
+    let mime_type = infer::get(&upload.data)
+        .map(|kind| kind.mime_type().to_owned())
+        .or(upload.content_type)
+        .unwrap_or_else(|| "application/octet-stream".to_owned());
+
+    let file = ItemFile {
+        is_image,
+        name,
+        hash: hex::encode(Sha256::digest(&upload.data)),
+        mime_type,
+        size: upload.data.len() as u64,
+    };
+
+    (file, upload.data)
+}
+
+/// Store the content and link the file to the item.
+async fn save_file(files: &dyn FileStore, blobs: &dyn BlobStore, item_id: u64, file: ItemFile, data: Vec<u8>, url: String) -> ApiResult<HttpResponse> {
+    // Remember the file that gets replaced, so its content can be deleted afterwards
+    let mut unused_hashes: Vec<String> = files
+        .get_item_files(item_id)
+        .await
+        .map_err(store_error("file"))?
+        .into_iter()
+        .filter(|other| other.is_image == file.is_image && (file.is_image || other.name == file.name))
+        .map(|other| other.hash)
+        .collect();
+
+    // The lock has to be released before the unused blobs are removed
+    let result = {
+        let _linking = BLOB_LINKS.read().await;
+        blobs.put_blob(&file.hash, data).await.map_err(store_error("file"))?;
+        files.put_item_file(item_id, &file, &url).await.map_err(store_error("item"))
+    };
+
+    // If the item doesn't exist, the new content isn't used either
+    if result.is_err() {
+        unused_hashes = vec![file.hash.clone()];
+    }
+    remove_unused_blobs(files, blobs, unused_hashes).await;
+    result?;
+
+    Ok(HttpResponse::Created().json(UploadedFile { file, url }))
+}
+
+async fn find_file(files: &dyn FileStore, item_id: u64, is_image: bool, name: Option<&str>) -> ApiResult<ItemFile> {
+    files
+        .get_item_files(item_id)
+        .await
+        .map_err(store_error("file"))?
+        .into_iter()
+        .find(|file| file.is_image == is_image && name.is_none_or(|name| file.name == name))
+        .ok_or(ApiError::NotFound("file"))
+}
+
+/// Send the file or one of its variants. If the variant doesn't exist
+/// (e.g. the thumbnails aren't rendered yet), the file itself is sent.
+async fn send_file(blobs: &dyn BlobStore, req: &HttpRequest, mut file: ItemFile, variant: Option<String>) -> ApiResult<HttpResponse> {
+    let variant_data = match &variant {
+        Some(variant) => match blobs.get_variant(&file.hash, variant).await {
+            Ok(data) => Some(data),
+            Err(StoreError::NotFound) => None,
+            Err(err) => return Err(ApiError::Internal(Box::new(err))),
+        },
+        None => None,
+    };
+
+    // The content is addressed by its hash, so the hash is a perfect ETag
+    let etag = match (&variant, &variant_data) {
+        (Some(variant), Some(_)) => EntityTag::new_strong(format!("{}.{variant}", file.hash)),
+        _ => EntityTag::new_strong(file.hash.clone()),
+    };
+    if let Some(IfNoneMatch::Items(tags)) = req.get_header::<IfNoneMatch>() {
+        if tags.iter().any(|tag| tag.strong_eq(&etag)) {
+            return Ok(HttpResponse::NotModified().insert_header(header::ETag(etag)).finish());
+        }
+    }
+
+    let data = match variant_data {
+        Some(data) => {
+            file.mime_type = infer::get(&data).map(|kind| kind.mime_type().to_owned()).unwrap_or(file.mime_type);
+            data
+        }
+        None => blobs.get_blob(&file.hash).await.map_err(store_error("file"))?,
+    };
+
+    // Uploaded files are never rendered as a page of our
+    // own origin, otherwise they could run scripts in it.
+    let disposition = ContentDisposition {
+        disposition: if file.is_image { DispositionType::Inline } else { DispositionType::Attachment },
+        parameters: vec![DispositionParam::Filename(file.name)],
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(file.mime_type)
+        .insert_header(disposition)
+        .insert_header(header::ETag(etag))
+        .insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache]))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .insert_header((header::CONTENT_SECURITY_POLICY, "sandbox"))
+        .body(data))
+}
+
+/// Strip the path that some browsers send with the file name and
+/// replace everything that isn't safe to use inside of an url.
+fn sanitize_file_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+
+    name.trim_start_matches('.')
+        .chars()
+        .map(|char| if char.is_ascii_alphanumeric() || "._-".contains(char) { char } else { '_' })
+        .collect()
+}
@@ -0,0 +1,26 @@
+use actix_web::web;
+
+use crate::graphql::{self, GraphqlSchema};
+use crate::models::AuthedUser;
+use crate::storage::Stores;
+
+/// Run a GraphQL query or mutation. Like the REST services, it's only open to users with a session.
+/// Errors inside of the query don't fail the request, they are part of the result.
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    tag = "graphql",
+    request_body(content = Object, description = "The query, e.g. {\"query\": \"{ items { name location { name } } }\"}"),
+    responses((status = 200, description = "The data and the errors of the query", body = Object))
+)]
+#[actix_web::post("/graphql")]
+async fn post_graphql(
+    schema: web::Data<GraphqlSchema>,
+    stores: web::Data<Stores>,
+    user: AuthedUser,
+    request: web::Json<async_graphql::Request>,
+) -> web::Json<async_graphql::Response> {
+    let request = graphql::request_data(request.into_inner(), &stores, user);
+
+    web::Json(schema.execute(request).await)
+}
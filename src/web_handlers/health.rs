@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use log::warn;
+use serde::Serialize;
+
+use crate::storage::blob::BlobStore;
+use crate::storage::{StoreResult, Stores};
+
+/// A check that takes longer than this counts as failed, so the
+/// orchestrator isn't left waiting for the connection pool.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize)]
+struct Readiness {
+    /// "ok" if all checks passed, otherwise "degraded"
+    status: &'static str,
+    checks: BTreeMap<&'static str, Check>,
+}
+
+#[derive(Serialize)]
+struct Check {
+    /// "ok" or "failed"
+    status: &'static str,
+
+    /// Why the check failed. The details are only logged, this endpoint doesn't need a session.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Check {
+    const OK: Check = Check { status: "ok", error: None };
+}
+
+/// The server is running. It doesn't check anything else, so a
+/// broken database doesn't get the container restarted over and over.
+#[actix_web::get("/health/live")]
+async fn get_liveness() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+/// The server can handle requests: the database can be reached, its schema is up to date
+/// and uploads can be stored. Answers with "503 Service Unavailable" if anything is wrong.
+#[actix_web::get("/health/ready")]
+async fn get_readiness(stores: web::Data<Stores>, blobs: web::Data<dyn BlobStore>) -> HttpResponse {
+    let (database, migrations, uploads) = futures::join!(
+        check("database", "the database can't be reached", stores.status.ping()),
+        check("migrations", "the migrations can't be read", stores.status.pending_migrations()),
+        check("uploads", "the upload directory isn't writable", blobs.check_writable()),
+    );
+
+    let migrations = match migrations {
+        Err(check) => check,
+        Ok(pending) if pending.is_empty() => Check::OK,
+        Ok(pending) => Check {
+            status: "failed",
+            error: Some(format!("{} migrations are pending", pending.len())),
+        },
+    };
+
+    let checks = BTreeMap::from([
+        ("database", database.err().unwrap_or(Check::OK)),
+        ("migrations", migrations),
+        ("uploads", uploads.err().unwrap_or(Check::OK)),
+    ]);
+
+    if checks.values().all(|check| check.status == "ok") {
+        HttpResponse::Ok().json(Readiness { status: "ok", checks })
+    } else {
+        HttpResponse::ServiceUnavailable().json(Readiness { status: "degraded", checks })
+    }
+}
+
+/// Run a check with a timeout and turn its error into a failed [Check].
+async fn check<T>(name: &str, error: &str, operation: impl Future<Output = StoreResult<T>>) -> Result<T, Check> {
+    let failed = || Check {
+        status: "failed",
+        error: Some(error.to_owned()),
+    };
+
+    match actix_web::rt::time::timeout(CHECK_TIMEOUT, operation).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(err)) => {
+            warn!("Health check \"{name}\" failed: {err}");
+            Err(failed())
+        }
+        Err(_) => {
+            warn!("Health check \"{name}\" timed out");
+            Err(failed())
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use actix_multipart::Multipart;
+use actix_web::http::header::{self, CacheControl, CacheDirective, EntityTag, IfNoneMatch};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use utoipa::IntoParams;
+
+use crate::collection;
+use crate::error::{ApiError, ApiResult};
+use crate::icons::{self, BUILTIN_ICONS, ICON_SIZE};
+use crate::images;
+use crate::models::{AuthedUser, Icon};
+use crate::openapi::{Binary, FileUpload};
+use crate::storage::{IconStore, StoreError};
+use crate::web_handlers::{get_param, read_upload, store_error};
+
+/// Uploaded icons bigger than this (1 MiB) are rejected. Raster
+/// icons are scaled down afterwards, so they end up much smaller.
+const MAX_ICON_SIZE: usize = 1024 * 1024;
+
+const SVG_MIME_TYPE: &str = "image/svg+xml";
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct IconOptions {
+    /// The name of the icon. Defaults to the name of the uploaded file.
+    name: Option<String>,
+}
+
+/// The built-in icons first, then the uploaded ones.
+#[utoipa::path(
+    get,
+    path = "/icons",
+    tag = "icons",
+    responses((status = 200, description = "The icons", body = Vec<Icon>))
+)]
+#[actix_web::get("/icons")]
+async fn get_icons(store: web::Data<dyn IconStore>, _user: AuthedUser) -> ApiResult<web::Json<Vec<Icon>>> {
+    Ok(web::Json(all_icons(&**store).await?))
+}
+
+/// The built-in icons first, then the uploaded ones.
+pub(crate) async fn all_icons(store: &dyn IconStore) -> ApiResult<Vec<Icon>> {
+    let mut icons: Vec<Icon> = BUILTIN_ICONS
+        .iter()
+        .map(|icon| Icon {
+            id: icon.id,
+            name: icon.name.to_owned(),
+            mime_type: SVG_MIME_TYPE.to_owned(),
+            builtin: true,
+        })
+        .collect();
+    icons.extend(store.get_icons().await.map_err(store_error("icon"))?);
+
+    Ok(icons)
+}
+
+#[utoipa::path(
+    get,
+    path = "/icon/{icon_id}",
+    tag = "icons",
+    params(("icon_id" = u64, Path, description = "The id of the icon")),
+    responses(
+        (status = 200, description = "The icon", content((Binary = "image/svg+xml"), (Binary = "image/png"))),
+        (status = 304, description = "The content didn't change since the request that returned the ETag")
+    )
+)]
+#[actix_web::get("/icon/{icon_id}")]
+async fn get_icon(store: web::Data<dyn IconStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<HttpResponse> {
+    let icon_id: u64 = get_param(&req, "icon")?;
+
+    let (mime_type, data) = match icons::builtin_icon(icon_id) {
+        Some(icon) => (SVG_MIME_TYPE.to_owned(), icon.svg.as_bytes().to_vec()),
+        None => {
+            let (icon, data) = store.get_icon(icon_id).await.map_err(store_error("icon"))?;
+            (icon.mime_type, data)
+        }
+    };
+
+    // Icons are small, so their content is simply hashed for the ETag
+    let etag = EntityTag::new_strong(hex::encode(Sha256::digest(&data)));
+    if let Some(IfNoneMatch::Items(tags)) = req.get_header::<IfNoneMatch>() {
+        if tags.iter().any(|tag| tag.strong_eq(&etag)) {
+            return Ok(HttpResponse::NotModified().insert_header(header::ETag(etag)).finish());
+        }
+    }
+
+    // Like the uploaded files, icons are never rendered as a page of our own origin
+    Ok(HttpResponse::Ok()
+        .content_type(mime_type)
+        .insert_header(header::ETag(etag))
+        .insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache]))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .insert_header((header::CONTENT_SECURITY_POLICY, "sandbox"))
+        .body(data))
+}
+
+/// Upload an icon. SVG icons are stripped of everything that isn't a shape
+/// (e.g. scripts and links), other images are scaled down and stored as PNG.
+#[utoipa::path(
+    put,
+    path = "/icon",
+    tag = "icons",
+    params(IconOptions),
+    request_body(content = FileUpload, content_type = "multipart/form-data"),
+    responses((status = 201, description = "The icon was created", body = HashMap<String, u64>, example = json!({"icon_id": 1001})))
+)]
+#[actix_web::put("/icon")]
+async fn put_icon(store: web::Data<dyn IconStore>, _user: AuthedUser, options: web::Query<IconOptions>, payload: Multipart) -> ApiResult<HttpResponse> {
+    let upload = read_upload(payload, MAX_ICON_SIZE).await?;
+
+    let name = options
+        .name
+        .clone()
+        .or_else(|| upload.file_name.as_deref().map(|name| name.rsplit(['/', '\\']).next().unwrap_or_default().to_owned()))
+        .map(|name| name.trim().chars().take(255).collect::<String>())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "icon".to_owned());
+
+    let is_raster = infer::get(&upload.data).is_some_and(|kind| kind.mime_type().starts_with("image/"));
+    let (mime_type, data) = if is_raster {
+        let data = upload.data;
+        let png = web::block(move || images::render_icon(&data, ICON_SIZE))
+            .await
+            .map_err(|err| ApiError::Internal(Box::new(err)))?
+            .map_err(|err| ApiError::BadRequest("icon.invalid", format!("the image can't be read: {err}")))?;
+        ("image/png", png)
+    } else {
+        let svg = icons::sanitize_svg(&upload.data).ok_or_else(|| ApiError::BadRequest("icon.invalid", "the icon must be an SVG or an image!".to_owned()))?;
+        (SVG_MIME_TYPE, svg.into_bytes())
+    };
+
+    let icon = Icon {
+        id: 0,
+        name,
+        mime_type: mime_type.to_owned(),
+        builtin: false,
+    };
+    let icon_id = store.put_icon(&icon, &data).await.map_err(store_error("icon"))?;
+
+    let map: HashMap<&str, u64> = collection! {
+        "icon_id" => icon_id
+    };
+    Ok(HttpResponse::Created().json(map))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/icon/{icon_id}",
+    tag = "icons",
+    params(("icon_id" = u64, Path, description = "The id of the icon")),
+    responses((status = 200, description = "The icon was deleted"))
+)]
+#[actix_web::delete("/icon/{icon_id}")]
+async fn delete_icon(store: web::Data<dyn IconStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<HttpResponse> {
+    let icon_id: u64 = get_param(&req, "icon")?;
+    if icons::builtin_icon(icon_id).is_some() {
+        return Err(ApiError::BadRequest("icon.builtin", "the built-in icons can't be deleted!".to_owned()));
+    }
+
+    store.delete_icon(icon_id).await.map_err(store_error("icon"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Make sure that the icon of a tag, location or database exists.
+pub(crate) async fn check_icon(store: &dyn IconStore, icon: Option<u64>) -> ApiResult<()> {
+    let icon_id = match icon {
+        Some(icon_id) if icons::builtin_icon(icon_id).is_none() => icon_id,
+        _ => return Ok(()),
+    };
+
+    match store.get_icon(icon_id).await {
+        Ok(_) => Ok(()),
+        Err(StoreError::NotFound) => Err(ApiError::UnknownReference("icon")),
+        Err(err) => Err(store_error("icon")(err)),
+    }
+}
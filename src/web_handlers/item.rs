@@ -1,405 +1,700 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
-use actix_web::{error, web, HttpRequest, HttpResponse};
-use sqlx::{types::chrono, MySqlPool, Row};
+use actix_web::{web, HttpRequest, HttpResponse};
+use async_graphql::InputObject;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::collection;
-use crate::models::{AuthedUser, Item, Property};
-use crate::web_handlers::get_param;
+use crate::error::{ApiError, ApiResult};
+use crate::events::Events;
+use crate::models::{AuthedUser, Item, PropertyField};
+use crate::properties::{apply_schema, property_value, typed_value, TypedValue};
+use crate::storage::{descendants, location_descendants, tag_descendants, DatabaseStore, FileStore, ItemStore, LoanStore, LocationStore, StoreError, TagStore, TemplateStore};
+use crate::web_handlers::file::copy_item_files;
+use crate::web_handlers::loan::lent_amount;
+use crate::web_handlers::stock::notify_low_stock;
+use crate::web_handlers::{get_param, store_error, unix_now, user_change};
+
+/// An item together with how much of it is lent right now.
+#[derive(Serialize, ToSchema, Debug)]
+struct ItemWithAvailability {
+    #[serde(flatten)]
+    item: Item,
+    available: u64,
+    lent: u64,
+}
 
-#[actix_web::get("/items")]
-async fn get_items(pool: web::Data<MySqlPool>, _user: AuthedUser) -> actix_web::Result<web::Json<Vec<Item>>> {
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
+#[derive(Deserialize, IntoParams, InputObject, Debug)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ItemFilter {
+    /// Only return the items inside of this location
+    location: Option<u64>,
+
+    /// Whether the items of all locations inside of the location are included
+    #[serde(default = "default_recursive")]
+    #[graphql(default = true)]
+    recursive: bool,
+
+    /// Only return the items that have this tag or one of its child tags
+    tag: Option<u64>,
+
+    /// Only return the items that have this custom property. The values are
+    /// compared by the type of the property, e.g. as numbers or dates.
+    property: Option<String>,
+    value: Option<String>,
+    min: Option<String>,
+    max: Option<String>,
+
+    /// Sort the items by the value of this custom property.
+    /// Items without the property come last.
+    sort: Option<String>,
+    #[serde(default)]
+    #[graphql(default)]
+    desc: bool,
+}
 
-    let mut items: HashMap<u64, Item> = sqlx::query("SELECT * FROM items")
-        .fetch_all(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?
-        .iter()
-        .map(sqlrow_to_basic_item)
-        .map(|item| (item.id, item))
-        .collect();
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct ContentsOptions {
+    /// Whether the contents of the contents are included
+    #[serde(default = "default_recursive")]
+    recursive: bool,
+}
 
-    // (Look at the "attachments" query for an explanation)
-    sqlx::query("SELECT item_id, tag_id FROM item_tags")
-        .fetch_all(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?
-        .iter()
-        .for_each(|row| {
-            let item_id: u64 = row.get(0);
-            let tag_id: u64 = row.get(1);
-
-            if let Some(item) = items.get_mut(&item_id) {
-                item.tags.push(tag_id);
-            }
-        });
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct TemplateOptions {
+    template: u64,
+}
 
-    // (Look at the "attachments" query for an explanation)
-    sqlx::query("SELECT item_id, is_custom, name, value FROM item_properties")
-        .fetch_all(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?
-        .iter()
-        .for_each(|row| {
-            let item_id: u64 = row.get(0);
-            let is_custom: bool = row.get(1);
-            let name: String = row.get(2);
-            let value: String = row.get(3);
-
-            if let Some(item) = items.get_mut(&item_id) {
-                // Get the internal or custom properties list depending in 'is_custom'
-                let properties: &mut Vec<Property> = if is_custom { &mut item.properties_custom } else { &mut item.properties_internal };
-
-                properties.push(Property { name, value });
-            }
-        });
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct DuplicateOptions {
+    /// The name of the copy. By default, " (copy)" is appended to the name.
+    name: Option<String>,
+}
 
-    // Insert the list of sql rows into the item attachments map.
-    // The sql rows only contain one part of the final map
-    // so we need to go throw the hole list piece by piece.
-    sqlx::query("SELECT item_id, name, url FROM item_attachments")
-        .fetch_all(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?
-        .iter()
-        .for_each(|row| {
-            // Get the stuff from the sql row
-            let item_id: u64 = row.get(0);
-            let name: String = row.get(1);
-            let url: String = row.get(2);
-
-            // Get the attachments map and insert the name and url.
-            // Technically getting the item could fail if a invalid
-            // item id is in the attachments sql table, but this
-            // should be prevented by the foreign keys in the sql table.
-            if let Some(item) = items.get_mut(&item_id) {
-                item.attachments.insert(name, url);
-            }
-        });
+fn default_recursive() -> bool {
+    true
+}
 
-    // Convert the map back into an array:
-    // Map<u64, Item> -> Vec<Item>
-    Ok(web::Json(items.into_values().collect()))
+/// No filter at all, like an empty query string.
+impl Default for ItemFilter {
+    fn default() -> Self {
+        ItemFilter {
+            location: None,
+            recursive: default_recursive(),
+            tag: None,
+            property: None,
+            value: None,
+            min: None,
+            max: None,
+            sort: None,
+            desc: false,
+        }
+    }
 }
 
-#[actix_web::get("/item/{item_id}")]
-async fn get_item(pool: web::Data<MySqlPool>, _user: AuthedUser, req: HttpRequest) -> actix_web::Result<web::Json<Item>> {
-    let item_id: u64 = get_param(&req, "item_id", "item id must be a number!")?;
+#[utoipa::path(
+    get,
+    path = "/items",
+    tag = "items",
+    params(ItemFilter),
+    responses((status = 200, description = "The items", body = Vec<Item>))
+)]
+#[actix_web::get("/items")]
+async fn get_items(
+    store: web::Data<dyn ItemStore>,
+    locations: web::Data<dyn LocationStore>,
+    databases: web::Data<dyn DatabaseStore>,
+    tags: web::Data<dyn TagStore>,
+    _user: AuthedUser,
+    filter: web::Query<ItemFilter>,
+) -> ApiResult<web::Json<Vec<Item>>> {
+    let items = find_items(&**store, &**locations, &**databases, &**tags, &filter).await?;
+
+    Ok(web::Json(items))
+}
 
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
+/// The items that match the filter, sorted like the filter says.
+pub(crate) async fn find_items(
+    store: &dyn ItemStore,
+    locations: &dyn LocationStore,
+    databases: &dyn DatabaseStore,
+    tags: &dyn TagStore,
+    filter: &ItemFilter,
+) -> ApiResult<Vec<Item>> {
+    let mut items = store.get_items().await.map_err(store_error("item"))?;
+
+    if let Some(tag_id) = filter.tag {
+        let mut tag_ids = vec![tag_id];
+        tag_ids.extend(tag_descendants(tag_id, &tags.get_tags().await.map_err(store_error("tag"))?));
+
+        items.retain(|item| item.tags.iter().any(|id| tag_ids.contains(id)));
+    }
 
-    let query: Result<sqlx::mysql::MySqlRow, sqlx::Error> = sqlx::query("SELECT * FROM items WHERE id = ?").bind(item_id).fetch_one(&mut connection).await;
+    if let Some(location_id) = filter.location {
+        let mut location_ids = vec![location_id];
+        if filter.recursive {
+            let locations = locations.get_locations().await.map_err(store_error("location"))?;
+            location_ids.extend(location_descendants(location_id, &locations));
+        }
 
-    // Check if the query was successful, convert the row into an item.
-    // If the item could not be found, set the status code to 404.
-    // Should a different kind of error occur, return an Internal Server Error (code: 500).
-    let row = query.map_err(|err| match err {
-        sqlx::Error::RowNotFound => error::ErrorNotFound("item not found!"),
-        _ => error::ErrorInternalServerError(err),
-    })?;
+        items.retain(|item| location_ids.contains(&item.location));
+    }
 
-    let mut item = sqlrow_to_basic_item(&row);
+    if filter.property.is_none() && filter.sort.is_none() {
+        return Ok(items);
+    }
 
-    item.tags = sqlx::query("SELECT tag_id FROM item_tags WHERE item_id = ?")
-        .bind(&item.id)
-        .fetch_all(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?
-        .iter()
-        .map(|row| row.get(0))
-        .collect();
+    // The type of a property depends on the database of the item
+    let schemas = property_schemas(locations, databases).await?;
+    let no_schema = vec![];
+    let schema_of = |item: &Item| schemas.get(&item.location).unwrap_or(&no_schema);
+
+    if let Some(name) = &filter.property {
+        items.retain(|item| {
+            let schema = schema_of(item);
+            let value = match property_value(schema, &item.properties_custom, name) {
+                Some(value) => value,
+                None => return false,
+            };
+
+            // A bound that doesn't match the type of the property doesn't match any value
+            let matches = |bound: &Option<String>, accept: fn(Ordering) -> bool| match bound {
+                Some(bound) => typed_value(schema, name, bound).and_then(|bound| value.partial_cmp(&bound)).is_some_and(accept),
+                None => true,
+            };
+            matches(&filter.value, Ordering::is_eq) && matches(&filter.min, Ordering::is_ge) && matches(&filter.max, Ordering::is_le)
+        });
+    }
 
-    sqlx::query("SELECT is_custom, name, value FROM item_properties WHERE item_id = ?")
-        .bind(&item.id)
-        .fetch_all(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?
-        .iter()
-        .for_each(|row| {
-            let is_custom: bool = row.get(0);
-            let name: String = row.get(1);
-            let value: String = row.get(2);
+    if let Some(name) = &filter.sort {
+        let mut sorted: Vec<(Option<TypedValue>, Item)> = items
+            .into_iter()
+            .map(|item| (property_value(schema_of(&item), &item.properties_custom, name), item))
+            .collect();
+        sorted.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) => {
+                let ordering = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+                if filter.desc {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        items = sorted.into_iter().map(|(_, item)| item).collect();
+    }
 
-            // Get the internal or custom properties list depending in 'is_custom'
-            let properties: &mut Vec<Property> = if is_custom { &mut item.properties_custom } else { &mut item.properties_internal };
+    Ok(items)
+}
 
-            properties.push(Property { name, value });
-        });
+#[utoipa::path(
+    get,
+    path = "/item/{item_id}",
+    tag = "items",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    responses((status = 200, description = "The item", body = ItemWithAvailability))
+)]
+#[actix_web::get("/item/{item_id}")]
+async fn get_item(store: web::Data<dyn ItemStore>, loans: web::Data<dyn LoanStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<ItemWithAvailability>> {
+    let item_id: u64 = get_param(&req, "item")?;
 
-    item.attachments = sqlx::query("SELECT name, url FROM item_attachments WHERE item_id = ?")
-        .bind(&item.id)
-        .fetch_all(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?
-        .iter()
-        .map(|row| (row.get(0), row.get(1)))
-        .collect();
+    // If the item could not be found, the error code is "item.not_found".
+    // Should a different kind of error occur, return an Internal Server Error (code: 500).
+    let item = store.get_item(item_id).await.map_err(store_error("item"))?;
+
+    let loans = loans.get_loans_by_items(&[item_id]).await.map_err(store_error("loan"))?;
+    let lent = lent_amount(&loans, item_id);
 
-    Ok(web::Json(item))
+    Ok(web::Json(ItemWithAvailability {
+        available: item.amount.saturating_sub(lent),
+        lent,
+        item,
+    }))
 }
 
-#[rustfmt::skip]
-#[actix_web::put("/item")]
-async fn put_item(pool: web::Data<MySqlPool>, _user: AuthedUser, item: web::Json<Item>) -> actix_web::Result<HttpResponse> {
-    if item.id != 0 {
-        return Err(error::ErrorBadRequest("item id must be 0!"));
+#[utoipa::path(
+    get,
+    path = "/item/{item_id}/contents",
+    tag = "items",
+    params(("item_id" = u64, Path, description = "The id of the item"), ContentsOptions),
+    responses((status = 200, description = "The items inside of the item", body = Vec<Item>))
+)]
+#[actix_web::get("/item/{item_id}/contents")]
+async fn get_item_contents(store: web::Data<dyn ItemStore>, _user: AuthedUser, req: HttpRequest, options: web::Query<ContentsOptions>) -> ApiResult<web::Json<Vec<Item>>> {
+    let item_id: u64 = get_param(&req, "item")?;
+
+    // Make sure the container exists, otherwise it would just look empty
+    store.get_item(item_id).await.map_err(store_error("item"))?;
+    let mut items = store.get_items().await.map_err(store_error("item"))?;
+
+    if options.recursive {
+        let parents = items.iter().map(|item| (item.id, item.parent_item)).collect();
+        let content_ids = descendants(item_id, &parents);
+        items.retain(|item| content_ids.contains(&item.id));
+    } else {
+        items.retain(|item| item.parent_item == Some(item_id));
     }
 
-    // We need to make a transaction here for two reasons:
-    // 1. we want to make 2 queries that relate to each other
-    // 2. if something goes wrong along the function,
-    //    all changes to the database will be discarded.
-    let mut tx = pool.begin().await.map_err(error::ErrorInternalServerError)?;
-
-    // First insert the object into the sql table...
-    let insertion_query: Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> =
-        sqlx::query("INSERT INTO items (name,description,image,location_id,amount,last_edited,created) VALUES (?,?,?,?,?,?,?)")
-            .bind(&item.name)
-            .bind(&item.description)
-            .bind(&item.image)
-            .bind(&item.location)
-            .bind(&item.amount)
-            .bind(&chrono::NaiveDateTime::from_timestamp(item.last_edited, 0))
-            .bind(&chrono::NaiveDateTime::from_timestamp(item.created, 0))
-            .execute(&mut tx)
-            .await;
-
-    // ...then make sure it didn't fail.
-    if let Err(error) = insertion_query {
-        return Err(match error {
-            sqlx::Error::Database(db_error) if db_error.message().starts_with("Duplicate entry") => error::ErrorConflict("there already is a item with this name!"),
-            sqlx::Error::Database(db_error) if db_error.message().starts_with("Cannot add or update a child row: a foreign key constraint fails") => error::ErrorNotFound("unknown location id!"),
-            _ => error::ErrorInternalServerError(error),
-        });
-    }
+    Ok(web::Json(items))
+}
 
-    // After that we need to get the autogenerated item id from the table.
-    let item_id: u64 = sqlx::query("SELECT LAST_INSERT_ID()")
-        .fetch_one(&mut tx)
-        .await
-        .map_err(error::ErrorInternalServerError)?
-        .get(0);
+#[utoipa::path(
+    put,
+    path = "/item",
+    tag = "items",
+    responses((status = 201, description = "The item was created", body = HashMap<String, u64>, example = json!({"item_id": 1})))
+)]
+#[actix_web::put("/item")]
+async fn put_item(
+    store: web::Data<dyn ItemStore>,
+    locations: web::Data<dyn LocationStore>,
+    databases: web::Data<dyn DatabaseStore>,
+    user: AuthedUser,
+    item: web::Json<Item>,
+) -> ApiResult<HttpResponse> {
+    let item_id = create_item(&**store, &**locations, &**databases, &user, item.into_inner()).await?;
 
-    // (Look at the "attachments" query for an explanation)
-    if !item.tags.is_empty() {
-        let tag_sql: String = format!("INSERT INTO item_tags (item_id,tag_id) VALUES (?,?){}", ", (?,?)".repeat(item.tags.len() - 1));
+    let map: HashMap<&str, u64> = collection! {
+        "item_id" => item_id
+    };
+    Ok(HttpResponse::Created().json(map))
+}
 
-        let mut tag_insertion = sqlx::query(tag_sql.as_str());
-        for tag in &item.tags {
-            tag_insertion = tag_insertion.bind(&item_id).bind(tag);
-        }
+/// Create an item from a template. The fields in the body override the ones of the template.
+#[utoipa::path(
+    post,
+    path = "/item",
+    tag = "items",
+    params(TemplateOptions),
+    request_body(content = Object, description = "The fields of the item that differ from the template"),
+    responses((status = 201, description = "The item was created", body = HashMap<String, u64>, example = json!({"item_id": 1})))
+)]
+#[actix_web::post("/item")]
+async fn create_item_from_template(
+    store: web::Data<dyn ItemStore>,
+    templates: web::Data<dyn TemplateStore>,
+    locations: web::Data<dyn LocationStore>,
+    databases: web::Data<dyn DatabaseStore>,
+    user: AuthedUser,
+    options: web::Query<TemplateOptions>,
+    overrides: web::Json<Map<String, Value>>,
+) -> ApiResult<HttpResponse> {
+    let template = templates.get_template(options.template).await.map_err(store_error("template"))?;
+    let now = unix_now();
+
+    let prefilled = Item {
+        id: 0,
+        name: String::new(),
+        description: template.description,
+        image: None,
+        location: template.location.unwrap_or_default(),
+        parent_item: None,
+        tags: template.tags,
+        amount: 0,
+        min_amount: None,
+        properties_internal: template.properties_internal,
+        properties_custom: template.properties_custom,
+        attachments: HashMap::new(),
+        last_edited: now,
+        created: now,
+    };
+    let mut fields = match serde_json::to_value(prefilled) {
+        Ok(Value::Object(fields)) => fields,
+        Ok(_) => return Err(ApiError::Internal("an item isn't serialized as object".into())),
+        Err(err) => return Err(ApiError::Internal(Box::new(err))),
+    };
+    fields.extend(overrides.into_inner());
 
-        // Execute the query and check for errors.
-        if let Err(error) = tag_insertion.execute(&mut tx).await {
-            return Err(match error {
-                sqlx::Error::Database(db_error) if db_error.message().starts_with("Cannot add or update a child row: a foreign key constraint fails") => error::ErrorNotFound("unknown tag id!"),
-                _ => error::ErrorInternalServerError(error),
-            });
-        }
+    let mut item: Item = serde_json::from_value(Value::Object(fields)).map_err(|err| ApiError::BadRequest("item.invalid", err.to_string()))?;
+    if item.id != 0 {
+        return Err(ApiError::IdNotZero("item"));
     }
 
-    // (Look at the "attachments" query for an explanation)
-    if !item.properties_internal.is_empty() || !item.properties_custom.is_empty() {
-        let property_sql: String = format!(
-            "INSERT INTO item_properties (item_id,is_custom,name,value) VALUES (?,?,?,?){}",
-            ", (?,?,?,?)".repeat(item.properties_internal.len() + item.properties_custom.len() - 1),
-        );
+    apply_property_schema(&mut item, &**store, &**locations, &**databases).await?;
 
-        let mut property_insertion = sqlx::query(property_sql.as_str());
-        for property in &item.properties_internal {
-            property_insertion = property_insertion.bind(&item_id).bind(false).bind(&property.name).bind(&property.value);
-        }
+    let change = user_change(&user, &format!("created from template {}", template.name));
+    let item_id = store.put_item(&item, &change).await.map_err(store_error("item"))?;
 
-        for property in &item.properties_custom {
-            property_insertion = property_insertion.bind(&item_id).bind(true).bind(&property.name).bind(&property.value);
-        }
+    let map: HashMap<&str, u64> = collection! {
+        "item_id" => item_id
+    };
+    Ok(HttpResponse::Created().json(map))
+}
 
-        property_insertion.execute(&mut tx).await.map_err(error::ErrorInternalServerError)?;
-    }
+/// Create a copy of the item with its tags, properties and files.
+#[utoipa::path(
+    post,
+    path = "/item/{item_id}/duplicate",
+    tag = "items",
+    params(("item_id" = u64, Path, description = "The id of the item"), DuplicateOptions),
+    responses((status = 201, description = "The copy was created", body = HashMap<String, u64>, example = json!({"item_id": 2})))
+)]
+#[actix_web::post("/item/{item_id}/duplicate")]
+async fn duplicate_item(
+    store: web::Data<dyn ItemStore>,
+    files: web::Data<dyn FileStore>,
+    user: AuthedUser,
+    req: HttpRequest,
+    options: web::Query<DuplicateOptions>,
+) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
+    let item = store.get_item(item_id).await.map_err(store_error("item"))?;
+
+    // The uploaded files get linked again below, with the urls of the copy.
+    // Images and attachments from somewhere else are kept as they are.
+    let item_files = files.get_item_files(item_id).await.map_err(store_error("file"))?;
+    let image = if item_files.iter().any(|file| file.is_image) { None } else { item.image.clone() };
+    let mut attachments = item.attachments.clone();
+    attachments.retain(|name, _| !item_files.iter().any(|file| !file.is_image && file.name == *name));
+
+    let now = unix_now();
+    let copy = Item {
+        id: 0,
+        name: options.name.clone().unwrap_or_else(|| format!("{} (copy)", item.name)),
+        image,
+        attachments,
+        last_edited: now,
+        created: now,
+        ..item
+    };
 
-    // If we have attachments, store them in a separate table.
-    if !item.attachments.is_empty() {
-        // Create an sql query with the right amount of values
-        // to fit all the attachments into a single query.
-        // We only need to the string n -1 times, because we
-        // already have the fist one in the formatting string.
-        let attachment_sql: String = format!(
-            "INSERT INTO item_attachments (item_id,name,url) VALUES (?,?,?){}",
-            ", (?,?,?)".repeat(item.attachments.len() - 1)
-        );
-
-        // Insert all attachments into the sql query.
-        let mut attachment_insertion = sqlx::query(attachment_sql.as_str());
-        for attachment in &item.attachments {
-            attachment_insertion = attachment_insertion.bind(&item_id).bind(attachment.0).bind(attachment.1);
-        }
+    let change = user_change(&user, &format!("duplicated item {item_id}"));
+    let copy_id = store.put_item(&copy, &change).await.map_err(store_error("item"))?;
 
-        // Execute the query and check for errors.
-        attachment_insertion.execute(&mut tx).await.map_err(error::ErrorInternalServerError)?;
+    // A copy without the files of the original isn't kept
+    if let Err(err) = copy_item_files(&**files, &req, item_id, copy_id).await {
+        store
+            .delete_item(copy_id, &user_change(&user, &format!("failed to duplicate item {item_id}")))
+            .await
+            .map_err(store_error("item"))?;
+        return Err(err);
     }
 
-    // Finally, commit the changes to make them permanent
-    tx.commit().await.map_err(error::ErrorInternalServerError)?;
-
     let map: HashMap<&str, u64> = collection! {
-        "item_id" => item_id
+        "item_id" => copy_id
     };
     Ok(HttpResponse::Created().json(map))
 }
 
-#[rustfmt::skip]
+#[utoipa::path(
+    post,
+    path = "/item/{item_id}",
+    tag = "items",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    responses((status = 200, description = "The item was updated"))
+)]
 #[actix_web::post("/item/{item_id}")]
-async fn update_item(pool: web::Data<MySqlPool>, _user: AuthedUser, req: HttpRequest, item: web::Json<Item>) -> actix_web::Result<HttpResponse> {
-    let item_id: u64 = get_param(&req, "item_id", "item id must be a number!")?;
+async fn update_item(
+    store: web::Data<dyn ItemStore>,
+    locations: web::Data<dyn LocationStore>,
+    databases: web::Data<dyn DatabaseStore>,
+    events: web::Data<Events>,
+    user: AuthedUser,
+    req: HttpRequest,
+    item: web::Json<Item>,
+) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
     if item.id != item_id {
-        return Err(error::ErrorBadRequest("the item ids don't match!"));
+        return Err(ApiError::IdMismatch("item"));
     }
 
-    // ### FIXME ###
-    // The following code is some real shit,
-    // because it's basically just copy pasted code.
-    // The code does two things:
-    // 1. delete the item
-    // 2. put the modified item back in
-    //
-    // This is really inefficient and should be optimized in the future!
-    let mut tx = pool.begin().await.map_err(error::ErrorInternalServerError)?;
-
-    // Delete the item
-    let deletion_query: Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> = sqlx::query("DELETE FROM items WHERE id = ?").bind(&item_id).execute(&mut tx).await;
-    if deletion_query.map_err(error::ErrorInternalServerError)?.rows_affected() == 0 {
-        return Err(error::ErrorNotFound("item not found!"));
-    }
+    edit_item(&**store, &**locations, &**databases, &events, &user, item.into_inner()).await?;
 
-    // Add the new item back in
-    let insertion_query: Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> =
-        sqlx::query("INSERT INTO items (id,name,description,image,location_id,amount,last_edited,created) VALUES (?,?,?,?,?,?,?,?)")
-            .bind(&item.id)
-            .bind(&item.name)
-            .bind(&item.description)
-            .bind(&item.image)
-            .bind(&item.location)
-            .bind(&item.amount)
-            .bind(&chrono::NaiveDateTime::from_timestamp(item.last_edited, 0))
-            .bind(&chrono::NaiveDateTime::from_timestamp(item.created, 0))
-            .execute(&mut tx)
-            .await;
-
-    if let Err(error) = insertion_query {
-        return Err(match error {
-            sqlx::Error::Database(db_error) if db_error.message().starts_with("Duplicate entry") => error::ErrorConflict("there already is a item with this name!"),
-            sqlx::Error::Database(db_error) if db_error.message().starts_with("Cannot add or update a child row: a foreign key constraint fails") => error::ErrorNotFound("unknown location id!"),
-            _ => error::ErrorInternalServerError(error),
-        });
-    }
+    Ok(HttpResponse::Ok().finish())
+}
 
-    if !item.tags.is_empty() {
-        let tag_sql: String = format!("INSERT INTO item_tags (item_id,tag_id) VALUES (?,?){}", ", (?,?)".repeat(item.tags.len() - 1));
+#[utoipa::path(
+    delete,
+    path = "/item/{item_id}",
+    tag = "items",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    responses((status = 200, description = "The item was moved to the trash"))
+)]
+#[actix_web::delete("/item/{item_id}")]
+async fn delete_item(store: web::Data<dyn ItemStore>, user: AuthedUser, req: HttpRequest) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
 
-        let mut tag_insertion = sqlx::query(tag_sql.as_str());
-        for tag in &item.tags {
-            tag_insertion = tag_insertion.bind(&item_id).bind(tag);
-        }
+    // The item is moved to the trash. Its files stay until the trash is purged.
+    store.delete_item(item_id, &user_change(&user, "deleted")).await.map_err(store_error("item"))?;
 
-        if let Err(error) = tag_insertion.execute(&mut tx).await {
-            return Err(match error {
-                sqlx::Error::Database(db_error) if db_error.message().starts_with("Cannot add or update a child row: a foreign key constraint fails") => error::ErrorNotFound("unknown tag id!"),
-                _ => error::ErrorInternalServerError(error),
-            });
-        }
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Check the new item and insert it. Returns the id of the item.
+pub(crate) async fn create_item(items: &dyn ItemStore, locations: &dyn LocationStore, databases: &dyn DatabaseStore, user: &AuthedUser, mut item: Item) -> ApiResult<u64> {
+    if item.id != 0 {
+        return Err(ApiError::IdNotZero("item"));
     }
 
-    if !item.properties_internal.is_empty() || !item.properties_custom.is_empty() {
-        let property_sql: String = format!(
-            "INSERT INTO item_properties (item_id,is_custom,name,value) VALUES (?,?,?,?){}",
-            ", (?,?,?,?)".repeat(item.properties_internal.len() + item.properties_custom.len() - 1),
-        );
+    apply_property_schema(&mut item, items, locations, databases).await?;
+    items.put_item(&item, &user_change(user, "created")).await.map_err(store_error("item"))
+}
 
-        let mut property_insertion = sqlx::query(property_sql.as_str());
-        for property in &item.properties_internal {
-            property_insertion = property_insertion.bind(&item_id).bind(false).bind(&property.name).bind(&property.value);
-        }
+/// Check the item and replace the stored one with it.
+pub(crate) async fn edit_item(
+    items: &dyn ItemStore,
+    locations: &dyn LocationStore,
+    databases: &dyn DatabaseStore,
+    events: &Events,
+    user: &AuthedUser,
+    mut item: Item,
+) -> ApiResult<()> {
+    apply_property_schema(&mut item, items, locations, databases).await?;
+
+    let old_item = items.get_item(item.id).await.map_err(store_error("item"))?;
+    items.update_item(&item, &user_change(user, "edited")).await.map_err(store_error("item"))?;
+    notify_low_stock(events, old_item.is_low_stock(), item.id, &item.name, item.amount, item.min_amount);
+
+    Ok(())
+}
 
-        for property in &item.properties_custom {
-            property_insertion = property_insertion.bind(&item_id).bind(true).bind(&property.name).bind(&property.value);
+/// Validate the custom properties of the item against the schema of its database.
+/// Items inside of a container end up in the location of the container.
+pub(crate) async fn apply_property_schema(item: &mut Item, items: &dyn ItemStore, locations: &dyn LocationStore, databases: &dyn DatabaseStore) -> ApiResult<()> {
+    let location_id = match item.parent_item {
+        Some(parent_id) => {
+            let container = items.get_item(parent_id).await.map_err(|err| match err {
+                StoreError::NotFound => ApiError::UnknownReference("item"),
+                err => store_error("item")(err),
+            })?;
+            container.location
         }
+        None => item.location,
+    };
+    let location = locations.get_location(location_id).await.map_err(|err| match err {
+        StoreError::NotFound => ApiError::UnknownReference("location"),
+        err => store_error("location")(err),
+    })?;
+    let database = databases.get_database(location.database).await.map_err(store_error("database"))?;
 
-        property_insertion.execute(&mut tx).await.map_err(error::ErrorInternalServerError)?;
-    }
+    apply_schema(&database.properties, &mut item.properties_custom)
+}
 
-    if !item.attachments.is_empty() {
-        let attachment_sql: String = format!(
-            "INSERT INTO item_attachments (item_id,name,url) VALUES (?,?,?){}",
-            ", (?,?,?)".repeat(item.attachments.len() - 1)
-        );
+/// The property schemas of all locations, by the id of the location.
+async fn property_schemas(locations: &dyn LocationStore, databases: &dyn DatabaseStore) -> ApiResult<HashMap<u64, Vec<PropertyField>>> {
+    let databases: HashMap<u64, Vec<PropertyField>> = databases
+        .get_databases()
+        .await
+        .map_err(store_error("database"))?
+        .into_iter()
+        .map(|database| (database.id, database.properties))
+        .collect();
 
-        let mut attachment_insertion = sqlx::query(attachment_sql.as_str());
-        for attachment in &item.attachments {
-            attachment_insertion = attachment_insertion.bind(&item_id).bind(attachment.0).bind(attachment.1);
-        }
+    Ok(locations
+        .get_locations()
+        .await
+        .map_err(store_error("location"))?
+        .into_iter()
+        .filter_map(|location| Some((location.id, databases.get(&location.database)?.clone())))
+        .collect())
+}
 
-        attachment_insertion.execute(&mut tx).await.map_err(error::ErrorInternalServerError)?;
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web};
+    use serde_json::{json, Value};
+
+    use crate::models::{ItemFile, PropertyField, PropertyType};
+    use crate::web_handlers::testing::{authed, create_item, create_location, init_stores, send};
+
+    fn services(config: &mut web::ServiceConfig) {
+        config
+            .service(super::get_item)
+            .service(super::put_item)
+            .service(super::update_item)
+            .service(super::delete_item)
+            .service(super::duplicate_item);
     }
 
-    tx.commit().await.map_err(error::ErrorInternalServerError)?;
-    Ok(HttpResponse::Ok().finish())
-}
+    /// A required number, so that the items are checked against a schema.
+    fn voltage() -> PropertyField {
+        PropertyField {
+            name: "voltage".to_owned(),
+            kind: PropertyType::Number,
+            required: true,
+            default: None,
+            options: vec![],
+        }
+    }
 
-#[actix_web::delete("/item/{item_id}")]
-async fn delete_item(pool: web::Data<MySqlPool>, _user: AuthedUser, req: HttpRequest) -> actix_web::Result<HttpResponse> {
-    let item_id: u64 = get_param(&req, "item_id", "item id must be a number!")?;
-
-    // If something goes wrong (I don't know how),
-    // we roll back to a save state automatically.
-    let mut tx = pool.begin().await.map_err(error::ErrorInternalServerError)?;
-
-    // Delete the item from the database. This also
-    // deletes the corresponding entries in the other
-    // tables because of the foreign key constraints.
-    let deletion_query: sqlx::mysql::MySqlQueryResult = sqlx::query("DELETE FROM items WHERE id = ?")
-        .bind(&item_id)
-        .execute(&mut tx)
-        .await
-        .map_err(error::ErrorInternalServerError)?;
+    fn item(location_id: u64, parent_item: Option<u64>, voltage: Option<&str>) -> Value {
+        let properties: Vec<Value> = voltage.into_iter().map(|value| json!({"name": "voltage", "value": value})).collect();
+        json!({
+            "id": 0,
+            "name": "Battery",
+            "description": "",
+            "image": null,
+            "location": location_id,
+            "parent_item": parent_item,
+            "tags": [],
+            "amount": 4,
+            "properties_internal": [],
+            "properties_custom": properties,
+            "attachments": {},
+            "last_edited": 0,
+            "created": 0
+        })
+    }
 
-    // If nothing was deleted, the item didn't even exist!
-    if deletion_query.rows_affected() == 0 {
-        return Err(error::ErrorNotFound("item not found!"));
+    #[actix_web::test]
+    async fn items_can_be_created_updated_and_deleted() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![voltage()]).await;
+
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::put().uri("/v1/item")).set_json(item(location_id, None, Some("1.5"))),
+        )
+        .await;
+        assert_eq!(res.status, 201);
+        let item_id = res.body["item_id"].as_u64().unwrap();
+
+        let res = send(&stores, services, authed(test::TestRequest::get().uri(&format!("/v1/item/{item_id}")))).await;
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body["name"], "Battery");
+        assert_eq!(res.body["available"], 4);
+
+        let mut updated = res.body.clone();
+        updated["name"] = json!("AA battery");
+        let res = send(&stores, services, authed(test::TestRequest::post().uri(&format!("/v1/item/{item_id}"))).set_json(&updated)).await;
+        assert_eq!(res.status, 200);
+        assert_eq!(stores.items.get_item(item_id).await.unwrap().name, "AA battery");
+
+        let res = send(&stores, services, authed(test::TestRequest::delete().uri(&format!("/v1/item/{item_id}")))).await;
+        assert_eq!(res.status, 200);
+
+        let res = send(&stores, services, authed(test::TestRequest::get().uri(&format!("/v1/item/{item_id}")))).await;
+        assert_eq!(res.status, 404);
+        assert_eq!(res.body["code"], "item.not_found");
     }
 
-    // To be able to tell offline clients that something got
-    // deleted, we need to keep track of deleted item ids.
-    sqlx::query("INSERT INTO item_deleted VALUES (?, CURRENT_TIMESTAMP())")
-        .bind(&item_id)
-        .execute(&mut tx)
-        .await
-        .map_err(error::ErrorInternalServerError)?;
+    #[actix_web::test]
+    async fn errors_are_problem_details() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![voltage()]).await;
+
+        let res = send(&stores, services, authed(test::TestRequest::get().uri("/v1/item/42"))).await;
+        assert_eq!(res.status, 404);
+        assert_eq!(res.content_type, "application/problem+json");
+        assert_eq!(res.body["type"], "about:blank");
+        assert_eq!(res.body["title"], "Not Found");
+        assert_eq!(res.body["status"], 404);
+        assert_eq!(res.body["detail"], "item not found!");
+
+        let res = send(&stores, services, authed(test::TestRequest::get().uri("/v1/item/abc"))).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "item.invalid_id");
+
+        let mut with_id = item(location_id, None, Some("1.5"));
+        with_id["id"] = json!(7);
+        let res = send(&stores, services, authed(test::TestRequest::put().uri("/v1/item")).set_json(&with_id)).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "item.id_not_zero");
+
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::put().uri("/v1/item")).set_json(item(location_id + 1, None, Some("1.5"))),
+        )
+        .await;
+        assert_eq!(res.status, 404);
+        assert_eq!(res.body["code"], "location.unknown_reference");
+
+        // Errors of actix itself (like a broken json body) are converted as well
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::put().uri("/v1/item"))
+                .insert_header(("Content-Type", "application/json"))
+                .set_payload("{"),
+        )
+        .await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.content_type, "application/problem+json");
+        assert_eq!(res.body["code"], "request.invalid");
+
+        let res = send(&stores, services, test::TestRequest::get().uri("/v1/item/1")).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "auth.missing_session");
+    }
 
-    tx.commit().await.map_err(error::ErrorInternalServerError)?;
-    Ok(HttpResponse::Ok().finish())
-}
+    #[actix_web::test]
+    async fn contained_items_get_the_schema_of_the_container() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![voltage()]).await;
+        let other_location_id = create_location(&stores, "Kitchen", vec![]).await;
+
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::put().uri("/v1/item")).set_json(item(location_id, None, Some("9"))),
+        )
+        .await;
+        let container_id = res.body["item_id"].as_u64().unwrap();
+
+        // The item ends up in the database of the container, which requires the property
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::put().uri("/v1/item")).set_json(item(other_location_id, Some(container_id), None)),
+        )
+        .await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "property.required");
+
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::put().uri("/v1/item")).set_json(item(other_location_id, Some(container_id), Some("abc"))),
+        )
+        .await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "property.invalid_value");
+    }
 
-/// The item table consists out of multiple tables.
-/// Because of that, we need to make small steps,
-/// to reconstruct the item in code. This function
-/// does the first part of that.
-pub(crate) fn sqlrow_to_basic_item(row: &sqlx::mysql::MySqlRow) -> Item {
-    let last_edited: chrono::NaiveDateTime = row.get(6);
-    let created: chrono::NaiveDateTime = row.get(7);
-
-    Item {
-        id: row.get(0),
-        name: row.get(1),
-        description: row.get(2),
-        image: row.get(3),
-        location: row.get(4),
-        amount: row.get(5),
-        last_edited: last_edited.timestamp(),
-        created: created.timestamp(),
-        tags: vec![],
-        properties_custom: vec![],
-        properties_internal: vec![],
-        attachments: HashMap::new(),
+    #[actix_web::test]
+    async fn a_copy_without_the_files_is_discarded() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![]).await;
+        let item_id = create_item(&stores, location_id, 1, vec![]).await;
+        let file = ItemFile {
+            is_image: false,
+            name: "manual.pdf".to_owned(),
+            hash: "abc".to_owned(),
+            mime_type: "application/pdf".to_owned(),
+            size: 3,
+        };
+        stores
+            .files
+            .put_item_file(item_id, &file, &format!("/v1/item/{item_id}/attachments/manual.pdf"))
+            .await
+            .unwrap();
+        let duplicate = || authed(test::TestRequest::post().uri(&format!("/v1/item/{item_id}/duplicate")));
+
+        // Without the route of the attachments, their urls can't be built for the copy
+        let res = send(&stores, services, duplicate()).await;
+        assert_eq!(res.status, 500);
+        assert_eq!(stores.items.get_items().await.unwrap().len(), 1);
+
+        fn with_files(config: &mut web::ServiceConfig) {
+            services(config);
+            config.service(crate::web_handlers::file::get_item_attachment);
+        }
+        let res = send(&stores, with_files, duplicate()).await;
+        assert_eq!(res.status, 201);
+        let copy_id = res.body["item_id"].as_u64().unwrap();
+        let copy = stores.items.get_item(copy_id).await.unwrap();
+        assert_eq!(copy.attachments["manual.pdf"], format!("/v1/item/{copy_id}/attachments/manual.pdf"));
+        assert_eq!(stores.files.get_item_files(copy_id).await.unwrap().len(), 1);
     }
 }
@@ -0,0 +1,656 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+
+use actix_multipart::Multipart;
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::{ApiError, ApiResult};
+use crate::models::{AuthedUser, Database, Item, Location, Property, PropertyField};
+use crate::openapi::FileUpload;
+use crate::properties::apply_schema;
+use crate::storage::{DatabaseStore, ImportedItem, ItemStore, LocationPath, LocationStore, TagStore};
+use crate::web_handlers::file::remove_missing_files;
+use crate::web_handlers::{read_upload, store_error, unix_now, user_change};
+
+/// The columns that map to item fields. Every other column is a custom
+/// property. The "id" column is only used by the "parent_item" column,
+/// imported items always get a new id.
+const COLUMNS: [&str; 11] = [
+    "id",
+    "name",
+    "description",
+    "image",
+    "location",
+    "parent_item",
+    "amount",
+    "min_amount",
+    "tags",
+    "last_edited",
+    "created",
+];
+
+/// Separates the tag names in the "tags" column.
+const TAG_SEPARATOR: char = ';';
+
+/// Separates the database name and the names of the nested
+/// locations in the "location" column, e.g. "Lab/Shelf/Box 1".
+const PATH_SEPARATOR: char = '/';
+
+/// Escapes a separator (or itself) inside of a name in the "location" column.
+const PATH_ESCAPE: char = '\\';
+
+/// CSV files bigger than this (10 MiB) are rejected.
+const MAX_IMPORT_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct ImportOptions {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+struct ImportReport {
+    dry_run: bool,
+
+    /// Whether the changes were saved. This is only
+    /// the case if there were no errors at all.
+    applied: bool,
+
+    /// The number of items that were (or would be) created.
+    imported: usize,
+    errors: Vec<RowError>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+struct RowError {
+    /// The line in the CSV file (the header is line 1).
+    row: u64,
+    code: String,
+    message: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/items/export.csv",
+    tag = "items",
+    responses((status = 200, description = "All items as CSV file", content_type = "text/csv", body = String))
+)]
+#[actix_web::get("/items/export.csv")]
+async fn export_items_csv(
+    items: web::Data<dyn ItemStore>,
+    tags: web::Data<dyn TagStore>,
+    locations: web::Data<dyn LocationStore>,
+    databases: web::Data<dyn DatabaseStore>,
+    _user: AuthedUser,
+) -> ApiResult<HttpResponse> {
+    let mut items = items.get_items().await.map_err(store_error("item"))?;
+    items.sort_by_key(|item| item.id);
+
+    // Tags and locations are exported by name, so we need to look them up.
+    let tag_names: HashMap<u64, String> = tags.get_tags().await.map_err(store_error("tag"))?.into_iter().map(|tag| (tag.id, tag.name)).collect();
+    let database_names: HashMap<u64, String> = databases
+        .get_databases()
+        .await
+        .map_err(store_error("database"))?
+        .into_iter()
+        .map(|database| (database.id, database.name))
+        .collect();
+    let locations: HashMap<u64, Location> = locations
+        .get_locations()
+        .await
+        .map_err(store_error("location"))?
+        .into_iter()
+        .map(|location| (location.id, location))
+        .collect();
+    let location_paths: HashMap<u64, String> = locations
+        .keys()
+        .map(|location_id| (*location_id, location_path(*location_id, &locations, &database_names)))
+        .collect();
+
+    // Every custom property gets its own column
+    let property_names: BTreeSet<&str> = items.iter().flat_map(|item| item.properties_custom.iter()).map(|property| property.name.as_str()).collect();
+    if let Some(property_name) = property_names.iter().find(|property_name| COLUMNS.contains(property_name)) {
+        return Err(ApiError::BadRequest(
+            "export.reserved_property",
+            format!("the property \"{property_name}\" has the name of an item column, rename it to export the items!"),
+        ));
+    }
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer
+        .write_record(COLUMNS.iter().copied().chain(property_names.iter().copied()))
+        .map_err(internal_csv_error)?;
+
+    for item in &items {
+        let tags: Vec<&str> = item.tags.iter().filter_map(|tag_id| tag_names.get(tag_id)).map(String::as_str).collect();
+
+        let mut record = vec![
+            item.id.to_string(),
+            item.name.clone(),
+            item.description.clone(),
+            item.image.clone().unwrap_or_default(),
+            location_paths.get(&item.location).cloned().unwrap_or_default(),
+            item.parent_item.map(|parent_id| parent_id.to_string()).unwrap_or_default(),
+            item.amount.to_string(),
+            item.min_amount.map(|min_amount| min_amount.to_string()).unwrap_or_default(),
+            tags.join(&TAG_SEPARATOR.to_string()),
+            item.last_edited.to_string(),
+            item.created.to_string(),
+        ];
+
+        for property_name in &property_names {
+            let property = item.properties_custom.iter().find(|property| property.name == *property_name);
+            record.push(property.map(|property| property.value.clone()).unwrap_or_default());
+        }
+
+        writer.write_record(&record).map_err(internal_csv_error)?;
+    }
+
+    let data = writer.into_inner().map_err(|err| ApiError::Internal(Box::new(err.into_error())))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"items.csv\""))
+        .body(data))
+}
+
+#[utoipa::path(
+    post,
+    path = "/items/import",
+    tag = "items",
+    params(ImportOptions),
+    request_body(content = FileUpload, content_type = "multipart/form-data", description = "The CSV file, in the same format as the export"),
+    responses(
+        (status = 201, description = "The items were imported", body = ImportReport),
+        (status = 200, description = "The file was checked (dry run)", body = ImportReport),
+        (status = 422, description = "Nothing was imported, because some rows are invalid", body = ImportReport)
+    )
+)]
+#[actix_web::post("/items/import")]
+async fn import_items_csv(
+    req: HttpRequest,
+    store: web::Data<dyn ItemStore>,
+    locations: web::Data<dyn LocationStore>,
+    databases: web::Data<dyn DatabaseStore>,
+    user: AuthedUser,
+    options: web::Query<ImportOptions>,
+    payload: Multipart,
+) -> ApiResult<HttpResponse> {
+    let data = read_upload(payload, MAX_IMPORT_SIZE).await?.data;
+
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(data.as_slice());
+    let headers = reader.headers().map_err(|err| ApiError::BadRequest("import.invalid_csv", err.to_string()))?.clone();
+
+    for column in ["name", "location"] {
+        if !headers.iter().any(|header| header == column) {
+            return Err(ApiError::BadRequest("import.missing_column", format!("the column \"{column}\" is missing!")));
+        }
+    }
+
+    // Otherwise a custom property could have the name of an item column
+    for (index, header) in headers.iter().enumerate() {
+        if headers.iter().take(index).any(|other| other == header) {
+            return Err(ApiError::BadRequest("import.duplicate_column", format!("the column \"{header}\" appears more than once!")));
+        }
+    }
+
+    // First check every row on its own
+    let mut rows = vec![];
+    let mut errors = vec![];
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(err) => {
+                errors.push(RowError {
+                    row: err.position().map(|position| position.line()).unwrap_or_default(),
+                    code: "import.invalid_csv".to_owned(),
+                    message: err.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let line = record.position().map(|position| position.line()).unwrap_or_default();
+        match parse_record(&headers, &record, line) {
+            Ok(mut row) => {
+                // The files of the exported item aren't copied, so its local image is removed
+                if let Ok(source_id) = row.reference.parse() {
+                    let mut source = Item {
+                        id: source_id,
+                        ..row.imported.item
+                    };
+                    remove_missing_files(&req, &mut source, &[])?;
+                    row.imported.item = Item { id: 0, ..source };
+                }
+                rows.push(row);
+            }
+            Err(message) => errors.push(RowError {
+                row: line,
+                code: "import.invalid_row".to_owned(),
+                message: message.to_owned(),
+            }),
+        }
+    }
+
+    // Then the rows are linked to their containers. The properties are checked
+    // against the schema of the database the item ends up in, which is the one
+    // of the outermost container.
+    let mut rows = link_containers(rows, &mut errors);
+    let databases = databases.get_databases().await.map_err(store_error("database"))?;
+    let locations = locations.get_locations().await.map_err(store_error("location"))?;
+
+    let mut checked = vec![true; rows.len()];
+    for index in 0..rows.len() {
+        let mut outermost = index;
+        while let Some(container) = rows[outermost].imported.container {
+            outermost = container;
+        }
+
+        let schema = row_schema(&rows[outermost].imported.location, &databases, &locations);
+        if let Some(Err(err)) = schema.map(|schema| apply_schema(schema, &mut rows[index].imported.item.properties_custom)) {
+            checked[index] = false;
+            errors.push(RowError {
+                row: rows[index].line,
+                code: err.code(),
+                message: err.to_string(),
+            });
+        }
+    }
+
+    // The valid rows are handed to the store, which checks the rest. The contents of
+    // an invalid container are left out as well, the import isn't applied anyway.
+    let mut items = vec![];
+    let mut lines = vec![];
+    let mut new_indices: Vec<Option<usize>> = Vec::with_capacity(rows.len());
+    for (mut row, checked) in rows.into_iter().zip(checked) {
+        let container = row.imported.container.map(|container| new_indices[container]);
+        if !checked || container == Some(None) {
+            new_indices.push(None);
+            continue;
+        }
+
+        row.imported.container = container.flatten();
+        new_indices.push(Some(items.len()));
+        lines.push(row.line);
+        items.push(row.imported);
+    }
+
+    // If some rows are already invalid, the import
+    // won't be applied. So we only do a dry run.
+    let dry_run = options.dry_run || !errors.is_empty();
+    let results = store.import_items(&items, dry_run, &user_change(&user, "imported")).await.map_err(store_error("item"))?;
+
+    let mut imported = 0;
+    for (line, result) in lines.into_iter().zip(results) {
+        match result.map_err(store_error("item")) {
+            Ok(_) => imported += 1,
+            Err(err @ ApiError::Internal(_)) => return Err(err),
+            Err(err) => errors.push(RowError {
+                row: line,
+                code: err.code(),
+                message: err.to_string(),
+            }),
+        }
+    }
+    errors.sort_by_key(|error| error.row);
+
+    let applied = !options.dry_run && errors.is_empty();
+    let mut response = if applied {
+        HttpResponse::Created()
+    } else if options.dry_run {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::UnprocessableEntity()
+    };
+
+    Ok(response.json(ImportReport {
+        dry_run: options.dry_run,
+        applied,
+        imported,
+        errors,
+    }))
+}
+
+/// The property schema of the database of the location. Without a
+/// known database the store rejects the row anyway, so there's no schema.
+fn row_schema<'a>(location: &LocationPath, databases: &'a [Database], locations: &[Location]) -> Option<&'a [PropertyField]> {
+    let database_id = match &location.database {
+        Some(database_name) => databases.iter().find(|database| database.name == *database_name)?.id,
+        None => {
+            let location_name = location.path.last()?;
+            let mut matching = locations.iter().filter(|location| location.name == *location_name);
+            match (matching.next(), matching.next()) {
+                (Some(location), None) => location.database,
+                _ => return None,
+            }
+        }
+    };
+
+    databases.iter().find(|database| database.id == database_id).map(|database| database.properties.as_slice())
+}
+
+/// Replace the "parent_item" column of every row by the index of the row with
+/// that id and sort the rows, so that every container comes before its contents.
+/// References that can't be resolved are added to the errors and removed.
+fn link_containers(mut rows: Vec<ParsedRow>, errors: &mut Vec<RowError>) -> Vec<ParsedRow> {
+    let mut invalid_reference = |row: &ParsedRow, code: &str, message: &str| {
+        errors.push(RowError {
+            row: row.line,
+            code: code.to_owned(),
+            message: message.to_owned(),
+        })
+    };
+
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    for (index, row) in rows.iter().enumerate().filter(|(_, row)| !row.reference.is_empty()) {
+        match indices.entry(&row.reference) {
+            Entry::Occupied(_) => invalid_reference(row, "import.duplicate_id", "another row already has this id!"),
+            Entry::Vacant(entry) => {
+                entry.insert(index);
+            }
+        }
+    }
+
+    let mut containers: Vec<Option<usize>> = Vec::with_capacity(rows.len());
+    for row in &rows {
+        containers.push(match row.container.as_str() {
+            "" => None,
+            reference => match indices.get(reference) {
+                Some(index) => Some(*index),
+                None => {
+                    invalid_reference(row, "import.invalid_row", "parent_item must be the id of another row!");
+                    None
+                }
+            },
+        });
+    }
+
+    // Break every cycle at the first of its rows
+    for index in 0..rows.len() {
+        let mut current = containers[index];
+        let mut steps = 0;
+        while let Some(container) = current.filter(|_| steps <= rows.len()) {
+            if container == index {
+                invalid_reference(&rows[index], "item.cycle", "an item can't be inside of itself!");
+                containers[index] = None;
+                break;
+            }
+            current = containers[container];
+            steps += 1;
+        }
+    }
+
+    // Sort the rows by the number of containers around them
+    let depth = |index: usize| std::iter::successors(containers[index], |container| containers[*container]).count();
+    let mut order: Vec<usize> = (0..rows.len()).collect();
+    order.sort_by_key(|index| depth(*index));
+
+    let mut new_indices = vec![0; rows.len()];
+    for (new_index, old_index) in order.iter().enumerate() {
+        new_indices[*old_index] = new_index;
+    }
+    for (row, container) in rows.iter_mut().zip(&containers) {
+        row.imported.container = container.map(|container| new_indices[container]);
+    }
+
+    let mut rows: Vec<Option<ParsedRow>> = rows.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|index| rows[index].take()).collect()
+}
+
+/// The path of a location in the "location" column: the name
+/// of its database, of its parent locations and its own name.
+fn location_path(location_id: u64, locations: &HashMap<u64, Location>, database_names: &HashMap<u64, String>) -> String {
+    let mut names = vec![];
+    let mut current = locations.get(&location_id);
+    while let Some(location) = current.filter(|_| names.len() <= locations.len()) {
+        names.push(escape_path_name(&location.name));
+        current = location.parent.and_then(|parent_id| locations.get(&parent_id));
+    }
+
+    let database_name = locations.get(&location_id).and_then(|location| database_names.get(&location.database));
+    names.push(database_name.map(|database_name| escape_path_name(database_name)).unwrap_or_default());
+    names.reverse();
+
+    names.join(&PATH_SEPARATOR.to_string())
+}
+
+fn escape_path_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for character in name.chars() {
+        if character == PATH_SEPARATOR || character == PATH_ESCAPE {
+            escaped.push(PATH_ESCAPE);
+        }
+        escaped.push(character);
+    }
+
+    escaped
+}
+
+/// Split the "location" column at every separator that isn't escaped.
+fn split_path(path: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut name = String::new();
+    let mut characters = path.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            PATH_ESCAPE => name.push(characters.next().unwrap_or(PATH_ESCAPE)),
+            PATH_SEPARATOR => names.push(std::mem::take(&mut name)),
+            character => name.push(character),
+        }
+    }
+    names.push(name);
+
+    names.into_iter().map(|name| name.trim().to_owned()).collect()
+}
+
+/// A row of the CSV file that could be parsed.
+struct ParsedRow {
+    line: u64,
+
+    /// The "id" column, which other rows use as "parent_item".
+    reference: String,
+
+    /// The "parent_item" column.
+    container: String,
+    imported: ImportedItem,
+}
+
+/// Convert a CSV record into an item or return
+/// a message that describes why the row is invalid.
+fn parse_record(headers: &csv::StringRecord, record: &csv::StringRecord, line: u64) -> Result<ParsedRow, &'static str> {
+    let column = |name: &str| headers.iter().position(|header| header == name).and_then(|index| record.get(index)).unwrap_or_default();
+
+    let name = column("name");
+    if name.is_empty() {
+        return Err("the name is missing!");
+    }
+
+    let location = column("location");
+    if location.is_empty() {
+        return Err("the location is missing!");
+    }
+
+    // The first name is the database, unless there's only one
+    let mut path = split_path(location);
+    if path.iter().any(String::is_empty) {
+        return Err("the location contains an empty name!");
+    }
+    let location = LocationPath {
+        database: if path.len() > 1 { Some(path.remove(0)) } else { None },
+        path,
+    };
+
+    let amount: u64 = match column("amount") {
+        "" => 0,
+        amount => amount.parse().map_err(|_| "the amount must be a whole number (0 or more)!")?,
+    };
+    let min_amount: Option<u64> = match column("min_amount") {
+        "" => None,
+        min_amount => Some(min_amount.parse().map_err(|_| "min_amount must be a whole number (0 or more)!")?),
+    };
+
+    let now = unix_now();
+    let last_edited: i64 = match column("last_edited") {
+        "" => now,
+        timestamp => timestamp.parse().map_err(|_| "last_edited must be a unix timestamp!")?,
+    };
+    let created: i64 = match column("created") {
+        "" => now,
+        timestamp => timestamp.parse().map_err(|_| "created must be a unix timestamp!")?,
+    };
+
+    let mut tags: Vec<String> = vec![];
+    for tag in column("tags").split(TAG_SEPARATOR).map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|other| other == tag) {
+            tags.push(tag.to_owned());
+        }
+    }
+
+    // Every unknown column is a custom property, empty cells are skipped.
+    let properties_custom = headers
+        .iter()
+        .zip(record.iter())
+        .filter(|(header, value)| !COLUMNS.contains(header) && !value.is_empty())
+        .map(|(header, value)| Property {
+            name: header.to_owned(),
+            value: value.to_owned(),
+        })
+        .collect();
+
+    let image = match column("image") {
+        "" => None,
+        image => Some(image.to_owned()),
+    };
+
+    Ok(ParsedRow {
+        line,
+        reference: column("id").to_owned(),
+        container: column("parent_item").to_owned(),
+        imported: ImportedItem {
+            item: Item {
+                id: 0,
+                name: name.to_owned(),
+                description: column("description").to_owned(),
+                image,
+                location: 0,
+                parent_item: None,
+                tags: vec![],
+                amount,
+                min_amount,
+                properties_internal: vec![],
+                properties_custom,
+                attachments: HashMap::new(),
+                last_edited,
+                created,
+            },
+            location,
+            tags,
+            container: None,
+        },
+    })
+}
+
+fn internal_csv_error(err: csv::Error) -> ApiError {
+    ApiError::Internal(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web};
+
+    use crate::models::{Location, Property};
+    use crate::storage::Change;
+    use crate::web_handlers::testing::{authed, create_item, create_location, init_stores, send, upload};
+
+    fn services(config: &mut web::ServiceConfig) {
+        config
+            .service(super::export_items_csv)
+            .service(super::import_items_csv)
+            .service(crate::web_handlers::file::get_item_image);
+    }
+
+    #[actix_web::test]
+    async fn exported_items_can_be_imported_into_another_server() {
+        let stores = init_stores().await;
+        let shelf_id = create_location(&stores, "Lab", vec![]).await;
+        let box_id = stores
+            .locations
+            .put_location(&Location {
+                id: 0,
+                name: "Box 1/2".to_owned(),
+                database: 1,
+                parent: Some(shelf_id),
+                icon: None,
+            })
+            .await
+            .unwrap();
+
+        // The container is newer than the item inside of it
+        let screw_id = create_item(&stores, shelf_id, 10, vec![]).await;
+        let case_id = create_item(&stores, box_id, 1, vec![]).await;
+        let mut screw = stores.items.get_item(screw_id).await.unwrap();
+        screw.parent_item = Some(case_id);
+        screw.image = Some(format!("/v1/item/{screw_id}/image"));
+        let change = Change {
+            user: None,
+            time: 0,
+            reason: "packed".to_owned(),
+        };
+        stores.items.update_item(&screw, &change).await.unwrap();
+
+        let res = send(&stores, services, authed(test::TestRequest::get().uri("/v1/items/export.csv"))).await;
+        assert_eq!(res.status, 200);
+        assert!(res.text.contains("Lab/Shelf/Box 1\\/2"), "{}", res.text);
+
+        // Only the database has to exist on the other server
+        let other = init_stores().await;
+        create_location(&other, "Lab", vec![]).await;
+        let import = upload(test::TestRequest::post().uri("/v1/items/import"), "items.csv", "text/csv", res.text.as_bytes());
+        let res = send(&other, services, authed(import)).await;
+        assert_eq!(res.status, 201, "{}", res.body);
+        assert_eq!(res.body["imported"], 2);
+
+        let locations = other.locations.get_locations().await.unwrap();
+        let imported_box = locations.iter().find(|location| location.name == "Box 1/2").unwrap();
+        assert_eq!(locations.iter().find(|location| Some(location.id) == imported_box.parent).unwrap().name, "Shelf");
+
+        let items = other.items.get_items().await.unwrap();
+        let imported_case = items.iter().find(|item| item.name == "Item 2").unwrap();
+        let imported_screw = items.iter().find(|item| item.name == "Item 1").unwrap();
+        assert_eq!(imported_screw.parent_item, Some(imported_case.id));
+        assert_eq!(imported_screw.location, imported_box.id);
+        assert_eq!(imported_screw.image, None);
+    }
+
+    #[actix_web::test]
+    async fn property_columns_cant_have_the_name_of_an_item_column() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Lab", vec![]).await;
+
+        let csv = "name,location,amount,amount\nScrew,Lab/Shelf,1,2\n";
+        let import = upload(test::TestRequest::post().uri("/v1/items/import"), "items.csv", "text/csv", csv.as_bytes());
+        let res = send(&stores, services, authed(import)).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "import.duplicate_column");
+
+        let item_id = create_item(&stores, location_id, 1, vec![]).await;
+        let mut item = stores.items.get_item(item_id).await.unwrap();
+        item.properties_custom = vec![Property {
+            name: "amount".to_owned(),
+            value: "2".to_owned(),
+        }];
+        let change = Change {
+            user: None,
+            time: 0,
+            reason: "edited".to_owned(),
+        };
+        stores.items.update_item(&item, &change).await.unwrap();
+
+        let res = send(&stores, services, authed(test::TestRequest::get().uri("/v1/items/export.csv"))).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "export.reserved_property");
+    }
+}
@@ -0,0 +1,191 @@
+use actix_web::http::header;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::{ApiError, ApiResult};
+use crate::labels::{self, Label, LabelTemplate, LABEL_TEMPLATES};
+use crate::models::{AuthedUser, Location};
+use crate::openapi::Binary;
+use crate::storage::{ItemStore, LocationStore};
+use crate::web_handlers::location::location_path;
+use crate::web_handlers::store_error;
+
+/// The url of the web UI (config option "public_url"), for the deep links on
+/// the labels. Labels can't be printed without it, because the host of the
+/// request can be spoofed and would end up in the printed QR codes.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PublicUrl(pub(crate) Option<String>);
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct LabelOptions {
+    /// Comma separated ids of the items, e.g. "1,2,3"
+    #[serde(default)]
+    items: String,
+
+    /// Comma separated ids of the locations
+    #[serde(default)]
+    locations: String,
+
+    /// The name of the label template, e.g. "avery-l7160" or "thermal-57x32"
+    template: String,
+    format: Option<LabelFormat>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, ToSchema, Debug)]
+#[serde(rename_all = "lowercase")]
+enum LabelFormat {
+    Pdf,
+
+    /// For Zebra printers
+    Zpl,
+}
+
+#[utoipa::path(
+    get,
+    path = "/labels",
+    tag = "labels",
+    params(LabelOptions),
+    responses((status = 200, description = "The labels as PDF or as ZPL for label printers", content((Binary = "application/pdf"), (String = "text/plain"))))
+)]
+#[actix_web::get("/labels")]
+async fn get_labels(
+    items: web::Data<dyn ItemStore>,
+    locations: web::Data<dyn LocationStore>,
+    public_url: web::Data<PublicUrl>,
+    _user: AuthedUser,
+    options: web::Query<LabelOptions>,
+) -> ApiResult<HttpResponse> {
+    let template = LabelTemplate::find(&options.template).ok_or_else(|| {
+        let names: Vec<&str> = LABEL_TEMPLATES.iter().map(|template| template.name).collect();
+        ApiError::BadRequest("label.unknown_template", format!("the template has to be one of {}!", names.join(", ")))
+    })?;
+
+    let item_ids = parse_ids(&options.items)?;
+    let location_ids = parse_ids(&options.locations)?;
+    if item_ids.is_empty() && location_ids.is_empty() {
+        return Err(ApiError::BadRequest("label.empty", "there are no items or locations to print!".to_owned()));
+    }
+
+    let base_url = public_url.0.as_deref().ok_or_else(|| {
+        ApiError::BadRequest(
+            "label.missing_public_url",
+            "the server has no public url (config option \"public_url\") for the links on the labels!".to_owned(),
+        )
+    })?;
+
+    let all_locations = locations.get_locations().await.map_err(store_error("location"))?;
+    let mut labels = Vec::with_capacity(item_ids.len() + location_ids.len());
+
+    for item_id in item_ids {
+        let item = items.get_item(item_id).await.map_err(store_error("item"))?;
+        let location = all_locations.iter().find(|location| location.id == item.location);
+
+        labels.push(Label {
+            link: labels::label_link(base_url, "item", item.id),
+            name: item.name,
+            path: location.map(|location| path_text(location, &all_locations)).unwrap_or_default(),
+        });
+    }
+
+    for location_id in location_ids {
+        let location = all_locations.iter().find(|location| location.id == location_id).ok_or(ApiError::NotFound("location"))?;
+
+        // The location itself is already the name, so the path shows where it is
+        let path = location
+            .parent
+            .and_then(|parent_id| all_locations.iter().find(|other| other.id == parent_id))
+            .map(|parent| path_text(parent, &all_locations))
+            .unwrap_or_default();
+
+        labels.push(Label {
+            link: labels::label_link(base_url, "location", location.id),
+            name: location.name.clone(),
+            path,
+        });
+    }
+
+    let too_long = |_| ApiError::BadRequest("label.link_too_long", "the link doesn't fit into a QR code!".to_owned());
+    match options.format.unwrap_or(LabelFormat::Pdf) {
+        LabelFormat::Pdf => Ok(HttpResponse::Ok()
+            .content_type("application/pdf")
+            .insert_header((header::CONTENT_DISPOSITION, "inline; filename=\"labels.pdf\""))
+            .body(labels::render_pdf(template, &labels).map_err(too_long)?)),
+        LabelFormat::Zpl => Ok(HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"labels.zpl\""))
+            .body(labels::render_zpl(template, &labels).map_err(too_long)?)),
+    }
+}
+
+/// Parse a comma separated list of ids. An empty list has no ids.
+fn parse_ids(ids: &str) -> ApiResult<Vec<u64>> {
+    ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().map_err(|_| ApiError::BadRequest("label.invalid_id", format!("{id} isn't a valid id!"))))
+        .collect()
+}
+
+/// The breadcrumb path of the location, e.g. "Garage / Shelf 2 / Box 3".
+fn path_text(location: &Location, locations: &[Location]) -> String {
+    location_path(location, locations).into_iter().map(|segment| segment.name).collect::<Vec<_>>().join(" / ")
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web};
+
+    use crate::web_handlers::testing::{authed, create_item, create_location, init_stores, send, PUBLIC_URL};
+
+    fn services(config: &mut web::ServiceConfig) {
+        config.service(super::get_labels);
+    }
+
+    #[actix_web::test]
+    async fn labels_are_rendered_as_pdf_and_zpl() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![]).await;
+        let item_id = create_item(&stores, location_id, 1, vec![]).await;
+        let labels = |query: &str| authed(test::TestRequest::get().uri(&format!("/v1/labels?items={item_id}&locations={location_id}&{query}")));
+
+        let res = send(&stores, services, labels("template=avery-l7160")).await;
+        assert_eq!(res.status, 200);
+        assert_eq!(res.content_type, "application/pdf");
+        assert!(res.text.starts_with("%PDF-"));
+
+        let res = send(&stores, services, labels("template=thermal-57x32&format=zpl")).await;
+        assert_eq!(res.status, 200);
+        assert_eq!(res.content_type, "text/plain; charset=utf-8");
+
+        // One label per item or location, each with the deep link as QR code and the name
+        let zpl: Vec<&str> = res.text.lines().collect();
+        assert_eq!(zpl.len(), 2);
+        assert!(zpl.iter().all(|label| label.starts_with("^XA") && label.ends_with("^XZ")));
+        assert!(zpl[0].contains(&format!("^FDMA,{PUBLIC_URL}/#/item/{item_id}^FS")));
+        assert!(zpl[0].contains("^FDItem 1^FS"));
+        assert!(zpl[0].contains("^FDShelf^FS"));
+        assert!(zpl[1].contains(&format!("^FDMA,{PUBLIC_URL}/#/location/{location_id}^FS")));
+
+        let res = send(&stores, services, labels("template=unknown")).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "label.unknown_template");
+    }
+
+    #[actix_web::test]
+    async fn labels_need_the_public_url() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![]).await;
+
+        // The host of the request is never used instead
+        fn without_public_url(config: &mut web::ServiceConfig) {
+            config.app_data(web::Data::new(super::PublicUrl(None))).service(super::get_labels);
+        }
+        let request =
+            authed(test::TestRequest::get().uri(&format!("/v1/labels?locations={location_id}&template=thermal-57x32&format=zpl"))).insert_header(("Host", "attacker.example.com"));
+        let res = send(&stores, without_public_url, request).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "label.missing_public_url");
+    }
+}
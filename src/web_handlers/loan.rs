@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use async_graphql::{Enum, InputObject};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::collection;
+use crate::error::{ApiError, ApiResult};
+use crate::models::{AuthedUser, Loan};
+use crate::storage::{ItemStore, LoanStore};
+use crate::web_handlers::{get_param, store_error, unix_now};
+
+#[derive(Deserialize, ToSchema, InputObject, Debug)]
+pub(crate) struct CheckOut {
+    /// How much of the item is lent
+    #[serde(default = "default_amount")]
+    #[graphql(default = 1)]
+    amount: u64,
+
+    /// The borrower, if it's a user of this server
+    #[serde(default)]
+    user: Option<u64>,
+
+    /// The name of the borrower. Required if no user is given.
+    #[serde(default)]
+    #[graphql(default)]
+    borrower: String,
+
+    /// Unix timestamp (seconds)
+    #[serde(default)]
+    due: Option<i64>,
+}
+
+fn default_amount() -> u64 {
+    1
+}
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct LoanFilter {
+    #[serde(default)]
+    status: LoanStatus,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, ToSchema, Enum, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LoanStatus {
+    /// Loans that aren't returned yet
+    #[default]
+    Open,
+
+    /// Open loans that are past their due date
+    Overdue,
+    Returned,
+    All,
+}
+
+#[utoipa::path(
+    get,
+    path = "/loans",
+    tag = "loans",
+    params(LoanFilter),
+    responses((status = 200, description = "The loans", body = Vec<Loan>))
+)]
+#[actix_web::get("/loans")]
+async fn get_loans(store: web::Data<dyn LoanStore>, _user: AuthedUser, filter: web::Query<LoanFilter>) -> ApiResult<web::Json<Vec<Loan>>> {
+    let loans = store.get_loans().await.map_err(store_error("loan"))?;
+
+    Ok(web::Json(loans_with_status(loans, filter.status)))
+}
+
+/// The loans with the status, sorted by their id.
+pub(crate) fn loans_with_status(mut loans: Vec<Loan>, status: LoanStatus) -> Vec<Loan> {
+    let now = unix_now();
+    loans.retain(|loan| match status {
+        LoanStatus::Open => loan.returned.is_none(),
+        LoanStatus::Overdue => loan.returned.is_none() && loan.due.is_some_and(|due| due < now),
+        LoanStatus::Returned => loan.returned.is_some(),
+        LoanStatus::All => true,
+    });
+    loans.sort_by_key(|loan| loan.id);
+
+    loans
+}
+
+#[utoipa::path(
+    get,
+    path = "/loan/{loan_id}",
+    tag = "loans",
+    params(("loan_id" = u64, Path, description = "The id of the loan")),
+    responses((status = 200, description = "The loan", body = Loan))
+)]
+#[actix_web::get("/loan/{loan_id}")]
+async fn get_loan(store: web::Data<dyn LoanStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<Loan>> {
+    let loan_id: u64 = get_param(&req, "loan")?;
+
+    let loan = store.get_loan(loan_id).await.map_err(store_error("loan"))?;
+
+    Ok(web::Json(loan))
+}
+
+#[utoipa::path(
+    get,
+    path = "/item/{item_id}/loans",
+    tag = "loans",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    responses((status = 200, description = "The loans of the item, the newest first", body = Vec<Loan>))
+)]
+#[actix_web::get("/item/{item_id}/loans")]
+async fn get_item_loans(store: web::Data<dyn LoanStore>, items: web::Data<dyn ItemStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<Vec<Loan>>> {
+    let item_id: u64 = get_param(&req, "item")?;
+
+    // Make sure the item exists, otherwise it would just look like it was never lent
+    items.get_item(item_id).await.map_err(store_error("item"))?;
+
+    // The history of the item, the latest loan first
+    let mut loans = store.get_loans_by_items(&[item_id]).await.map_err(store_error("loan"))?;
+    loans.sort_by_key(|loan| std::cmp::Reverse((loan.lent, loan.id)));
+
+    Ok(web::Json(loans))
+}
+
+#[utoipa::path(
+    post,
+    path = "/item/{item_id}/checkout",
+    tag = "loans",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    responses((status = 201, description = "The item was checked out", body = HashMap<String, u64>, example = json!({"loan_id": 1})))
+)]
+#[actix_web::post("/item/{item_id}/checkout")]
+async fn check_out_item(store: web::Data<dyn LoanStore>, _user: AuthedUser, req: HttpRequest, check_out: web::Json<CheckOut>) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
+
+    let loan_id = lend_item(&**store, item_id, &check_out).await?;
+
+    let map: HashMap<&str, u64> = collection! {
+        "loan_id" => loan_id
+    };
+    Ok(HttpResponse::Created().json(map))
+}
+
+/// Check the item out and return the id of the loan.
+pub(crate) async fn lend_item(store: &dyn LoanStore, item_id: u64, check_out: &CheckOut) -> ApiResult<u64> {
+    if check_out.amount == 0 {
+        return Err(ApiError::BadRequest("loan.invalid_amount", "the amount must be at least 1!".to_owned()));
+    }
+
+    let borrower = check_out.borrower.trim();
+    if borrower.is_empty() && check_out.user.is_none() {
+        return Err(ApiError::BadRequest("loan.missing_borrower", "either a user or a borrower name is needed!".to_owned()));
+    }
+
+    store
+        .check_out(&Loan {
+            id: 0,
+            item: item_id,
+            amount: check_out.amount,
+            user: check_out.user,
+            borrower: borrower.to_owned(),
+            lent: unix_now(),
+            due: check_out.due,
+            returned: None,
+        })
+        .await
+        .map_err(store_error("loan"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/loan/{loan_id}/checkin",
+    tag = "loans",
+    params(("loan_id" = u64, Path, description = "The id of the loan")),
+    responses((status = 200, description = "The item was checked in"))
+)]
+#[actix_web::post("/loan/{loan_id}/checkin")]
+async fn check_in_loan(store: web::Data<dyn LoanStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<HttpResponse> {
+    let loan_id: u64 = get_param(&req, "loan")?;
+
+    store.check_in(loan_id, unix_now()).await.map_err(store_error("loan"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// How much of the item is currently lent.
+pub(crate) fn lent_amount(loans: &[Loan], item_id: u64) -> u64 {
+    loans.iter().filter(|loan| loan.item == item_id && loan.returned.is_none()).map(|loan| loan.amount).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web};
+    use serde_json::json;
+
+    use crate::web_handlers::testing::{authed, create_item, create_location, init_stores, send};
+
+    fn services(config: &mut web::ServiceConfig) {
+        config
+            .service(super::check_out_item)
+            .service(super::check_in_loan)
+            .service(crate::web_handlers::item::get_item)
+            .service(crate::web_handlers::item::update_item)
+            .service(crate::web_handlers::movement::post_item_movement);
+    }
+
+    #[actix_web::test]
+    async fn only_the_available_amount_can_be_lent() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![]).await;
+        let item_id = create_item(&stores, location_id, 3, vec![]).await;
+        let check_out = |amount: u64| authed(test::TestRequest::post().uri(&format!("/v1/item/{item_id}/checkout"))).set_json(json!({"amount": amount, "borrower": "Alice"}));
+
+        let res = send(&stores, services, check_out(2)).await;
+        assert_eq!(res.status, 201);
+        let loan_id = res.body["loan_id"].as_u64().unwrap();
+
+        let res = send(&stores, services, authed(test::TestRequest::get().uri(&format!("/v1/item/{item_id}")))).await;
+        assert_eq!(res.body["available"], 1);
+        assert_eq!(res.body["lent"], 2);
+
+        let res = send(&stores, services, check_out(2)).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "loan.not_available");
+
+        let res = send(&stores, services, check_out(0)).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "loan.invalid_amount");
+
+        // A returned loan makes the amount available again
+        let res = send(&stores, services, authed(test::TestRequest::post().uri(&format!("/v1/loan/{loan_id}/checkin")))).await;
+        assert_eq!(res.status, 200);
+        let res = send(&stores, services, check_out(3)).await;
+        assert_eq!(res.status, 201);
+
+        let res = send(&stores, services, authed(test::TestRequest::post().uri(&format!("/v1/loan/{loan_id}/checkin")))).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "loan.already_returned");
+    }
+
+    #[actix_web::test]
+    async fn lent_pieces_cant_be_taken_away() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![]).await;
+        let item_id = create_item(&stores, location_id, 3, vec![]).await;
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::post().uri(&format!("/v1/item/{item_id}/checkout"))).set_json(json!({"amount": 2, "borrower": "Alice"})),
+        )
+        .await;
+        assert_eq!(res.status, 201);
+        let consume = |amount: i64| authed(test::TestRequest::post().uri(&format!("/v1/item/{item_id}/movements"))).set_json(json!({"kind": "consume", "amount": amount}));
+
+        let res = send(&stores, services, consume(2)).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "item.amount_lent");
+
+        let mut item = stores.items.get_item(item_id).await.unwrap();
+        item.amount = 1;
+        let res = send(&stores, services, authed(test::TestRequest::post().uri(&format!("/v1/item/{item_id}"))).set_json(&item)).await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "item.amount_lent");
+
+        // The pieces that aren't lent can still be used
+        let res = send(&stores, services, consume(1)).await;
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body["amount"], 2);
+    }
+}
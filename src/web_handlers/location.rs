@@ -1,81 +1,129 @@
 use std::collections::HashMap;
 
-use actix_web::{error, web, HttpRequest, HttpResponse};
-use sqlx::{MySqlPool, Row};
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::collection;
+use crate::error::{ApiError, ApiResult};
 use crate::models::{AuthedUser, Location};
-use crate::web_handlers::get_param;
+use crate::storage::{ChildPolicy, IconStore, LocationStore};
+use crate::web_handlers::icon::check_icon;
+use crate::web_handlers::{get_param, store_error, user_change};
+
+/// A location together with its breadcrumb path.
+#[derive(Serialize, ToSchema, Debug)]
+struct LocationWithPath {
+    #[serde(flatten)]
+    location: Location,
+
+    /// All locations from the top level location down to this one (inclusive)
+    path: Vec<PathSegment>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub(crate) struct PathSegment {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+}
+
+/// A location with everything inside of it.
+#[derive(Serialize, ToSchema, Debug)]
+struct LocationTree {
+    #[serde(flatten)]
+    location: Location,
+    #[schema(no_recursion)]
+    children: Vec<LocationTree>,
+}
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct DeleteOptions {
+    /// What happens to the locations inside. By default, the
+    /// location can only be deleted if there are none.
+    #[serde(default = "default_child_policy")]
+    children: ChildPolicy,
+}
 
+fn default_child_policy() -> ChildPolicy {
+    ChildPolicy::Reject
+}
+
+#[utoipa::path(
+    get,
+    path = "/locations",
+    tag = "locations",
+    responses((status = 200, description = "The locations", body = Vec<LocationWithPath>))
+)]
 #[actix_web::get("/locations")]
-async fn get_locations(pool: web::Data<MySqlPool>, _user: AuthedUser) -> actix_web::Result<web::Json<Vec<Location>>> {
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
+async fn get_locations(store: web::Data<dyn LocationStore>, _user: AuthedUser) -> ApiResult<web::Json<Vec<LocationWithPath>>> {
+    let locations = store.get_locations().await.map_err(store_error("location"))?;
 
-    let locations = sqlx::query_as::<_, Location>("SELECT * FROM locations")
-        .fetch_all(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?;
+    let locations = locations
+        .iter()
+        .map(|location| LocationWithPath {
+            location: location.clone(),
+            path: location_path(location, &locations),
+        })
+        .collect();
 
     Ok(web::Json(locations))
 }
 
+#[utoipa::path(
+    get,
+    path = "/location/{location_id}",
+    tag = "locations",
+    params(("location_id" = u64, Path, description = "The id of the location")),
+    responses((status = 200, description = "The location", body = LocationWithPath))
+)]
 #[actix_web::get("/location/{location_id}")]
-async fn get_location(pool: web::Data<MySqlPool>, _user: AuthedUser, req: HttpRequest) -> actix_web::Result<web::Json<Location>> {
-    let location_id: u64 = get_param(&req, "location_id", "location id must be a number!")?;
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
-
-    // Query for the object and auto convert it.
-    let query: Result<Location, sqlx::Error> = sqlx::query_as::<_, Location>("SELECT * FROM locations WHERE id = ?")
-        .bind(location_id)
-        .fetch_one(&mut connection)
-        .await;
-
-    // Check if the query was successful and return the location,
-    // if the location could not be found, set the status code to 404.
+async fn get_location(store: web::Data<dyn LocationStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<LocationWithPath>> {
+    let location_id: u64 = get_param(&req, "location")?;
+
+    // If the location could not be found, the error code is "location.not_found".
     // Should a different kind of error occur, return an Internal Server Error (code: 500).
-    let location = query.map_err(|err| match err {
-        sqlx::Error::RowNotFound => error::ErrorNotFound("location not found!"),
-        _ => error::ErrorInternalServerError(err),
-    })?;
+    let location = store.get_location(location_id).await.map_err(store_error("location"))?;
+
+    // The parents are needed for the path
+    let locations = store.get_locations().await.map_err(store_error("location"))?;
+    let path = location_path(&location, &locations);
+
+    Ok(web::Json(LocationWithPath { location, path }))
+}
 
-    Ok(web::Json(location))
+#[utoipa::path(
+    get,
+    path = "/location/{location_id}/tree",
+    tag = "locations",
+    params(("location_id" = u64, Path, description = "The id of the location")),
+    responses((status = 200, description = "The location with all locations inside of it", body = LocationTree))
+)]
+#[actix_web::get("/location/{location_id}/tree")]
+async fn get_location_tree(store: web::Data<dyn LocationStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<LocationTree>> {
+    let location_id: u64 = get_param(&req, "location")?;
+
+    let locations = store.get_locations().await.map_err(store_error("location"))?;
+    let location = locations.iter().find(|location| location.id == location_id).ok_or(ApiError::NotFound("location"))?;
+
+    Ok(web::Json(location_tree(location, &locations)))
 }
 
-#[rustfmt::skip]
+#[utoipa::path(
+    put,
+    path = "/location",
+    tag = "locations",
+    responses((status = 201, description = "The location was created", body = HashMap<String, u64>, example = json!({"location_id": 1})))
+)]
 #[actix_web::put("/location")]
-async fn put_location(pool: web::Data<MySqlPool>, _user: AuthedUser, location: web::Json<Location>) -> actix_web::Result<HttpResponse> {
+async fn put_location(store: web::Data<dyn LocationStore>, icons: web::Data<dyn IconStore>, _user: AuthedUser, location: web::Json<Location>) -> ApiResult<HttpResponse> {
     if location.id != 0 {
-        return Err(error::ErrorBadRequest("location id must be 0!"));
+        return Err(ApiError::IdNotZero("location"));
     }
 
-    // We need to make a transaction here because we want to make 2 queries that relate to each other.
-    let mut tx = pool.begin().await.map_err(error::ErrorInternalServerError)?;
-
-    // First insert the object into the sql table...
-    let insertion_query: Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> = sqlx::query("INSERT INTO locations (name,database_id) VALUES (?,?)")
-        .bind(&location.name)
-        .bind(&location.database)
-        .execute(&mut tx)
-        .await;
-
-    // ...then make sure it didn't fail.
-    if let Err(error) = insertion_query {
-        return Err(match error {
-            sqlx::Error::Database(db_error) if db_error.message().starts_with("Duplicate entry") => error::ErrorConflict("there already is a location with this name!"),
-            sqlx::Error::Database(db_error) if db_error.message().starts_with("Cannot add or update a child row: a foreign key constraint fails") => error::ErrorNotFound("unknown database id!"),
-            _ => error::ErrorInternalServerError(error),
-        });
-    }
-
-    // After that we need to get the autogenerated id from the table.
-    let selection_query: Result<sqlx::mysql::MySqlRow, sqlx::Error> = sqlx::query("SELECT LAST_INSERT_ID()").fetch_one(&mut tx).await;
-
-    // If we encounter an error then return status 500,
-    // if not, extract the id from the query.
-    let location_id: u64 = selection_query.map_err(error::ErrorInternalServerError)?.get(0);
+    check_icon(&**icons, location.icon).await?;
 
-    // Finally, commit the changes to make them permanent
-    tx.commit().await.map_err(error::ErrorInternalServerError)?;
+    let location_id = store.put_location(&location).await.map_err(store_error("location"))?;
 
     let map: HashMap<&str, u64> = collection! {
         "location_id" => location_id
@@ -83,54 +131,88 @@ async fn put_location(pool: web::Data<MySqlPool>, _user: AuthedUser, location: w
     Ok(HttpResponse::Created().json(map))
 }
 
-#[rustfmt::skip]
+#[utoipa::path(
+    post,
+    path = "/location/{location_id}",
+    tag = "locations",
+    params(("location_id" = u64, Path, description = "The id of the location")),
+    responses((status = 200, description = "The location was updated"))
+)]
 #[actix_web::post("/location/{location_id}")]
-async fn update_location(pool: web::Data<MySqlPool>, _user: AuthedUser, req: HttpRequest, location: web::Json<Location>) -> actix_web::Result<HttpResponse> {
-    let location_id: u64 = get_param(&req, "location_id", "location id must be a number!")?;
+async fn update_location(
+    store: web::Data<dyn LocationStore>,
+    icons: web::Data<dyn IconStore>,
+    _user: AuthedUser,
+    req: HttpRequest,
+    location: web::Json<Location>,
+) -> ApiResult<HttpResponse> {
+    let location_id: u64 = get_param(&req, "location")?;
     if location.id != location_id {
-        return Err(error::ErrorBadRequest("the location ids don't match!"));
+        return Err(ApiError::IdMismatch("location"));
     }
 
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
-
-    // Update the object in the sql table...
-    let query: Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> = sqlx::query("UPDATE locations SET name = ?, database_id = ? WHERE id = ?")
-        .bind(&location.name)
-        .bind(&location.database)
-        .bind(&location.id)
-        .execute(&mut connection)
-        .await;
-
-    // ...then make sure it didn't fail.
-    let result = query.map_err(|err| match err {
-        sqlx::Error::Database(db_error) if db_error.message().starts_with("Duplicate entry") => error::ErrorConflict("there already is a location with this name!"),
-        sqlx::Error::Database(db_error) if db_error.message().starts_with("Cannot add or update a child row: a foreign key constraint fails") => error::ErrorNotFound("unknown database id!"),
-        _ => error::ErrorInternalServerError(err),
-    })?;
-
-    // If nothing was changed, the location didn't even exist!
-    if result.rows_affected() == 0 {
-        return Err(error::ErrorNotFound("location not found!"));
-    }
+    check_icon(&**icons, location.icon).await?;
+
+    store.update_location(&location).await.map_err(store_error("location"))?;
 
     Ok(HttpResponse::Ok().finish())
 }
 
+#[utoipa::path(
+    delete,
+    path = "/location/{location_id}",
+    tag = "locations",
+    params(("location_id" = u64, Path, description = "The id of the location"), DeleteOptions),
+    responses((status = 200, description = "The location was moved to the trash"))
+)]
 #[actix_web::delete("/location/{location_id}")]
-async fn delete_location(pool: web::Data<MySqlPool>, _user: AuthedUser, req: HttpRequest) -> actix_web::Result<HttpResponse> {
-    let location_id: u64 = get_param(&req, "location_id", "location id must be a number!")?;
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
+async fn delete_location(store: web::Data<dyn LocationStore>, user: AuthedUser, req: HttpRequest, options: web::Query<DeleteOptions>) -> ApiResult<HttpResponse> {
+    let location_id: u64 = get_param(&req, "location")?;
 
-    let query: sqlx::mysql::MySqlQueryResult = sqlx::query("DELETE FROM locations WHERE id = ?")
-        .bind(&location_id)
-        .execute(&mut connection)
+    // All items inside are moved to the trash as well. Their files stay until the trash is purged.
+    store
+        .delete_location(location_id, options.children, &user_change(&user, "location deleted"))
         .await
-        .map_err(error::ErrorInternalServerError)?;
+        .map_err(store_error("location"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
 
-    // If nothing was deleted, the location didn't even exist!
-    if query.rows_affected() == 0 {
-        return Err(error::ErrorNotFound("location not found!"));
+/// The breadcrumb path from the top level location down to the location.
+pub(crate) fn location_path(location: &Location, locations: &[Location]) -> Vec<PathSegment> {
+    let mut path = vec![PathSegment {
+        id: location.id,
+        name: location.name.clone(),
+    }];
+
+    // The stores prevent cycles, but the length check makes sure we never loop forever
+    let mut parent_id = location.parent;
+    while let Some(parent) = parent_id.and_then(|parent_id| locations.iter().find(|other| other.id == parent_id)) {
+        if path.len() > locations.len() {
+            break;
+        }
+
+        path.push(PathSegment {
+            id: parent.id,
+            name: parent.name.clone(),
+        });
+        parent_id = parent.parent;
     }
 
-    Ok(HttpResponse::Ok().finish())
+    path.reverse();
+    path
+}
+
+fn location_tree(location: &Location, locations: &[Location]) -> LocationTree {
+    let mut children: Vec<LocationTree> = locations
+        .iter()
+        .filter(|other| other.parent == Some(location.id) && other.id != location.id)
+        .map(|child| location_tree(child, locations))
+        .collect();
+    children.sort_by(|a, b| a.location.name.cmp(&b.location.name));
+
+    LocationTree {
+        location: location.clone(),
+        children,
+    }
 }
@@ -0,0 +1,78 @@
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::error::{ApiError, ApiResult};
+use crate::metrics::{Metrics, MetricsText};
+use crate::storage::Stores;
+use crate::web_handlers::store_error;
+
+/// The token Prometheus has to send as "Authorization: Bearer <token>".
+/// Without one, everyone who can reach the service can read the metrics.
+pub(crate) struct MetricsToken(pub(crate) Option<String>);
+
+/// The metrics in the Prometheus text format. They are served next to the api,
+/// or on a port of their own if "metrics_port" is configured.
+#[actix_web::get("/metrics")]
+async fn get_metrics(metrics: web::Data<Metrics>, token: web::Data<MetricsToken>, stores: web::Data<Stores>, req: HttpRequest) -> ApiResult<HttpResponse> {
+    if let Some(token) = &token.0 {
+        let sent = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !sent.is_some_and(|sent| constant_time_eq(sent.as_bytes(), token.as_bytes())) {
+            return Err(ApiError::Forbidden("metrics.invalid_token", "invalid metrics token!"));
+        }
+    }
+
+    let mut out = MetricsText::default();
+    metrics.write_requests(&mut out);
+
+    if let Some(pool) = stores.status.pool_status().await.map_err(store_error("status"))? {
+        out.family("store_db_pool_connections", "gauge", "The open database connections, including the idle ones");
+        out.sample("store_db_pool_connections", &[], pool.size);
+        out.family("store_db_pool_idle_connections", "gauge", "The database connections that aren't used right now");
+        out.sample("store_db_pool_idle_connections", &[], pool.idle);
+        out.family("store_db_pool_acquire_seconds", "gauge", "How long it took to get a database connection for this scrape");
+        out.sample("store_db_pool_acquire_seconds", &[], pool.acquire_seconds);
+    }
+
+    let sessions = stores.sessions.count_sessions().await.map_err(store_error("session"))?;
+    out.family("store_sessions_active", "gauge", "The sessions of logged in users");
+    out.sample("store_sessions_active", &[], sessions);
+
+    write_entity_counts(&mut out, &stores).await?;
+
+    Ok(HttpResponse::Ok().content_type(MetricsText::CONTENT_TYPE).body(out.into_string()))
+}
+
+/// The number of locations, items and tags of every database. Global tags aren't counted.
+async fn write_entity_counts(out: &mut MetricsText, stores: &Stores) -> ApiResult<()> {
+    let databases = stores.databases.get_databases().await.map_err(store_error("database"))?;
+    let location_counts = stores.locations.count_locations().await.map_err(store_error("location"))?;
+    let item_counts = stores.items.count_items().await.map_err(store_error("item"))?;
+    let tag_counts = stores.tags.count_tags().await.map_err(store_error("tag"))?;
+
+    for (name, help, counts) in [
+        ("store_database_locations", "The locations of the database", location_counts),
+        ("store_database_items", "The items of the database", item_counts),
+        ("store_database_tags", "The tags that can only be used in the database", tag_counts),
+    ] {
+        out.family(name, "gauge", help);
+        for database in &databases {
+            let database_id = database.id.to_string();
+            out.sample(
+                name,
+                &[("database", &database_id), ("name", &database.name)],
+                counts.get(&database.id).copied().unwrap_or_default(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the tokens without leaking how much of them matches through the timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
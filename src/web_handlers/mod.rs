@@ -1,18 +1,44 @@
 use std::str::FromStr;
 
-use actix_web::{dev::ServiceResponse, error, middleware::ErrorHandlerResponse, web, HttpRequest, HttpResponse};
+use actix_multipart::Multipart;
+use actix_web::{error, web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
 use serde::{Deserialize, Serialize};
+use utoipa::openapi::OpenApi;
+use utoipa::ToSchema;
 
-use log::error;
 use sysinfo::SystemExt;
 
+use crate::error::{ApiError, ApiResult};
+use crate::models::AuthedUser;
+use crate::storage::{Change, StoreError};
+
 pub(crate) mod auth;
+pub(crate) mod backup;
 pub(crate) mod database;
+pub(crate) mod event;
+pub(crate) mod file;
+pub(crate) mod graphql;
+pub(crate) mod health;
+pub(crate) mod icon;
 pub(crate) mod item;
+pub(crate) mod item_csv;
+pub(crate) mod label;
+pub(crate) mod loan;
 pub(crate) mod location;
+pub(crate) mod metrics;
+pub(crate) mod movement;
+pub(crate) mod revision;
+pub(crate) mod scan;
+pub(crate) mod stock;
 pub(crate) mod tag;
+pub(crate) mod template;
+pub(crate) mod trash;
+
+#[cfg(test)]
+pub(crate) mod testing;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
 struct ServerInfo {
     supported_api_versions: Vec<u32>,
     server_version: String,
@@ -20,6 +46,13 @@ struct ServerInfo {
     os_version: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/info",
+    tag = "info",
+    security(()),
+    responses((status = 200, description = "The versions of the server and the api", body = ServerInfo))
+)]
 #[actix_web::get("/info")]
 async fn get_system_info() -> actix_web::Result<web::Json<ServerInfo>> {
     let system_info = sysinfo::System::new();
@@ -32,31 +65,100 @@ async fn get_system_info() -> actix_web::Result<web::Json<ServerInfo>> {
     }))
 }
 
+/// The description of the api in the OpenAPI 3 format.
+#[actix_web::get("/openapi.json")]
+async fn get_openapi(openapi: web::Data<OpenApi>) -> HttpResponse {
+    HttpResponse::Ok().json(&**openapi)
+}
+
+/// Serve the interactive documentation of the api (Swagger UI) at "docs/".
+/// It's only included if the server was built with the "swagger-ui" feature.
+pub(crate) fn api_docs(config: &mut web::ServiceConfig) {
+    #[cfg(feature = "swagger-ui")]
+    config.service(utoipa_swagger_ui::SwaggerUi::new("/docs/{_:.*}").config(utoipa_swagger_ui::Config::from("../openapi.json")));
+
+    #[cfg(not(feature = "swagger-ui"))]
+    let _ = config;
+}
+
 #[actix_web::get("/teapod")]
 async fn teapod() -> HttpResponse {
     HttpResponse::from_error(error::ErrorImATeapot("Your Coffee is in Another Castle!"))
 }
 
-pub(crate) fn sanitize_internal_error<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
-    let (request, old_response) = res.into_parts();
-    let new_response = actix_web::error::ErrorInternalServerError("");
-    let service_response = ServiceResponse::from_err(new_response, request);
+pub(crate) async fn not_implemented() -> actix_web::Result<HttpResponse> {
+    Ok(HttpResponse::NotImplemented().finish())
+}
 
-    if let Some(err) = old_response.error() {
-        error!("Internal Server Error: {}", err);
+/// Extract the id of the specified entity (e.g. "item" => "item_id")
+/// from a request or return error 400 (Bad Request).
+#[rustfmt::skip]
+fn get_param<T: FromStr>(req: &HttpRequest, entity: &'static str) -> ApiResult<T> {
+    req.match_info().query(&format!("{entity}_id")).parse::<T>().map_err(|_| ApiError::InvalidId(entity))
+}
+
+/// Convert a storage error into the matching api error.
+/// The entity name is used for the error codes and
+/// messages, e.g. "item.not_found" or "item not found!".
+pub(crate) fn store_error(entity: &'static str) -> impl Fn(StoreError) -> ApiError {
+    move |err| match err {
+        StoreError::NotFound => ApiError::NotFound(entity),
+        StoreError::Conflict => ApiError::Conflict(entity),
+        StoreError::UnknownReference(reference) => ApiError::UnknownReference(reference),
+        StoreError::Invalid(code, message) => ApiError::BadRequest(code, message.to_owned()),
+        StoreError::Internal(err) => ApiError::Internal(err),
     }
+}
 
-    Ok(ErrorHandlerResponse::Response(service_response.map_into_right_body()))
+/// A file from a multipart upload.
+struct Upload {
+    /// The file name that was sent by the client (if any)
+    file_name: Option<String>,
+
+    /// The content type that was sent by the client (if any)
+    content_type: Option<String>,
+    data: Vec<u8>,
 }
 
-pub(crate) async fn not_implemented() -> actix_web::Result<HttpResponse> {
-    Ok(HttpResponse::NotImplemented().finish())
+/// Read the first file of a multipart upload into memory.
+/// Uploads bigger than `max_size` bytes are rejected.
+async fn read_upload(mut payload: Multipart, max_size: usize) -> ApiResult<Upload> {
+    let invalid_upload = |err: actix_multipart::MultipartError| ApiError::BadRequest("upload.invalid", err.to_string());
+
+    let mut field = payload
+        .try_next()
+        .await
+        .map_err(invalid_upload)?
+        .ok_or_else(|| ApiError::BadRequest("upload.missing_file", "no file was uploaded!".to_owned()))?;
+
+    let file_name = field.content_disposition().and_then(|disposition| disposition.get_filename()).map(str::to_owned);
+    let content_type = field.content_type().map(|mime| mime.essence_str().to_owned());
+
+    let mut data = vec![];
+    while let Some(chunk) = field.try_next().await.map_err(invalid_upload)? {
+        if data.len() + chunk.len() > max_size {
+            return Err(ApiError::BadRequest("upload.too_large", format!("the file can't be bigger than {max_size} bytes!")));
+        }
+
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok(Upload { file_name, content_type, data })
 }
 
-/// Extract the specified field from a request
-/// or return error 400 (Bad Request) with the
-/// provided error message.
-#[rustfmt::skip]
-fn get_param<T: FromStr>(req: &HttpRequest, field_name: &str, error: &'static str) -> actix_web::Result<T, actix_web::Error> {
-    req.match_info().query(field_name).parse::<T>().map_err(|_| error::ErrorBadRequest(error))
+/// The current time as unix timestamp (seconds).
+pub(crate) fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// A change by the user that happens right now.
+pub(crate) fn user_change(user: &AuthedUser, reason: &str) -> Change {
+    Change {
+        user: Some(user.user_id),
+        time: unix_now(),
+        reason: reason.to_owned(),
+    }
 }
@@ -0,0 +1,101 @@
+use actix_web::{web, HttpRequest};
+use async_graphql::InputObject;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::error::{ApiError, ApiResult};
+use crate::events::Events;
+use crate::models::{AuthedUser, Movement, MovementKind};
+use crate::storage::{ItemStore, MovementStore};
+use crate::web_handlers::stock::{record_movements, AdjustedItem};
+use crate::web_handlers::{get_param, store_error, unix_now};
+
+#[derive(Deserialize, ToSchema, InputObject, Debug)]
+pub(crate) struct NewMovement {
+    kind: MovementKind,
+
+    /// How much was received or consumed. For adjustments, this is
+    /// the difference to the current amount (negative to take something away).
+    amount: i64,
+
+    #[serde(default)]
+    #[graphql(default)]
+    reason: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/item/{item_id}/movements",
+    tag = "stock",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    responses((status = 200, description = "The stock ledger of the item", body = Vec<Movement>))
+)]
+#[actix_web::get("/item/{item_id}/movements")]
+async fn get_item_movements(store: web::Data<dyn MovementStore>, items: web::Data<dyn ItemStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<Vec<Movement>>> {
+    let item_id: u64 = get_param(&req, "item")?;
+
+    // Make sure the item exists, otherwise it would just look unchanged
+    items.get_item(item_id).await.map_err(store_error("item"))?;
+    let movements = store.get_movements(item_id).await.map_err(store_error("movement"))?;
+
+    Ok(web::Json(movements))
+}
+
+#[utoipa::path(
+    post,
+    path = "/item/{item_id}/movements",
+    tag = "stock",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    responses((status = 200, description = "The new amount of the item", body = AdjustedItem))
+)]
+#[actix_web::post("/item/{item_id}/movements")]
+async fn post_item_movement(
+    store: web::Data<dyn MovementStore>,
+    items: web::Data<dyn ItemStore>,
+    events: web::Data<Events>,
+    user: AuthedUser,
+    req: HttpRequest,
+    movement: web::Json<NewMovement>,
+) -> ApiResult<web::Json<AdjustedItem>> {
+    let item_id: u64 = get_param(&req, "item")?;
+
+    Ok(web::Json(record_movement(&**store, &**items, &events, &user, item_id, &movement).await?))
+}
+
+/// Record a movement of the item by the user and return the new amount.
+pub(crate) async fn record_movement(
+    store: &dyn MovementStore,
+    items: &dyn ItemStore,
+    events: &Events,
+    user: &AuthedUser,
+    item_id: u64,
+    movement: &NewMovement,
+) -> ApiResult<AdjustedItem> {
+    items.get_item(item_id).await.map_err(store_error("item"))?;
+
+    let amount = match movement.kind {
+        MovementKind::Receive | MovementKind::Consume if movement.amount <= 0 => {
+            return Err(ApiError::BadRequest("movement.invalid_amount", "the amount must be a positive number!".to_owned()));
+        }
+        MovementKind::Receive | MovementKind::Adjust => movement.amount,
+        MovementKind::Consume => -movement.amount,
+        MovementKind::Transfer => {
+            return Err(ApiError::BadRequest("movement.transfer", "items are transferred by changing their location!".to_owned()));
+        }
+    };
+
+    let movement = Movement {
+        id: 0,
+        item: item_id,
+        kind: movement.kind,
+        amount,
+        location: None,
+        from_location: None,
+        reason: movement.reason.clone(),
+        user: Some(user.user_id),
+        time: unix_now(),
+    };
+
+    let adjusted = record_movements(store, events, &[movement]).await?;
+    adjusted.into_iter().next().ok_or(ApiError::NotFound("item"))
+}
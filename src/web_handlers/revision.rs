@@ -0,0 +1,172 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::{ApiError, ApiResult};
+use crate::events::Events;
+use crate::models::{AuthedUser, Item, ItemRevision};
+use crate::storage::{FileStore, ItemStore, RevisionStore, StoreError};
+use crate::web_handlers::file::remove_missing_files;
+use crate::web_handlers::stock::notify_low_stock;
+use crate::web_handlers::{get_param, store_error, user_change};
+
+/// A revision without the snapshot, for the history list.
+#[derive(Serialize, ToSchema, Debug)]
+struct RevisionInfo {
+    revision: u64,
+    user: Option<u64>,
+    time: i64,
+    reason: String,
+    deleted: bool,
+}
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct DiffOptions {
+    from: u64,
+    to: u64,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+struct RevisionDiff {
+    from: u64,
+    to: u64,
+    changes: Vec<FieldChange>,
+}
+
+/// A field of the item that is different between two revisions.
+#[derive(Serialize, ToSchema, Debug)]
+struct FieldChange {
+    field: String,
+    old: Value,
+    new: Value,
+}
+
+#[utoipa::path(
+    get,
+    path = "/item/{item_id}/history",
+    tag = "history",
+    params(("item_id" = u64, Path, description = "The id of the item")),
+    responses((status = 200, description = "The revisions of the item, the oldest first", body = Vec<RevisionInfo>))
+)]
+#[actix_web::get("/item/{item_id}/history")]
+async fn get_item_history(store: web::Data<dyn RevisionStore>, items: web::Data<dyn ItemStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<Vec<RevisionInfo>>> {
+    let item_id: u64 = get_param(&req, "item")?;
+
+    let revisions = store.get_revisions(item_id).await.map_err(store_error("revision"))?;
+    if revisions.is_empty() {
+        // Items that weren't changed since revisions exist have no history yet
+        items.get_item(item_id).await.map_err(store_error("item"))?;
+    }
+
+    Ok(web::Json(
+        revisions
+            .into_iter()
+            .map(|revision| RevisionInfo {
+                revision: revision.revision,
+                user: revision.user,
+                time: revision.time,
+                reason: revision.reason,
+                deleted: revision.deleted,
+            })
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/item/{item_id}/history/{revision_id}",
+    tag = "history",
+    params(("item_id" = u64, Path, description = "The id of the item"), ("revision_id" = u64, Path, description = "The number of the revision")),
+    responses((status = 200, description = "The revision with the snapshot of the item", body = ItemRevision))
+)]
+#[actix_web::get("/item/{item_id}/history/{revision_id}")]
+async fn get_item_revision(store: web::Data<dyn RevisionStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<ItemRevision>> {
+    let item_id: u64 = get_param(&req, "item")?;
+    let revision: u64 = get_param(&req, "revision")?;
+
+    Ok(web::Json(store.get_revision(item_id, revision).await.map_err(store_error("revision"))?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/item/{item_id}/diff",
+    tag = "history",
+    params(("item_id" = u64, Path, description = "The id of the item"), DiffOptions),
+    responses((status = 200, description = "The fields that are different", body = RevisionDiff))
+)]
+#[actix_web::get("/item/{item_id}/diff")]
+async fn get_item_diff(store: web::Data<dyn RevisionStore>, _user: AuthedUser, req: HttpRequest, options: web::Query<DiffOptions>) -> ApiResult<web::Json<RevisionDiff>> {
+    let item_id: u64 = get_param(&req, "item")?;
+
+    let old = store.get_revision(item_id, options.from).await.map_err(store_error("revision"))?;
+    let new = store.get_revision(item_id, options.to).await.map_err(store_error("revision"))?;
+
+    Ok(web::Json(RevisionDiff {
+        from: options.from,
+        to: options.to,
+        changes: diff_items(&old.snapshot, &new.snapshot)?,
+    }))
+}
+
+/// Bring the item back to the state of the revision. Deleted items are inserted again.
+#[utoipa::path(
+    post,
+    path = "/item/{item_id}/restore/{revision_id}",
+    tag = "history",
+    params(("item_id" = u64, Path, description = "The id of the item"), ("revision_id" = u64, Path, description = "The number of the revision")),
+    responses((status = 200, description = "The item was restored"))
+)]
+#[actix_web::post("/item/{item_id}/restore/{revision_id}")]
+async fn restore_item_revision(
+    store: web::Data<dyn RevisionStore>,
+    items: web::Data<dyn ItemStore>,
+    files: web::Data<dyn FileStore>,
+    events: web::Data<Events>,
+    user: AuthedUser,
+    req: HttpRequest,
+) -> ApiResult<HttpResponse> {
+    let item_id: u64 = get_param(&req, "item")?;
+    let revision: u64 = get_param(&req, "revision")?;
+
+    let revision = store.get_revision(item_id, revision).await.map_err(store_error("revision"))?;
+    let change = user_change(&user, &format!("restored revision {}", revision.revision));
+    let mut item = Item {
+        last_edited: change.time,
+        ..revision.snapshot
+    };
+
+    // The uploaded files might have been deleted (or purged with the item) since the revision
+    let item_files = files.get_item_files(item_id).await.map_err(store_error("file"))?;
+    remove_missing_files(&req, &mut item, &item_files)?;
+
+    let was_low_stock = match items.get_item(item_id).await {
+        Ok(old_item) => old_item.is_low_stock(),
+        Err(StoreError::NotFound) => false,
+        Err(err) => return Err(store_error("item")(err)),
+    };
+    store.restore_revision(&item, &change).await.map_err(store_error("item"))?;
+    notify_low_stock(&events, was_low_stock, item.id, &item.name, item.amount, item.min_amount);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Compare the fields of two items.
+fn diff_items(old: &Item, new: &Item) -> ApiResult<Vec<FieldChange>> {
+    let to_fields = |item: &Item| match serde_json::to_value(item) {
+        Ok(Value::Object(fields)) => Ok(fields),
+        Ok(_) => Err(ApiError::Internal("an item isn't serialized as object".into())),
+        Err(err) => Err(ApiError::Internal(Box::new(err))),
+    };
+    let old = to_fields(old)?;
+    let mut new = to_fields(new)?;
+
+    Ok(old
+        .into_iter()
+        .filter_map(|(field, old)| {
+            let new = new.remove(&field).unwrap_or(Value::Null);
+            (old != new).then_some(FieldChange { field, old, new })
+        })
+        .collect())
+}
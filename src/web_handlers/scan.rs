@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::collection;
+use crate::error::{ApiError, ApiResult};
+use crate::labels::parse_label_link;
+use crate::models::{AuthedUser, CodeKind, Item, Location, ScanCode};
+use crate::storage::{CodeStore, ItemStore, LocationStore, StoreError};
+use crate::web_handlers::store_error;
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct CodeFilter {
+    /// Only return the codes of this item
+    item: Option<u64>,
+
+    /// Only return the codes of this location
+    location: Option<u64>,
+}
+
+/// The object a scanned code belongs to. The code is missing if
+/// the scan was the deep link on a printed label.
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ScanResult {
+    Item { code: Option<ScanCode>, item: Box<Item> },
+    Location { code: Option<ScanCode>, location: Location },
+}
+
+#[utoipa::path(
+    get,
+    path = "/codes",
+    tag = "codes",
+    params(CodeFilter),
+    responses((status = 200, description = "The codes", body = Vec<ScanCode>))
+)]
+#[actix_web::get("/codes")]
+async fn get_codes(store: web::Data<dyn CodeStore>, _user: AuthedUser, filter: web::Query<CodeFilter>) -> ApiResult<web::Json<Vec<ScanCode>>> {
+    let mut codes = store.get_codes().await.map_err(store_error("code"))?;
+
+    if filter.item.is_some() {
+        codes.retain(|code| code.item == filter.item);
+    }
+    if filter.location.is_some() {
+        codes.retain(|code| code.location == filter.location);
+    }
+
+    Ok(web::Json(codes))
+}
+
+#[utoipa::path(
+    put,
+    path = "/code",
+    tag = "codes",
+    responses((status = 201, description = "The code was created", body = HashMap<String, String>, example = json!({"code": "4006381333931"})))
+)]
+#[actix_web::put("/code")]
+async fn put_code(store: web::Data<dyn CodeStore>, _user: AuthedUser, code: web::Json<ScanCode>) -> ApiResult<HttpResponse> {
+    let mut code = code.into_inner();
+    if code.item.is_some() == code.location.is_some() {
+        return Err(ApiError::BadRequest("code.invalid_owner", "a code belongs to either an item or a location!".to_owned()));
+    }
+
+    // The code is stored the way the scan lookup expects it
+    code.code = normalize_code(code.kind, &code.code).ok_or_else(|| ApiError::BadRequest("code.invalid", format!("this isn't a valid {} code!", code.kind.as_str())))?;
+
+    store.put_code(&code).await.map_err(store_error("code"))?;
+
+    let map: HashMap<&str, String> = collection! {
+        "code" => code.code
+    };
+    Ok(HttpResponse::Created().json(map))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/code/{code}",
+    tag = "codes",
+    params(("code" = String, Path, description = "The code as it is stored")),
+    responses((status = 200, description = "The code was deleted"))
+)]
+#[actix_web::delete("/code/{code}")]
+async fn delete_code(store: web::Data<dyn CodeStore>, _user: AuthedUser, code: web::Path<String>) -> ApiResult<HttpResponse> {
+    store.delete_code(&code).await.map_err(store_error("code"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Find the item or location of a scanned code, so that the app can jump straight to it.
+#[utoipa::path(
+    get,
+    path = "/scan/{code}",
+    tag = "codes",
+    params(("code" = String, Path, description = "The scanned code or the link on a printed label")),
+    responses((status = 200, description = "The item or location of the code", body = ScanResult))
+)]
+#[actix_web::get("/scan/{code}")]
+async fn scan_code(
+    store: web::Data<dyn CodeStore>,
+    items: web::Data<dyn ItemStore>,
+    locations: web::Data<dyn LocationStore>,
+    _user: AuthedUser,
+    code: web::Path<String>,
+) -> ApiResult<web::Json<ScanResult>> {
+    let (item_id, location_id, code) = match store.find_code(&scan_candidates(&code)).await {
+        Ok(code) => (code.item, code.location, Some(code)),
+        Err(StoreError::NotFound) => match parse_label_link(&code) {
+            Some(("item", item_id)) => (Some(item_id), None, None),
+            Some((_, location_id)) => (None, Some(location_id), None),
+            None => return Err(ApiError::NotFound("code")),
+        },
+        Err(err) => return Err(store_error("code")(err)),
+    };
+
+    let result = match (item_id, location_id) {
+        (Some(item_id), _) => ScanResult::Item {
+            item: Box::new(items.get_item(item_id).await.map_err(store_error("item"))?),
+            code,
+        },
+        (None, Some(location_id)) => ScanResult::Location {
+            location: locations.get_location(location_id).await.map_err(store_error("location"))?,
+            code,
+        },
+        (None, None) => return Err(ApiError::NotFound("code")),
+    };
+
+    Ok(web::Json(result))
+}
+
+/// Check the code and bring it into the form it's stored in.
+/// Returns None if the code doesn't match its kind.
+fn normalize_code(kind: CodeKind, code: &str) -> Option<String> {
+    if code.is_empty() || code.len() > 255 {
+        return None;
+    }
+
+    match kind {
+        CodeKind::Ean => (is_digits(code) && (code.len() == 8 || code.len() == 13) && has_valid_check_digit(code)).then(|| code.to_owned()),
+        CodeKind::Upc => (is_digits(code) && code.len() == 12 && has_valid_check_digit(code)).then(|| code.to_owned()),
+        CodeKind::Nfc => normalize_nfc_uid(code),
+        CodeKind::Qr | CodeKind::Other => Some(code.to_owned()),
+    }
+}
+
+/// The spellings of a scanned code, the exact one first. Scanners report
+/// UPC-A codes with or without the leading zero of EAN-13, and NFC readers
+/// format the UID in different ways (e.g. "04:a2:3b:..." or "04A23B...").
+fn scan_candidates(code: &str) -> Vec<String> {
+    let mut candidates = vec![code.to_owned()];
+
+    if is_digits(code) && code.len() == 12 {
+        candidates.push(format!("0{code}"));
+    }
+    if is_digits(code) && code.len() == 13 && code.starts_with('0') {
+        candidates.push(code[1..].to_owned());
+    }
+    if let Some(uid) = normalize_nfc_uid(code) {
+        candidates.push(uid);
+    }
+
+    candidates.dedup();
+    candidates
+}
+
+/// NFC UIDs are 4, 7 or 10 bytes. They are stored as uppercase hex without separators.
+fn normalize_nfc_uid(uid: &str) -> Option<String> {
+    let uid: String = uid.chars().filter(|c| !matches!(c, ':' | '-' | ' ')).collect();
+    let is_hex = uid.chars().all(|c| c.is_ascii_hexdigit());
+
+    (is_hex && matches!(uid.len(), 8 | 14 | 20)).then(|| uid.to_ascii_uppercase())
+}
+
+fn is_digits(code: &str) -> bool {
+    !code.is_empty() && code.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// The last digit of EAN and UPC codes is a checksum of the other ones
+/// (GS1 algorithm: starting from the right, every other digit counts three times).
+fn has_valid_check_digit(code: &str) -> bool {
+    let mut digits = code.bytes().rev().map(|byte| u32::from(byte - b'0'));
+    let check_digit = match digits.next() {
+        Some(digit) => digit,
+        None => return false,
+    };
+
+    let sum: u32 = digits.enumerate().map(|(index, digit)| if index % 2 == 0 { digit * 3 } else { digit }).sum();
+    (10 - sum % 10) % 10 == check_digit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_valid_check_digit, normalize_code, normalize_nfc_uid, scan_candidates};
+    use crate::models::CodeKind;
+
+    #[test]
+    fn check_digits_are_verified() {
+        let cases = [
+            ("96385074", true),       // EAN-8
+            ("4006381333931", true),  // EAN-13
+            ("036000291452", true),   // UPC-A
+            ("96385075", false),      // EAN-8 with a wrong check digit
+            ("4006381333932", false), // EAN-13 with a wrong check digit
+            ("036000291453", false),  // UPC-A with a wrong check digit
+            ("", false),
+        ];
+        for (code, valid) in cases {
+            assert_eq!(has_valid_check_digit(code), valid, "{code}");
+        }
+    }
+
+    #[test]
+    fn codes_are_checked_by_their_kind() {
+        let cases = [
+            (CodeKind::Ean, "96385074", Some("96385074")),
+            (CodeKind::Ean, "4006381333931", Some("4006381333931")),
+            (CodeKind::Ean, "4006381333932", None),
+            (CodeKind::Ean, "036000291452", None),
+            (CodeKind::Ean, "400638133393a", None),
+            (CodeKind::Upc, "036000291452", Some("036000291452")),
+            (CodeKind::Upc, "036000291453", None),
+            (CodeKind::Upc, "4006381333931", None),
+            (CodeKind::Nfc, "04:a2:3b:c4", Some("04A23BC4")),
+            (CodeKind::Nfc, "04:a2", None),
+            (CodeKind::Qr, "https://example.com/?a=b", Some("https://example.com/?a=b")),
+            (CodeKind::Other, "", None),
+        ];
+        for (kind, code, normalized) in cases {
+            assert_eq!(normalize_code(kind, code).as_deref(), normalized, "{kind:?} {code}");
+        }
+        assert_eq!(normalize_code(CodeKind::Other, &"a".repeat(256)), None);
+    }
+
+    #[test]
+    fn nfc_uids_are_uppercase_hex_without_separators() {
+        let cases = [
+            ("04A23BC4", Some("04A23BC4")),
+            ("04:a2:3b:c4", Some("04A23BC4")),
+            ("04-A2-3b-C4-11-22-33", Some("04A23BC4112233")),
+            ("04 a2 3b c4 11 22 33 44 55 66", Some("04A23BC4112233445566")),
+            ("04:a2:3b", None),
+            ("04:a2:3b:c4:11", None),
+            ("04:g2:3b:c4", None),
+        ];
+        for (uid, normalized) in cases {
+            assert_eq!(normalize_nfc_uid(uid).as_deref(), normalized, "{uid}");
+        }
+    }
+
+    #[test]
+    fn scans_match_the_other_spellings() {
+        let cases: [(&str, &[&str]); 5] = [
+            ("036000291452", &["036000291452", "0036000291452"]),
+            ("0036000291452", &["0036000291452", "036000291452"]),
+            ("4006381333931", &["4006381333931"]),
+            ("04:a2:3b:c4", &["04:a2:3b:c4", "04A23BC4"]),
+            ("04A23BC4", &["04A23BC4"]),
+        ];
+        for (code, candidates) in cases {
+            assert_eq!(scan_candidates(code), candidates, "{code}");
+        }
+    }
+}
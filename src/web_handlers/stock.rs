@@ -0,0 +1,165 @@
+use actix_web::web;
+use async_graphql::{InputObject, SimpleObject};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiResult;
+use crate::events::{Event, Events};
+use crate::models::{AuthedUser, Item, Movement, MovementKind};
+use crate::storage::{ItemStore, MovementStore};
+use crate::web_handlers::{store_error, unix_now};
+
+#[derive(Deserialize, ToSchema, InputObject, Debug)]
+pub(crate) struct Adjustment {
+    item: u64,
+
+    /// The difference to the current amount (negative to take something away)
+    delta: i64,
+
+    /// Why the amount changed (e.g. "counted")
+    #[serde(default)]
+    #[graphql(default)]
+    reason: String,
+}
+
+#[derive(Serialize, ToSchema, SimpleObject, Debug)]
+pub(crate) struct AdjustedItem {
+    item: u64,
+    old_amount: u64,
+    amount: u64,
+    low_stock: bool,
+}
+
+/// An entry of the shopping list.
+#[derive(Serialize, ToSchema, Debug)]
+struct ShoppingListEntry {
+    item: u64,
+    name: String,
+    location: u64,
+    amount: u64,
+    min_amount: u64,
+
+    /// How much has to be bought to be above the minimum stock level again
+    needed: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/items/low-stock",
+    tag = "stock",
+    responses((status = 200, description = "The items that are low on stock", body = Vec<Item>))
+)]
+#[actix_web::get("/items/low-stock")]
+async fn get_low_stock_items(store: web::Data<dyn ItemStore>, _user: AuthedUser) -> ApiResult<web::Json<Vec<Item>>> {
+    let mut items = store.get_items().await.map_err(store_error("item"))?;
+    items.retain(Item::is_low_stock);
+
+    Ok(web::Json(items))
+}
+
+/// The shopping list is derived from the items that are low on stock,
+/// so it is always up to date without having to be maintained.
+#[utoipa::path(
+    get,
+    path = "/shopping-list",
+    tag = "stock",
+    responses((status = 200, description = "The shopping list, sorted by name", body = Vec<ShoppingListEntry>))
+)]
+#[actix_web::get("/shopping-list")]
+async fn get_shopping_list(store: web::Data<dyn ItemStore>, _user: AuthedUser) -> ApiResult<web::Json<Vec<ShoppingListEntry>>> {
+    let items = store.get_items().await.map_err(store_error("item"))?;
+
+    let mut entries: Vec<ShoppingListEntry> = items
+        .into_iter()
+        .filter_map(|item| {
+            let min_amount = item.min_amount.filter(|min_amount| item.amount <= *min_amount)?;
+
+            Some(ShoppingListEntry {
+                needed: min_amount - item.amount + 1,
+                item: item.id,
+                name: item.name,
+                location: item.location,
+                amount: item.amount,
+                min_amount,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(web::Json(entries))
+}
+
+/// Adjust the amounts of many items at once. They are recorded as adjustments in the stock ledger.
+#[utoipa::path(
+    post,
+    path = "/items/adjust",
+    tag = "stock",
+    responses((status = 200, description = "The new amounts of the items", body = Vec<AdjustedItem>))
+)]
+#[actix_web::post("/items/adjust")]
+async fn adjust_amounts(
+    store: web::Data<dyn MovementStore>,
+    events: web::Data<Events>,
+    user: AuthedUser,
+    adjustments: web::Json<Vec<Adjustment>>,
+) -> ApiResult<web::Json<Vec<AdjustedItem>>> {
+    Ok(web::Json(adjust_items(&**store, &events, &user, &adjustments).await?))
+}
+
+/// Record the adjustments by the user and return the new amounts.
+pub(crate) async fn adjust_items(store: &dyn MovementStore, events: &Events, user: &AuthedUser, adjustments: &[Adjustment]) -> ApiResult<Vec<AdjustedItem>> {
+    let time = unix_now();
+    let movements: Vec<Movement> = adjustments
+        .iter()
+        .map(|adjustment| Movement {
+            id: 0,
+            item: adjustment.item,
+            kind: MovementKind::Adjust,
+            amount: adjustment.delta,
+            location: None,
+            from_location: None,
+            reason: adjustment.reason.clone(),
+            user: Some(user.user_id),
+            time,
+        })
+        .collect();
+
+    record_movements(store, events, &movements).await
+}
+
+/// Apply the movements and notify about items that are low on stock now.
+pub(crate) async fn record_movements(store: &dyn MovementStore, events: &Events, movements: &[Movement]) -> ApiResult<Vec<AdjustedItem>> {
+    let adjusted = store.record_movements(movements).await.map_err(store_error("movement"))?;
+
+    let mut response = Vec::with_capacity(adjusted.len());
+    for adjusted in adjusted {
+        let was_low_stock = adjusted.min_amount.is_some_and(|min_amount| adjusted.old_amount <= min_amount);
+        notify_low_stock(events, was_low_stock, adjusted.item_id, &adjusted.name, adjusted.amount, adjusted.min_amount);
+
+        response.push(AdjustedItem {
+            item: adjusted.item_id,
+            old_amount: adjusted.old_amount,
+            amount: adjusted.amount,
+            low_stock: adjusted.min_amount.is_some_and(|min_amount| adjusted.amount <= min_amount),
+        });
+    }
+
+    Ok(response)
+}
+
+/// Publish a low stock event if the item just dropped to its minimum
+/// stock level. Items that already were low on stock are ignored,
+/// otherwise every change would cause another notification.
+pub(crate) fn notify_low_stock(events: &Events, was_low_stock: bool, item_id: u64, name: &str, amount: u64, min_amount: Option<u64>) {
+    let min_amount = match min_amount {
+        Some(min_amount) if amount <= min_amount && !was_low_stock => min_amount,
+        _ => return,
+    };
+
+    events.publish(Event::LowStock {
+        item: item_id,
+        name: name.to_owned(),
+        amount,
+        min_amount,
+    });
+}
@@ -1,77 +1,81 @@
 use std::collections::HashMap;
 
-use actix_web::{error, web, HttpRequest, HttpResponse};
-use sqlx::{MySqlPool, Row};
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use utoipa::IntoParams;
 
 use crate::collection;
+use crate::error::{ApiError, ApiResult};
 use crate::models::{AuthedUser, Tag};
-use crate::web_handlers::get_param;
+use crate::storage::{IconStore, TagStore};
+use crate::web_handlers::icon::check_icon;
+use crate::web_handlers::{get_param, store_error, unix_now, user_change};
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct TagFilter {
+    /// Only return the tags that can be used in this database (its own and the global ones)
+    database: Option<u64>,
+}
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+struct MergeOptions {
+    /// The tag that replaces the merged one
+    into: u64,
+}
 
+#[utoipa::path(
+    get,
+    path = "/tags",
+    tag = "tags",
+    params(TagFilter),
+    responses((status = 200, description = "The tags", body = Vec<Tag>))
+)]
 #[actix_web::get("/tags")]
-async fn get_tags(pool: web::Data<MySqlPool>, _user: AuthedUser) -> actix_web::Result<web::Json<Vec<Tag>>> {
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
+async fn get_tags(store: web::Data<dyn TagStore>, _user: AuthedUser, filter: web::Query<TagFilter>) -> ApiResult<web::Json<Vec<Tag>>> {
+    let mut tags = store.get_tags().await.map_err(store_error("tag"))?;
 
-    let tags = sqlx::query_as::<_, Tag>("SELECT * FROM tags")
-        .fetch_all(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?;
+    if let Some(database_id) = filter.database {
+        tags.retain(|tag| tag.database.is_none_or(|tag_database| tag_database == database_id));
+    }
 
     Ok(web::Json(tags))
 }
 
+#[utoipa::path(
+    get,
+    path = "/tag/{tag_id}",
+    tag = "tags",
+    params(("tag_id" = u64, Path, description = "The id of the tag")),
+    responses((status = 200, description = "The tag", body = Tag))
+)]
 #[actix_web::get("/tag/{tag_id}")]
-async fn get_tag(pool: web::Data<MySqlPool>, _user: AuthedUser, req: HttpRequest) -> actix_web::Result<web::Json<Tag>> {
-    let tag_id: u64 = get_param(&req, "tag_id", "tag id must be a number!")?;
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
-
-    // Query for the object and auto convert it.
-    let query: Result<Tag, sqlx::Error> = sqlx::query_as::<_, Tag>("SELECT * FROM tags WHERE id = ?").bind(tag_id).fetch_one(&mut connection).await;
+async fn get_tag(store: web::Data<dyn TagStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<Tag>> {
+    let tag_id: u64 = get_param(&req, "tag")?;
 
-    // Check if the query was successful and return the tag,
-    // if the tag could not be found, set the status code to 404.
+    // If the tag could not be found, the error code is "tag.not_found".
     // Should a different kind of error occur, return an Internal Server Error (code: 500).
-    let tag = query.map_err(|err| match err {
-        sqlx::Error::RowNotFound => error::ErrorNotFound("tag not found!"),
-        _ => error::ErrorInternalServerError(err),
-    })?;
+    let tag = store.get_tag(tag_id).await.map_err(store_error("tag"))?;
 
     Ok(web::Json(tag))
 }
 
+#[utoipa::path(
+    put,
+    path = "/tag",
+    tag = "tags",
+    responses((status = 201, description = "The tag was created", body = HashMap<String, u64>, example = json!({"tag_id": 1})))
+)]
 #[actix_web::put("/tag")]
-async fn put_tag(pool: web::Data<MySqlPool>, _user: AuthedUser, tag: web::Json<Tag>) -> actix_web::Result<HttpResponse> {
+async fn put_tag(store: web::Data<dyn TagStore>, icons: web::Data<dyn IconStore>, _user: AuthedUser, tag: web::Json<Tag>) -> ApiResult<HttpResponse> {
     if tag.id != 0 {
-        return Err(error::ErrorBadRequest("tag id must be 0!"));
-    }
-
-    // We need to make a transaction here because we want to make 2 queries that relate to each other.
-    let mut tx = pool.begin().await.map_err(error::ErrorInternalServerError)?;
-
-    // First insert the object into the sql table...
-    let insertion_query: Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> = sqlx::query("INSERT INTO tags (name,color,icon) VALUES (?,?,?)")
-        .bind(&tag.name)
-        .bind(&tag.color)
-        .bind(&tag.icon)
-        .execute(&mut tx)
-        .await;
-
-    // ...then make sure it didn't fail.
-    if let Err(error) = insertion_query {
-        return Err(match error {
-            sqlx::Error::Database(db_error) if db_error.message().starts_with("Duplicate entry") => error::ErrorConflict("there already is a tag with this name!"),
-            _ => error::ErrorInternalServerError(error),
-        });
+        return Err(ApiError::IdNotZero("tag"));
     }
 
-    // After that we need to get the autogenerated id from the table.
-    let tag_id: u64 = sqlx::query("SELECT LAST_INSERT_ID()")
-        .fetch_one(&mut tx)
-        .await
-        .map_err(error::ErrorInternalServerError)?
-        .get(0);
+    check_icon(&**icons, tag.icon).await?;
 
-    // Finally, commit the changes to make them permanent
-    tx.commit().await.map_err(error::ErrorInternalServerError)?;
+    let tag_id = store.put_tag(&tag).await.map_err(store_error("tag"))?;
 
     let map: HashMap<&str, u64> = collection! {
         "tag_id" => tag_id
@@ -79,53 +83,170 @@ async fn put_tag(pool: web::Data<MySqlPool>, _user: AuthedUser, tag: web::Json<T
     Ok(HttpResponse::Created().json(map))
 }
 
+#[utoipa::path(
+    post,
+    path = "/tag/{tag_id}",
+    tag = "tags",
+    params(("tag_id" = u64, Path, description = "The id of the tag")),
+    responses((status = 200, description = "The tag was updated"))
+)]
 #[actix_web::post("/tag/{tag_id}")]
-async fn update_tag(pool: web::Data<MySqlPool>, _user: AuthedUser, req: HttpRequest, tag: web::Json<Tag>) -> actix_web::Result<HttpResponse> {
-    let tag_id: u64 = get_param(&req, "tag_id", "tag id must be a number!")?;
+async fn update_tag(store: web::Data<dyn TagStore>, icons: web::Data<dyn IconStore>, _user: AuthedUser, req: HttpRequest, tag: web::Json<Tag>) -> ApiResult<HttpResponse> {
+    let tag_id: u64 = get_param(&req, "tag")?;
     if tag.id != tag_id {
-        return Err(error::ErrorBadRequest("the tag ids don't match!"));
+        return Err(ApiError::IdMismatch("tag"));
     }
 
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
+    check_icon(&**icons, tag.icon).await?;
 
-    // Update the object in the sql table...
-    let query: Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> = sqlx::query("UPDATE tags SET name = ?, color = ?, icon = ? WHERE id = ?")
-        .bind(&tag.name)
-        .bind(&tag.color)
-        .bind(&tag.icon)
-        .bind(&tag.id)
-        .execute(&mut connection)
-        .await;
+    store.update_tag(&tag).await.map_err(store_error("tag"))?;
 
-    // ...then make sure it didn't fail.
-    let result = query.map_err(|err| match err {
-        sqlx::Error::Database(db_error) if db_error.message().starts_with("Duplicate entry") => error::ErrorConflict("there already is a tag with this name!"),
-        _ => error::ErrorInternalServerError(err),
-    })?;
+    Ok(HttpResponse::Ok().finish())
+}
 
-    // If nothing was changed, the tag didn't even exist!
-    if result.rows_affected() == 0 {
-        return Err(error::ErrorNotFound("tag not found!"));
-    }
+#[utoipa::path(
+    delete,
+    path = "/tag/{tag_id}",
+    tag = "tags",
+    params(("tag_id" = u64, Path, description = "The id of the tag")),
+    responses((status = 200, description = "The tag was moved to the trash"))
+)]
+#[actix_web::delete("/tag/{tag_id}")]
+async fn delete_tag(store: web::Data<dyn TagStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<HttpResponse> {
+    let tag_id: u64 = get_param(&req, "tag")?;
+
+    store.delete_tag(tag_id, unix_now()).await.map_err(store_error("tag"))?;
 
     Ok(HttpResponse::Ok().finish())
 }
 
-#[actix_web::delete("/tag/{tag_id}")]
-async fn delete_tag(pool: web::Data<MySqlPool>, _user: AuthedUser, req: HttpRequest) -> actix_web::Result<HttpResponse> {
-    let tag_id: u64 = get_param(&req, "tag_id", "tag id must be a number!")?;
-    let mut connection = pool.acquire().await.map_err(error::ErrorInternalServerError)?;
-
-    let query: sqlx::mysql::MySqlQueryResult = sqlx::query("DELETE FROM tags WHERE id = ?")
-        .bind(&tag_id)
-        .execute(&mut connection)
-        .await
-        .map_err(error::ErrorInternalServerError)?;
-
-    // If nothing was deleted, the tag didn't even exist!
-    if query.rows_affected() == 0 {
-        return Err(error::ErrorNotFound("tag not found!"));
-    }
+/// Merge the tag into another one, e.g. "USB C" into "USB-C". The items and templates
+/// get the other tag instead and the child tags move below it. The tag itself is gone afterwards.
+#[utoipa::path(
+    post,
+    path = "/tag/{tag_id}/merge",
+    tag = "tags",
+    params(("tag_id" = u64, Path, description = "The id of the tag"), MergeOptions),
+    responses((status = 200, description = "The tag was merged"))
+)]
+#[actix_web::post("/tag/{tag_id}/merge")]
+async fn merge_tag(store: web::Data<dyn TagStore>, user: AuthedUser, req: HttpRequest, options: web::Query<MergeOptions>) -> ApiResult<HttpResponse> {
+    let tag_id: u64 = get_param(&req, "tag")?;
+
+    store.merge_tag(tag_id, options.into, &user_change(&user, "tag merged")).await.map_err(store_error("tag"))?;
 
     Ok(HttpResponse::Ok().finish())
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web};
+    use serde_json::json;
+
+    use crate::models::Tag;
+    use crate::storage::{Change, Stores};
+    use crate::web_handlers::testing::{authed, create_item, create_location, init_stores, send};
+
+    fn services(config: &mut web::ServiceConfig) {
+        config.service(super::update_tag).service(super::merge_tag);
+    }
+
+    async fn create_tag(stores: &Stores, name: &str, parent: Option<u64>, database: Option<u64>) -> u64 {
+        stores
+            .tags
+            .put_tag(&Tag {
+                id: 0,
+                name: name.to_owned(),
+                color: 0,
+                icon: None,
+                parent,
+                database,
+            })
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn a_tag_cant_be_below_itself() {
+        let stores = init_stores().await;
+        let parent_id = create_tag(&stores, "Electronics", None, None).await;
+        let child_id = create_tag(&stores, "Cables", Some(parent_id), None).await;
+
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::post().uri(&format!("/v1/tag/{parent_id}"))).set_json(json!({"id": parent_id, "name": "Electronics", "color": 0, "parent": child_id})),
+        )
+        .await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "tag.cycle");
+
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::post().uri(&format!("/v1/tag/{parent_id}/merge?into={child_id}"))),
+        )
+        .await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "tag.cycle");
+
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::post().uri(&format!("/v1/tag/{parent_id}/merge?into={parent_id}"))),
+        )
+        .await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "tag.merge_into_itself");
+    }
+
+    #[actix_web::test]
+    async fn merging_replaces_the_tag_everywhere() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![]).await;
+        let tag_id = create_tag(&stores, "USB C", None, None).await;
+        let into_id = create_tag(&stores, "USB-C", None, None).await;
+        let child_id = create_tag(&stores, "Chargers", Some(tag_id), None).await;
+        let item_id = create_item(&stores, location_id, 1, vec![tag_id, into_id]).await;
+
+        let res = send(&stores, services, authed(test::TestRequest::post().uri(&format!("/v1/tag/{tag_id}/merge?into={into_id}")))).await;
+        assert_eq!(res.status, 200);
+
+        assert!(stores.tags.get_tag(tag_id).await.is_err());
+        assert_eq!(stores.tags.get_tag(child_id).await.unwrap().parent, Some(into_id));
+        assert_eq!(stores.items.get_item(item_id).await.unwrap().tags, vec![into_id]);
+
+        // The changed tags of the item are part of its history
+        let revisions = stores.revisions.get_revisions(item_id).await.unwrap();
+        let last = revisions.last().unwrap();
+        assert_eq!(last.reason, "tag merged");
+        assert_eq!(last.snapshot.tags, vec![into_id]);
+    }
+
+    #[actix_web::test]
+    async fn a_tag_used_by_deleted_items_of_another_database_cant_be_limited() {
+        let stores = init_stores().await;
+        let location_id = create_location(&stores, "Workshop", vec![]).await;
+        let other_location_id = create_location(&stores, "Kitchen", vec![]).await;
+        let other_database_id = stores.locations.get_location(other_location_id).await.unwrap().database;
+        let tag_id = create_tag(&stores, "Fragile", None, None).await;
+        let item_id = create_item(&stores, location_id, 1, vec![tag_id]).await;
+
+        // The deleted item could still be restored with the tag
+        let change = Change {
+            user: None,
+            time: 0,
+            reason: "deleted".to_owned(),
+        };
+        stores.items.delete_item(item_id, &change).await.unwrap();
+
+        let res = send(
+            &stores,
+            services,
+            authed(test::TestRequest::post().uri(&format!("/v1/tag/{tag_id}"))).set_json(json!({"id": tag_id, "name": "Fragile", "color": 0, "database": other_database_id})),
+        )
+        .await;
+        assert_eq!(res.status, 400);
+        assert_eq!(res.body["code"], "tag.used_in_other_database");
+    }
+}
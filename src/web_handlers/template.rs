@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::collection;
+use crate::error::{ApiError, ApiResult};
+use crate::models::{AuthedUser, ItemTemplate};
+use crate::storage::TemplateStore;
+use crate::web_handlers::{get_param, store_error};
+
+#[utoipa::path(
+    get,
+    path = "/templates",
+    tag = "templates",
+    responses((status = 200, description = "The templates", body = Vec<ItemTemplate>))
+)]
+#[actix_web::get("/templates")]
+async fn get_templates(store: web::Data<dyn TemplateStore>, _user: AuthedUser) -> ApiResult<web::Json<Vec<ItemTemplate>>> {
+    let templates = store.get_templates().await.map_err(store_error("template"))?;
+
+    Ok(web::Json(templates))
+}
+
+#[utoipa::path(
+    get,
+    path = "/template/{template_id}",
+    tag = "templates",
+    params(("template_id" = u64, Path, description = "The id of the template")),
+    responses((status = 200, description = "The template", body = ItemTemplate))
+)]
+#[actix_web::get("/template/{template_id}")]
+async fn get_template(store: web::Data<dyn TemplateStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<web::Json<ItemTemplate>> {
+    let template_id: u64 = get_param(&req, "template")?;
+
+    let template = store.get_template(template_id).await.map_err(store_error("template"))?;
+
+    Ok(web::Json(template))
+}
+
+#[utoipa::path(
+    put,
+    path = "/template",
+    tag = "templates",
+    responses((status = 201, description = "The template was created", body = HashMap<String, u64>, example = json!({"template_id": 1})))
+)]
+#[actix_web::put("/template")]
+async fn put_template(store: web::Data<dyn TemplateStore>, _user: AuthedUser, template: web::Json<ItemTemplate>) -> ApiResult<HttpResponse> {
+    if template.id != 0 {
+        return Err(ApiError::IdNotZero("template"));
+    }
+
+    let template_id = store.put_template(&template).await.map_err(store_error("template"))?;
+
+    let map: HashMap<&str, u64> = collection! {
+        "template_id" => template_id
+    };
+    Ok(HttpResponse::Created().json(map))
+}
+
+#[utoipa::path(
+    post,
+    path = "/template/{template_id}",
+    tag = "templates",
+    params(("template_id" = u64, Path, description = "The id of the template")),
+    responses((status = 200, description = "The template was updated"))
+)]
+#[actix_web::post("/template/{template_id}")]
+async fn update_template(store: web::Data<dyn TemplateStore>, _user: AuthedUser, req: HttpRequest, template: web::Json<ItemTemplate>) -> ApiResult<HttpResponse> {
+    let template_id: u64 = get_param(&req, "template")?;
+    if template.id != template_id {
+        return Err(ApiError::IdMismatch("template"));
+    }
+
+    store.update_template(&template).await.map_err(store_error("template"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/template/{template_id}",
+    tag = "templates",
+    params(("template_id" = u64, Path, description = "The id of the template")),
+    responses((status = 200, description = "The template was deleted"))
+)]
+#[actix_web::delete("/template/{template_id}")]
+async fn delete_template(store: web::Data<dyn TemplateStore>, _user: AuthedUser, req: HttpRequest) -> ApiResult<HttpResponse> {
+    let template_id: u64 = get_param(&req, "template")?;
+
+    store.delete_template(template_id).await.map_err(store_error("template"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
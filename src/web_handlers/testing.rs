@@ -0,0 +1,154 @@
+//! Helpers for the tests of the web handlers. The handlers run on top
+//! of a memory store, with a user that is already logged in.
+
+use std::collections::HashMap;
+
+use actix_web::dev::Service;
+use actix_web::{http::header, test, web, App};
+use futures_util::FutureExt;
+use serde_json::Value;
+
+use crate::error;
+use crate::events::Events;
+use crate::models::{Database, Item, Location, PropertyField};
+use crate::storage::memory::MemoryStore;
+use crate::storage::{Change, Stores};
+use crate::web_handlers::auth::{Admins, SESSION_HEADER};
+use crate::web_handlers::label::PublicUrl;
+
+/// The url of the web UI, e.g. for the links on the labels.
+pub(crate) const PUBLIC_URL: &str = "https://storage.example.com";
+
+/// The session of the user that sends the requests.
+pub(crate) const SESSION_ID: &str = "test-session";
+
+/// A memory store with a logged in user.
+pub(crate) async fn init_stores() -> Stores {
+    let store = MemoryStore::new();
+    let user_id = store.add_user("user", "password");
+    let stores = Stores::new(store);
+    stores.sessions.create_session(SESSION_ID, user_id).await.unwrap();
+
+    stores
+}
+
+/// A database with the properties and a location inside of it.
+pub(crate) async fn create_location(stores: &Stores, database_name: &str, properties: Vec<PropertyField>) -> u64 {
+    let database_id = stores
+        .databases
+        .put_database(&Database {
+            id: 0,
+            name: database_name.to_owned(),
+            properties,
+            icon: None,
+        })
+        .await
+        .unwrap();
+    stores
+        .locations
+        .put_location(&Location {
+            id: 0,
+            name: "Shelf".to_owned(),
+            database: database_id,
+            parent: None,
+            icon: None,
+        })
+        .await
+        .unwrap()
+}
+
+/// Insert an item into the location.
+pub(crate) async fn create_item(stores: &Stores, location_id: u64, amount: u64, tags: Vec<u64>) -> u64 {
+    let change = Change {
+        user: None,
+        time: 0,
+        reason: "created".to_owned(),
+    };
+    stores
+        .items
+        .put_item(
+            &Item {
+                id: 0,
+                name: format!("Item {}", stores.items.get_items().await.unwrap().len() + 1),
+                description: String::new(),
+                image: None,
+                location: location_id,
+                parent_item: None,
+                tags,
+                amount,
+                min_amount: None,
+                properties_internal: vec![],
+                properties_custom: vec![],
+                attachments: HashMap::new(),
+                last_edited: 0,
+                created: 0,
+            },
+            &change,
+        )
+        .await
+        .unwrap()
+}
+
+/// A request of the logged in user.
+pub(crate) fn authed(request: test::TestRequest) -> test::TestRequest {
+    request.insert_header((SESSION_HEADER, SESSION_ID))
+}
+
+/// A request that uploads a file, like a browser sends it from a form.
+pub(crate) fn upload(request: test::TestRequest, file_name: &str, content_type: &str, data: &[u8]) -> test::TestRequest {
+    let boundary = "test-boundary";
+    let mut body = format!("--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\nContent-Type: {content_type}\r\n\r\n").into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+
+    request
+        .insert_header((header::CONTENT_TYPE, format!("multipart/form-data; boundary={boundary}")))
+        .set_payload(body)
+}
+
+/// The response to a request, with the body parsed as json (or null).
+pub(crate) struct TestResponse {
+    pub(crate) status: u16,
+    pub(crate) content_type: String,
+    pub(crate) body: Value,
+
+    /// The body as it was sent, for responses that aren't json.
+    pub(crate) text: String,
+}
+
+/// Send the request to an app with the services. Like the
+/// real server, every error is turned into problem details.
+pub(crate) async fn send(stores: &Stores, services: fn(&mut web::ServiceConfig), request: test::TestRequest) -> TestResponse {
+    let app = App::new()
+        .wrap_fn(|req, srv| srv.call(req).map(|res| res.map(error::problem_details)))
+        .app_data(web::Data::from(stores.items.clone()))
+        .app_data(web::Data::from(stores.tags.clone()))
+        .app_data(web::Data::from(stores.locations.clone()))
+        .app_data(web::Data::from(stores.databases.clone()))
+        .app_data(web::Data::from(stores.sessions.clone()))
+        .app_data(web::Data::from(stores.backups.clone()))
+        .app_data(web::Data::from(stores.files.clone()))
+        .app_data(web::Data::from(stores.loans.clone()))
+        .app_data(web::Data::from(stores.movements.clone()))
+        .app_data(web::Data::from(stores.revisions.clone()))
+        .app_data(web::Data::from(stores.trash.clone()))
+        .app_data(web::Data::from(stores.templates.clone()))
+        .app_data(web::Data::from(stores.icons.clone()))
+        .app_data(web::Data::new(Events::new()))
+        .app_data(web::Data::new(Admins::default()))
+        .app_data(web::Data::new(PublicUrl(Some(PUBLIC_URL.to_owned()))))
+        .service(web::scope("/v1").configure(services));
+    let app = test::init_service(app).await;
+
+    let res = test::call_service(&app, request.to_request()).await;
+    let status = res.status().as_u16();
+    let content_type = res.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or_default().to_owned();
+    let body = test::read_body(res).await;
+
+    TestResponse {
+        status,
+        content_type,
+        body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+        text: String::from_utf8_lossy(&body).into_owned(),
+    }
+}
@@ -0,0 +1,73 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::{ApiError, ApiResult};
+use crate::models::{AdminUser, AuthedUser, TrashEntry, TrashKind};
+use crate::storage::blob::BlobStore;
+use crate::storage::{FileStore, TrashStore};
+use crate::web_handlers::file::remove_all_unused_blobs;
+use crate::web_handlers::{get_param, store_error, user_change};
+
+#[derive(Serialize, ToSchema, Debug)]
+struct PurgedTrash {
+    purged: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/trash",
+    tag = "trash",
+    responses((status = 200, description = "The deleted objects, the most recently deleted first", body = Vec<TrashEntry>))
+)]
+#[actix_web::get("/trash")]
+async fn get_trash(store: web::Data<dyn TrashStore>, _user: AuthedUser) -> ApiResult<web::Json<Vec<TrashEntry>>> {
+    let mut entries = store.get_trash().await.map_err(store_error("trash"))?;
+
+    // The most recently deleted objects come first
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+
+    Ok(web::Json(entries))
+}
+
+#[utoipa::path(
+    post,
+    path = "/trash/{kind}/{entry_id}/restore",
+    tag = "trash",
+    params(
+        ("kind" = TrashKind, Path, description = "The kind of the deleted object"),
+        ("entry_id" = u64, Path, description = "The id of the deleted object")
+    ),
+    responses((status = 200, description = "The object was restored"))
+)]
+#[actix_web::post("/trash/{kind}/{entry_id}/restore")]
+async fn restore_trash_entry(store: web::Data<dyn TrashStore>, user: AuthedUser, req: HttpRequest) -> ApiResult<HttpResponse> {
+    let kind = req
+        .match_info()
+        .get("kind")
+        .and_then(TrashKind::parse)
+        .ok_or_else(|| ApiError::BadRequest("trash.invalid_kind", "the kind has to be item, tag, location or database!".to_owned()))?;
+    let entry_id: u64 = get_param(&req, "entry")?;
+
+    store
+        .restore_from_trash(kind, entry_id, &user_change(&user, "restored from trash"))
+        .await
+        .map_err(store_error("trash"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Empty the trash, which deletes everything in it for good.
+#[utoipa::path(
+    delete,
+    path = "/trash",
+    tag = "trash",
+    responses((status = 200, description = "The trash was emptied (only for admins)", body = PurgedTrash))
+)]
+#[actix_web::delete("/trash")]
+async fn empty_trash(store: web::Data<dyn TrashStore>, files: web::Data<dyn FileStore>, blobs: web::Data<dyn BlobStore>, _admin: AdminUser) -> ApiResult<web::Json<PurgedTrash>> {
+    let purged = store.purge_trash(i64::MAX).await.map_err(store_error("trash"))?;
+    remove_all_unused_blobs(&**files, &**blobs).await;
+
+    Ok(web::Json(PurgedTrash { purged }))
+}